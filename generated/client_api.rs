@@ -834,6 +834,185 @@ impl RustHydrusApiClient {
             .map_err(|e| ureq::Error::Other(Box::new(e)))?;
         Ok(res)
     }
+    /// Sets the log level for a module path like rusthydrus::download. None sets the
+    /// default level. Returns false if the level isn't valid
+    pub fn log_level_set(
+        &self,
+        module: &Option<String>,
+        level: &String,
+    ) -> Result<bool, ureq::Error> {
+        let url = format!("{}/{}/{}", self.base_url, "main", "log_level_set");
+        let payload = bitcode::serialize(&(module, level))
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        let response_bytes = ureq::post(url)
+            .header("content-type", "application/bitcode")
+            .header("accept", "application/bitcode")
+            .send(payload)?
+            .into_body()
+            .read_to_vec()?;
+        let res: bool = bitcode::deserialize(&response_bytes)
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        Ok(res)
+    }
+    /// Removes a modules log level so it uses the default again
+    pub fn log_level_clear(&self, module: &String) -> Result<bool, ureq::Error> {
+        let url = format!("{}/{}/{}", self.base_url, "main", "log_level_clear");
+        let payload = bitcode::serialize(&(module))
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        let response_bytes = ureq::post(url)
+            .header("content-type", "application/bitcode")
+            .header("accept", "application/bitcode")
+            .send(payload)?
+            .into_body()
+            .read_to_vec()?;
+        let res: bool = bitcode::deserialize(&response_bytes)
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        Ok(res)
+    }
+    /// Gets the default log level and any per module levels
+    pub fn log_levels_get(&self) -> Result<BTreeMap<String, String>, ureq::Error> {
+        let url = format!("{}/{}/{}", self.base_url, "main", "log_levels_get");
+        let response_bytes = ureq::get(url)
+            .header("accept", "application/bitcode")
+            .call()?
+            .into_body()
+            .read_to_vec()?;
+        let res: BTreeMap<String, String> = bitcode::deserialize(&response_bytes)
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        Ok(res)
+    }
+    /// Switches the log output between json lines and text
+    pub fn log_json_set(&self, json: &bool) -> Result<(), ureq::Error> {
+        let url = format!("{}/{}/{}", self.base_url, "main", "log_json_set");
+        let payload = bitcode::serialize(&(json))
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        let response_bytes = ureq::post(url)
+            .header("content-type", "application/bitcode")
+            .header("accept", "application/bitcode")
+            .send(payload)?
+            .into_body()
+            .read_to_vec()?;
+        let res: () = bitcode::deserialize(&response_bytes)
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        Ok(res)
+    }
+    /// Checks if all downloads are paused
+    pub fn downloads_pause_get(&self) -> Result<bool, ureq::Error> {
+        let url = format!("{}/{}/{}", self.base_url, "main", "downloads_pause_get");
+        let response_bytes = ureq::get(url)
+            .header("accept", "application/bitcode")
+            .call()?
+            .into_body()
+            .read_to_vec()?;
+        let res: bool = bitcode::deserialize(&response_bytes)
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        Ok(res)
+    }
+    /// Pauses or resumes all downloads. Workers wait at the next url or file while paused
+    pub fn downloads_pause_set(&self, paused: &bool) -> Result<(), ureq::Error> {
+        let url = format!("{}/{}/{}", self.base_url, "main", "downloads_pause_set");
+        let payload = bitcode::serialize(&(paused))
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        let response_bytes = ureq::post(url)
+            .header("content-type", "application/bitcode")
+            .header("accept", "application/bitcode")
+            .send(payload)?
+            .into_body()
+            .read_to_vec()?;
+        let res: () = bitcode::deserialize(&response_bytes)
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        Ok(res)
+    }
+    /// Pauses a job so it won't get ran
+    pub fn jobs_pause(&self, job_id: &u64) -> Result<(), ureq::Error> {
+        let url = format!("{}/{}/{}", self.base_url, "main", "jobs_pause");
+        let payload = bitcode::serialize(&(job_id))
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        let response_bytes = ureq::post(url)
+            .header("content-type", "application/bitcode")
+            .header("accept", "application/bitcode")
+            .send(payload)?
+            .into_body()
+            .read_to_vec()?;
+        let res: () = bitcode::deserialize(&response_bytes)
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        Ok(res)
+    }
+    /// Resumes a paused job
+    pub fn jobs_resume(&self, job_id: &u64) -> Result<(), ureq::Error> {
+        let url = format!("{}/{}/{}", self.base_url, "main", "jobs_resume");
+        let payload = bitcode::serialize(&(job_id))
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        let response_bytes = ureq::post(url)
+            .header("content-type", "application/bitcode")
+            .header("accept", "application/bitcode")
+            .send(payload)?
+            .into_body()
+            .read_to_vec()?;
+        let res: () = bitcode::deserialize(&response_bytes)
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        Ok(res)
+    }
+    /// Cancels a running job at the next url or file
+    pub fn jobs_cancel(&self, job_id: &u64) -> Result<(), ureq::Error> {
+        let url = format!("{}/{}/{}", self.base_url, "main", "jobs_cancel");
+        let payload = bitcode::serialize(&(job_id))
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        let response_bytes = ureq::post(url)
+            .header("content-type", "application/bitcode")
+            .header("accept", "application/bitcode")
+            .send(payload)?
+            .into_body()
+            .read_to_vec()?;
+        let res: () = bitcode::deserialize(&response_bytes)
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        Ok(res)
+    }
+    /// Pauses a scraper's worker
+    pub fn scraper_pause(&self, scraper_name: &String) -> Result<(), ureq::Error> {
+        let url = format!("{}/{}/{}", self.base_url, "main", "scraper_pause");
+        let payload = bitcode::serialize(&(scraper_name))
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        let response_bytes = ureq::post(url)
+            .header("content-type", "application/bitcode")
+            .header("accept", "application/bitcode")
+            .send(payload)?
+            .into_body()
+            .read_to_vec()?;
+        let res: () = bitcode::deserialize(&response_bytes)
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        Ok(res)
+    }
+    /// Resumes a paused or cancelled scraper's worker
+    pub fn scraper_resume(&self, scraper_name: &String) -> Result<(), ureq::Error> {
+        let url = format!("{}/{}/{}", self.base_url, "main", "scraper_resume");
+        let payload = bitcode::serialize(&(scraper_name))
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        let response_bytes = ureq::post(url)
+            .header("content-type", "application/bitcode")
+            .header("accept", "application/bitcode")
+            .send(payload)?
+            .into_body()
+            .read_to_vec()?;
+        let res: () = bitcode::deserialize(&response_bytes)
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        Ok(res)
+    }
+    /// Drains a scraper's worker. Stops running jobs and doesn't start new ones
+    pub fn scraper_cancel(&self, scraper_name: &String) -> Result<(), ureq::Error> {
+        let url = format!("{}/{}/{}", self.base_url, "main", "scraper_cancel");
+        let payload = bitcode::serialize(&(scraper_name))
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        let response_bytes = ureq::post(url)
+            .header("content-type", "application/bitcode")
+            .header("accept", "application/bitcode")
+            .send(payload)?
+            .into_body()
+            .read_to_vec()?;
+        let res: () = bitcode::deserialize(&response_bytes)
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        Ok(res)
+    }
     /// Gets all jobs loaded in the db
     pub fn jobs_get_all(
         &self,
@@ -1302,4 +1481,116 @@ impl RustHydrusApiClient {
             .map_err(|e| ureq::Error::Other(Box::new(e)))?;
         Ok(res)
     }
+    /// Gets a value out of a plugin's storage. Plugins are namespaced by their name
+    pub fn plugin_storage_get(
+        &self,
+        plugin: &String,
+        key: &String,
+    ) -> Result<Option<sharedtypes::PluginStorageValue>, ureq::Error> {
+        let url = format!("{}/{}/{}", self.base_url, "main", "plugin_storage_get");
+        let payload = bitcode::serialize(&(plugin, key))
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        let response_bytes = ureq::post(url)
+            .header("content-type", "application/bitcode")
+            .header("accept", "application/bitcode")
+            .send(payload)?
+            .into_body()
+            .read_to_vec()?;
+        let res: Option<sharedtypes::PluginStorageValue> = bitcode::deserialize(&response_bytes)
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        Ok(res)
+    }
+    /// Puts a value into a plugin's storage. Overwrites whatever was at key
+    pub fn plugin_storage_put(
+        &self,
+        plugin: &String,
+        key: &String,
+        value: &sharedtypes::PluginStorageValue,
+    ) -> Result<bool, ureq::Error> {
+        let url = format!("{}/{}/{}", self.base_url, "main", "plugin_storage_put");
+        let payload = bitcode::serialize(&(plugin, key, value))
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        let response_bytes = ureq::post(url)
+            .header("content-type", "application/bitcode")
+            .header("accept", "application/bitcode")
+            .send(payload)?
+            .into_body()
+            .read_to_vec()?;
+        let res: bool = bitcode::deserialize(&response_bytes)
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        Ok(res)
+    }
+    /// Removes a key from a plugin's storage
+    pub fn plugin_storage_delete(
+        &self,
+        plugin: &String,
+        key: &String,
+    ) -> Result<bool, ureq::Error> {
+        let url = format!("{}/{}/{}", self.base_url, "main", "plugin_storage_delete");
+        let payload = bitcode::serialize(&(plugin, key))
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        let response_bytes = ureq::post(url)
+            .header("content-type", "application/bitcode")
+            .header("accept", "application/bitcode")
+            .send(payload)?
+            .into_body()
+            .read_to_vec()?;
+        let res: bool = bitcode::deserialize(&response_bytes)
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        Ok(res)
+    }
+    /// Lists every key and value in a plugin's storage that starts with prefix.
+    /// An empty prefix lists everything.
+    pub fn plugin_storage_list(
+        &self,
+        plugin: &String,
+        prefix: &String,
+    ) -> Result<Vec<(String, sharedtypes::PluginStorageValue)>, ureq::Error> {
+        let url = format!("{}/{}/{}", self.base_url, "main", "plugin_storage_list");
+        let payload = bitcode::serialize(&(plugin, prefix))
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        let response_bytes = ureq::post(url)
+            .header("content-type", "application/bitcode")
+            .header("accept", "application/bitcode")
+            .send(payload)?
+            .into_body()
+            .read_to_vec()?;
+        let res: Vec<(String, sharedtypes::PluginStorageValue)> = bitcode::deserialize(&response_bytes)
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        Ok(res)
+    }
+    /// Applies a batch of puts and deletes to a plugin's storage in one transaction
+    pub fn plugin_storage_batch(
+        &self,
+        plugin: &String,
+        ops: &Vec<sharedtypes::PluginStorageOp>,
+    ) -> Result<bool, ureq::Error> {
+        let url = format!("{}/{}/{}", self.base_url, "main", "plugin_storage_batch");
+        let payload = bitcode::serialize(&(plugin, ops))
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        let response_bytes = ureq::post(url)
+            .header("content-type", "application/bitcode")
+            .header("accept", "application/bitcode")
+            .send(payload)?
+            .into_body()
+            .read_to_vec()?;
+        let res: bool = bitcode::deserialize(&response_bytes)
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        Ok(res)
+    }
+    /// Removes everything a plugin stored. Returns how many keys were removed
+    pub fn plugin_storage_purge(&self, plugin: &String) -> Result<u64, ureq::Error> {
+        let url = format!("{}/{}/{}", self.base_url, "main", "plugin_storage_purge");
+        let payload = bitcode::serialize(&(plugin))
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        let response_bytes = ureq::post(url)
+            .header("content-type", "application/bitcode")
+            .header("accept", "application/bitcode")
+            .send(payload)?
+            .into_body()
+            .read_to_vec()?;
+        let res: u64 = bitcode::deserialize(&response_bytes)
+            .map_err(|e| ureq::Error::Other(Box::new(e)))?;
+        Ok(res)
+    }
 }
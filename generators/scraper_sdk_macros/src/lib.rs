@@ -0,0 +1,134 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{FnArg, ImplItem, ItemImpl, ReturnType, Type, Visibility, parse_macro_input};
+
+///
+/// Exports every pub fn in the impl as a scraper entry point through the stable plugin ABI.
+/// Needs get_global_info and parser.
+///
+#[proc_macro_attribute]
+pub fn scraper(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    exports(item, &["get_global_info", "parser"])
+}
+
+///
+/// Exports every pub fn in the impl as a plugin entry point through the stable plugin ABI.
+/// Needs get_global_info.
+///
+#[proc_macro_attribute]
+pub fn plugin(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    exports(item, &["get_global_info"])
+}
+
+fn exports(item: TokenStream, required: &[&str]) -> TokenStream {
+    let input = parse_macro_input!(item as ItemImpl);
+    let self_ty = &input.self_ty;
+    let mut exported = vec![];
+    let mut entries = vec![];
+    let mut names = vec![];
+
+    for item in &input.items {
+        let ImplItem::Fn(m) = item else {
+            continue;
+        };
+        if !matches!(m.vis, Visibility::Public(_)) {
+            continue;
+        }
+        let fn_name = &m.sig.ident;
+        if let Some(FnArg::Receiver(receiver)) = m.sig.inputs.first() {
+            return syn::Error::new_spanned(
+                receiver,
+                "exported functions can't take self. The host calls them as free functions",
+            )
+            .to_compile_error()
+            .into();
+        }
+
+        // Patterns like _ can't be forwarded so every arg gets a fresh name
+        let arg_names: Vec<_> = (0..m.sig.inputs.len())
+            .map(|cnt| format_ident!("arg{}", cnt))
+            .collect();
+        // Arguments arrive decoded and owned so borrowed ones get their owned type
+        let mut arg_types = vec![];
+        let mut arg_passes = vec![];
+        for (arg, arg_name) in m.sig.inputs.iter().zip(&arg_names) {
+            let FnArg::Typed(typed) = arg else {
+                continue;
+            };
+            match owned_type(&typed.ty) {
+                Ok((owned, borrowed)) => {
+                    arg_types.push(owned);
+                    arg_passes.push(if borrowed {
+                        quote! { #arg_name }
+                    } else {
+                        quote! { ::std::clone::Clone::clone(#arg_name) }
+                    });
+                }
+                Err(err) => return err.to_compile_error().into(),
+            }
+        }
+        let output = match &m.sig.output {
+            ReturnType::Default => quote! {},
+            ReturnType::Type(arrow, ty) => quote! { #arrow #ty },
+        };
+
+        exported.push(quote! {
+            pub fn #fn_name(#(#arg_names: &#arg_types),*) #output {
+                <#self_ty>::#fn_name(#(#arg_passes),*)
+            }
+        });
+        entries.push(quote! { #fn_name(#(#arg_names: #arg_types),*) });
+        names.push(fn_name.to_string());
+    }
+
+    let missing: Vec<_> = required
+        .iter()
+        .filter(|name| !names.iter().any(|have| have == *name))
+        .map(|name| {
+            syn::Error::new(
+                Span::call_site(),
+                format!(
+                    "missing pub fn {}. The host won't load this without it",
+                    name
+                ),
+            )
+            .to_compile_error()
+        })
+        .collect();
+
+    quote! {
+        #input
+        #(#missing)*
+        #(#exported)*
+        ::scraper_sdk::sharedtypes::plugin_export! {
+            #(#entries;)*
+        }
+    }
+    .into()
+}
+
+///
+/// Type an argument gets decoded into and whether the method borrows it.
+/// &str becomes String and &[T] becomes Vec<T> so the borrow still derefs to it
+///
+fn owned_type(ty: &Type) -> syn::Result<(proc_macro2::TokenStream, bool)> {
+    let Type::Reference(reference) = ty else {
+        return Ok((quote! { #ty }, false));
+    };
+    if let Some(mutability) = &reference.mutability {
+        return Err(syn::Error::new_spanned(
+            mutability,
+            "exported functions can't take &mut. Arguments are decoded fresh for every call",
+        ));
+    }
+    let owned = match &*reference.elem {
+        Type::Path(path) if path.path.is_ident("str") => quote! { ::std::string::String },
+        Type::Slice(slice) => {
+            let elem = &slice.elem;
+            quote! { ::std::vec::Vec<#elem> }
+        }
+        elem => quote! { #elem },
+    };
+    Ok((owned, true))
+}
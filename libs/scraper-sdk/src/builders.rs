@@ -0,0 +1,278 @@
+use sharedtypes::{
+    GenericNamespaceObj, GlobalCallbacks, GlobalPluginScraper, LoginNeed, LoginType, PluginInfo2,
+    ScraperInfo, ScraperOrPlugin, StoredInfo, SubTag, Tag, TagObject, TagType, TargetModifiers,
+};
+use std::time::Duration;
+
+///
+/// Makes a namespace. Scrapers usually prefix the name with their site
+///
+pub fn namespace(name: impl Into<String>, description: Option<&str>) -> GenericNamespaceObj {
+    GenericNamespaceObj {
+        name: name.into(),
+        description: description.map(|description| description.to_string()),
+    }
+}
+
+///
+/// Builds the GlobalPluginScraper a scraper hands back from get_global_info
+///
+pub struct ScraperBuilder {
+    global: GlobalPluginScraper,
+    info: ScraperInfo,
+}
+
+impl ScraperBuilder {
+    pub fn new(name: impl Into<String>) -> Self {
+        let mut global = sharedtypes::return_default_globalpluginparser();
+        global.name = name.into();
+        ScraperBuilder {
+            global,
+            info: ScraperInfo {
+                ratelimit: (1, Duration::from_secs(1)),
+                priority: sharedtypes::DEFAULT_PRIORITY,
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn version(mut self, version: u64) -> Self {
+        self.global.version = version;
+        self
+    }
+
+    ///
+    /// Site names that jobs can use to reach this scraper
+    ///
+    pub fn sites<S: Into<String>>(mut self, sites: impl IntoIterator<Item = S>) -> Self {
+        self.info.sites.extend(sites.into_iter().map(Into::into));
+        self
+    }
+
+    ///
+    /// How many requests can go out per duration
+    ///
+    pub fn ratelimit(mut self, count: u64, per: Duration) -> Self {
+        self.info.ratelimit = (count, per);
+        self
+    }
+
+    pub fn priority(mut self, priority: u64) -> Self {
+        self.info.priority = priority;
+        self
+    }
+
+    pub fn num_threads(mut self, num_threads: u64) -> Self {
+        self.info.num_threads = Some(num_threads);
+        self
+    }
+
+    pub fn modifier(mut self, modifier: TargetModifiers) -> Self {
+        self.info.modifiers.push(modifier);
+        self
+    }
+
+    ///
+    /// Gets copied into user_data of every job this scraper runs
+    ///
+    pub fn stored(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let pair = (key.into(), value.into());
+        match &mut self.global.stored_info {
+            Some(StoredInfo::Storage(storage)) => storage.push(pair),
+            None => self.global.stored_info = Some(StoredInfo::Storage(vec![pair])),
+        }
+        self
+    }
+
+    pub fn login(
+        mut self,
+        name: impl Into<String>,
+        login: LoginType,
+        need: LoginNeed,
+        description: Option<&str>,
+    ) -> Self {
+        self.global.login_type.push((
+            name.into(),
+            login,
+            need,
+            description.map(|description| description.to_string()),
+            false,
+        ));
+        self
+    }
+
+    pub fn callback(mut self, callback: GlobalCallbacks) -> Self {
+        self.global.callbacks.push(callback);
+        self
+    }
+
+    pub fn handles_file_download(mut self) -> Self {
+        self.global.should_handle_file_download = true;
+        self
+    }
+
+    pub fn handles_text_scraping(mut self) -> Self {
+        self.global.should_handle_text_scraping = true;
+        self
+    }
+
+    pub fn build(mut self) -> GlobalPluginScraper {
+        self.global.storage_type = Some(ScraperOrPlugin::Scraper(self.info));
+        self.global
+    }
+}
+
+///
+/// Builds the GlobalPluginScraper a plugin hands back from get_global_info
+///
+pub struct PluginBuilder {
+    global: GlobalPluginScraper,
+    info: PluginInfo2,
+}
+
+impl PluginBuilder {
+    pub fn new(name: impl Into<String>) -> Self {
+        let mut global = sharedtypes::return_default_globalpluginparser();
+        global.name = name.into();
+        PluginBuilder {
+            global,
+            info: PluginInfo2 {
+                com_channel: false,
+                redirect: None,
+            },
+        }
+    }
+
+    pub fn version(mut self, version: u64) -> Self {
+        self.global.version = version;
+        self
+    }
+
+    pub fn priority(mut self, priority: u64) -> Self {
+        self.global.priority = priority;
+        self
+    }
+
+    ///
+    /// Lets the plugin call back into the host with the client
+    ///
+    pub fn com_channel(mut self) -> Self {
+        self.info.com_channel = true;
+        self
+    }
+
+    ///
+    /// Sends anything this plugin parses to the scraper for that site instead
+    ///
+    pub fn redirect(mut self, site: impl Into<String>) -> Self {
+        self.info.redirect = Some(site.into());
+        self
+    }
+
+    pub fn callback(mut self, callback: GlobalCallbacks) -> Self {
+        self.global.callbacks.push(callback);
+        self
+    }
+
+    pub fn build(mut self) -> GlobalPluginScraper {
+        self.global.storage_type = Some(ScraperOrPlugin::Plugin(self.info));
+        self.global
+    }
+}
+
+///
+/// Builds tags and the relationships between them
+///
+#[derive(Debug, Clone)]
+pub struct TagBuilder {
+    namespace: GenericNamespaceObj,
+    tag: String,
+    tag_type: TagType,
+    relates_to: Option<SubTag>,
+}
+
+impl TagBuilder {
+    pub fn new(namespace: &GenericNamespaceObj, tag: impl Into<String>) -> Self {
+        TagBuilder {
+            namespace: namespace.clone(),
+            tag: tag.into(),
+            tag_type: TagType::Normal,
+            relates_to: None,
+        }
+    }
+
+    pub fn tag_type(mut self, tag_type: TagType) -> Self {
+        self.tag_type = tag_type;
+        self
+    }
+
+    ///
+    /// Links this tag to another one, ie a post id to its thread
+    ///
+    pub fn relates_to(mut self, relates_to: SubTag) -> Self {
+        self.relates_to = Some(relates_to);
+        self
+    }
+
+    ///
+    /// Use this tag as the target of another tag's relationship
+    ///
+    pub fn sub(&self, limit_to: Option<Tag>) -> SubTag {
+        SubTag {
+            namespace: self.namespace.clone(),
+            tag: self.tag.clone(),
+            limit_to,
+            tag_type: self.tag_type.clone(),
+        }
+    }
+
+    pub fn tag(&self) -> Tag {
+        Tag {
+            tag: self.tag.clone(),
+            namespace: self.namespace.clone(),
+        }
+    }
+
+    pub fn build(self) -> TagObject {
+        TagObject {
+            namespace: self.namespace,
+            tag: self.tag,
+            tag_type: self.tag_type,
+            relates_to: self.relates_to,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builders_fill_in_storage_type() {
+        let scraper = ScraperBuilder::new("Example")
+            .sites(["ex", "example.net"])
+            .stored("loaded_site", "Example")
+            .build();
+        let Some(ScraperOrPlugin::Scraper(info)) = &scraper.storage_type else {
+            panic!("not a scraper");
+        };
+        assert_eq!(
+            info.sites,
+            vec!["ex".to_string(), "example.net".to_string()]
+        );
+        assert_eq!(
+            scraper.stored_info,
+            Some(StoredInfo::Storage(vec![(
+                "loaded_site".into(),
+                "Example".into()
+            )]))
+        );
+
+        let ns = namespace("Example_Pool", None);
+        let pool = TagBuilder::new(&ns, "12");
+        let post = TagBuilder::new(&namespace("Example_Id", None), "34")
+            .relates_to(pool.sub(None))
+            .build();
+        assert_eq!(post.relates_to.unwrap().tag, "12");
+    }
+}
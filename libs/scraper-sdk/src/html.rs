@@ -0,0 +1,165 @@
+///
+/// Turns the common html entities back into text
+///
+pub fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out += &rest[..start];
+        rest = &rest[start..];
+        let Some(end) = rest.find(';').filter(|end| *end <= 10) else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" | "#039" | "#39" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|num| num.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(decoded) => {
+                out.push(decoded);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out + rest
+}
+
+///
+/// Strips tags out of a snippet of html like a post comment.
+/// <br> and </p> turn into newlines and <wbr> is dropped.
+///
+pub fn text(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        out += &rest[..start];
+        let Some(end) = rest[start..].find('>') else {
+            out += &rest[start..];
+            rest = "";
+            break;
+        };
+        let tag = rest[start + 1..start + end]
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        if tag == "br" || (tag == "p" && rest[start + 1..].starts_with('/')) {
+            out.push('\n');
+        }
+        rest = &rest[start + end + 1..];
+    }
+    unescape(&(out + rest))
+}
+
+///
+/// Runs one css selector or xpath through the host's html parser. Empty when nothing
+/// matched or the selector is bad
+///
+#[cfg(feature = "client")]
+pub fn select(
+    html: &str,
+    base_url: Option<&str>,
+    selector: &str,
+    extract: sharedtypes::HtmlExtract,
+) -> Vec<String> {
+    crate::client::html_select(
+        html.to_string(),
+        base_url.map(str::to_string),
+        vec![sharedtypes::HtmlQuery {
+            selector: selector.to_string(),
+            extract,
+        }],
+    )
+    .into_iter()
+    .next()
+    .and_then(Result::ok)
+    .unwrap_or_default()
+}
+
+///
+/// Values of an attribute on every element the selector matches. ie attr_values(body, "a", "href")
+///
+#[cfg(feature = "client")]
+pub fn attr_values(html: &str, selector: &str, attr: &str) -> Vec<String> {
+    select(
+        html,
+        None,
+        selector,
+        sharedtypes::HtmlExtract::Attr(attr.to_string()),
+    )
+}
+
+///
+/// Resolves a link found on a page against the url the page came from
+///
+pub fn absolute(base: &str, link: &str) -> String {
+    if link.contains("://") {
+        return link.to_string();
+    }
+    let scheme_end = base.find("://").map(|at| at + 3).unwrap_or(0);
+    let scheme = base[..scheme_end].trim_end_matches("//");
+    if let Some(rest) = link.strip_prefix("//") {
+        return format!("{}//{}", scheme, rest);
+    }
+    let host_end = base[scheme_end..]
+        .find(['/', '?', '#'])
+        .map(|at| scheme_end + at)
+        .unwrap_or(base.len());
+    if link.starts_with('/') {
+        return format!("{}{}", &base[..host_end], link);
+    }
+    let path = &base[..base[host_end..]
+        .find(['?', '#'])
+        .map(|at| host_end + at)
+        .unwrap_or(base.len())];
+    if link.starts_with('?') || link.starts_with('#') {
+        return format!("{}{}", path, link);
+    }
+    let dir = match path[host_end..].rfind('/') {
+        Some(at) => &path[..host_end + at + 1],
+        None => return format!("{}/{}", path, link),
+    };
+    format!("{}{}", dir, link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html_snippets_become_text() {
+        assert_eq!(
+            text("<a href=\"#p1\">&gt;&gt;1</a><br>cannot bor<wbr>row &amp; move"),
+            ">>1\ncannot borrow & move"
+        );
+        let base = "https://example.net/posts/list?page=1";
+        assert_eq!(absolute(base, "/post/1"), "https://example.net/post/1");
+        assert_eq!(absolute(base, "show/2"), "https://example.net/posts/show/2");
+        assert_eq!(
+            absolute(base, "//cdn.example.net/a.png"),
+            "https://cdn.example.net/a.png"
+        );
+        assert_eq!(
+            absolute("https://example.net", "a"),
+            "https://example.net/a"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use sharedtypes::ScraperReturn;
+
+///
+/// Percent encodes a query value. Leaves unreserved characters alone
+///
+pub fn query_encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => out += &format!("%{:02X}", byte),
+        }
+    }
+    out
+}
+
+///
+/// Adds query pairs onto a url. Works if the url already has a query
+///
+pub fn url_with_query(base: &str, query: &[(&str, &str)]) -> String {
+    let mut out = base.to_string();
+    for (key, value) in query {
+        out.push(if out.contains('?') { '&' } else { '?' });
+        out += &query_encode(key);
+        out.push('=');
+        out += &query_encode(value);
+    }
+    out
+}
+
+///
+/// Joins search terms the way booru style sites expect them, ie tag+tag
+///
+pub fn tags_join<S: AsRef<str>>(tags: &[S]) -> String {
+    tags.iter()
+        .map(|tag| query_encode(tag.as_ref()))
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+///
+/// Spots the usual pages a site hands back instead of data and says what to do about it.
+/// None means the body looks like a real response.
+///
+pub fn error_page(body: &str) -> Option<ScraperReturn> {
+    if body.contains("Please confirm you are not a robot.")
+        || body.contains("cf-browser-verification")
+        || body.contains("Just a moment...")
+    {
+        return Some(ScraperReturn::Timeout(20));
+    }
+    if body.contains("502: Bad gateway")
+        || body.contains("502 Bad Gateway")
+        || body.contains("503 Service Unavailable")
+        || body.contains("SSL handshake failed")
+    {
+        return Some(ScraperReturn::Timeout(10));
+    }
+    if body.contains("429 Too Many Requests") || body.contains("Rate limit exceeded") {
+        return Some(ScraperReturn::Timeout(60));
+    }
+    if body.contains("Maintenance") && body.len() < 4096 {
+        return Some(ScraperReturn::Timeout(240));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_are_encoded() {
+        assert_eq!(
+            url_with_query(
+                "https://example.net/posts.json",
+                &[("tags", "fox solo"), ("page", "2")]
+            ),
+            "https://example.net/posts.json?tags=fox%20solo&page=2"
+        );
+        assert_eq!(
+            url_with_query("https://example.net/?a=1", &[("b", "é")]),
+            "https://example.net/?a=1&b=%C3%A9"
+        );
+        assert_eq!(tags_join(&["fox", "rating:s"]), "fox+rating%3As");
+    }
+}
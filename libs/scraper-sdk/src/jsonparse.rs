@@ -0,0 +1,50 @@
+use crate::http;
+use json::JsonValue;
+use sharedtypes::ScraperReturn;
+
+///
+/// Parses a response body. When it isn't json works out if the site is throttling us
+/// or if the scraper should give up on the job.
+/// The error is what the parser should hand back so it isn't boxed.
+///
+#[allow(clippy::result_large_err)]
+pub fn parse(body: &str) -> Result<JsonValue, ScraperReturn> {
+    json::parse(body).map_err(|err| {
+        http::error_page(body).unwrap_or_else(|| ScraperReturn::Stop(format!("Bad json: {}", err)))
+    })
+}
+
+///
+/// Walks a dotted path like posts.0.file.url. Missing keys give back null
+///
+pub fn path<'a>(value: &'a JsonValue, path: &str) -> &'a JsonValue {
+    path.split('.')
+        .filter(|key| !key.is_empty())
+        .fold(value, |value, key| match (value, key.parse::<usize>()) {
+            (JsonValue::Array(_), Ok(index)) => &value[index],
+            _ => &value[key],
+        })
+}
+
+///
+/// Every string in an array. Numbers get turned into strings and anything else is skipped
+///
+pub fn strings(value: &JsonValue) -> Vec<String> {
+    value
+        .members()
+        .filter_map(|member| match member {
+            JsonValue::Number(_) => Some(member.dump()),
+            _ => member.as_str().map(|text| text.to_string()),
+        })
+        .collect()
+}
+
+///
+/// Gets a string or number as text. Handy for ids that sites send either way
+///
+pub fn text(value: &JsonValue) -> Option<String> {
+    match value {
+        JsonValue::Number(_) => Some(value.dump()),
+        _ => value.as_str().map(|text| text.to_string()),
+    }
+}
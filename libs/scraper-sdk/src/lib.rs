@@ -0,0 +1,43 @@
+//!
+//! Shared pieces for writing scrapers and plugins.
+//!
+//! Put `#[scraper]` or `#[plugin]` on an impl and every pub fn in it gets exported
+//! through the stable plugin ABI with the name the host looks up.
+//!
+//! ```ignore
+//! use scraper_sdk::prelude::*;
+//!
+//! struct Example;
+//!
+//! #[scraper]
+//! impl Example {
+//!     pub fn get_global_info() -> Vec<GlobalPluginScraper> {
+//!         vec![ScraperBuilder::new("Example").sites(["example"]).build()]
+//!     }
+//!     pub fn parser(body: &str, url: &str, data: &ScraperDataReturn) -> Vec<ScraperReturn> {
+//!         vec![ScraperReturn::Nothing]
+//!     }
+//! }
+//! ```
+
+pub mod builders;
+pub mod html;
+pub mod http;
+pub mod jsonparse;
+
+#[cfg(feature = "client")]
+#[path = "../../../src/client.rs"]
+pub mod client;
+
+pub use json;
+pub use scraper_sdk_macros::{plugin, scraper};
+pub use sharedtypes;
+
+pub mod prelude {
+    pub use crate::builders::{PluginBuilder, ScraperBuilder, TagBuilder, namespace};
+    pub use crate::{plugin, scraper};
+    pub use sharedtypes::{
+        GenericNamespaceObj, GlobalPluginScraper, ScraperDataReturn, ScraperObject, ScraperReturn,
+        SubTag, Tag, TagObject, TagType,
+    };
+}
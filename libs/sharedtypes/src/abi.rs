@@ -0,0 +1,257 @@
+//!
+//! Stable boundary between the host and plugins.
+//!
+//! Only plain C types cross the boundary. Arguments and return values are bitcode
+//! encoded into byte buffers so a plugin built with a different rustc doesn't have
+//! to agree with the host on the layout of Vec, String or HashMap.
+//!
+//! Plugins export three symbols, normally generated by [`plugin_export!`]:
+//! - `hydrus_plugin_abi_version` returns the [`PLUGIN_ABI_VERSION`] it was built with
+//! - `hydrus_plugin_call` takes a function name and encoded arguments
+//! - `hydrus_plugin_free` frees a buffer the plugin allocated
+//!
+
+/// Bump whenever the encoding or any type sent across the boundary changes.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+pub const ABI_VERSION_SYMBOL: &[u8] = b"hydrus_plugin_abi_version\0";
+pub const ABI_CALL_SYMBOL: &[u8] = b"hydrus_plugin_call\0";
+pub const ABI_FREE_SYMBOL: &[u8] = b"hydrus_plugin_free\0";
+
+///
+/// Result of a call across the boundary
+///
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbiStatus {
+    Ok = 0,
+    // Plugin doesn't export a function with this name
+    UnknownFunction = 1,
+    // Arguments couldn't be decoded. Usually a version mismatch
+    DecodeError = 2,
+    // Plugin panicked. The panic was caught before crossing the boundary
+    Panic = 3,
+}
+
+///
+/// Byte buffer owned by whoever allocated it. Must be freed by the same side.
+///
+#[repr(C)]
+#[derive(Debug)]
+pub struct AbiBuffer {
+    pub ptr: *mut u8,
+    pub len: usize,
+    pub cap: usize,
+}
+
+impl AbiBuffer {
+    pub fn empty() -> Self {
+        AbiBuffer::from_vec(Vec::new())
+    }
+
+    pub fn from_vec(vec: Vec<u8>) -> Self {
+        let mut vec = std::mem::ManuallyDrop::new(vec);
+        AbiBuffer {
+            ptr: vec.as_mut_ptr(),
+            len: vec.len(),
+            cap: vec.capacity(),
+        }
+    }
+
+    ///
+    /// # Safety
+    /// Buffer must have come from [`AbiBuffer::from_vec`] and not been freed.
+    ///
+    pub unsafe fn as_slice(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    ///
+    /// # Safety
+    /// Must only be called by the side that allocated the buffer.
+    ///
+    pub unsafe fn into_vec(self) -> Vec<u8> {
+        unsafe { Vec::from_raw_parts(self.ptr, self.len, self.cap) }
+    }
+}
+
+///
+/// What `hydrus_plugin_call` returns
+///
+#[repr(C)]
+#[derive(Debug)]
+pub struct AbiResult {
+    pub status: u32,
+    pub data: AbiBuffer,
+}
+
+pub type AbiVersionFn = unsafe extern "C" fn() -> u32;
+pub type AbiCallFn = unsafe extern "C" fn(*const u8, usize, *const u8, usize) -> AbiResult;
+pub type AbiFreeFn = unsafe extern "C" fn(AbiBuffer);
+
+///
+/// Encodes a value to send across the boundary
+///
+pub fn abi_encode<T: bitcode::Encode + ?Sized>(value: &T) -> Vec<u8> {
+    bitcode::encode(value)
+}
+
+///
+/// Decodes a value that came across the boundary
+///
+pub fn abi_decode<T: bitcode::DecodeOwned>(bytes: &[u8]) -> Result<T, bitcode::Error> {
+    bitcode::decode(bytes)
+}
+
+///
+/// Checks the version a plugin reports from `hydrus_plugin_abi_version`
+///
+pub fn abi_version_check(plugin_version: u32) -> Result<(), String> {
+    if plugin_version != PLUGIN_ABI_VERSION {
+        return Err(format!(
+            "plugin was built for plugin ABI version {} but this build uses version {}. Rebuild the plugin against the current sharedtypes",
+            plugin_version, PLUGIN_ABI_VERSION
+        ));
+    }
+    Ok(())
+}
+
+///
+/// Generates the plugin side of the stable ABI.
+/// Each function gets its arguments by reference in the order they're listed.
+/// Functions that aren't listed are reported as not exported and never get called.
+///
+/// ```ignore
+/// sharedtypes::plugin_export! {
+///     get_global_info();
+///     on_start(site_struct: sharedtypes::GlobalPluginScraper);
+///     url_dump(params: Vec<sharedtypes::ScraperParam>, scraperdata: sharedtypes::ScraperDataReturn);
+/// }
+/// ```
+///
+#[macro_export]
+macro_rules! plugin_export {
+    ($($func:ident($($arg:ident : $ty:ty),* $(,)?));* $(;)?) => {
+        #[unsafe(no_mangle)]
+        pub extern "C" fn hydrus_plugin_abi_version() -> u32 {
+            $crate::abi::PLUGIN_ABI_VERSION
+        }
+
+        #[unsafe(no_mangle)]
+        pub unsafe extern "C" fn hydrus_plugin_free(buffer: $crate::abi::AbiBuffer) {
+            drop(unsafe { buffer.into_vec() });
+        }
+
+        #[unsafe(no_mangle)]
+        pub unsafe extern "C" fn hydrus_plugin_call(
+            name_ptr: *const u8,
+            name_len: usize,
+            input_ptr: *const u8,
+            input_len: usize,
+        ) -> $crate::abi::AbiResult {
+            let name = unsafe { std::slice::from_raw_parts(name_ptr, name_len) };
+            let input: &[u8] = if input_len == 0 {
+                &[]
+            } else {
+                unsafe { std::slice::from_raw_parts(input_ptr, input_len) }
+            };
+
+            let out = std::panic::catch_unwind(|| -> Result<Vec<u8>, $crate::abi::AbiStatus> {
+                $(
+                    if name == stringify!($func).as_bytes() {
+                        let out = $crate::plugin_export!(@call $func, input; $($arg: $ty),*);
+                        return Ok($crate::abi::abi_encode(&out));
+                    }
+                )*
+                Err($crate::abi::AbiStatus::UnknownFunction)
+            });
+
+            match out {
+                Ok(Ok(data)) => $crate::abi::AbiResult {
+                    status: $crate::abi::AbiStatus::Ok as u32,
+                    data: $crate::abi::AbiBuffer::from_vec(data),
+                },
+                Ok(Err(status)) => $crate::abi::AbiResult {
+                    status: status as u32,
+                    data: $crate::abi::AbiBuffer::empty(),
+                },
+                Err(_) => $crate::abi::AbiResult {
+                    status: $crate::abi::AbiStatus::Panic as u32,
+                    data: $crate::abi::AbiBuffer::empty(),
+                },
+            }
+        }
+    };
+    // Functions without arguments ignore whatever the host sent
+    (@call $func:ident, $input:ident;) => {{
+        let _ = $input;
+        $func()
+    }};
+    (@call $func:ident, $input:ident; $($arg:ident : $ty:ty),+) => {{
+        #[allow(unused_parens)]
+        let ($($arg),+): ($($ty),+) = $crate::abi::abi_decode($input)
+            .map_err(|_| $crate::abi::AbiStatus::DecodeError)?;
+        $func($(&$arg),+)
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(left: &u64, right: &u64) -> u64 {
+        left + right
+    }
+
+    fn names() -> Vec<String> {
+        vec!["a".to_string(), "b".to_string()]
+    }
+
+    fn boom() -> u64 {
+        panic!("boom")
+    }
+
+    crate::plugin_export! {
+        add(left: u64, right: u64);
+        names();
+        boom();
+    }
+
+    fn call(func: &str, input: &[u8]) -> (u32, Vec<u8>) {
+        let result =
+            unsafe { hydrus_plugin_call(func.as_ptr(), func.len(), input.as_ptr(), input.len()) };
+        let data = unsafe { result.data.as_slice() }.to_vec();
+        unsafe { hydrus_plugin_free(result.data) };
+        (result.status, data)
+    }
+
+    #[test]
+    fn exported_calls_roundtrip() {
+        assert_eq!(hydrus_plugin_abi_version(), PLUGIN_ABI_VERSION);
+
+        let (status, data) = call("add", &abi_encode(&(2u64, 3u64)));
+        assert_eq!(status, AbiStatus::Ok as u32);
+        assert_eq!(abi_decode::<u64>(&data).unwrap(), 5);
+
+        let (status, data) = call("names", &[]);
+        assert_eq!(status, AbiStatus::Ok as u32);
+        assert_eq!(abi_decode::<Vec<String>>(&data).unwrap(), names());
+
+        assert_eq!(call("missing", &[]).0, AbiStatus::UnknownFunction as u32);
+        assert_eq!(
+            call("add", &abi_encode("not numbers")).0,
+            AbiStatus::DecodeError as u32
+        );
+        assert_eq!(call("boom", &[]).0, AbiStatus::Panic as u32);
+    }
+
+    #[test]
+    fn mismatched_version_is_rejected() {
+        assert!(abi_version_check(PLUGIN_ABI_VERSION).is_ok());
+        assert!(abi_version_check(PLUGIN_ABI_VERSION + 1).is_err());
+        assert!(abi_version_check(0).is_err());
+    }
+}
@@ -20,6 +20,8 @@ use strum_macros::EnumIter;
 use strum_macros::{Display, EnumString};
 
 use redact::{Secret, expose_secret};
+
+pub mod abi;
 // Default priority for a scraper
 pub const DEFAULT_PRIORITY: u64 = 10;
 
@@ -43,7 +45,7 @@ pub enum TagPartialSearchType {
     PopularCount,
 }
 
-#[derive(Clone)]
+#[derive(Clone, bitcode::Encode, bitcode::Decode)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ClientAPIInfo {
     pub url: SocketAddr,
@@ -84,9 +86,10 @@ pub enum CheckSourceUrlsEnum {
 }
 
 ///
-/// Manages the conditions that determines which enclave should trigger
+/// Manages the conditions that determines which enclave should trigger.
+/// No bitcode derive because it can't handle the recursion in All / AnyOf / Not
 ///
-#[derive(Debug, bitcode::Encode, bitcode::Decode)]
+#[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum EnclaveCondition {
     Any,
@@ -94,6 +97,41 @@ pub enum EnclaveCondition {
     FileSizeGreater(u64),
     FileSizeLessthan(u64),
     TagNameAndNamespace((String, String)),
+    /// MIME type detected from the bytes. Just the type like "image" matches every subtype
+    MimeType(String),
+    /// Extension detected from the bytes like "gif"
+    Extension(String),
+    /// Host of the source url. Subdomains match too so "4chan.org" matches "i.4chan.org"
+    SourceHost(String),
+    /// Regex checked against the source url
+    SourceUrlRegex(String),
+    /// Name of the scraper that found the file
+    Scraper(String),
+    /// Width and height in pixels of an image or video. Bounds are inclusive
+    Dimensions(DimensionBounds),
+    /// Length in seconds of a video or audio file
+    DurationGreater(u64),
+    DurationLessthan(u64),
+    /// (tag, namespace) the file comes with or already has in the db. Exact match
+    TagInDb((String, String)),
+    /// Passes when every condition passes
+    All(Vec<EnclaveCondition>),
+    /// Passes when at least one condition passes
+    AnyOf(Vec<EnclaveCondition>),
+    Not(Box<EnclaveCondition>),
+}
+
+///
+/// Limits for EnclaveCondition::Dimensions. None leaves that side open
+///
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct DimensionBounds {
+    pub min_width: Option<u64>,
+    pub max_width: Option<u64>,
+    pub min_height: Option<u64>,
+    pub max_height: Option<u64>,
 }
 
 #[derive(Debug, bitcode::Encode, bitcode::Decode)]
@@ -113,7 +151,7 @@ pub enum EnclaveStopCondition {
     FileDownloadLocation,
 }
 
-#[derive(Debug, bitcode::Encode, bitcode::Decode)]
+#[derive(Debug, Clone, PartialEq, bitcode::Encode, bitcode::Decode)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum EnclaveAction {
     DownloadToLocation(u64),
@@ -122,6 +160,326 @@ pub enum EnclaveAction {
     //Functionally similar to DownloadToLocation however this is used for just putting an item in a
     //location and does not download it. Really just for nice logging :D
     PutAtDefault,
+    /// Drops the file without storing it. Nothing after this runs
+    Skip,
+}
+
+///
+/// One link in an enclave's chain. action runs when condition passes,
+/// failed_action when it doesn't
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EnclaveStep {
+    pub condition: EnclaveCondition,
+    pub action: EnclaveAction,
+    pub failed_action: Option<EnclaveAction>,
+}
+
+///
+/// An enclave and its chain in the order it runs. Higher priorities run first
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EnclaveInfo {
+    pub id: u64,
+    pub name: String,
+    pub priority: u64,
+    pub steps: Vec<EnclaveStep>,
+    /// Made by the program. These get remade on startup so they can't be renamed,
+    /// deleted or have steps removed
+    pub builtin: bool,
+}
+
+///
+/// What a dry run saw at one step. action is None when the condition failed and
+/// there's no failed_action
+///
+#[derive(Debug, Clone, PartialEq, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EnclaveDryRunStep {
+    pub enclave: String,
+    pub position: u64,
+    pub passed: bool,
+    pub action: Option<EnclaveAction>,
+    /// Where the file would have been stored
+    pub location: Option<String>,
+    /// This action would have stored or skipped the file and ended processing
+    pub stops: bool,
+}
+
+///
+/// A storage location with limits on how much goes into it. capacity of None means
+/// only the free space on the disk limits it
+///
+#[derive(Debug, Clone, PartialEq, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StorageRoot {
+    pub storage_id: u64,
+    pub location: String,
+    pub capacity: Option<u64>,
+    /// Free space in bytes to leave on the disk
+    pub reserve: u64,
+    /// Bytes that have been stored here
+    pub used: u64,
+    /// How many ab/cd/ef folders deep files get put
+    pub shard_depth: u64,
+    pub backend: StorageBackend,
+}
+
+///
+/// How a storage root keeps the files written into it
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StorageBackend {
+    /// Each file as is, named after its hash
+    #[default]
+    Plain,
+    /// Each file zstd compressed with .zst on the end of its name
+    Zstd,
+    /// Files appended into large pack files and found through an index by hash. The same
+    /// bytes only ever get stored once per root
+    Packed,
+    /// Objects in an S3 compatible bucket keyed by their sharded hash
+    S3,
+}
+
+///
+/// Where an S3 root's bucket is and how to get into it
+///
+#[derive(Debug, Clone, PartialEq, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct S3Root {
+    /// Base url of the S3 api, ie http://127.0.0.1:9000
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    /// Goes in front of every key. Can be empty
+    pub prefix: String,
+    pub access_key: String,
+    #[serde(serialize_with = "expose_secret")]
+    pub secret_key: Secret<String>,
+    /// Folder holding local copies of objects that have been read
+    pub cache: String,
+    /// Oldest copies get dropped from the cache once it's bigger than this many bytes
+    pub cache_max: Option<u64>,
+    /// When set get_file hands out presigned urls that last this many seconds instead
+    /// of cached paths
+    pub presign_secs: Option<u64>,
+}
+
+impl S3Root {
+    ///
+    /// The location string an S3 root is known by in the storage table
+    ///
+    pub fn location(&self) -> String {
+        format!("s3://{}/{}", self.bucket, self.prefix)
+            .trim_end_matches('/')
+            .to_string()
+    }
+}
+
+///
+/// What a scrub found when it last checked a file
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ScrubStatus {
+    Ok,
+    /// On disk but the bytes don't hash to File.hash anymore
+    Bitrot,
+    Missing,
+}
+
+///
+/// Which files a scrub checks and what it does about problems
+///
+#[derive(Debug, Clone, PartialEq, Default, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ScrubOptions {
+    /// Most files to check this run. Files verified longest ago go first
+    pub limit: Option<u64>,
+    /// Skips files that were verified less than this many days ago
+    pub older_than_days: Option<u64>,
+    /// Tries to fix bitrot and missing files from the mirror or their source urls
+    pub repair: bool,
+    /// Root laid out like the storage roots to copy good files from. Falls back to the
+    /// scrubmirrorloc setting
+    pub mirror: Option<String>,
+    /// Also walks the storage roots for files the db doesn't know about
+    pub orphans: bool,
+}
+
+///
+/// What a scrub run found. Repaired files aren't in bitrot or missing
+///
+#[derive(Debug, Clone, PartialEq, Default, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScrubReport {
+    pub checked: u64,
+    pub bitrot: Vec<u64>,
+    pub missing: Vec<u64>,
+    pub repaired: Vec<u64>,
+    /// Paths in storage roots that don't belong to any file in the db
+    pub orphaned: Vec<String>,
+    /// Bytes freed by rewriting packs that removed files were still taking up
+    pub compacted: u64,
+}
+
+///
+/// Which files a storage migration moves
+///
+#[derive(Debug, Clone, PartialEq, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StorageSelection {
+    /// Files that come back from a search
+    Search(SearchObj),
+    /// Files with this extension like "png"
+    Extension(String),
+    /// Files stored at this storage id
+    StorageId(u64),
+    All,
+}
+
+///
+/// How an export puts files into its destination
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "clap", derive(ValueEnum))]
+#[cfg_attr(feature = "clap", clap(rename_all = "kebab_case"))]
+pub enum ExportMode {
+    Copy,
+    /// Falls back to copying when the file isn't kept as a plain file
+    Hardlink,
+    /// Falls back to copying when the file isn't kept as a plain file
+    Symlink,
+    /// Destination is the zip file to write
+    Zip,
+    /// Destination is the tar file to write
+    Tar,
+}
+
+///
+/// Tag files written next to each file so the folder can be imported back in
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "clap", derive(ValueEnum))]
+#[cfg_attr(feature = "clap", clap(rename_all = "kebab_case"))]
+pub enum SidecarFormat {
+    /// <file>.txt with a namespace:tag per line
+    Txt,
+    /// <file>.json holding a SidecarJson
+    Json,
+}
+
+///
+/// What to export and how
+///
+#[derive(Debug, Clone, PartialEq, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExportOptions {
+    pub search: SearchObj,
+    /// Folder to export into, or the archive to write for Zip and Tar
+    pub destination: String,
+    pub mode: ExportMode,
+    /// Path of each file under destination. {id}, {hash} and {ext} are filled in from
+    /// the file and any other {namespace} from its tags, ie {creator}/{title} - {id}.{ext}
+    pub template: String,
+    pub sidecars: Vec<SidecarFormat>,
+}
+
+///
+/// What an export did
+///
+#[derive(Debug, Clone, PartialEq, Default, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExportReport {
+    pub exported: u64,
+    /// Files that couldn't be exported and why
+    pub failed: Vec<(u64, String)>,
+    /// Files left alone because the same file was already at their path
+    pub unchanged: u64,
+    /// Files that got a numbered name because something else was already at their path
+    pub collisions: u64,
+}
+
+/// Layout version written into new JSON sidecars
+pub const SIDECAR_JSON_VERSION: u64 = 1;
+
+///
+/// A JSON sidecar. Written by exports and read back by imports. Everything but the
+/// tags can be left out
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "tags": [
+///     {"namespace": "creator", "tag": "bob"},
+///     {"namespace": "character", "tag": "alice", "parents": [{"namespace": "series", "tag": "wonderland"}]}
+///   ],
+///   "source_urls": ["https://example.com/post/1"],
+///   "timestamps": {"posted": 1700000000}
+/// }
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct SidecarJson {
+    pub version: u64,
+    pub tags: Vec<SidecarJsonTag>,
+    /// Pages the file came from
+    pub source_urls: Vec<String>,
+    /// Unix seconds keyed by what happened at that time, ie posted
+    pub timestamps: BTreeMap<String, i64>,
+}
+
+impl Default for SidecarJson {
+    fn default() -> Self {
+        SidecarJson {
+            version: SIDECAR_JSON_VERSION,
+            tags: Vec::new(),
+            source_urls: Vec::new(),
+            timestamps: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SidecarJsonTag {
+    pub namespace: String,
+    pub tag: String,
+    /// Tags this one implies. They're added as its parents
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub parents: Vec<SidecarJsonParent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SidecarJsonParent {
+    pub namespace: String,
+    pub tag: String,
+}
+
+///
+/// What a storage migration run did. Failed files stay queued for the next run
+///
+#[derive(Debug, Clone, PartialEq, Default, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StorageMigrationReport {
+    pub moved: u64,
+    /// Already where they needed to be or no longer in the db
+    pub skipped: u64,
+    pub failed: u64,
+    pub pending: u64,
 }
 
 /// Database Tags Object
@@ -202,7 +560,7 @@ pub enum LoginNeed {
     Optional,
 }
 
-#[derive(Debug, Clone, Eq, Hash, PartialEq)]
+#[derive(Debug, Clone, Eq, Hash, PartialEq, bitcode::Encode, bitcode::Decode)]
 pub enum StoredInfo {
     Storage(Vec<(String, String)>),
 }
@@ -210,7 +568,7 @@ pub enum StoredInfo {
 ///
 /// Info for scrapers as apart of the Global merge
 ///
-#[derive(Debug, Clone, Eq, Hash, PartialEq, Default)]
+#[derive(Debug, Clone, Eq, Hash, PartialEq, Default, bitcode::Encode, bitcode::Decode)]
 pub struct ScraperInfo {
     /// Ratelimit for this site
     pub ratelimit: (u64, std::time::Duration),
@@ -255,7 +613,7 @@ pub enum ModifierTarget {
 ///
 /// Info for plugins as apart of the Global merge
 ///
-#[derive(Debug, Clone, Eq, Hash, PartialEq)]
+#[derive(Debug, Clone, Eq, Hash, PartialEq, bitcode::Encode, bitcode::Decode)]
 pub struct PluginInfo2 {
     pub com_channel: bool,
     // If this redirect tag exists as a site then we direct any processed data to the specified
@@ -267,14 +625,14 @@ pub struct PluginInfo2 {
 ///
 /// Used to hold plugin or scraper data.
 ///
-#[derive(Debug, Clone, Eq, Hash, PartialEq)]
+#[derive(Debug, Clone, Eq, Hash, PartialEq, bitcode::Encode, bitcode::Decode)]
 pub enum ScraperOrPlugin {
     Scraper(ScraperInfo),
     Plugin(PluginInfo2),
 }
 
 /// A conjoined twin of scrapers and plugins
-#[derive(Debug, Clone, Eq, Hash, PartialEq, Default)]
+#[derive(Debug, Clone, Eq, Hash, PartialEq, Default, bitcode::Encode, bitcode::Decode)]
 pub struct GlobalPluginScraper {
     /// Name of the site (human readable plz)
     pub name: String,
@@ -317,7 +675,7 @@ pub fn return_default_globalpluginparser() -> GlobalPluginScraper {
 }
 
 /// Determines how to run a function
-#[derive(Debug, Clone, Eq, Hash, PartialEq)]
+#[derive(Debug, Clone, Eq, Hash, PartialEq, bitcode::Encode, bitcode::Decode)]
 #[cfg_attr(feature = "clap", derive(EnumIter, Display))]
 pub enum StartupThreadType {
     // Runs plugin and waits until it finished
@@ -380,7 +738,7 @@ pub fn stringto_commit_type(into: &String) -> CommitType {
 
 /// Dummy Holder Dummy thick
 #[allow(dead_code)]
-#[derive(Debug, PartialEq, bitcode::Encode, bitcode::Decode)]
+#[derive(Debug, PartialEq, Clone, bitcode::Encode, bitcode::Decode)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "clap", derive(EnumIter))]
 pub enum SearchHolder {
@@ -393,7 +751,7 @@ pub enum SearchHolder {
 /// & 1 are an AND search then the 4 search items are AND searched in db in
 /// addition to the 4 terms in the searches
 #[allow(dead_code)]
-#[derive(Debug, PartialEq, bitcode::Encode, bitcode::Decode)]
+#[derive(Debug, PartialEq, Clone, bitcode::Encode, bitcode::Decode)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SearchObj {
     pub search_relate: Option<Vec<SearchHolder>>,
@@ -440,7 +798,7 @@ pub enum ScraperType {
     Automatic,
 }
 
-#[derive(Debug, PartialEq, Default)]
+#[derive(Debug, PartialEq, Default, bitcode::Encode, bitcode::Decode)]
 #[allow(dead_code)]
 pub enum ScraperReturn {
     // Valid data from the system
@@ -461,14 +819,14 @@ pub enum ScraperReturn {
 ///
 /// Kinda stupid. Will see if I need this in the future
 ///
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, bitcode::Encode, bitcode::Decode)]
 pub enum ScraperFlags {
     Redo,
 }
 
 /// What the scraper passes between loaded 3rd party scrapers and the internal
 /// scrpaer.
-#[derive(Debug, PartialEq, Default, Clone)]
+#[derive(Debug, PartialEq, Default, Clone, bitcode::Encode, bitcode::Decode)]
 pub struct ScraperObject {
     pub files: HashSet<FileObject>,
     pub tags: HashSet<TagObject>,
@@ -497,11 +855,13 @@ pub struct ScraperDataReturn {
 
 /// Defines what we need to reimport a file to derive a source URL. Currently only
 /// support hash.
+#[derive(bitcode::Encode, bitcode::Decode)]
 pub struct ScraperFileRegen {
     pub hash: HashesSupported,
 }
 
 /// Input for the scraper to parse the info from the system.
+#[derive(bitcode::Encode, bitcode::Decode)]
 pub struct ScraperFileInput {
     pub hash: Option<String>,
     pub ext: Option<String>,
@@ -706,6 +1066,47 @@ pub struct DbSettingObj {
     pub param: Option<String>,
 }
 
+/// A value a plugin keeps in its own storage
+#[derive(Debug, Clone, PartialEq, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PluginStorageValue {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+/// A single change inside of a plugin storage batch
+#[derive(Debug, Clone, PartialEq, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PluginStorageOp {
+    Put(String, PluginStorageValue),
+    Delete(String),
+}
+
+/// What to pull out of each element an html query matches
+#[derive(Debug, Clone, PartialEq, Eq, Hash, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum HtmlExtract {
+    /// Text inside of the element with whitespace collapsed
+    Text,
+    /// The element's outer html
+    Html,
+    Attr(String),
+    /// An attribute holding a url. Resolved against the page's url
+    Link(String),
+}
+
+/// A CSS selector, or an XPath starting with / that can be written as one
+#[derive(Debug, Clone, PartialEq, Eq, Hash, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HtmlQuery {
+    pub selector: String,
+    pub extract: HtmlExtract,
+}
+
 /// Database search object
 #[derive(Debug)]
 pub struct DbSearchObject {
@@ -730,14 +1131,14 @@ pub struct DbSearchQuery {
 }
 
 /// Database Relationship For Plugin passing
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, bitcode::Encode, bitcode::Decode)]
 pub struct DbPluginRelationshipObj {
     pub file_hash: String,
     pub tag_name: String,
     pub tag_namespace: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, bitcode::Encode, bitcode::Decode)]
 pub enum DBPluginOutputEnum {
     Add(Vec<DBPluginOutput>),
     Del(Vec<DBPluginOutput>),
@@ -762,9 +1163,10 @@ pub struct DbPluginParentsObj {
 ///
 /// Defines actions we can do on import
 ///
-#[cfg(feature = "clap")]
-#[derive(Debug, clap::Parser, Clone, Copy, ValueEnum)]
-#[clap(rename_all = "kebab_case")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "clap", derive(clap::Parser, ValueEnum))]
+#[cfg_attr(feature = "clap", clap(rename_all = "kebab_case"))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum FileAction {
     ///Copies the file
     Copy,
@@ -774,8 +1176,27 @@ pub enum FileAction {
     HardLink,
 }
 
+///
+/// A folder that gets imported from whenever files show up in it
+///
+#[derive(Debug, Clone, PartialEq, bitcode::Encode, bitcode::Decode)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WatchFolder {
+    pub id: u64,
+    pub path: String,
+    /// What happens to a file once it's in the db
+    pub file_action: FileAction,
+    pub recursive: bool,
+    /// Seconds a file has to go without changing before it gets imported
+    pub settle_secs: u64,
+    /// Globs of files to leave alone, ie *.part. Matched against the path inside the folder
+    pub ignore: Vec<String>,
+    /// Added to every file imported from here
+    pub tags: Vec<Tag>,
+}
+
 /// Plugin output for the passed object
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, bitcode::Encode, bitcode::Decode)]
 pub struct DBPluginOutput {
     // Adds a tag to DB
     pub tag: Vec<TagObject>,
@@ -891,7 +1312,7 @@ pub enum SourceOrUrl {
 /// String just checks that a string exists in the tag and it runs the callback
 /// Regex searches the tag via regex searching
 ///
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, bitcode::Encode, bitcode::Decode)]
 pub enum SearchType {
     String(String),
     Regex(String),
@@ -1062,7 +1483,7 @@ pub enum PluginCallback {
     Tag(Vec<(Option<SearchType>, Option<String>, Option<String>)>),
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, bitcode::Encode, bitcode::Decode)]
 pub enum GlobalCallbacks {
     // Ran when a file is downloaded
     Download,
@@ -1100,7 +1521,7 @@ pub enum ParentsType {
 }
 
 /// Callback info for live plugins
-#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+#[derive(Debug, PartialEq, Clone, Eq, Hash, bitcode::Encode, bitcode::Decode)]
 pub struct CallbackInfo {
     // Name of plugin's function
     pub func: String,
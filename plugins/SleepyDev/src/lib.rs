@@ -29,3 +29,8 @@ pub fn on_start() {
         thread::sleep(wait);
     }
 }
+
+sharedtypes::plugin_export! {
+    get_global_info();
+    on_start();
+}
@@ -1,3 +1,4 @@
+use scraper_sdk::client;
 use std::time::Duration;
 
 #[macro_export]
@@ -7,6 +8,14 @@ macro_rules! vec_of_strings {
 
 static PLUGIN_NAME: &str = "File Downloader";
 
+///
+/// Extensions we download straight away. Anything else is treated as a page
+///
+const MEDIA_EXTENSIONS: [&str; 14] = [
+    "jpg", "jpeg", "png", "gif", "webp", "avif", "bmp", "mp4", "webm", "mkv", "mov", "mp3", "ogg",
+    "flac",
+];
+
 pub const REGEX_COLLECTIONS: &str = r"(http(s)?://www.|((www.|http(s)?://)))[a-zA-Z0-9-].[a-zA-Z0-9-_.]*/[a-zA-Z0-9/_%-]+\.[a-zA-Z0-9/_%\.?=&-]+";
 
 #[no_mangle]
@@ -83,25 +92,78 @@ pub fn on_regex_match(
     };
     let _ratelimit = (1, Duration::from_secs(1));
 
-    let job = sharedtypes::DbJobsObj {
-        site: "direct download".to_string(),
-        param: vec![sharedtypes::ScraperParam::Url(regex_match.into())],
-        jobmanager: sharedtypes::DbJobsManager {
-            jobtype: sharedtypes::DbJobType::FileUrl,
-            recreation: None,
-        },
-        ..Default::default()
-    };
+    let mut urls = Vec::new();
+    if !is_media(regex_match) {
+        urls = embedded_media(regex_match);
+    }
+    // Pages without og tags still get a go as a file
+    if urls.is_empty() {
+        urls.push(regex_match.to_string());
+    }
+    let jobs = urls
+        .into_iter()
+        .map(|url| sharedtypes::DbJobsObj {
+            site: "direct download".to_string(),
+            param: vec![sharedtypes::ScraperParam::Url(url)],
+            jobmanager: sharedtypes::DbJobsManager {
+                jobtype: sharedtypes::DbJobType::FileUrl,
+                recreation: None,
+            },
+            ..Default::default()
+        })
+        .collect();
 
     out.push(sharedtypes::DBPluginOutputEnum::Add(vec![
         sharedtypes::DBPluginOutput {
             tag: vec![],
             setting: vec![],
             relationship: vec![],
-            jobs: vec![job],
+            jobs,
             file: vec![],
         },
     ]));
 
     out
 }
+
+///
+/// Checks the last path segment for a media extension. Query strings don't count
+///
+fn is_media(url: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.rsplit('/')
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .is_some_and(|(_, ext)| MEDIA_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+///
+/// Asks the host to load the page and pull out what its og tags point at.
+/// Videos come first since pages with a video usually have a thumbnail as the image
+///
+fn embedded_media(url: &str) -> Vec<String> {
+    let queries = ["og:video", "og:image"]
+        .map(|property| sharedtypes::HtmlQuery {
+            selector: format!("meta[property='{}']", property),
+            extract: sharedtypes::HtmlExtract::Link("content".to_string()),
+        })
+        .to_vec();
+    let Ok(results) = client::html_fetch_select(url.to_string(), queries) else {
+        return Vec::new();
+    };
+    results
+        .into_iter()
+        .flatten()
+        .find(|found| !found.is_empty())
+        .unwrap_or_default()
+}
+
+sharedtypes::plugin_export! {
+    get_global_info();
+    on_regex_match(
+        tag_name: String,
+        tag_namespace: sharedtypes::GenericNamespaceObj,
+        regex_match: String,
+        callback: Option<sharedtypes::SearchType>,
+    );
+}
@@ -418,3 +418,15 @@ fn hash_file(hashtype: &Supset, byte: &[u8]) -> Option<String> {
         }
     }
 }
+
+sharedtypes::plugin_export! {
+    get_global_info();
+    on_start();
+    on_import(byte_c: Vec<u8>, hash_in: String);
+    on_download(
+        byte_c: Vec<u8>,
+        hash_in: String,
+        ext_in: String,
+        api_info: sharedtypes::ClientAPIInfo,
+    );
+}
@@ -776,3 +776,15 @@ pub fn on_download(
     }
     output
 }
+
+// The thumbnail callbacks still go through their legacy symbols
+sharedtypes::plugin_export! {
+    get_global_info();
+    on_start();
+    on_download(
+        byte_c: Vec<u8>,
+        hash_in: String,
+        ext_in: String,
+        api_info: sharedtypes::ClientAPIInfo,
+    );
+}
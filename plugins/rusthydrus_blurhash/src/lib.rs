@@ -172,6 +172,7 @@ pub fn on_download(
     byte_c: &[u8],
     hash_in: &String,
     ext_in: &String,
+    _api_info: &sharedtypes::ClientAPIInfo,
 ) -> Vec<sharedtypes::DBPluginOutputEnum> {
     let mut output = Vec::new();
 
@@ -216,3 +217,14 @@ pub fn on_download(
     }
     output
 }
+
+sharedtypes::plugin_export! {
+    get_global_info();
+    on_start();
+    on_download(
+        byte_c: Vec<u8>,
+        hash_in: String,
+        ext_in: String,
+        api_info: sharedtypes::ClientAPIInfo,
+    );
+}
@@ -733,3 +733,18 @@ pub fn parser(
 pub fn scraper_download_get() -> bool {
     false
 }
+
+sharedtypes::plugin_export! {
+    get_global_info();
+    on_start(site_struct: sharedtypes::GlobalPluginScraper);
+    overall_ordering(input: sharedtypes::CallbackInfoInput);
+    url_dump(
+        params: Vec<sharedtypes::ScraperParam>,
+        scraperdata: sharedtypes::ScraperDataReturn,
+    );
+    parser(
+        html_input: String,
+        source_url: String,
+        scraperdata: sharedtypes::ScraperDataReturn,
+    );
+}
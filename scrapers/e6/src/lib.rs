@@ -1531,3 +1531,19 @@ pub fn on_start(site_struct: &sharedtypes::GlobalPluginScraper) {
         client::log_no_print("E6-Scraper: Finished processing migrations".to_string());
     }
 }
+
+sharedtypes::plugin_export! {
+    get_global_info();
+    on_start(site_struct: sharedtypes::GlobalPluginScraper);
+    db_upgrade_call(db_version: u64, site_struct: sharedtypes::GlobalPluginScraper);
+    scraper_file_regen();
+    url_dump(
+        params: Vec<sharedtypes::ScraperParam>,
+        scraperdata: sharedtypes::ScraperDataReturn,
+    );
+    parser(
+        html_input: String,
+        source_url: String,
+        scraperdata: sharedtypes::ScraperDataReturn,
+    );
+}
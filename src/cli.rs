@@ -107,7 +107,594 @@ fn parse_string_to_scraperparam(input: &str) -> Vec<sharedtypes::ScraperParam> {
     out
 }
 
+/// Returns if we should run headless without the TUI.
+pub fn daemon_mode() -> bool {
+    cli_structs::MainWrapper::parse().daemon
+}
+
+/// Returns if plugins should run in their own worker processes.
+pub fn plugin_isolation() -> bool {
+    cli_structs::MainWrapper::parse().plugin_isolation
+}
+
+/// Returns if libraries without the stable plugin ABI can be loaded.
+pub fn allow_legacy_plugins() -> bool {
+    cli_structs::MainWrapper::parse().allow_legacy_plugins
+}
+
+/// Returns the library and socket name if we were started as a plugin worker.
+pub fn plugin_worker() -> Option<(std::path::PathBuf, String)> {
+    let args = cli_structs::MainWrapper::parse();
+    Some((args.plugin_worker?, args.plugin_socket?))
+}
+
+/// Returns how the logger should be setup from the cli flags.
+pub fn log_config() -> logging::LogConfig {
+    let args = cli_structs::MainWrapper::parse();
+    let mut config = logging::LogConfig {
+        json: args.log_json,
+        rotate_mb: args.log_rotate_mb,
+        keep: args.log_keep,
+        ..Default::default()
+    };
+    match logging::level_parse(&args.log_level) {
+        Some(level) => config.level = level,
+        None => eprintln!(
+            "Unknown log level: {} using: {}",
+            args.log_level, config.level
+        ),
+    }
+    config
+}
+
+/// Runs a scraper's recorded fixtures and prints what passed
+fn scraper_fixture(fixture: &cli_structs::ScraperFixture) {
+    let runner = match crate::scrapertest::FixtureRunner::new(&fixture.library) {
+        Ok(runner) => runner,
+        Err(err) => {
+            logging::error_log(err);
+            std::process::exit(1);
+        }
+    };
+    let mode = crate::scrapertest::FixtureMode {
+        update: fixture.update_snapshots,
+        record: fixture.record,
+    };
+    let results = runner.run_all(&fixture.path, mode);
+    let mut failed = 0;
+    for (case, outcome) in results.iter() {
+        match outcome {
+            crate::scrapertest::FixtureOutcome::Passed => println!("ok      {}", case.display()),
+            crate::scrapertest::FixtureOutcome::Updated => {
+                println!("updated {}", case.display())
+            }
+            crate::scrapertest::FixtureOutcome::Failed(err) => {
+                failed += 1;
+                println!("FAILED  {}\n{}", case.display(), err);
+            }
+        }
+    }
+    println!("{} fixtures, {} failed", results.len(), failed);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Runs the plugin registry commands
+fn plugin_cli(data: &Main, plugin: &cli_structs::PluginStruct) {
+    let found = match plugin {
+        cli_structs::PluginStruct::List => {
+            for entry in data.plugin_registry_list() {
+                println!(
+                    "{} order: {} enabled: {} version: {} names: {:?} sha256: {} pinned: {}",
+                    entry.file,
+                    entry.load_order,
+                    entry.enabled,
+                    entry.version,
+                    entry.names,
+                    entry.sha256,
+                    entry.pinned_sha256.is_some()
+                );
+            }
+            println!(
+                "Signatures required: {} Trusted keys: {:?}",
+                data.plugin_require_signature_get(),
+                data.plugin_trusted_keys_get()
+            );
+            true
+        }
+        cli_structs::PluginStruct::Enable(plugin) => {
+            data.plugin_registry_enabled_set(&plugin.file, true)
+        }
+        cli_structs::PluginStruct::Disable(plugin) => {
+            data.plugin_registry_enabled_set(&plugin.file, false)
+        }
+        cli_structs::PluginStruct::Pin(plugin) => data.plugin_registry_pin_set(&plugin.file, true),
+        cli_structs::PluginStruct::Unpin(plugin) => {
+            data.plugin_registry_pin_set(&plugin.file, false)
+        }
+        cli_structs::PluginStruct::Order(plugin) => {
+            data.plugin_registry_order_set(&plugin.file, plugin.order)
+        }
+        cli_structs::PluginStruct::Forget(plugin) => data.plugin_registry_remove(&plugin.file),
+        cli_structs::PluginStruct::Uninstall(plugin) => data.plugin_uninstall(&plugin.file),
+        cli_structs::PluginStruct::Trust(key) => {
+            if let Err(err) = crate::pluginverify::key_parse(&key.key) {
+                logging::error_log(format!("Cannot trust key: {}", err));
+                return;
+            }
+            let mut keys = data.plugin_trusted_keys_get();
+            if !keys.contains(&key.key) {
+                keys.push(key.key.clone());
+                data.plugin_trusted_keys_set(&keys);
+            }
+            true
+        }
+        cli_structs::PluginStruct::Untrust(key) => {
+            let mut keys = data.plugin_trusted_keys_get();
+            keys.retain(|trusted| trusted != &key.key);
+            data.plugin_trusted_keys_set(&keys);
+            true
+        }
+        cli_structs::PluginStruct::RequireSignature(require) => {
+            data.plugin_require_signature_set(require.required);
+            true
+        }
+    };
+    if !found {
+        logging::error_log(
+            "No plugin or scraper with that file name has been loaded yet. See plugin list.",
+        );
+    }
+}
+
+/// Runs the enclave commands
+fn enclave_cli(data: &Main, enclave: &cli_structs::EnclaveStruct) {
+    let out = match enclave {
+        cli_structs::EnclaveStruct::List => {
+            for info in data.enclave_list() {
+                println!(
+                    "{} id: {} priority: {}{}",
+                    info.name,
+                    info.id,
+                    info.priority,
+                    if info.builtin { " builtin" } else { "" }
+                );
+                for (position, step) in info.steps.iter().enumerate() {
+                    println!(
+                        "  {}: if {:?} then {:?} else {:?}",
+                        position, step.condition, step.action, step.failed_action
+                    );
+                }
+            }
+            Ok(())
+        }
+        cli_structs::EnclaveStruct::Create(create) => data
+            .enclave_create(&create.name, &create.priority)
+            .map(|_| ()),
+        cli_structs::EnclaveStruct::Rename(rename) => {
+            data.enclave_rename(&rename.name, &rename.new_name)
+        }
+        cli_structs::EnclaveStruct::Delete(delete) => data.enclave_delete(&delete.name),
+        cli_structs::EnclaveStruct::Priority(priority) => {
+            data.enclave_priority_set(&priority.name, &priority.priority)
+        }
+        cli_structs::EnclaveStruct::StepAdd(add) => enclave_step_parse(add)
+            .and_then(|step| data.enclave_step_add(&add.name, &step, &add.position)),
+        cli_structs::EnclaveStruct::StepRemove(remove) => {
+            data.enclave_step_remove(&remove.name, &remove.position)
+        }
+        cli_structs::EnclaveStruct::StepMove(step) => {
+            data.enclave_step_move(&step.name, &step.from, &step.to)
+        }
+        cli_structs::EnclaveStruct::DryRun(dry_run) => enclave_dry_run_cli(data, dry_run),
+    };
+    if let Err(err) = out {
+        logging::error_log(format!("Enclave: {}", err));
+    }
+}
+
+/// Runs the storage root commands
+fn storage_cli(data: &Main, storage: &cli_structs::StorageStruct) {
+    match storage {
+        cli_structs::StorageStruct::Roots => {
+            for root in data.storage_roots_list() {
+                println!(
+                    "{} id: {} used: {} capacity: {} reserve: {} backend: {:?}",
+                    root.location,
+                    root.storage_id,
+                    root.used,
+                    root.capacity
+                        .map(|capacity| capacity.to_string())
+                        .unwrap_or("none".to_string()),
+                    root.reserve,
+                    root.backend
+                );
+            }
+        }
+        cli_structs::StorageStruct::RootSet(set) => {
+            let storage_id = data.storage_root_set(&set.location, &set.capacity, &set.reserve);
+            println!("{} is storage id {}", set.location, storage_id);
+        }
+        cli_structs::StorageStruct::RootRemove(remove) => {
+            if !data.storage_root_remove(&remove.location) {
+                logging::error_log(format!(
+                    "Storage: {} is not a storage root",
+                    remove.location
+                ));
+            }
+        }
+        cli_structs::StorageStruct::Backend(set) => {
+            let storage_id = data.storage_backend_set(&set.location, &storage_backend(set.backend));
+            println!("{} is storage id {}", set.location, storage_id);
+        }
+        cli_structs::StorageStruct::S3Set(set) => {
+            let root = sharedtypes::S3Root {
+                endpoint: set.endpoint.clone(),
+                region: set.region.clone(),
+                bucket: set.bucket.clone(),
+                prefix: set.prefix.clone(),
+                access_key: set.access_key.clone(),
+                secret_key: set.secret_key.clone().into(),
+                cache: set.cache.clone(),
+                cache_max: set.cache_max,
+                presign_secs: set.presign_secs,
+            };
+            let storage_id = data.storage_s3_set(&root);
+            println!("{} is storage id {}", root.location(), storage_id);
+        }
+        cli_structs::StorageStruct::Migrate(migrate) => {
+            match storage_selection_parse(data, migrate).and_then(|selection| {
+                data.storage_migrate_queue(
+                    &selection,
+                    &migrate.location,
+                    &migrate.shard_depth,
+                    &migrate.backend.map(storage_backend),
+                )
+            }) {
+                Ok(queued) => {
+                    println!("Queued {} files to move into {}", queued, migrate.location);
+                    storage_migrate_cli(data);
+                }
+                Err(err) => logging::error_log(format!("Storage: {}", err)),
+            }
+        }
+        cli_structs::StorageStruct::MigrateResume => storage_migrate_cli(data),
+    }
+}
+
+/// Runs the watch folder commands
+fn watch_cli(data: &Main, watch: &cli_structs::WatchStruct) {
+    match watch {
+        cli_structs::WatchStruct::List => {
+            for folder in data.watch_folders_list() {
+                println!(
+                    "{} id: {} action: {:?} recursive: {} settle: {}s ignore: {:?} tags: {}",
+                    folder.path,
+                    folder.id,
+                    folder.file_action,
+                    folder.recursive,
+                    folder.settle_secs,
+                    folder.ignore,
+                    folder
+                        .tags
+                        .iter()
+                        .map(|tag| format!("{}:{}", tag.namespace.name, tag.tag))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                );
+            }
+        }
+        cli_structs::WatchStruct::Add(add) => {
+            if !Path::new(&add.path).is_dir() {
+                logging::error_log(format!("Watch: {} is not a folder", add.path));
+                return;
+            }
+            let tags = add
+                .tags
+                .iter()
+                .map(|tag| {
+                    let (namespace, tag) = tag
+                        .split_once(':')
+                        .ok_or_else(|| format!("{} needs to be namespace:tag", tag))?;
+                    Ok(sharedtypes::Tag {
+                        tag: tag.to_string(),
+                        namespace: sharedtypes::GenericNamespaceObj {
+                            name: namespace.to_string(),
+                            description: None,
+                        },
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>();
+            let tags = match tags {
+                Ok(tags) => tags,
+                Err(err) => {
+                    logging::error_log(format!("Watch: {}", err));
+                    return;
+                }
+            };
+            let id = data.watch_folder_set(&sharedtypes::WatchFolder {
+                id: 0,
+                path: add.path.clone(),
+                file_action: add.file_action,
+                recursive: !add.no_recursive,
+                settle_secs: add.settle_secs,
+                ignore: add.ignore.clone(),
+                tags,
+            });
+            println!("Watching {} as id {}", add.path, id);
+        }
+        cli_structs::WatchStruct::Remove(remove) => {
+            if !data.watch_folder_remove(&remove.path) {
+                logging::error_log(format!("Watch: {} is not a watch folder", remove.path));
+            }
+        }
+    }
+}
+
+fn storage_backend(backend: cli_structs::StorageBackendType) -> sharedtypes::StorageBackend {
+    match backend {
+        cli_structs::StorageBackendType::Plain => sharedtypes::StorageBackend::Plain,
+        cli_structs::StorageBackendType::Zstd => sharedtypes::StorageBackend::Zstd,
+        cli_structs::StorageBackendType::Packed => sharedtypes::StorageBackend::Packed,
+    }
+}
+
+/// Runs a scrub and prints what it found
+fn scrub_cli(data: &Main, scrub: &cli_structs::ScrubStruct) {
+    let report = data.file_scrub(&sharedtypes::ScrubOptions {
+        limit: scrub.limit,
+        older_than_days: scrub.older_than_days,
+        repair: scrub.repair,
+        mirror: scrub.mirror.clone(),
+        orphans: scrub.orphans,
+    });
+    for file_id in report.bitrot.iter() {
+        println!("Bitrot: {}", file_id);
+    }
+    for file_id in report.missing.iter() {
+        println!("Missing: {}", file_id);
+    }
+    for path in report.orphaned.iter() {
+        println!("Orphaned: {}", path);
+    }
+    println!(
+        "Checked {} files. {} bitrot, {} missing, {} repaired, {} orphaned, {} bytes compacted",
+        report.checked,
+        report.bitrot.len(),
+        report.missing.len(),
+        report.repaired.len(),
+        report.orphaned.len(),
+        report.compacted
+    );
+}
+
+fn storage_selection_parse(
+    data: &Main,
+    migrate: &cli_structs::StorageMigrateStruct,
+) -> Result<sharedtypes::StorageSelection, String> {
+    if migrate.all {
+        return Ok(sharedtypes::StorageSelection::All);
+    }
+    if let Some(storage_id) = migrate.storage_id {
+        return Ok(sharedtypes::StorageSelection::StorageId(storage_id));
+    }
+    if let Some(extension) = &migrate.extension {
+        return Ok(sharedtypes::StorageSelection::Extension(extension.clone()));
+    }
+    if migrate.tags.is_empty() {
+        if migrate.shard_depth.is_some() || migrate.backend.is_some() {
+            // Only changing the layout so the files already in location are the selection
+            return Ok(sharedtypes::StorageSelection::StorageId(
+                data.storage_put(&migrate.location),
+            ));
+        }
+        return Err("Pick files with --tag, --extension, --storage-id or --all".to_string());
+    }
+    Ok(sharedtypes::StorageSelection::Search(search_from_tags(
+        data,
+        &migrate.tags,
+    )?))
+}
+
+///
+/// Search for files that have every one of tags, given as namespace:tag
+///
+fn search_from_tags(data: &Main, tags: &[String]) -> Result<sharedtypes::SearchObj, String> {
+    let tag_ids = tags
+        .iter()
+        .map(|tag| {
+            let (namespace, tag) = tag
+                .split_once(':')
+                .ok_or_else(|| format!("{} needs to be namespace:tag", tag))?;
+            data.namespace_get(&namespace.to_string())
+                .and_then(|namespace_id| data.tag_get_name(tag.to_string(), namespace_id))
+                .ok_or_else(|| format!("{}:{} isn't in the db", namespace, tag))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(sharedtypes::SearchObj {
+        search_relate: None,
+        searches: vec![sharedtypes::SearchHolder::And(tag_ids)],
+    })
+}
+
+fn export_cli(data: &Main, export: &cli_structs::ExportStruct) {
+    let search = match search_from_tags(data, &export.tags) {
+        Ok(search) => search,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+    let options = sharedtypes::ExportOptions {
+        search,
+        destination: export.destination.clone(),
+        mode: export.mode,
+        template: export.template.clone(),
+        sidecars: export.sidecars.clone(),
+    };
+    match data.export_files(&options) {
+        Ok(report) => {
+            println!(
+                "Exported {} files to {}. {} already there, {} renamed to get around existing files",
+                report.exported, export.destination, report.unchanged, report.collisions
+            );
+            for (file_id, err) in report.failed.iter() {
+                println!("File {} {}", file_id, err);
+            }
+        }
+        Err(err) => println!("Export failed: {}", err),
+    }
+}
+
+fn storage_migrate_cli(data: &Main) {
+    let report = data.storage_migrate_run();
+    println!(
+        "Moved {} files, skipped {}, {} failed. {} still queued",
+        report.moved, report.skipped, report.failed, report.pending
+    );
+    if report.pending > 0 {
+        println!("Run storage migrate-resume to retry the ones left");
+    }
+}
+
+fn enclave_step_parse(
+    add: &cli_structs::EnclaveStepAddStruct,
+) -> Result<sharedtypes::EnclaveStep, String> {
+    let failed_action = match &add.failed_action {
+        Some(failed_action) => Some(
+            serde_json::from_str(failed_action)
+                .map_err(|err| format!("Bad failed action {}: {}", failed_action, err))?,
+        ),
+        None => None,
+    };
+    Ok(sharedtypes::EnclaveStep {
+        condition: serde_json::from_str(&add.condition)
+            .map_err(|err| format!("Bad condition {}: {}", add.condition, err))?,
+        action: serde_json::from_str(&add.action)
+            .map_err(|err| format!("Bad action {}: {}", add.action, err))?,
+        failed_action,
+    })
+}
+
+fn enclave_dry_run_cli(
+    data: &Main,
+    dry_run: &cli_structs::EnclaveDryRunStruct,
+) -> Result<(), String> {
+    let bytes = std::fs::read(&dry_run.file)
+        .map_err(|err| format!("Cannot read {}: {}", dry_run.file.display(), err))?;
+    let tags = dry_run
+        .tags
+        .iter()
+        .map(|tag| {
+            let (namespace, tag) = tag
+                .split_once(':')
+                .ok_or_else(|| format!("{} needs to be namespace:tag", tag))?;
+            Ok(sharedtypes::Tag {
+                tag: tag.to_string(),
+                namespace: sharedtypes::GenericNamespaceObj {
+                    name: namespace.to_string(),
+                    description: None,
+                },
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let steps = data.enclave_dry_run(&bytes, &tags, &dry_run.source_url, &dry_run.scraper);
+    for step in steps.iter() {
+        println!(
+            "{} step {}: {} -> {}",
+            step.enclave,
+            step.position,
+            if step.passed { "passed" } else { "failed" },
+            match &step.action {
+                Some(action) => match &step.location {
+                    Some(location) => format!("{:?} (stops, stored at {})", action, location),
+                    None => format!("{:?}", action),
+                },
+                None => "nothing".to_string(),
+            }
+        );
+    }
+    if !steps.iter().any(|step| step.stops) {
+        println!("No enclave would store this file");
+    }
+    Ok(())
+}
+
 /// Returns the main argument and parses data.
+///
+/// Sends a control command to the instance already running on the db. None means
+/// the command needs the db to itself
+///
+pub async fn remote_main(api: std::net::SocketAddr) -> Option<Result<(), String>> {
+    let args = cli_structs::MainWrapper::parse();
+    let (route, body) = match args.a.as_ref()? {
+        cli_structs::Test::Job(cli_structs::JobStruct::Pause(job)) => {
+            ("jobs_pause", Some(serde_json::json!(job.id)))
+        }
+        cli_structs::Test::Job(cli_structs::JobStruct::Resume(job)) => {
+            ("jobs_resume", Some(serde_json::json!(job.id)))
+        }
+        cli_structs::Test::Job(cli_structs::JobStruct::Cancel(job)) => {
+            ("jobs_cancel", Some(serde_json::json!(job.id)))
+        }
+        cli_structs::Test::Scraper(cli_structs::ScraperControlStruct::Pause(scraper)) => {
+            ("scraper_pause", Some(serde_json::json!(scraper.name)))
+        }
+        cli_structs::Test::Scraper(cli_structs::ScraperControlStruct::Resume(scraper)) => {
+            ("scraper_resume", Some(serde_json::json!(scraper.name)))
+        }
+        cli_structs::Test::Scraper(cli_structs::ScraperControlStruct::Cancel(scraper)) => {
+            ("scraper_cancel", Some(serde_json::json!(scraper.name)))
+        }
+        cli_structs::Test::Downloads(cli_structs::DownloadsStruct::Pause) => {
+            ("downloads_pause_set", Some(serde_json::json!(true)))
+        }
+        cli_structs::Test::Downloads(cli_structs::DownloadsStruct::Resume) => {
+            ("downloads_pause_set", Some(serde_json::json!(false)))
+        }
+        cli_structs::Test::Downloads(cli_structs::DownloadsStruct::Status) => {
+            ("downloads_pause_get", None)
+        }
+        _ => return None,
+    };
+    Some(remote_call(api, route, body).await.map(|out| {
+        if route == "downloads_pause_get" {
+            println!("Downloads paused: {}", out);
+        }
+    }))
+}
+
+///
+/// Calls one of the generated api routes on a running instance
+///
+async fn remote_call(
+    api: std::net::SocketAddr,
+    route: &str,
+    body: Option<serde_json::Value>,
+) -> Result<serde_json::Value, String> {
+    let url = format!("http://{}/main/{}", api, route);
+    let client = reqwest::Client::new();
+    let request = match body {
+        Some(body) => client
+            .post(&url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string()),
+        None => client.get(&url),
+    };
+    let response = request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| format!("Cannot reach the running instance at {}: {}", api, err))?;
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|err| format!("Bad response from the running instance: {}", err))?;
+    serde_json::from_slice(&bytes)
+        .map_err(|err| format!("Bad response from the running instance: {}", err))
+}
+
 pub fn main(data: Main) {
     //pub fn main(data: database::Main, scraper: Arc<RwLock<GlobalLoad>>) {
     let args = cli_structs::MainWrapper::parse();
@@ -176,8 +763,39 @@ pub fn main(data: Main) {
                     // remove.site.to_string(), query: remove.query.to_string(), time:
                     // remove.time.to_string(), })
                 }
+                cli_structs::JobStruct::Pause(job) => {
+                    data.load_table(&sharedtypes::LoadDBTable::Jobs);
+                    data.jobs_pause(&job.id);
+                }
+                cli_structs::JobStruct::Resume(job) => {
+                    data.load_table(&sharedtypes::LoadDBTable::Jobs);
+                    data.jobs_resume(&job.id);
+                }
+                cli_structs::JobStruct::Cancel(job) => {
+                    println!("Nothing is running so job {} has nothing to cancel", job.id);
+                }
             }
         }
+        // Scrapers only get paused or cancelled in memory so there has to be an
+        // instance running for these to do anything
+        cli_structs::Test::Scraper(_) => {
+            println!("No running instance to send the scraper command to");
+        }
+        cli_structs::Test::Downloads(downloads) => match downloads {
+            cli_structs::DownloadsStruct::Pause => {
+                data.downloads_pause_set(&true);
+            }
+            cli_structs::DownloadsStruct::Resume => {
+                data.downloads_pause_set(&false);
+            }
+            cli_structs::DownloadsStruct::Status => {
+                println!("Downloads paused: {}", data.downloads_pause_get());
+            }
+        },
+        cli_structs::Test::Plugin(plugin) => plugin_cli(&data, plugin),
+        cli_structs::Test::Enclave(enclave) => enclave_cli(&data, enclave),
+        cli_structs::Test::Storage(storage) => storage_cli(&data, storage),
+        cli_structs::Test::Watch(watch) => watch_cli(&data, watch),
         cli_structs::Test::Search(searchstruct) => match searchstruct {
             cli_structs::SearchStruct::Parent(parent) => {
                 data.load_table(&sharedtypes::LoadDBTable::Parents);
@@ -381,6 +999,7 @@ pub fn main(data: Main) {
                 cli_structs::ScraperAction::Test(inp) => {
                     dbg!(&inp);
                 }
+                cli_structs::ScraperAction::Fixture(fixture) => scraper_fixture(fixture),
             },
             cli_structs::TasksStruct::Reimport(reimp) => match reimp {
                 cli_structs::Reimport::DirectoryLocation(loc) => {
@@ -527,6 +1146,12 @@ pub fn main(data: Main) {
                             CheckFilesEnum::StorageCheck => {
                                 data.fix_storage_locations();
                             }
+                            CheckFilesEnum::Scrub(scrub) => scrub_cli(&data, scrub),
+                            CheckFilesEnum::ScrubStatus => {
+                                for (file_id, status) in data.file_scrub_problems() {
+                                    println!("{} {:?}", file_id, status);
+                                }
+                            }
                             _ => {}
                         }
                         /*   data.check_db_paths();
@@ -595,7 +1220,7 @@ pub fn main(data: Main) {
                             }
                             let client = &mut download::client_create(vec![], false);
                             if !Path::new(&lispa).exists() {
-                                logging::main(&format!("Cannot find hash: {}", &file.hash));
+                                logging::error_log(format!("Cannot find hash: {}", &file.hash));
                                 match action {
                                     cli_structs::CheckFilesEnum::Redownload => {}
                                     cli_structs::CheckFilesEnum::Print => {
@@ -769,6 +1394,7 @@ pub fn main(data: Main) {
                 }
             }
             cli_structs::TasksStruct::Csv(_csvstruct) => {}
+            cli_structs::TasksStruct::Export(export) => export_cli(&data, export),
         },
     }
 
@@ -7,6 +7,35 @@ use sharedtypes;
 pub struct MainWrapper {
     #[command(subcommand)]
     pub a: Option<Test>,
+    /// Runs without the TUI as a service. Stops cleanly on SIGTERM.
+    #[arg(long)]
+    pub daemon: bool,
+    /// Writes logs as one json object per line.
+    #[arg(long)]
+    pub log_json: bool,
+    /// Default log level. off, error, warn, info, debug or trace.
+    #[arg(long, default_value = "info")]
+    pub log_level: String,
+    /// Size in MB before the log gets rotated.
+    #[arg(long, default_value_t = 2048)]
+    pub log_rotate_mb: u64,
+    /// Number of rotated logs to keep.
+    #[arg(long, default_value_t = 2)]
+    pub log_keep: i64,
+    /// Runs plugins and scrapers in child processes. A crashing plugin only takes
+    /// down its own process. Libraries without the stable ABI are not loaded.
+    #[arg(long)]
+    pub plugin_isolation: bool,
+    /// Loads plugins and scrapers that don't export the stable plugin ABI. They have
+    /// to be built with the same toolchain and sharedtypes as this build.
+    #[arg(long)]
+    pub allow_legacy_plugins: bool,
+    /// Internal. Runs as a plugin worker for the library at this path.
+    #[arg(long, hide = true, requires = "plugin_socket")]
+    pub plugin_worker: Option<std::path::PathBuf>,
+    /// Internal. Socket name the plugin worker listens on.
+    #[arg(long, hide = true)]
+    pub plugin_socket: Option<String>,
 }
 
 #[derive(Debug, Parser)]
@@ -20,6 +49,349 @@ pub enum Test {
     /// Tasks to perform with the program.
     #[clap(subcommand)]
     Tasks(TasksStruct),
+    /// Pauses or resumes all downloads.
+    #[clap(subcommand)]
+    Downloads(DownloadsStruct),
+    /// Pauses, resumes or cancels a scraper in the running instance.
+    #[clap(subcommand)]
+    Scraper(ScraperControlStruct),
+    /// Manages which plugins and scrapers get loaded.
+    #[clap(subcommand)]
+    Plugin(PluginStruct),
+    /// Manages enclaves, the rules that decide where files go.
+    #[clap(subcommand)]
+    Enclave(EnclaveStruct),
+    /// Manages the storage roots files get downloaded into.
+    #[clap(subcommand)]
+    Storage(StorageStruct),
+    /// Manages folders that get imported from automatically.
+    #[clap(subcommand)]
+    Watch(WatchStruct),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum WatchStruct {
+    /// Lists the watch folders.
+    List,
+    /// Watches a folder or changes how it's watched.
+    Add(WatchAddStruct),
+    /// Stops watching a folder. Files in it are left alone.
+    Remove(WatchRemoveStruct),
+}
+
+#[derive(Debug, Parser)]
+pub struct WatchAddStruct {
+    #[arg(exclusive = false, required = true)]
+    pub path: String,
+    #[arg(long, value_enum, default_value = "copy")]
+    pub file_action: sharedtypes::FileAction,
+    /// Only looks at files directly inside path
+    #[arg(long)]
+    pub no_recursive: bool,
+    /// Seconds a file has to sit unchanged before it gets imported
+    #[arg(long, default_value_t = 10)]
+    pub settle_secs: u64,
+    /// Leaves files matching this glob alone, ie "*.part". Can be given more than once
+    #[arg(long)]
+    pub ignore: Vec<String>,
+    /// Adds namespace:tag to everything imported from path. Can be given more than once
+    #[arg(long = "tag")]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct WatchRemoveStruct {
+    #[arg(exclusive = false, required = true)]
+    pub path: String,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum StorageStruct {
+    /// Lists storage roots with their limits and usage.
+    Roots,
+    /// Adds a storage root or changes its limits.
+    RootSet(StorageRootSetStruct),
+    /// Stops using a folder as a storage root. Files in it are kept.
+    RootRemove(StorageRootStruct),
+    /// Changes how new files get stored in a root. Use migrate to convert what's in it.
+    Backend(StorageBackendStruct),
+    /// Adds an S3 bucket as a storage root. Its location is s3://bucket/prefix.
+    S3Set(StorageS3Struct),
+    /// Moves files into another storage root and runs the move.
+    Migrate(StorageMigrateStruct),
+    /// Carries on with a migration that got interrupted.
+    MigrateResume,
+}
+
+#[derive(Debug, Parser)]
+pub struct StorageMigrateStruct {
+    /// Folder to move the files into
+    #[arg(exclusive = false, required = true)]
+    pub location: String,
+    /// Moves files with every one of these tags as namespace:tag. Can be given more than once
+    #[arg(long = "tag", conflicts_with_all = ["extension", "storage_id", "all"])]
+    pub tags: Vec<String>,
+    /// Moves files with this extension
+    #[arg(long, conflicts_with_all = ["storage_id", "all"])]
+    pub extension: Option<String>,
+    /// Moves files stored at this storage id
+    #[arg(long, conflicts_with = "all")]
+    pub storage_id: Option<u64>,
+    /// Moves every file
+    #[arg(long)]
+    pub all: bool,
+    /// Folders deep to shard files in location. Files already in it get moved to match
+    #[arg(long)]
+    pub shard_depth: Option<u64>,
+    /// How location stores files. Files already in it get converted
+    #[arg(long, value_enum)]
+    pub backend: Option<StorageBackendType>,
+}
+
+#[derive(Debug, Parser)]
+pub struct StorageBackendStruct {
+    #[arg(exclusive = false, required = true)]
+    pub location: String,
+    #[arg(value_enum, required = true)]
+    pub backend: StorageBackendType,
+}
+
+#[derive(Debug, Parser)]
+pub struct StorageS3Struct {
+    /// Base url of the S3 api, ie http://127.0.0.1:9000
+    #[arg(exclusive = false, required = true)]
+    pub endpoint: String,
+    #[arg(exclusive = false, required = true)]
+    pub bucket: String,
+    /// Put in front of every key
+    #[arg(long, default_value = "")]
+    pub prefix: String,
+    #[arg(long, default_value = "us-east-1")]
+    pub region: String,
+    #[arg(long, required = true)]
+    pub access_key: String,
+    #[arg(long, required = true)]
+    pub secret_key: String,
+    /// Folder for local copies of files read from the bucket. Temp folder if left out
+    #[arg(long, default_value = "")]
+    pub cache: String,
+    /// Bytes the cache can grow to before old copies get dropped
+    #[arg(long)]
+    pub cache_max: Option<u64>,
+    /// Hand out presigned urls lasting this many seconds instead of cached copies
+    #[arg(long)]
+    pub presign_secs: Option<u64>,
+}
+
+/// How a storage root keeps its files on disk.
+#[derive(Debug, Copy, Eq, PartialEq, Clone, ValueEnum)]
+#[clap(rename_all = "kebab_case")]
+pub enum StorageBackendType {
+    /// Files as they are.
+    Plain,
+    /// Each file zstd compressed.
+    Zstd,
+    /// Files appended into large pack files, stored once per root.
+    Packed,
+}
+
+#[derive(Debug, Parser)]
+pub struct StorageRootSetStruct {
+    #[arg(exclusive = false, required = true)]
+    pub location: String,
+    /// Most bytes to store here. No limit when left out
+    #[arg(long)]
+    pub capacity: Option<u64>,
+    /// Bytes to keep free on the disk
+    #[arg(long, default_value_t = 0)]
+    pub reserve: u64,
+}
+
+#[derive(Debug, Parser)]
+pub struct StorageRootStruct {
+    #[arg(exclusive = false, required = true)]
+    pub location: String,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum EnclaveStruct {
+    /// Lists enclaves and their steps in the order they get tried.
+    List,
+    /// Makes an empty enclave.
+    Create(EnclavePriorityStruct),
+    /// Renames an enclave.
+    Rename(EnclaveRenameStruct),
+    /// Deletes an enclave and its steps.
+    Delete(EnclaveNameStruct),
+    /// Sets the priority of an enclave. Higher gets tried first.
+    Priority(EnclavePriorityStruct),
+    /// Adds a step to an enclave.
+    StepAdd(EnclaveStepAddStruct),
+    /// Removes a step from an enclave.
+    StepRemove(EnclaveStepStruct),
+    /// Moves a step to another position.
+    StepMove(EnclaveStepMoveStruct),
+    /// Shows which steps would fire for a file without storing it.
+    DryRun(EnclaveDryRunStruct),
+}
+
+#[derive(Debug, Parser)]
+pub struct EnclaveNameStruct {
+    #[arg(exclusive = false, required = true)]
+    pub name: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct EnclavePriorityStruct {
+    #[arg(exclusive = false, required = true)]
+    pub name: String,
+    #[arg(exclusive = false, required = true)]
+    pub priority: u64,
+}
+
+#[derive(Debug, Parser)]
+pub struct EnclaveRenameStruct {
+    #[arg(exclusive = false, required = true)]
+    pub name: String,
+    #[arg(exclusive = false, required = true)]
+    pub new_name: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct EnclaveStepAddStruct {
+    #[arg(exclusive = false, required = true)]
+    pub name: String,
+    /// Condition as json. Example: '{"FileSizeGreater":1048576}' or '"Any"'
+    #[arg(exclusive = false, required = true)]
+    pub condition: String,
+    /// Action to run when the condition passes as json. Example: '"DownloadToDefault"'
+    #[arg(exclusive = false, required = true)]
+    pub action: String,
+    /// Action to run when the condition fails as json
+    #[arg(long)]
+    pub failed_action: Option<String>,
+    /// Position to put the step at. Defaults to the end
+    #[arg(long)]
+    pub position: Option<u64>,
+}
+
+#[derive(Debug, Parser)]
+pub struct EnclaveStepStruct {
+    #[arg(exclusive = false, required = true)]
+    pub name: String,
+    #[arg(exclusive = false, required = true)]
+    pub position: u64,
+}
+
+#[derive(Debug, Parser)]
+pub struct EnclaveStepMoveStruct {
+    #[arg(exclusive = false, required = true)]
+    pub name: String,
+    #[arg(exclusive = false, required = true)]
+    pub from: u64,
+    #[arg(exclusive = false, required = true)]
+    pub to: u64,
+}
+
+#[derive(Debug, Parser)]
+pub struct EnclaveDryRunStruct {
+    /// File to test
+    #[arg(exclusive = false, required = true)]
+    pub file: std::path::PathBuf,
+    /// Tags the file would have as namespace:tag. Can be given more than once
+    #[arg(long = "tag")]
+    pub tags: Vec<String>,
+    /// Url the file would have been downloaded from
+    #[arg(long)]
+    pub source_url: Option<String>,
+    /// Name of the scraper the file would have come from
+    #[arg(long)]
+    pub scraper: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PluginStruct {
+    /// Lists every plugin and scraper that has been loaded before.
+    List,
+    /// Lets a library load again.
+    Enable(PluginFileStruct),
+    /// Stops a library from loading.
+    Disable(PluginFileStruct),
+    /// Only loads the library if it matches the hash it was last loaded with.
+    Pin(PluginFileStruct),
+    /// Removes a pin.
+    Unpin(PluginFileStruct),
+    /// Sets the load order. Lower loads first.
+    Order(PluginOrderStruct),
+    /// Forgets a library. Lets an older version load.
+    Forget(PluginFileStruct),
+    /// Deletes a library and purges everything it stored.
+    Uninstall(PluginFileStruct),
+    /// Trusts a hex ed25519 public key to sign libraries.
+    Trust(PluginKeyStruct),
+    /// Stops trusting a key.
+    Untrust(PluginKeyStruct),
+    /// Refuses libraries without a valid <library>.sig when set.
+    RequireSignature(PluginRequireStruct),
+}
+
+/// Holder of a library file name.
+#[derive(Debug, Parser)]
+pub struct PluginFileStruct {
+    /// File name of the library. Example: libe6scraper.so
+    #[arg(exclusive = false, required = true)]
+    pub file: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct PluginOrderStruct {
+    /// File name of the library.
+    #[arg(exclusive = false, required = true)]
+    pub file: String,
+    /// Position to load at
+    #[arg(exclusive = false, required = true, allow_negative_numbers = true)]
+    pub order: i64,
+}
+
+#[derive(Debug, Parser)]
+pub struct PluginKeyStruct {
+    /// Hex encoded ed25519 public key
+    #[arg(exclusive = false, required = true)]
+    pub key: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct PluginRequireStruct {
+    #[arg(exclusive = false, required = true, action = clap::ArgAction::Set)]
+    pub required: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DownloadsStruct {
+    /// Pauses all downloads. Persists across restarts.
+    Pause,
+    /// Resumes all downloads.
+    Resume,
+    /// Prints if downloads are paused.
+    Status,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ScraperControlStruct {
+    /// Stops a scraper from starting new jobs.
+    Pause(ScraperNameStruct),
+    /// Resumes a paused or cancelled scraper.
+    Resume(ScraperNameStruct),
+    /// Cancels the jobs a scraper is running and stops it from starting new ones.
+    Cancel(ScraperNameStruct),
+}
+
+/// Holder of a scraper name.
+#[derive(Debug, Parser)]
+pub struct ScraperNameStruct {
+    /// Name of the scraper
+    #[arg(exclusive = false, required = true)]
+    pub name: String,
 }
 
 #[derive(Subcommand, Debug)]
@@ -39,6 +411,27 @@ pub enum TasksStruct {
 
     /// Imports a file into the db.
     Import(Directory),
+    /// Copies, links or archives the files a search finds.
+    Export(ExportStruct),
+}
+
+#[derive(Debug, Parser)]
+pub struct ExportStruct {
+    /// Folder, or archive file for zip and tar
+    #[arg(exclusive = false, required = true)]
+    pub destination: String,
+    /// Exports files with every one of these tags as namespace:tag. Can be given more than once
+    #[arg(long = "tag", required = true)]
+    pub tags: Vec<String>,
+    #[arg(long, value_enum, default_value = "copy")]
+    pub mode: sharedtypes::ExportMode,
+    /// Names files from their tags, ie "{creator}/{title} - {id}.{ext}". {id}, {hash} and
+    /// {ext} are always there and a missing namespace comes out as unknown
+    #[arg(long, default_value = "{hash}.{ext}")]
+    pub template: String,
+    /// Writes the file's tags next to it so importing the export brings them back
+    #[arg(long = "sidecar", value_enum)]
+    pub sidecars: Vec<sharedtypes::SidecarFormat>,
 }
 
 /// Type of job in db. Will be used to confirm what the scraping logic should work.
@@ -59,6 +452,22 @@ pub enum DbJobType {
 pub enum ScraperAction {
     /// Tests a scraper
     Test(ScraperTest),
+    /// Runs a scraper against recorded responses and checks the snapshots. No network.
+    Fixture(ScraperFixture),
+}
+
+#[derive(Debug, Parser)]
+pub struct ScraperFixture {
+    /// Built scraper library or wasm component to test
+    pub library: std::path::PathBuf,
+    /// Fixture folder or a folder of them. Each one needs a case.json
+    pub path: std::path::PathBuf,
+    /// Overwrites snapshots with the current output
+    #[arg(long)]
+    pub update_snapshots: bool,
+    /// Downloads and saves any responses that aren't recorded yet
+    #[arg(long)]
+    pub record: bool,
 }
 
 #[derive(Debug, Parser)]
@@ -99,6 +508,30 @@ pub enum CheckFilesEnum {
     Redownload,
     /// Checks the storage locations of the files and fixes them
     StorageCheck,
+    /// Re-hashes files a batch at a time, oldest verified first
+    Scrub(ScrubStruct),
+    /// Lists files that had a problem on their last scrub
+    ScrubStatus,
+}
+
+#[derive(Debug, Parser)]
+pub struct ScrubStruct {
+    /// Most files to check this run
+    #[arg(long)]
+    pub limit: Option<u64>,
+    /// Skips files verified less than this many days ago
+    #[arg(long)]
+    pub older_than_days: Option<u64>,
+    /// Fixes bad or missing files from the mirror or their source urls
+    #[arg(long)]
+    pub repair: bool,
+    /// Folder with good copies laid out like a storage root. Defaults to the
+    /// scrubmirrorloc setting
+    #[arg(long)]
+    pub mirror: Option<String>,
+    /// Also lists files in storage that the db doesn't know about
+    #[arg(long)]
+    pub orphans: bool,
 }
 
 #[derive(Debug, Subcommand)]
@@ -164,6 +597,20 @@ pub enum JobStruct {
     AddBulk(JobBulkAddStruct),
     /// Removes a job from the system
     Remove(JobRemovalStruct),
+    /// Pauses a job so it won't get ran
+    Pause(JobIdStruct),
+    /// Resumes a paused job
+    Resume(JobIdStruct),
+    /// Cancels a job that's running in the running instance
+    Cancel(JobIdStruct),
+}
+
+/// Holder of a job id.
+#[derive(Debug, Parser)]
+pub struct JobIdStruct {
+    /// Id of the job
+    #[arg(exclusive = false, required = true)]
+    pub id: u64,
 }
 
 /// Adds support for bulk adding jobs
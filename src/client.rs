@@ -412,6 +412,87 @@ pub fn external_plugin_call(
     ))
 }
 
+///
+/// Gets a value out of this plugin's storage. plugin is the name from its
+/// GlobalPluginScraper. The host takes whatever name it's sent so this only keeps
+/// plugins that use their own name apart, it doesn't stop one reading another's keys
+///
+pub fn plugin_storage_get(plugin: String, key: String) -> Option<sharedtypes::PluginStorageValue> {
+    init_data_request(&types::SupportedRequests::Database(
+        types::SupportedDBRequests::PluginStorageGet(plugin, key),
+    ))
+}
+
+///
+/// Puts a value into this plugin's storage
+///
+pub fn plugin_storage_put(
+    plugin: String,
+    key: String,
+    value: sharedtypes::PluginStorageValue,
+) -> bool {
+    init_data_request(&types::SupportedRequests::Database(
+        types::SupportedDBRequests::PluginStoragePut(plugin, key, value),
+    ))
+}
+
+///
+/// Removes a key from this plugin's storage
+///
+pub fn plugin_storage_delete(plugin: String, key: String) -> bool {
+    init_data_request(&types::SupportedRequests::Database(
+        types::SupportedDBRequests::PluginStorageDelete(plugin, key),
+    ))
+}
+
+///
+/// Lists the keys and values in this plugin's storage that start with prefix
+///
+pub fn plugin_storage_list(
+    plugin: String,
+    prefix: String,
+) -> Vec<(String, sharedtypes::PluginStorageValue)> {
+    init_data_request(&types::SupportedRequests::Database(
+        types::SupportedDBRequests::PluginStorageList(plugin, prefix),
+    ))
+}
+
+///
+/// Applies puts and deletes in one transaction. Returns false if nothing was applied
+///
+pub fn plugin_storage_batch(plugin: String, ops: Vec<sharedtypes::PluginStorageOp>) -> bool {
+    init_data_request(&types::SupportedRequests::Database(
+        types::SupportedDBRequests::PluginStorageBatch(plugin, ops),
+    ))
+}
+
+///
+/// Runs css or xpath queries against a page the caller already has. base_url is
+/// what HtmlExtract::Link resolves against. One result per query
+///
+pub fn html_select(
+    body: String,
+    base_url: Option<String>,
+    queries: Vec<sharedtypes::HtmlQuery>,
+) -> Vec<Result<Vec<String>, String>> {
+    init_data_request(&types::SupportedRequests::Database(
+        types::SupportedDBRequests::HtmlSelect(body, base_url, queries),
+    ))
+}
+
+///
+/// Has the host download url and run the queries against it. Errors if the page
+/// can't be downloaded
+///
+pub fn html_fetch_select(
+    url: String,
+    queries: Vec<sharedtypes::HtmlQuery>,
+) -> Result<Vec<Result<Vec<String>, String>>, String> {
+    init_data_request(&types::SupportedRequests::Database(
+        types::SupportedDBRequests::HtmlFetchSelect(url, queries),
+    ))
+}
+
 /// This shouldn't come back to haunt me. :x Returns a Vec of bytes that represent
 /// the data structure sent from server.
 fn init_data_request<T: bitcode::Encode + for<'de> bitcode::Decode<'de>>(
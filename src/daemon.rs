@@ -0,0 +1,331 @@
+use crate::Main;
+use crate::jobs::Jobs;
+use crate::logging;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long to wait for running jobs to finish their current files on shutdown
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(120);
+
+///
+/// Lock file that stops two instances from running against the same db.
+/// Holds our PID, when we started and once it's up the address of our API.
+/// The OS lock on it is what decides who owns the db so the file never gets deleted,
+/// it's emptied when dropped.
+///
+pub struct InstanceLock {
+    path: PathBuf,
+    file: File,
+    // If the last instance didn't clean up its lock file
+    pub was_stale: bool,
+}
+
+///
+/// The live instance a lock file belongs to
+///
+#[derive(Debug, PartialEq)]
+pub struct LockHolder {
+    pub pid: u32,
+    // Lets a command hand its work to the running instance
+    pub api: Option<SocketAddr>,
+}
+
+impl InstanceLock {
+    ///
+    /// Creates a lock file next to the db.
+    /// Errors if another live instance already holds the lock.
+    ///
+    pub fn acquire(db_path: &str) -> Result<Self, String> {
+        let path = lock_path(db_path);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|err| format!("Cannot open lock file: {} {}", path.display(), err))?;
+
+        // Held until the file gets closed so it goes away with us even after a crash
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let pid = InstanceLock::holder(db_path)
+                    .map(|holder| holder.pid.to_string())
+                    .unwrap_or_else(|| "unknown".to_string());
+                return Err(format!(
+                    "Another instance with PID: {} is already using db: {}",
+                    pid, db_path
+                ));
+            }
+            Err(TryLockError::Error(err)) => {
+                return Err(format!("Cannot lock lock file: {} {}", path.display(), err));
+            }
+        }
+
+        let mut contents = String::new();
+        let _ = file.read_to_string(&mut contents);
+        let was_stale = !contents.trim().is_empty();
+        if was_stale {
+            logging::info_log(format!(
+                "Found stale lock file at: {} last instance didn't shut down cleanly.",
+                path.display()
+            ));
+        }
+
+        // Whatever is in there isn't ours. Nobody reads an empty file as a live holder
+        file.set_len(0).map_err(|err| err.to_string())?;
+        let lock = InstanceLock {
+            path,
+            file,
+            was_stale,
+        };
+        let pid = std::process::id();
+        lock.contents_set(&lock_render(pid, process_started(pid), None))?;
+        Ok(lock)
+    }
+
+    ///
+    /// Returns who holds the lock for a db if they're still running. A PID that got
+    /// reused by some other process after a crash doesn't count
+    ///
+    pub fn holder(db_path: &str) -> Option<LockHolder> {
+        let contents = std::fs::read_to_string(lock_path(db_path)).ok()?;
+        let (pid, started, api) = lock_parse(&contents)?;
+        if !process_alive(pid) {
+            return None;
+        }
+        if let Some(started) = started
+            && let Some(now) = process_started(pid)
+            && started != now
+        {
+            return None;
+        }
+        Some(LockHolder { pid, api })
+    }
+
+    ///
+    /// Records where our API is listening so other commands can find it
+    ///
+    pub fn api_set(&self, api: SocketAddr) {
+        let pid = std::process::id();
+        if let Err(err) = self.contents_set(&lock_render(pid, process_started(pid), Some(api))) {
+            logging::error_log(format!(
+                "Cannot write the api address into {}: {}",
+                self.path.display(),
+                err
+            ));
+        }
+    }
+
+    ///
+    /// Overwrites the lock in place. Readers don't take the lock so the new contents go
+    /// over the old ones in one write instead of emptying the file first. The api line
+    /// only gets added to what acquire wrote so a reader sees one or the other
+    ///
+    fn contents_set(&self, contents: &str) -> Result<(), String> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(0))
+            .and_then(|_| file.write_all(contents.as_bytes()))
+            .and_then(|_| file.set_len(contents.len() as u64))
+            .and_then(|_| file.sync_data())
+            .map_err(|err| err.to_string())
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // Deleting it would let someone lock a file nobody else can see anymore
+        let _ = self.file.set_len(0);
+    }
+}
+
+fn lock_path(db_path: &str) -> PathBuf {
+    PathBuf::from(format!("{}.lock", db_path))
+}
+
+///
+/// One value per line. Older lock files only have the PID
+///
+fn lock_render(pid: u32, started: Option<u64>, api: Option<SocketAddr>) -> String {
+    let mut out = pid.to_string();
+    out.push('\n');
+    if let Some(started) = started {
+        out.push_str(&started.to_string());
+    }
+    out.push('\n');
+    if let Some(api) = api {
+        out.push_str(&api.to_string());
+    }
+    out
+}
+
+fn lock_parse(contents: &str) -> Option<(u32, Option<u64>, Option<SocketAddr>)> {
+    let mut lines = contents.lines().map(str::trim);
+    let pid = lines.next()?.parse().ok()?;
+    let started = lines.next().and_then(|line| line.parse().ok());
+    let api = lines.next().and_then(|line| line.parse().ok());
+    Some((pid, started, api))
+}
+
+///
+/// Checks if a process is still running
+///
+#[cfg(unix)]
+pub fn process_alive(pid: u32) -> bool {
+    // Signal 0 only checks if we could signal the process
+    let ret = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    ret == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+///
+/// No cheap way to check without pulling in winapi so assume it's alive
+///
+#[cfg(not(unix))]
+pub fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+///
+/// When a process started in clock ticks since boot. Tells a PID that got reused
+/// apart from the process that wrote the lock
+///
+#[cfg(target_os = "linux")]
+fn process_started(pid: u32) -> Option<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The name in brackets can have spaces so count from after it. starttime is field 22
+    let (_, rest) = stat.rsplit_once(')')?;
+    rest.split_whitespace().nth(19)?.parse().ok()
+}
+
+///
+/// Other platforms only get the PID check
+///
+#[cfg(not(target_os = "linux"))]
+fn process_started(_pid: u32) -> Option<u64> {
+    None
+}
+
+///
+/// Waits until we get told to shut down. SIGTERM or Ctrl-C
+///
+pub async fn wait_for_shutdown() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = sigterm.recv() => {
+                        logging::info_log("Recieved SIGTERM".to_string());
+                    }
+                    _ = tokio::signal::ctrl_c() => {
+                        logging::info_log("Recieved Ctrl-C".to_string());
+                    }
+                }
+                return;
+            }
+            Err(err) => {
+                logging::error_log(format!("Cannot listen for SIGTERM: {:?}", err));
+            }
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+    logging::info_log("Recieved Ctrl-C".to_string());
+}
+
+///
+/// Stops new jobs from starting, waits for running jobs to finish their current files
+/// then flushes the db to disk.
+///
+pub async fn graceful_shutdown(jobs: &Arc<Jobs>, db: &Main) {
+    logging::info_log("Starting graceful shutdown. No new work will be started.".to_string());
+    jobs.shutdown_begin();
+
+    let start = std::time::Instant::now();
+    loop {
+        let running = jobs.jobs_running_count();
+        if running == 0 {
+            break;
+        }
+        if start.elapsed() >= SHUTDOWN_TIMEOUT {
+            logging::error_log(format!(
+                "Shutdown timed out with {} jobs still running. They will get recovered on next start.",
+                running
+            ));
+            break;
+        }
+        logging::info_log(format!("Waiting on {} running jobs to stop.", running));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    db.shutdown_flush();
+    logging::info_log("Shutdown complete".to_string());
+    log::logger().flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn lock_files_roundtrip() {
+        let api: SocketAddr = "127.0.0.1:3031".parse().unwrap();
+        assert_eq!(
+            lock_parse(&lock_render(42, Some(1234), Some(api))),
+            Some((42, Some(1234), Some(api)))
+        );
+        assert_eq!(
+            lock_parse(&lock_render(42, None, None)),
+            Some((42, None, None))
+        );
+        // Written before start times were kept
+        assert_eq!(lock_parse("42"), Some((42, None, None)));
+        assert_eq!(lock_parse("not a pid"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn reused_pids_are_stale() {
+        let dir = TempDir::new("instancelock");
+        let db_path = dir.location("main.db");
+        let pid = std::process::id();
+        let started = process_started(pid).unwrap();
+
+        std::fs::write(lock_path(&db_path), lock_render(pid, Some(started), None)).unwrap();
+        assert_eq!(
+            InstanceLock::holder(&db_path),
+            Some(LockHolder { pid, api: None })
+        );
+        std::fs::write(
+            lock_path(&db_path),
+            lock_render(pid, Some(started + 1), None),
+        )
+        .unwrap();
+        assert_eq!(InstanceLock::holder(&db_path), None);
+    }
+
+    #[test]
+    fn second_instance_is_refused() {
+        let dir = TempDir::new("instancelock-twice");
+        let db_path = dir.location("main.db");
+
+        let first = InstanceLock::acquire(&db_path).unwrap();
+        assert!(!first.was_stale);
+        first.api_set("127.0.0.1:3031".parse().unwrap());
+        assert!(InstanceLock::acquire(&db_path).is_err());
+        assert_eq!(
+            InstanceLock::holder(&db_path).and_then(|holder| holder.api),
+            Some("127.0.0.1:3031".parse().unwrap())
+        );
+
+        drop(first);
+        let second = InstanceLock::acquire(&db_path).unwrap();
+        assert!(!second.was_stale);
+        drop(second);
+    }
+}
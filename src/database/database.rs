@@ -4,11 +4,12 @@ use crate::file;
 use crate::globalload::GlobalLoad;
 use crate::helpers::check_url;
 use crate::logging;
+use crate::metrics;
 use crate::roaring_bitmap::InternalCacheType;
 use crate::roaring_bitmap::RelationshipStorage;
 use eta::{Eta, TimeAcc};
 use log::{error, info};
-use parking_lot::{Mutex, RwLock};
+use parking_lot::{Mutex, MutexGuard, RwLock};
 use r2d2::Pool;
 use r2d2::PooledConnection;
 use r2d2_sqlite::SqliteConnectionManager;
@@ -21,12 +22,14 @@ use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::net::SocketAddr;
+use std::ops::{Deref, DerefMut};
 use std::panic;
 use std::path;
 use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
+use std::time::Instant;
 
 /// I dont want to keep writing .to_string on EVERY vector of strings. Keeps me
 /// lazy. vec_of_strings["one", "two"];
@@ -57,7 +60,7 @@ pub(crate) struct Main {
     pub(super) _dbpath: Option<String>,
     pub(super) _vers: u64,
     pub(super) pool: Pool<SqliteConnectionManager>,
-    pub(in crate::database) write_conn: Arc<Mutex<PooledConnection<SqliteConnectionManager>>>,
+    pub(in crate::database) write_conn: Arc<WriteConn>,
     pub(super) write_conn_istransaction: Arc<Mutex<bool>>,
     pub(super) _active_vers: u64,
     pub(super) _inmemdb: Arc<RwLock<NewinMemDB>>,
@@ -71,6 +74,53 @@ pub(crate) struct Main {
     pub(in crate::database) relationship_roaring_storage: Option<Arc<RwLock<RelationshipStorage>>>,
 }
 
+///
+/// The single write connection. Records how long it's held as the write
+/// transaction time.
+///
+pub(crate) struct WriteConn {
+    conn: Mutex<PooledConnection<SqliteConnectionManager>>,
+}
+
+impl WriteConn {
+    pub fn new(conn: PooledConnection<SqliteConnectionManager>) -> Self {
+        WriteConn {
+            conn: Mutex::new(conn),
+        }
+    }
+
+    pub fn lock(&self) -> WriteConnGuard<'_> {
+        WriteConnGuard {
+            guard: self.conn.lock(),
+            start: Instant::now(),
+        }
+    }
+}
+
+pub(crate) struct WriteConnGuard<'a> {
+    guard: MutexGuard<'a, PooledConnection<SqliteConnectionManager>>,
+    start: Instant,
+}
+
+impl Deref for WriteConnGuard<'_> {
+    type Target = PooledConnection<SqliteConnectionManager>;
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl DerefMut for WriteConnGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+impl Drop for WriteConnGuard<'_> {
+    fn drop(&mut self) {
+        metrics::db_write_lock_record(self.start.elapsed());
+    }
+}
+
 /// Handles transactional pushes.
 fn transaction_execute(trans: &Transaction, inp: String) {
     trans.execute(&inp, params![]).unwrap();
@@ -115,7 +165,7 @@ impl Main {
                 let memdb = Arc::new(RwLock::new(NewinMemDB::new()));
                 let manager = SqliteConnectionManager::memory();
                 let pool = r2d2::Builder::new().max_size(8).build(manager).unwrap();
-                let write_conn = Arc::new(Mutex::new({
+                let write_conn = Arc::new(WriteConn::new({
                     let mut pool = pool.get().unwrap();
                     pool.execute_batch(
                         "PRAGMA busy_timeout = 20000;
@@ -171,7 +221,7 @@ PRAGMA cache_size = -1000000;
                     .max_size(8)
                     .build(manager)
                     .unwrap();
-                let write_conn = Arc::new(Mutex::new(pool.get().unwrap()));
+                let write_conn = Arc::new(WriteConn::new(pool.get().unwrap()));
                 let write_conn_istransaction = Arc::new(Mutex::new(false));
                 let mut main = Main {
                     _dbpath: path,
@@ -220,7 +270,7 @@ PRAGMA journal_mode = WAL;
                 let pool = r2d2::Builder::new().max_size(8).build(manager).unwrap();
 
                 // Grab a "write" connection for operations that need exclusive access
-                let write_conn = Arc::new(Mutex::new(pool.get().unwrap()));
+                let write_conn = Arc::new(WriteConn::new(pool.get().unwrap()));
                 let write_conn_istransaction = Arc::new(Mutex::new(false));
 
                 let mut main = Main {
@@ -1001,6 +1051,15 @@ PRAGMA journal_mode = WAL;
         }
         self.tags_fts_create_v2(tn);
         self.namespace_properties_create_v1(tn);
+        self.plugin_registry_create_v1(tn);
+        self.plugin_storage_create_v1(tn);
+        self.storage_roots_create_v1(tn);
+        self.storage_roots_shard_depth_add_v1(tn);
+        self.storage_migration_create_v1(tn);
+        self.file_verify_create_v1(tn);
+        self.storage_backend_create_v1(tn);
+        self.storage_s3_create_v1(tn);
+        self.watch_folder_create_v1(tn);
 
         let count = self.get_relationship_popular_division_count(tn);
 
@@ -1159,6 +1218,22 @@ PRAGMA journal_mode = WAL;
                 self.db_update_ten_to_eleven();
             } else if db_vers == 11 {
                 self.db_update_eleven_to_twelve();
+            } else if db_vers == 12 {
+                self.db_update_twelve_to_thirteen();
+            } else if db_vers == 13 {
+                self.db_update_thirteen_to_fourteen();
+            } else if db_vers == 14 {
+                self.db_update_fourteen_to_fifteen();
+            } else if db_vers == 15 {
+                self.db_update_fifteen_to_sixteen();
+            } else if db_vers == 16 {
+                self.db_update_sixteen_to_seventeen();
+            } else if db_vers == 17 {
+                self.db_update_seventeen_to_eighteen();
+            } else if db_vers == 18 {
+                self.db_update_eighteen_to_nineteen();
+            } else if db_vers == 19 {
+                self.db_update_nineteen_to_twenty();
             }
 
             logging::info_log(format!("Finished upgrade to V{}.", db_vers));
@@ -1770,6 +1845,51 @@ PRAGMA journal_mode = WAL;
         }
     }
 
+    ///
+    /// Resets any jobs that were left running by an instance that didn't shut down
+    /// cleanly. Returns the number of jobs recovered.
+    ///
+    pub fn jobs_recover_stale(&self) -> usize {
+        let stale: Vec<sharedtypes::DbJobsObj> = self
+            .jobs_get_all()
+            .into_values()
+            .filter(|job| job.isrunning)
+            .collect();
+        for mut job in stale.iter().cloned() {
+            job.isrunning = false;
+            logging::info_log(format!("Recovering stale running job: {:?}", job.id));
+            self.jobs_update_db(job);
+        }
+        stale.len()
+    }
+
+    ///
+    /// Commits any open write transaction and checkpoints the WAL into the db file.
+    /// Called on shutdown.
+    ///
+    pub fn shutdown_flush(&self) {
+        self.transaction_flush();
+        let conn = self.write_conn.lock();
+        if let Err(err) = conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE);") {
+            logging::error_log(format!("Failed to checkpoint db on shutdown: {:?}", err));
+        }
+    }
+
+    ///
+    /// Sets or clears the paused flag inside of a jobs system_data
+    ///
+    pub fn jobs_paused_set_db(&self, job_id: &u64, paused: bool) {
+        if let Some(mut job) = self.jobs_get(job_id) {
+            if paused {
+                job.system_data
+                    .insert(crate::jobs::JOB_PAUSED_KEY.to_string(), "true".to_string());
+            } else {
+                job.system_data.remove(crate::jobs::JOB_PAUSED_KEY);
+            }
+            self.jobs_update_db(job);
+        }
+    }
+
     /// Adds global load into db
     pub fn setup_globalload(&mut self, globalload: GlobalLoad) {
         self.globalload = Some(globalload.into());
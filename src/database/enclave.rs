@@ -1,16 +1,20 @@
 use crate::database::database::Main;
 use crate::database::database::Transaction;
-use crate::download;
+use crate::database::storagebackend::location_remote;
 use crate::file::folder_make;
-use crate::logging;
+use crate::logging::LogCtx;
+use crate::mediainfo;
 use crate::vec_of_strings;
 use bytes::Bytes;
 use chrono::Utc;
 use core::panic;
 use file_format::FileFormat;
+use regex::Regex;
 use rusqlite::OptionalExtension;
 use rusqlite::params;
 use sharedtypes;
+use std::cell::OnceCell;
+use std::ops::ControlFlow;
 
 const DEFAULT_PRIORITY_DOWNLOAD: u64 = 10;
 const DEFAULT_PRIORITY_PUT: u64 = 5;
@@ -18,6 +22,7 @@ const DEFAULT_DOWNLOAD_DEFAULT: &str = "DownloadToDiskDefault";
 const DEFAULT_DOWNLOAD_DISK: &str = "DownloadToDisk";
 pub const DEFAULT_PUT_DISK: &str = "PutAtDefault";
 const DEFAULT_PRIORITY_LOWEST: u64 = 0;
+const DEFAULT_DOWNLOAD_ENCLAVE: &str = "File_Download_location_";
 
 impl Main {
     pub fn enclave_run_process(
@@ -30,13 +35,19 @@ impl Main {
         enclave_name: &str,
     ) -> Option<u64> {
         if let Some(enclave_id) = self.enclave_name_get_id(enclave_name) {
-            return self.enclave_run_logic(file, bytes, sha512hash, source_url, &enclave_id);
+            let input = ConditionInput::new(bytes, sha512hash, source_url, None);
+            if let ControlFlow::Break(file_id) =
+                self.enclave_run_logic(file, bytes, sha512hash, source_url, &input, &enclave_id)
+            {
+                return file_id;
+            }
         }
         None
     }
 
     ///
     /// Actually runs the enclave processing logic
+    /// Breaks once the file got stored or skipped. Skipped files break with None
     ///
     fn enclave_run_logic(
         &self,
@@ -44,60 +55,54 @@ impl Main {
         bytes: &Bytes,
         sha512hash: &String,
         source_url: Option<&String>,
+        input: &ConditionInput,
         enclave_id: &u64,
-    ) -> Option<u64> {
-        let loop_one;
-        let source_url_ns_id;
+    ) -> ControlFlow<Option<u64>> {
+        // NOTE bad practice but we called it in above code so this should already be handled
+        let source_url_ns_id = self.create_default_source_url_ns_id();
+
+        for (position, step) in self
+            .enclave_steps_get_sql(enclave_id)
+            .into_iter()
+            .enumerate()
         {
-            loop_one = self.enclave_action_order_enclave_get_list_id(enclave_id);
-            // NOTE bad practice but we called it in above code so this should already be handled
-            source_url_ns_id = self.create_default_source_url_ns_id();
-        }
-
-        for condition_list_id in loop_one {
-            logging::log(format!(
-                "Enclave FileHash {}: Pulled condition list id for {}, enclave_id: {}",
-                &sha512hash, condition_list_id, enclave_id
+            let passed = self.condition_passes(&step.condition, file, input);
+            LogCtx::new().hash(sha512hash).info(format!(
+                "Enclave: enclave_id: {} step: {} passed: {}",
+                enclave_id, position, passed
             ));
-            let condition_one = self.enclave_condition_list_get(&condition_list_id);
-
-            while let Some((enclave_action_id, failed_enclave_action_id, condition_id)) =
-                condition_one
-            {
-                let (action_bool, action_name) =
-                    self.enclave_condition_evaluate(&condition_id, file, bytes);
-                let run_option_action_id = if action_bool {
-                    Some(enclave_action_id)
-                } else {
-                    failed_enclave_action_id
-                };
-
-                if let Some(run_action_id) = run_option_action_id {
-                    logging::log(format!(
-                        "Enclave FileHash {}: Running action id: {}",
-                        &sha512hash, run_action_id
-                    ));
-                    if let Some(action_name) = action_name {
-                        logging::log(format!(
-                            "Enclave FileHash {}: Running action name: {:?}",
-                            &sha512hash, action_name
-                        ));
-                        if let Some(file_id) = self.enclave_run_action(
-                            &action_name,
-                            file,
-                            bytes,
-                            sha512hash,
-                            source_url,
-                            source_url_ns_id,
-                            &run_action_id,
-                        ) {
-                            return Some(file_id);
-                        }
-                    }
-                }
+            let action = if passed {
+                Some(step.action)
+            } else {
+                step.failed_action
+            };
+
+            if let Some(action) = action {
+                LogCtx::new()
+                    .hash(sha512hash)
+                    .info(format!("Enclave: Running action name: {:?}", action));
+                self.enclave_run_action(
+                    &action,
+                    file,
+                    bytes,
+                    sha512hash,
+                    source_url,
+                    source_url_ns_id,
+                    enclave_id,
+                )?;
             }
         }
-        None
+        ControlFlow::Continue(())
+    }
+
+    ///
+    /// Enclave ids in the order they get tried. Highest priority first
+    ///
+    fn enclave_order_get(&self) -> Vec<u64> {
+        self.enclave_priority_get()
+            .iter()
+            .flat_map(|priority_id| self.enclave_get_id_from_priority(priority_id))
+            .collect()
     }
 
     ///
@@ -109,19 +114,64 @@ impl Main {
         bytes: &Bytes,
         sha512hash: &String,
         source_url: Option<&String>,
+        scraper: Option<&String>,
     ) -> Option<u64> {
-        logging::info_log(format!(
-            "Enclave FileHash {}: Starting to process",
-            &sha512hash
-        ));
-        let mut out = None;
-        'priorityloop: for priority_id in self.enclave_priority_get() {
-            for enclave_id in self.enclave_get_id_from_priority(&priority_id) {
-                if let Some(file_id) =
-                    self.enclave_run_logic(file, bytes, sha512hash, source_url, &enclave_id)
-                {
-                    out = Some(file_id);
-                    break 'priorityloop;
+        LogCtx::new()
+            .hash(sha512hash)
+            .info("Enclave: Starting to process");
+        let input = ConditionInput::new(bytes, sha512hash, source_url, scraper);
+        for enclave_id in self.enclave_order_get() {
+            if let ControlFlow::Break(file_id) =
+                self.enclave_run_logic(file, bytes, sha512hash, source_url, &input, &enclave_id)
+            {
+                return file_id;
+            }
+        }
+        None
+    }
+
+    ///
+    /// Walks the enclaves like enclave_determine_processing without running anything.
+    /// Tags that actions would add go onto a copy so later conditions still see them
+    ///
+    pub(in crate::database) fn enclave_dry_run_sql(
+        &self,
+        file: &sharedtypes::FileObjectMain,
+        input: &ConditionInput,
+    ) -> Vec<sharedtypes::EnclaveDryRunStep> {
+        let size = input.bytes.len() as u64;
+        let mut file = file.clone();
+        let mut out = Vec::new();
+        for enclave_id in self.enclave_order_get() {
+            let enclave = self.enclave_name_get_name(&enclave_id).unwrap_or_default();
+            for (position, step) in self
+                .enclave_steps_get_sql(&enclave_id)
+                .into_iter()
+                .enumerate()
+            {
+                let passed = self.condition_passes(&step.condition, &file, input);
+                let action = if passed {
+                    Some(step.action)
+                } else {
+                    step.failed_action
+                };
+                let location = action
+                    .as_ref()
+                    .and_then(|action| self.enclave_action_location(action, size));
+                let stops = location.is_some() || action == Some(sharedtypes::EnclaveAction::Skip);
+                if let Some(action) = &action {
+                    tag_action_apply(&mut file, action);
+                }
+                out.push(sharedtypes::EnclaveDryRunStep {
+                    enclave: enclave.clone(),
+                    position: position as u64,
+                    passed,
+                    action,
+                    location,
+                    stops,
+                });
+                if stops {
+                    return out;
                 }
             }
         }
@@ -130,7 +180,7 @@ impl Main {
 
     ///
     /// Runs an action as it's valid
-    /// Returns weather we should stop on this action
+    /// Breaks when we should stop on this action
     ///
     fn enclave_run_action(
         &self,
@@ -141,64 +191,65 @@ impl Main {
         source_url: Option<&String>,
         source_url_ns_id: u64,
         enclave_id: &u64,
-    ) -> Option<u64> {
-        let download_location = { self.location_get() };
-
+    ) -> ControlFlow<Option<u64>> {
+        crate::metrics::enclave_action_record(action);
         match action {
-            sharedtypes::EnclaveAction::PutAtDefault => {
-                logging::log(format!(
-                    "Enclave FileHash {} Putting at Default location {}",
-                    &sha512hash, &download_location
-                ));
-                let file_id = self.download_and_do_parsing(
-                    bytes,
-                    sha512hash,
-                    source_url,
-                    source_url_ns_id,
-                    enclave_id,
-                    &download_location,
-                    file,
-                );
-                return Some(file_id);
-                //Some(fileid)
+            sharedtypes::EnclaveAction::AddTagAndNamespace(_) => {
+                tag_action_apply(file, action);
+                return ControlFlow::Continue(());
             }
-            sharedtypes::EnclaveAction::AddTagAndNamespace((
-                tag,
-                namespace,
-                tag_type,
-                relates_to,
-            )) => {
-                file.tag_list.push(sharedtypes::FileTagAction {
-                    operation: sharedtypes::TagOperation::Add,
-                    tags: vec![sharedtypes::TagObject {
-                        namespace: namespace.clone(),
-                        tag: tag.clone(),
-                        tag_type: tag_type.clone(),
-                        relates_to: relates_to.clone(),
-                    }],
-                });
-                //None
+            sharedtypes::EnclaveAction::Skip => {
+                LogCtx::new()
+                    .hash(sha512hash)
+                    .info("Enclave: Skipping file");
+                return ControlFlow::Break(None);
             }
-            sharedtypes::EnclaveAction::DownloadToDefault => {
-                logging::log(format!(
-                    "Enclave FileHash {}: Downloading to Default location {}",
-                    &sha512hash, &download_location
-                ));
-                let file_id = self.download_and_do_parsing(
-                    bytes,
-                    sha512hash,
-                    source_url,
-                    source_url_ns_id,
-                    enclave_id,
-                    &download_location,
-                    file,
-                );
-                return Some(file_id);
-                //Some(fileid)
-            }
-            sharedtypes::EnclaveAction::DownloadToLocation(_) => {} //None,
+            _ => {}
+        }
+
+        let Some(download_location) = self.enclave_action_location(action, bytes.len() as u64)
+        else {
+            LogCtx::new().hash(sha512hash).error(format!(
+                "Enclave: No storage root has room for {} bytes for action {:?}",
+                bytes.len(),
+                action
+            ));
+            return ControlFlow::Continue(());
+        };
+        LogCtx::new().hash(sha512hash).info(format!(
+            "Enclave: Storing at {} for action {:?}",
+            &download_location, action
+        ));
+        let file_id = self.download_and_do_parsing(
+            bytes,
+            sha512hash,
+            source_url,
+            source_url_ns_id,
+            enclave_id,
+            &download_location,
+            file,
+        );
+        ControlFlow::Break(Some(file_id))
+    }
+
+    ///
+    /// Where an action would store a file of size bytes. None for actions that don't
+    /// store anything or when DownloadToLocation can't find a root with room
+    ///
+    fn enclave_action_location(
+        &self,
+        action: &sharedtypes::EnclaveAction,
+        size: u64,
+    ) -> Option<String> {
+        match action {
+            sharedtypes::EnclaveAction::PutAtDefault
+            | sharedtypes::EnclaveAction::DownloadToDefault => Some(self.location_get()),
+            sharedtypes::EnclaveAction::DownloadToLocation(storage_id) => self
+                .storage_root_pick(storage_id, size)
+                .map(|(_, location)| location),
+            sharedtypes::EnclaveAction::AddTagAndNamespace(_)
+            | sharedtypes::EnclaveAction::Skip => None,
         }
-        None
     }
 
     ///
@@ -215,18 +266,30 @@ impl Main {
         file: &mut sharedtypes::FileObjectMain,
     ) -> u64 {
         let fileid;
-        let download_loc = std::path::Path::new(&download_location)
-            .canonicalize()
-            .unwrap(); // Gives file extension
+        let storage_id;
+        // error checking. We should have all dirs needed but hey if we're missing
+        if !location_remote(download_location) {
+            std::fs::create_dir_all(download_location).unwrap();
+        }
+        // Gives file extension
         let file_ext = FileFormat::from_bytes(bytes).extension().to_string();
 
-        // error checking. We should have all dirs needed but hey if we're missing
-        std::fs::create_dir_all(download_location).unwrap();
+        // Written before the db knows about it so a missing file gets caught by a scrub
+        // instead of being served half written
+        storage_id = self.storage_put(download_location);
+        let written = self
+            .file_store(download_location)
+            .write(sha512hash, &file_ext, bytes)
+            .unwrap_or_else(|err| {
+                LogCtx::new().hash(sha512hash).error(err);
+                0
+            });
+
         {
             let mut write_conn = self.write_conn.lock();
             let tn = write_conn.transaction().unwrap();
 
-            let storage_id = self.storage_put_internal(&tn, download_location);
+            self.storage_root_used_add(&tn, &storage_id, written as i64);
             let ext_id = self.extension_put_string_internal(&tn, &file_ext);
             let filestorage = sharedtypes::DbFileStorage::NoIdExist(sharedtypes::DbFileObjNoId {
                 hash: sha512hash.to_string(),
@@ -243,54 +306,9 @@ impl Main {
             self.enclave_file_mapping_add(&tn, &fileid, enclave_id);
             tn.commit().unwrap();
         }
-        download::write_to_disk(download_loc, bytes, sha512hash);
-
         fileid
     }
 
-    ///
-    /// Checks if a condition is true
-    ///
-    fn enclave_condition_evaluate(
-        &self,
-
-        condition_id: &u64,
-        file: &sharedtypes::FileObjectMain,
-        bytes: &Bytes,
-    ) -> (bool, Option<sharedtypes::EnclaveAction>) {
-        if let Some((action, condition)) = self.enclave_condition_get_data(condition_id) {
-            (
-                match condition {
-                    sharedtypes::EnclaveCondition::Any => true,
-                    sharedtypes::EnclaveCondition::None => false,
-                    sharedtypes::EnclaveCondition::FileSizeGreater(byte_len) => {
-                        byte_len < bytes.len() as u64
-                    }
-                    sharedtypes::EnclaveCondition::FileSizeLessthan(byte_len) => {
-                        byte_len > bytes.len() as u64
-                    }
-                    sharedtypes::EnclaveCondition::TagNameAndNamespace((tag_name, namespace)) => {
-                        let mut out = false;
-                        'tagaction: for tag_action in file.tag_list.iter() {
-                            for tag in tag_action.tags.iter() {
-                                if tag.tag.contains(&tag_name)
-                                    && tag.namespace.name.contains(&namespace)
-                                {
-                                    out = true;
-                                    break 'tagaction;
-                                }
-                            }
-                        }
-                        out
-                    }
-                },
-                Some(action),
-            )
-        } else {
-            (false, None)
-        }
-    }
-
     ///
     /// Adds a filemapping if it doesn't exist
     ///
@@ -502,7 +520,7 @@ impl Main {
             }
         };
 
-        let default_file_enclave = format!("File_Download_location_{}", location);
+        let default_file_enclave = format!("{}{}", DEFAULT_DOWNLOAD_ENCLAVE, location);
         let enclave_id = self.enclave_name_put(
             tn,
             default_file_enclave.clone(),
@@ -537,7 +555,6 @@ impl Main {
 
         self.enclave_name_get_sql(tn, &name).unwrap()
     }
-    //  pub fn enclave_name_edit(&self, id: &u64, name: String) {}
 
     ///
     /// Gets the enclave name from the enclave id
@@ -685,36 +702,6 @@ impl Main {
         .unwrap_or_default()
     }
 
-    ///
-    /// Gets conditional action and condition from id
-    ///
-    fn enclave_condition_get_data(
-        &self,
-
-        condition_id: &u64,
-    ) -> Option<(sharedtypes::EnclaveAction, sharedtypes::EnclaveCondition)> {
-        let tn = self.get_database_connection();
-        if let Ok(out) = tn
-            .query_row(
-                "SELECT action_name, action_condition from EnclaveCondition where id = ?",
-                params![condition_id],
-                |row| {
-                    let action: String = row.get(0).unwrap();
-                    let condition: String = row.get(1).unwrap();
-                    Ok(Some((
-                        serde_json::from_str(&action).unwrap(),
-                        serde_json::from_str(&condition).unwrap(),
-                    )))
-                },
-            )
-            .optional()
-        {
-            out?
-        } else {
-            None
-        }
-    }
-
     ///
     /// Adds a conditional action to the list of enclave actions
     ///
@@ -762,36 +749,6 @@ impl Main {
             .optional().unwrap_or_default()
     }
 
-    ///
-    /// Gets enclave conditional jump by enclave_id
-    ///
-    fn enclave_action_order_enclave_get_list_id(&self, enclave_id: &u64) -> Vec<u64> {
-        let mut out: Vec<u64> = Vec::new();
-        let tn = self.get_database_connection();
-        let mut stmt = tn
-            .prepare("SELECT enclave_conditional_list_id from EnclaveActionOrderList where enclave_id = ? ORDER BY enclave_action_position DESC")
-            .unwrap();
-        let row = stmt
-            .query_map([enclave_id], |row| {
-                let id: Option<u64> = row.get(0).unwrap();
-                if let Some(id) = id {
-                    Ok(id)
-                } else {
-                    Err(rusqlite::Error::InvalidQuery)
-                }
-            })
-            .unwrap();
-        for ids in row.flatten() {
-            if !out.contains(&ids) {
-                out.push(ids);
-            }
-        }
-
-        out.sort();
-
-        out
-    }
-
     ///
     /// Adds the conditional link between for a condition and it's priority in the enclave list
     ///
@@ -833,29 +790,6 @@ impl Main {
         .unwrap_or_default()
     }
 
-    ///
-    /// Gets conditional jumps for conditonlist id
-    ///
-    fn enclave_condition_list_get(
-        &self,
-
-        condition_list_id: &u64,
-    ) -> Option<(u64, Option<u64>, u64)> {
-        let tn = self.get_database_connection();
-        if let Ok(out) = tn
-            .query_row(
-                "SELECT enclave_action_id, failed_enclave_action_id, condition_id from EnclaveConditionList where id = ?",
-                params![condition_list_id],
-                |row| Ok(Some((row.get(0).unwrap(), row.get(1).unwrap(), row.get(2).unwrap()))),
-            )
-            .optional()
-        {
-            out?
-        } else {
-            None
-        }
-    }
-
     ///
     /// Inserts the name and action into the database
     ///
@@ -893,4 +827,717 @@ impl Main {
         .optional()
         .unwrap_or_default()
     }
+
+    ///
+    /// The steps of an enclave in the order they run
+    ///
+    pub(in crate::database) fn enclave_steps_get_sql(
+        &self,
+        enclave_id: &u64,
+    ) -> Vec<sharedtypes::EnclaveStep> {
+        let tn = self.get_database_connection();
+        let mut stmt = tn
+            .prepare(
+                "SELECT c.action_condition, a.action_text, f.action_text FROM EnclaveActionOrderList o
+                JOIN EnclaveConditionList l ON l.id = o.enclave_conditional_list_id
+                JOIN EnclaveCondition c ON c.id = l.condition_id
+                JOIN EnclaveAction a ON a.id = l.enclave_action_id
+                LEFT JOIN EnclaveAction f ON f.id = l.failed_enclave_action_id
+                WHERE o.enclave_id = ? ORDER BY o.enclave_action_position ASC, o.id ASC",
+            )
+            .unwrap();
+        stmt.query_map(params![enclave_id], |row| {
+            let condition: String = row.get(0)?;
+            let action: String = row.get(1)?;
+            let failed_action: Option<String> = row.get(2)?;
+            Ok((condition, action, failed_action))
+        })
+        .unwrap()
+        .flatten()
+        .filter_map(|(condition, action, failed_action)| {
+            Some(sharedtypes::EnclaveStep {
+                condition: serde_json::from_str(&condition).ok()?,
+                action: serde_json::from_str(&action).ok()?,
+                failed_action: failed_action.and_then(|text| serde_json::from_str(&text).ok()),
+            })
+        })
+        .collect()
+    }
+
+    ///
+    /// Ids of the order rows of an enclave in the order they run
+    ///
+    fn enclave_step_ids_sql(&self, tn: &Transaction, enclave_id: &u64) -> Vec<u64> {
+        let mut stmt = tn
+            .prepare(
+                "SELECT id FROM EnclaveActionOrderList WHERE enclave_id = ? ORDER BY enclave_action_position ASC, id ASC",
+            )
+            .unwrap();
+        stmt.query_map(params![enclave_id], |row| row.get(0))
+            .unwrap()
+            .flatten()
+            .collect()
+    }
+
+    ///
+    /// Rewrites positions so they go 0, 1, 2 in the order given
+    ///
+    fn enclave_step_positions_set_sql(&self, tn: &Transaction, step_ids: &[u64]) {
+        let mut prep = tn
+            .prepare("UPDATE EnclaveActionOrderList SET enclave_action_position = ? WHERE id = ?")
+            .unwrap();
+        for (position, step_id) in step_ids.iter().enumerate() {
+            prep.execute(params![position as u64, step_id]).unwrap();
+        }
+    }
+
+    ///
+    /// Every enclave with its steps, in the order they get tried
+    ///
+    pub(in crate::database) fn enclave_list_sql(&self) -> Vec<sharedtypes::EnclaveInfo> {
+        self.enclave_order_get()
+            .into_iter()
+            .filter_map(|id| {
+                let tn = self.get_database_connection();
+                let (name, priority): (String, u64) = tn
+                    .query_row(
+                        "SELECT enclave_name, priority FROM Enclave WHERE id = ?",
+                        params![id],
+                        |row| Ok((row.get(0)?, row.get(1)?)),
+                    )
+                    .ok()?;
+                Some(sharedtypes::EnclaveInfo {
+                    id,
+                    builtin: enclave_is_builtin(&name),
+                    steps: self.enclave_steps_get_sql(&id),
+                    name,
+                    priority,
+                })
+            })
+            .collect()
+    }
+
+    ///
+    /// Looks up an enclave inside of a transaction. Errors if it's missing or if
+    /// it's builtin and the change wouldn't survive a restart
+    ///
+    fn enclave_edit_get_sql(
+        &self,
+        tn: &Transaction,
+        name: &str,
+        builtin_ok: bool,
+    ) -> Result<u64, String> {
+        let enclave_id = self
+            .enclave_name_get_sql(tn, name)
+            .ok_or_else(|| format!("No enclave named {}", name))?;
+        if !builtin_ok && enclave_is_builtin(name) {
+            return Err(format!(
+                "{} is remade on startup. Lower its priority and add your own enclave instead",
+                name
+            ));
+        }
+        Ok(enclave_id)
+    }
+
+    ///
+    /// Runs an edit in a write transaction and only commits if it worked
+    ///
+    fn enclave_edit<T>(
+        &self,
+        edit: impl FnOnce(&Transaction) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut write_conn = self.write_conn.lock();
+        let tn = write_conn.transaction().unwrap();
+        let out = edit(&tn)?;
+        tn.commit().unwrap();
+        Ok(out)
+    }
+
+    pub(in crate::database) fn enclave_create_sql(
+        &self,
+        name: &str,
+        priority: &u64,
+    ) -> Result<u64, String> {
+        self.enclave_edit(|tn| {
+            if self.enclave_name_get_sql(tn, name).is_some() {
+                return Err(format!("Enclave {} already exists", name));
+            }
+            Ok(self.enclave_name_put(tn, name.to_string(), priority))
+        })
+    }
+
+    pub(in crate::database) fn enclave_rename_sql(
+        &self,
+        name: &str,
+        new_name: &str,
+    ) -> Result<(), String> {
+        self.enclave_edit(|tn| {
+            let enclave_id = self.enclave_edit_get_sql(tn, name, false)?;
+            if self.enclave_name_get_sql(tn, new_name).is_some() {
+                return Err(format!("Enclave {} already exists", new_name));
+            }
+            if enclave_is_builtin(new_name) {
+                return Err(format!("{} is reserved", new_name));
+            }
+            tn.execute(
+                "UPDATE Enclave SET enclave_name = ? WHERE id = ?",
+                params![new_name, enclave_id],
+            )
+            .unwrap();
+            Ok(())
+        })
+    }
+
+    pub(in crate::database) fn enclave_priority_set_sql(
+        &self,
+        name: &str,
+        priority: &u64,
+    ) -> Result<(), String> {
+        self.enclave_edit(|tn| {
+            let enclave_id = self.enclave_edit_get_sql(tn, name, true)?;
+            tn.execute(
+                "UPDATE Enclave SET priority = ? WHERE id = ?",
+                params![priority, enclave_id],
+            )
+            .unwrap();
+            Ok(())
+        })
+    }
+
+    ///
+    /// Removes an enclave, its steps and which files it handled
+    ///
+    pub(in crate::database) fn enclave_delete_sql(&self, name: &str) -> Result<(), String> {
+        self.enclave_edit(|tn| {
+            let enclave_id = self.enclave_edit_get_sql(tn, name, false)?;
+            for step_id in self.enclave_step_ids_sql(tn, &enclave_id) {
+                self.enclave_step_delete_sql(tn, &step_id);
+            }
+            tn.execute(
+                "DELETE FROM FileEnclaveMapping WHERE enclave_id = ?",
+                params![enclave_id],
+            )
+            .unwrap();
+            tn.execute("DELETE FROM Enclave WHERE id = ?", params![enclave_id])
+                .unwrap();
+            Ok(())
+        })
+    }
+
+    ///
+    /// Adds a step. None puts it at the end, otherwise it goes in at position and
+    /// everything after moves down one
+    ///
+    pub(in crate::database) fn enclave_step_add_sql(
+        &self,
+        name: &str,
+        step: &sharedtypes::EnclaveStep,
+        position: Option<u64>,
+    ) -> Result<(), String> {
+        condition_validate(&step.condition)?;
+        self.enclave_edit(|tn| {
+            let enclave_id = self.enclave_edit_get_sql(tn, name, true)?;
+            let mut step_ids = self.enclave_step_ids_sql(tn, &enclave_id);
+            let position = position.map_or(step_ids.len(), |position| position as usize);
+            if position > step_ids.len() {
+                return Err(format!("{} only has {} steps", name, step_ids.len()));
+            }
+
+            // Steps get their own condition row so editing one can't change another
+            tn.execute(
+                "INSERT INTO EnclaveCondition (action_name, action_condition) VALUES (?, ?)",
+                params![
+                    serde_json::to_string(&step.action).unwrap(),
+                    serde_json::to_string(&step.condition).unwrap()
+                ],
+            )
+            .unwrap();
+            let condition_id = tn.last_insert_rowid() as u64;
+            let action_id = self.enclave_action_put(
+                tn,
+                &serde_json::to_string(&step.action).unwrap(),
+                step.action.clone(),
+            );
+            let failed_action_id = step.failed_action.as_ref().map(|failed_action| {
+                self.enclave_action_put(
+                    tn,
+                    &serde_json::to_string(failed_action).unwrap(),
+                    failed_action.clone(),
+                )
+            });
+            let condition_link_id = self.enclave_condition_link_put(
+                tn,
+                &condition_id,
+                &action_id,
+                failed_action_id.as_ref(),
+            );
+            tn.execute(
+                "INSERT INTO EnclaveActionOrderList (enclave_id, enclave_conditional_list_id, enclave_action_position) VALUES (?, ?, ?)",
+                params![enclave_id, condition_link_id, position as u64],
+            )
+            .unwrap();
+            step_ids.insert(position, tn.last_insert_rowid() as u64);
+            self.enclave_step_positions_set_sql(tn, &step_ids);
+            Ok(())
+        })
+    }
+
+    pub(in crate::database) fn enclave_step_remove_sql(
+        &self,
+        name: &str,
+        position: &u64,
+    ) -> Result<(), String> {
+        self.enclave_edit(|tn| {
+            let enclave_id = self.enclave_edit_get_sql(tn, name, false)?;
+            let mut step_ids = self.enclave_step_ids_sql(tn, &enclave_id);
+            if *position as usize >= step_ids.len() {
+                return Err(format!("{} has no step {}", name, position));
+            }
+            let step_id = step_ids.remove(*position as usize);
+            self.enclave_step_delete_sql(tn, &step_id);
+            self.enclave_step_positions_set_sql(tn, &step_ids);
+            Ok(())
+        })
+    }
+
+    pub(in crate::database) fn enclave_step_move_sql(
+        &self,
+        name: &str,
+        from: &u64,
+        to: &u64,
+    ) -> Result<(), String> {
+        self.enclave_edit(|tn| {
+            let enclave_id = self.enclave_edit_get_sql(tn, name, true)?;
+            let mut step_ids = self.enclave_step_ids_sql(tn, &enclave_id);
+            if (*from.max(to)) as usize >= step_ids.len() {
+                return Err(format!("{} only has {} steps", name, step_ids.len()));
+            }
+            let step_id = step_ids.remove(*from as usize);
+            step_ids.insert(*to as usize, step_id);
+            self.enclave_step_positions_set_sql(tn, &step_ids);
+            Ok(())
+        })
+    }
+
+    ///
+    /// Drops an order row and the condition behind it if nothing else uses them.
+    /// Actions are shared by name so they stay
+    ///
+    fn enclave_step_delete_sql(&self, tn: &Transaction, step_id: &u64) {
+        let condition_link_id: Option<u64> = tn
+            .query_row(
+                "SELECT enclave_conditional_list_id FROM EnclaveActionOrderList WHERE id = ?",
+                params![step_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .unwrap_or_default();
+        tn.execute(
+            "DELETE FROM EnclaveActionOrderList WHERE id = ?",
+            params![step_id],
+        )
+        .unwrap();
+        let Some(condition_link_id) = condition_link_id else {
+            return;
+        };
+        let condition_id: Option<u64> = tn
+            .query_row(
+                "SELECT condition_id FROM EnclaveConditionList WHERE id = ?
+                AND NOT EXISTS (SELECT 1 FROM EnclaveActionOrderList WHERE enclave_conditional_list_id = ?)",
+                params![condition_link_id, condition_link_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .unwrap_or_default();
+        let Some(condition_id) = condition_id else {
+            return;
+        };
+        tn.execute(
+            "DELETE FROM EnclaveConditionList WHERE id = ?",
+            params![condition_link_id],
+        )
+        .unwrap();
+        tn.execute(
+            "DELETE FROM EnclaveCondition WHERE id = ?
+            AND NOT EXISTS (SELECT 1 FROM EnclaveConditionList WHERE condition_id = ?)",
+            params![condition_id, condition_id],
+        )
+        .unwrap();
+    }
+}
+
+///
+/// Enclaves the program makes itself on startup
+///
+fn enclave_is_builtin(name: &str) -> bool {
+    name == DEFAULT_PUT_DISK || name.starts_with(DEFAULT_DOWNLOAD_ENCLAVE)
+}
+
+///
+/// Catches conditions that could never pass because they can't be read, like a bad regex
+///
+fn condition_validate(condition: &sharedtypes::EnclaveCondition) -> Result<(), String> {
+    match condition {
+        sharedtypes::EnclaveCondition::SourceUrlRegex(pattern) => Regex::new(pattern)
+            .map(|_| ())
+            .map_err(|err| format!("Bad regex {}: {}", pattern, err)),
+        sharedtypes::EnclaveCondition::All(conditions)
+        | sharedtypes::EnclaveCondition::AnyOf(conditions) => {
+            conditions.iter().try_for_each(condition_validate)
+        }
+        sharedtypes::EnclaveCondition::Not(condition) => condition_validate(condition),
+        _ => Ok(()),
+    }
+}
+
+///
+/// Everything about a file that conditions look at besides its tags.
+/// Format detection and media probing only happen if a condition needs them
+///
+pub(in crate::database) struct ConditionInput<'a> {
+    bytes: &'a [u8],
+    hash: &'a String,
+    source_url: Option<&'a String>,
+    scraper: Option<&'a String>,
+    format: OnceCell<FileFormat>,
+    media: OnceCell<mediainfo::MediaInfo>,
+}
+
+impl<'a> ConditionInput<'a> {
+    pub(in crate::database) fn new(
+        bytes: &'a [u8],
+        hash: &'a String,
+        source_url: Option<&'a String>,
+        scraper: Option<&'a String>,
+    ) -> Self {
+        ConditionInput {
+            bytes,
+            hash,
+            source_url,
+            scraper,
+            format: OnceCell::new(),
+            media: OnceCell::new(),
+        }
+    }
+
+    fn format(&self) -> &FileFormat {
+        self.format
+            .get_or_init(|| FileFormat::from_bytes(self.bytes))
+    }
+
+    fn media(&self) -> &mediainfo::MediaInfo {
+        self.media.get_or_init(|| mediainfo::probe(self.bytes))
+    }
+
+    fn source_host(&self) -> Option<String> {
+        url::Url::parse(self.source_url?)
+            .ok()?
+            .host_str()
+            .map(|host| host.to_lowercase())
+    }
+}
+
+impl Main {
+    ///
+    /// Checks if a condition is true
+    ///
+    fn condition_passes(
+        &self,
+        condition: &sharedtypes::EnclaveCondition,
+        file: &sharedtypes::FileObjectMain,
+        input: &ConditionInput,
+    ) -> bool {
+        let size = input.bytes.len() as u64;
+        match condition {
+            sharedtypes::EnclaveCondition::Any => true,
+            sharedtypes::EnclaveCondition::None => false,
+            sharedtypes::EnclaveCondition::FileSizeGreater(byte_len) => *byte_len < size,
+            sharedtypes::EnclaveCondition::FileSizeLessthan(byte_len) => *byte_len > size,
+            sharedtypes::EnclaveCondition::TagNameAndNamespace((tag_name, namespace)) => {
+                file.tag_list.iter().any(|tag_action| {
+                    tag_action.tags.iter().any(|tag| {
+                        tag.tag.contains(tag_name) && tag.namespace.name.contains(namespace)
+                    })
+                })
+            }
+            sharedtypes::EnclaveCondition::MimeType(mime) => {
+                let media_type = input.format().media_type();
+                media_type.eq_ignore_ascii_case(mime)
+                    || media_type
+                        .split_once('/')
+                        .is_some_and(|(kind, _)| kind.eq_ignore_ascii_case(mime))
+            }
+            sharedtypes::EnclaveCondition::Extension(extension) => input
+                .format()
+                .extension()
+                .eq_ignore_ascii_case(extension.trim_start_matches('.')),
+            sharedtypes::EnclaveCondition::SourceHost(host) => {
+                let host = host.to_lowercase();
+                input.source_host().is_some_and(|source_host| {
+                    source_host == host || source_host.ends_with(&format!(".{}", host))
+                })
+            }
+            sharedtypes::EnclaveCondition::SourceUrlRegex(pattern) => {
+                input.source_url.is_some_and(|source_url| {
+                    Regex::new(pattern).is_ok_and(|regex| regex.is_match(source_url))
+                })
+            }
+            sharedtypes::EnclaveCondition::Scraper(name) => input.scraper == Some(name),
+            sharedtypes::EnclaveCondition::Dimensions(bounds) => {
+                let media = input.media();
+                let within = |value: u64, min: Option<u64>, max: Option<u64>| {
+                    min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max)
+                };
+                match (media.width, media.height) {
+                    (Some(width), Some(height)) => {
+                        within(width, bounds.min_width, bounds.max_width)
+                            && within(height, bounds.min_height, bounds.max_height)
+                    }
+                    _ => false,
+                }
+            }
+            sharedtypes::EnclaveCondition::DurationGreater(seconds) => input
+                .media()
+                .duration
+                .is_some_and(|duration| duration > *seconds as f64),
+            sharedtypes::EnclaveCondition::DurationLessthan(seconds) => input
+                .media()
+                .duration
+                .is_some_and(|duration| duration < *seconds as f64),
+            sharedtypes::EnclaveCondition::TagInDb((tag, namespace)) => {
+                // New files aren't in the db yet so the tags they come with count too
+                let incoming = file.tag_list.iter().any(|tag_action| {
+                    tag_action.operation != sharedtypes::TagOperation::Del
+                        && tag_action
+                            .tags
+                            .iter()
+                            .any(|object| &object.tag == tag && &object.namespace.name == namespace)
+                });
+                incoming || {
+                    let tag_id = self
+                        .namespace_get(namespace)
+                        .and_then(|namespace_id| self.tag_get_name(tag.clone(), namespace_id));
+                    match (self.file_get_hash(input.hash), tag_id) {
+                        (Some(file_id), Some(tag_id)) => {
+                            self.check_relationship_exists(&file_id, &tag_id)
+                        }
+                        _ => false,
+                    }
+                }
+            }
+            sharedtypes::EnclaveCondition::All(conditions) => conditions
+                .iter()
+                .all(|condition| self.condition_passes(condition, file, input)),
+            sharedtypes::EnclaveCondition::AnyOf(conditions) => conditions
+                .iter()
+                .any(|condition| self.condition_passes(condition, file, input)),
+            sharedtypes::EnclaveCondition::Not(condition) => {
+                !self.condition_passes(condition, file, input)
+            }
+        }
+    }
+}
+
+///
+/// Adds the tag from an AddTagAndNamespace onto the file. Other actions do nothing
+///
+fn tag_action_apply(file: &mut sharedtypes::FileObjectMain, action: &sharedtypes::EnclaveAction) {
+    if let sharedtypes::EnclaveAction::AddTagAndNamespace((tag, namespace, tag_type, relates_to)) =
+        action
+    {
+        file.tag_list.push(sharedtypes::FileTagAction {
+            operation: sharedtypes::TagOperation::Add,
+            tags: vec![sharedtypes::TagObject {
+                namespace: namespace.clone(),
+                tag: tag.clone(),
+                tag_type: tag_type.clone(),
+                relates_to: relates_to.clone(),
+            }],
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VERS;
+    use sharedtypes::{EnclaveAction, EnclaveCondition, EnclaveStep};
+
+    #[test]
+    fn enclaves_can_be_edited_and_dry_ran() {
+        let db = Main::new(None, VERS);
+        let name = "Large".to_string();
+        let size_ns = sharedtypes::GenericNamespaceObj {
+            name: "size".into(),
+            description: None,
+        };
+        db.enclave_create(&name, &20).unwrap();
+        assert!(db.enclave_create(&name, &1).is_err());
+        db.enclave_step_add(
+            &name,
+            &EnclaveStep {
+                condition: EnclaveCondition::TagNameAndNamespace(("big".into(), "size".into())),
+                action: EnclaveAction::PutAtDefault,
+                failed_action: None,
+            },
+            &None,
+        )
+        .unwrap();
+        db.enclave_step_add(
+            &name,
+            &EnclaveStep {
+                condition: EnclaveCondition::FileSizeGreater(100),
+                action: EnclaveAction::AddTagAndNamespace((
+                    "big".into(),
+                    size_ns,
+                    sharedtypes::TagType::Normal,
+                    None,
+                )),
+                failed_action: None,
+            },
+            &Some(0),
+        )
+        .unwrap();
+
+        let dry_run = |size: usize| db.enclave_dry_run(&vec![0; size], &Vec::new(), &None, &None);
+        let large = dry_run(500);
+        assert_eq!(
+            large
+                .iter()
+                .map(|step| (step.enclave.as_str(), step.passed, step.stops))
+                .collect::<Vec<_>>(),
+            vec![("Large", true, false), ("Large", true, true)]
+        );
+        // Small files fall through to the default download enclave
+        let small = dry_run(50);
+        assert!(!small[0].passed && !small[1].passed);
+        assert_eq!(
+            small.last().unwrap().action,
+            Some(EnclaveAction::DownloadToDefault)
+        );
+
+        // Tag check first means it can't see the tag the size check adds
+        db.enclave_step_move(&name, &1, &0).unwrap();
+        assert!(!dry_run(500)[0].passed);
+        db.enclave_step_remove(&name, &0).unwrap();
+        db.enclave_rename(&name, &"Big".to_string()).unwrap();
+        let listed = db.enclave_list();
+        assert_eq!(listed[0].name, "Big");
+        assert_eq!(listed[0].steps.len(), 1);
+
+        let builtin = listed.iter().find(|info| info.builtin).unwrap();
+        assert!(db.enclave_delete(&builtin.name).is_err());
+        db.enclave_delete(&"Big".to_string()).unwrap();
+        assert!(db.enclave_list().iter().all(|info| info.builtin));
+    }
+
+    #[test]
+    fn enclave_conditions_combine() {
+        let db = Main::new(None, VERS);
+        let name = "SkipChanGifs".to_string();
+        db.enclave_create(&name, &20).unwrap();
+        let step = |condition| EnclaveStep {
+            condition,
+            action: EnclaveAction::Skip,
+            failed_action: None,
+        };
+        assert!(
+            db.enclave_step_add(
+                &name,
+                &step(EnclaveCondition::SourceUrlRegex("(".into())),
+                &None
+            )
+            .is_err()
+        );
+        db.enclave_step_add(
+            &name,
+            &step(EnclaveCondition::All(vec![
+                EnclaveCondition::MimeType("image".into()),
+                EnclaveCondition::Extension("gif".into()),
+                EnclaveCondition::SourceHost("4chan.org".into()),
+                EnclaveCondition::Not(Box::new(EnclaveCondition::Scraper("Other".into()))),
+                EnclaveCondition::Dimensions(sharedtypes::DimensionBounds {
+                    min_width: Some(2),
+                    max_height: Some(3),
+                    ..Default::default()
+                }),
+            ])),
+            &None,
+        )
+        .unwrap();
+
+        let mut gif = b"GIF89a\x02\x00\x03\x00".to_vec();
+        gif.resize(64, 0);
+        let passes = |bytes: &Vec<u8>, source_url: &str| {
+            db.enclave_dry_run(bytes, &Vec::new(), &Some(source_url.into()), &None)[0].passed
+        };
+        assert!(passes(&gif, "https://i.4chan.org/a/1.gif"));
+        assert!(!passes(&gif, "https://not4chan.org/a/1.gif"));
+        assert!(!passes(&vec![0; 64], "https://i.4chan.org/a/1.gif"));
+
+        // Skipping ends processing without storing anything
+        let skipped = db.enclave_dry_run(
+            &gif,
+            &Vec::new(),
+            &Some("https://i.4chan.org/a/1.gif".into()),
+            &None,
+        );
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].stops);
+        assert_eq!(skipped[0].location, None);
+    }
+
+    #[test]
+    fn tag_in_db_sees_new_and_stored_tags() {
+        let db = Main::new(None, VERS);
+        let name = "KeepOnly".to_string();
+        db.enclave_create(&name, &20).unwrap();
+        db.enclave_step_add(
+            &name,
+            &EnclaveStep {
+                condition: EnclaveCondition::TagInDb(("keep".into(), "rating".into())),
+                action: EnclaveAction::PutAtDefault,
+                failed_action: Some(EnclaveAction::Skip),
+            },
+            &None,
+        )
+        .unwrap();
+        let keep = sharedtypes::Tag {
+            tag: "keep".into(),
+            namespace: sharedtypes::GenericNamespaceObj {
+                name: "rating".into(),
+                description: None,
+            },
+        };
+        let bytes = vec![7; 32];
+        let dry_run = |tags: &Vec<sharedtypes::Tag>| db.enclave_dry_run(&bytes, tags, &None, &None);
+
+        // A new file only has the tags it came with
+        let incoming = dry_run(&vec![keep.clone()]);
+        assert!(incoming[0].passed);
+        assert_eq!(incoming[0].action, Some(EnclaveAction::PutAtDefault));
+        let missing = dry_run(&Vec::new());
+        assert!(!missing[0].passed);
+        assert_eq!(missing[0].action, Some(EnclaveAction::Skip));
+        assert!(missing[0].stops);
+
+        // A file we already have gets checked against the db
+        let (hash, _) = crate::download::hash_bytes(
+            &Bytes::copy_from_slice(&bytes),
+            &sharedtypes::HashesSupported::Sha512(String::new()),
+        );
+        let storage_id = db.storage_put(&db.location_get());
+        let ext_id = db.extension_put_string(&"bin".to_string());
+        let file_id = db.file_add(sharedtypes::DbFileStorage::NoIdExist(
+            sharedtypes::DbFileObjNoId {
+                hash,
+                ext_id,
+                storage_id,
+            },
+        ));
+        let namespace_id = db.namespace_add(&"rating".to_string(), &None);
+        let tag_id = db.tag_add(&"keep".to_string(), namespace_id, None);
+        db.add_relationship(&file_id, &tag_id);
+        assert!(dry_run(&Vec::new())[0].passed);
+    }
 }
@@ -0,0 +1,513 @@
+use crate::database::database::Main;
+use crate::database::scrub::bytes_match;
+use bytes::Bytes;
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Longest a single folder or file name gets before it's cut short
+const NAME_MAX: usize = 200;
+
+/// Used when the template comes out empty
+const TEMPLATE_FALLBACK: &str = "{hash}.{ext}";
+
+///
+/// What happened to one file
+///
+enum Exported {
+    Written,
+    /// Something else was at the rendered path so it got a numbered name
+    Renamed,
+    /// The same file was already at the rendered path
+    Unchanged,
+}
+
+///
+/// Where exported files end up
+///
+enum ExportSink {
+    Folder(PathBuf),
+    Zip(zip::ZipWriter<File>),
+    Tar(tar::Builder<File>),
+}
+
+impl ExportSink {
+    fn new(options: &sharedtypes::ExportOptions) -> Result<Self, String> {
+        let destination = PathBuf::from(&options.destination);
+        let archive = || {
+            if let Some(parent) = destination.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|err| format!("Cannot make {}: {}", parent.display(), err))?;
+            }
+            File::create(&destination)
+                .map_err(|err| format!("Cannot create {}: {}", destination.display(), err))
+        };
+        Ok(match options.mode {
+            sharedtypes::ExportMode::Zip => ExportSink::Zip(zip::ZipWriter::new(archive()?)),
+            sharedtypes::ExportMode::Tar => ExportSink::Tar(tar::Builder::new(archive()?)),
+            _ => ExportSink::Folder(destination),
+        })
+    }
+
+    fn add(&mut self, name: &Path, bytes: &[u8]) -> Result<(), String> {
+        let entry = archive_name(name);
+        match self {
+            ExportSink::Folder(destination) => {
+                let path = destination.join(name);
+                std::fs::create_dir_all(path.parent().unwrap())
+                    .and_then(|_| std::fs::write(&path, bytes))
+                    .map_err(|err| format!("Cannot write {}: {}", path.display(), err))
+            }
+            ExportSink::Zip(zip) => zip
+                .start_file(&entry, zip::write::SimpleFileOptions::default())
+                .map_err(|err| err.to_string())
+                .and_then(|_| zip.write_all(bytes).map_err(|err| err.to_string()))
+                .map_err(|err| format!("Cannot add {} to the zip: {}", entry, err)),
+            ExportSink::Tar(tar) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(bytes.len() as u64);
+                header.set_mode(0o644);
+                header.set_mtime(Utc::now().timestamp().max(0) as u64);
+                header.set_cksum();
+                tar.append_data(&mut header, &entry, bytes)
+                    .map_err(|err| format!("Cannot add {} to the tar: {}", entry, err))
+            }
+        }
+    }
+
+    ///
+    /// Whether name is already taken by something other than the file with hash.
+    /// Archives are made fresh so only folders can have anything in them
+    ///
+    fn taken(&self, name: &Path, hash: &str) -> bool {
+        let ExportSink::Folder(destination) = self else {
+            return false;
+        };
+        let path = destination.join(name);
+        // symlink_metadata so a dangling link from an earlier export counts too
+        path.symlink_metadata().is_ok()
+            && !std::fs::read(&path).is_ok_and(|bytes| bytes_match(&Bytes::from(bytes), hash))
+    }
+
+    fn holds(&self, name: &Path) -> bool {
+        match self {
+            ExportSink::Folder(destination) => destination.join(name).symlink_metadata().is_ok(),
+            _ => false,
+        }
+    }
+
+    ///
+    /// Links name to source. Only folders can hold links, false means it has to be
+    /// copied instead
+    ///
+    fn link(
+        &mut self,
+        name: &Path,
+        source: &Path,
+        mode: sharedtypes::ExportMode,
+    ) -> Result<bool, String> {
+        let ExportSink::Folder(destination) = self else {
+            return Ok(false);
+        };
+        let path = destination.join(name);
+        std::fs::create_dir_all(path.parent().unwrap())
+            .map_err(|err| format!("Cannot make {}: {}", path.display(), err))?;
+        let linked = match mode {
+            sharedtypes::ExportMode::Hardlink => std::fs::hard_link(source, &path),
+            sharedtypes::ExportMode::Symlink => symlink(source, &path),
+            _ => return Ok(false),
+        };
+        linked.map_err(|err| {
+            format!(
+                "Cannot link {} to {}: {}",
+                path.display(),
+                source.display(),
+                err
+            )
+        })?;
+        Ok(true)
+    }
+
+    fn finish(self) -> Result<(), String> {
+        match self {
+            ExportSink::Folder(_) => Ok(()),
+            ExportSink::Zip(zip) => zip.finish().map(|_| ()).map_err(|err| err.to_string()),
+            ExportSink::Tar(tar) => tar.into_inner().map(|_| ()).map_err(|err| err.to_string()),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn symlink(source: &Path, path: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(source, path)
+}
+
+#[cfg(windows)]
+fn symlink(source: &Path, path: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(source, path)
+}
+
+impl Main {
+    ///
+    /// Exports every file a search finds
+    ///
+    pub(in crate::database) fn export_sql(
+        &self,
+        options: &sharedtypes::ExportOptions,
+    ) -> Result<sharedtypes::ExportReport, String> {
+        let file_ids = self
+            .search_db_files(options.search.clone(), None)
+            .unwrap_or_default();
+        self.export_ids(&file_ids, options)
+    }
+
+    fn export_ids(
+        &self,
+        file_ids: &[u64],
+        options: &sharedtypes::ExportOptions,
+    ) -> Result<sharedtypes::ExportReport, String> {
+        let mut sink = ExportSink::new(options)?;
+        let mut used = HashSet::new();
+        let mut report = sharedtypes::ExportReport::default();
+        for file_id in file_ids {
+            match self.export_file(&mut sink, options, file_id, &mut used) {
+                Ok(Exported::Written) => report.exported += 1,
+                Ok(Exported::Renamed) => {
+                    report.exported += 1;
+                    report.collisions += 1;
+                }
+                Ok(Exported::Unchanged) => report.unchanged += 1,
+                Err(err) => report.failed.push((*file_id, err)),
+            }
+        }
+        sink.finish()
+            .map_err(|err| format!("Cannot finish {}: {}", options.destination, err))?;
+        Ok(report)
+    }
+
+    fn export_file(
+        &self,
+        sink: &mut ExportSink,
+        options: &sharedtypes::ExportOptions,
+        file_id: &u64,
+        used: &mut HashSet<PathBuf>,
+    ) -> Result<Exported, String> {
+        let Some(sharedtypes::DbFileStorage::Exist(file)) = self.file_get_id(file_id) else {
+            return Err("isn't in the db".to_string());
+        };
+        let (store, ext) = self
+            .file_store_find(&file)
+            .ok_or_else(|| "is missing from disk".to_string())?;
+        let tags = self.export_tags(file_id);
+        let fill = |key: &str| match key {
+            "id" => Some(file_id.to_string()),
+            "hash" => Some(file.hash.clone()),
+            "ext" => Some(ext.clone()),
+            namespace => tags.get(namespace).map(|tags| tags.join(", ")),
+        };
+        let mut name = template_render(&options.template, &fill);
+        if name.as_os_str().is_empty() {
+            name = template_render(TEMPLATE_FALLBACK, &fill);
+        }
+        let mut collided = false;
+        let name = name_unique(name, used, |name| {
+            let taken = sink.taken(name, &file.hash);
+            collided |= taken;
+            taken
+        });
+        let exported = if sink.holds(&name) {
+            Exported::Unchanged
+        } else if collided {
+            Exported::Renamed
+        } else {
+            Exported::Written
+        };
+
+        if !matches!(exported, Exported::Unchanged) {
+            let linked = match (options.mode, store.backend()) {
+                (
+                    sharedtypes::ExportMode::Hardlink | sharedtypes::ExportMode::Symlink,
+                    sharedtypes::StorageBackend::Plain,
+                ) => match store.locate(&file.hash, &ext) {
+                    Some(source) => sink.link(&name, &canonical_absolute(&source), options.mode)?,
+                    None => false,
+                },
+                _ => false,
+            };
+            if !linked {
+                let bytes = store
+                    .read(&file.hash, &ext)
+                    .ok_or_else(|| "couldn't be read".to_string())?;
+                sink.add(&name, &bytes)?;
+            }
+        }
+
+        for sidecar in options.sidecars.iter() {
+            let (ext, contents) = match sidecar {
+                sharedtypes::SidecarFormat::Txt => ("txt", sidecar_txt(&tags)),
+                sharedtypes::SidecarFormat::Json => ("json", sidecar_json(&tags)),
+            };
+            // find_sidecar looks for the whole file name with the sidecar's extension added
+            let mut sidecar_name = name.clone().into_os_string();
+            sidecar_name.push(".");
+            sidecar_name.push(ext);
+            sink.add(Path::new(&sidecar_name), contents.as_bytes())?;
+        }
+        Ok(exported)
+    }
+
+    ///
+    /// A file's tags by namespace name, both sorted so exports come out the same
+    /// every time
+    ///
+    fn export_tags(&self, file_id: &u64) -> BTreeMap<String, Vec<String>> {
+        let mut out: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for tag_id in self.relationship_get_tagid(file_id) {
+            if let Some(tag) = self.tag_id_get(&tag_id)
+                && let Some(namespace) = self.namespace_get_string(&tag.namespace)
+            {
+                out.entry(namespace.name).or_default().push(tag.name);
+            }
+        }
+        for tags in out.values_mut() {
+            tags.sort();
+        }
+        out
+    }
+}
+
+///
+/// Fills in the {keys} of template. Each folder of the template gets filled in by
+/// itself so a tag with a slash in it can't make new folders or climb out of the
+/// destination
+///
+fn template_render(template: &str, fill: impl Fn(&str) -> Option<String>) -> PathBuf {
+    let mut out = PathBuf::new();
+    for part in template.split(['/', '\\']) {
+        let mut rendered = String::new();
+        let mut rest = part;
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            let Some(end) = rest[start..].find('}') else {
+                break;
+            };
+            let key = &rest[start + 1..start + end];
+            rendered.push_str(&name_clean(
+                &fill(key).unwrap_or_else(|| "unknown".to_string()),
+            ));
+            rest = &rest[start + end + 1..];
+        }
+        rendered.push_str(rest);
+        let rendered = name_cut(rendered.trim());
+        if !rendered.is_empty() && rendered != "." && rendered != ".." {
+            out.push(rendered);
+        }
+    }
+    out
+}
+
+///
+/// Swaps out characters that aren't allowed in file names on some system
+///
+fn name_clean(value: &str) -> String {
+    value
+        .chars()
+        .map(|chr| match chr {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            chr if chr.is_control() => '_',
+            chr => chr,
+        })
+        .collect()
+}
+
+fn name_cut(name: &str) -> String {
+    let mut end = name.len().min(NAME_MAX);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    name[..end].to_string()
+}
+
+///
+/// Adds (2), (3) and so on before the extension when two files render to the same name
+/// or taken says something is already there
+///
+fn name_unique(
+    name: PathBuf,
+    used: &mut HashSet<PathBuf>,
+    mut taken: impl FnMut(&Path) -> bool,
+) -> PathBuf {
+    let mut out = name.clone();
+    let mut cnt = 1;
+    while used.contains(&out) || taken(&out) {
+        cnt += 1;
+        let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+        let numbered = match name.extension() {
+            Some(ext) => format!("{} ({}).{}", stem, cnt, ext.to_string_lossy()),
+            None => format!("{} ({})", stem, cnt),
+        };
+        out = name.with_file_name(numbered);
+    }
+    used.insert(out.clone());
+    out
+}
+
+fn archive_name(name: &Path) -> String {
+    name.components()
+        .map(|part| part.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn canonical_absolute(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn sidecar_txt(tags: &BTreeMap<String, Vec<String>>) -> String {
+    tags.iter()
+        .flat_map(|(namespace, tags)| {
+            tags.iter()
+                .map(move |tag| format!("{}:{}\n", namespace, tag))
+        })
+        .collect()
+}
+
+fn sidecar_json(tags: &BTreeMap<String, Vec<String>>) -> String {
+    serde_json::to_string_pretty(&crate::sidecar::json_from_tags(tags)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::find_sidecar;
+    use crate::testutil::test_db;
+
+    #[test]
+    fn export_renders_names_and_sidecars() {
+        let (db, dir) = test_db("export");
+        let root = dir.location("root");
+
+        std::fs::create_dir_all(&root).unwrap();
+        let storage_id = db.storage_put(&root);
+        let hash = "ABCDEF0123".to_string();
+        db.file_store(&root)
+            .write(&hash, "txt", b"exported")
+            .unwrap();
+        let file_id = db.file_add(sharedtypes::DbFileStorage::NoIdExist(
+            sharedtypes::DbFileObjNoId {
+                hash: hash.clone(),
+                ext_id: db.extension_put_string(&"txt".to_string()),
+                storage_id,
+            },
+        ));
+        let creator = db.namespace_add(&"creator".to_string(), &None);
+        let title = db.namespace_add(&"title".to_string(), &None);
+        for (tag, namespace) in [("bob", creator), ("a/b: c", title)] {
+            let tag_id = db.tag_add(&tag.to_string(), namespace, None);
+            db.add_relationship(&file_id, &tag_id);
+        }
+
+        let mut options = sharedtypes::ExportOptions {
+            search: sharedtypes::SearchObj {
+                search_relate: None,
+                searches: Vec::new(),
+            },
+            destination: dir.location("out"),
+            mode: sharedtypes::ExportMode::Copy,
+            template: "{creator}/{title} - {id}.{ext}".to_string(),
+            sidecars: vec![sharedtypes::SidecarFormat::Txt],
+        };
+        let report = db.export_ids(&[file_id, file_id], &options).unwrap();
+        assert_eq!(report.exported, 2);
+
+        let exported = dir
+            .join("out")
+            .join("bob")
+            .join(format!("a_b_ c - {}.txt", file_id));
+        assert_eq!(std::fs::read(&exported).unwrap(), b"exported");
+        assert!(
+            exported
+                .with_file_name(format!("a_b_ c - {} (2).txt", file_id))
+                .is_file()
+        );
+        let sidecars = find_sidecar(&exported);
+        assert_eq!(sidecars.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&sidecars[0]).unwrap(),
+            "creator:bob\ntitle:a/b: c\n"
+        );
+
+        options.mode = sharedtypes::ExportMode::Zip;
+        options.destination = dir.location("out.zip");
+        options.template = "{missing}/{hash}.{ext}".to_string();
+        db.export_ids(&[file_id], &options).unwrap();
+        let zip = zip::ZipArchive::new(File::open(&options.destination).unwrap()).unwrap();
+        let mut names: Vec<_> = zip.file_names().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["unknown/ABCDEF0123.txt", "unknown/ABCDEF0123.txt.txt"]
+        );
+    }
+
+    #[test]
+    fn export_again_skips_or_renames() {
+        let (db, dir) = test_db("export-again");
+        let root = dir.location("root");
+
+        std::fs::create_dir_all(&root).unwrap();
+        let storage_id = db.storage_put(&root);
+        let bytes = Bytes::from_static(b"exported");
+        let (hash, _) = crate::download::hash_bytes(
+            &bytes,
+            &sharedtypes::HashesSupported::Sha512(String::new()),
+        );
+        db.file_store(&root).write(&hash, "txt", &bytes).unwrap();
+        let file_id = db.file_add(sharedtypes::DbFileStorage::NoIdExist(
+            sharedtypes::DbFileObjNoId {
+                hash: hash.clone(),
+                ext_id: db.extension_put_string(&"txt".to_string()),
+                storage_id,
+            },
+        ));
+
+        let options = sharedtypes::ExportOptions {
+            search: sharedtypes::SearchObj {
+                search_relate: None,
+                searches: Vec::new(),
+            },
+            destination: dir.location("out"),
+            mode: sharedtypes::ExportMode::Copy,
+            template: "{id}.{ext}".to_string(),
+            sidecars: Vec::new(),
+        };
+        let exported = dir.join("out").join(format!("{}.txt", file_id));
+        let report = db.export_ids(&[file_id], &options).unwrap();
+        assert_eq!(
+            (report.exported, report.unchanged, report.collisions),
+            (1, 0, 0)
+        );
+
+        let report = db.export_ids(&[file_id], &options).unwrap();
+        assert_eq!(
+            (report.exported, report.unchanged, report.collisions),
+            (0, 1, 0)
+        );
+
+        std::fs::write(&exported, b"someone else's").unwrap();
+        let report = db.export_ids(&[file_id], &options).unwrap();
+        assert_eq!(
+            (report.exported, report.unchanged, report.collisions),
+            (1, 0, 1)
+        );
+        assert_eq!(std::fs::read(&exported).unwrap(), b"someone else's");
+        assert_eq!(
+            std::fs::read(exported.with_file_name(format!("{} (2).txt", file_id))).unwrap(),
+            bytes
+        );
+    }
+}
@@ -1,12 +1,21 @@
 pub mod database;
 pub mod dbtraits;
 pub mod enclave;
+pub mod export;
 pub mod fuzzy_search;
 pub mod inmemdbnew;
+pub mod pluginregistry;
+pub mod pluginstorage;
 pub mod public_calls;
 pub mod roaring_bitmap;
+pub mod s3roots;
+pub mod scrub;
 pub mod sqlitedb;
+pub mod storagebackend;
+pub mod storagemigrate;
+pub mod storageroots;
 pub mod tagfunctions;
 pub mod updatehandler;
+pub mod watchfolders;
 
 pub use public_calls::*;
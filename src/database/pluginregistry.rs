@@ -0,0 +1,250 @@
+use crate::database::database::Main;
+use crate::database::database::Transaction;
+use crate::logging;
+use rusqlite::OptionalExtension;
+use rusqlite::params;
+
+const TRUSTED_KEYS_SETTING: &str = "PLUGIN_TRUSTED_KEYS";
+const REQUIRE_SIGNATURE_SETTING: &str = "PLUGIN_REQUIRE_SIGNATURE";
+
+///
+/// A library we've seen in the plugin or scraper folder
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginRegistryEntry {
+    /// File name of the library. Used as the key so folders can move
+    pub file: String,
+    /// Names of the entries the library registered
+    pub names: Vec<String>,
+    /// Highest GlobalPluginScraper version the library reported
+    pub version: u64,
+    pub sha256: String,
+    pub enabled: bool,
+    /// Lower loads first
+    pub load_order: i64,
+    /// When set the library only loads if its hash matches
+    pub pinned_sha256: Option<String>,
+}
+
+impl PluginRegistryEntry {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        let names: String = row.get(1)?;
+        Ok(PluginRegistryEntry {
+            file: row.get(0)?,
+            // Stored as a json list. Names can have commas in them
+            names: serde_json::from_str(&names).unwrap_or_else(|_| vec![names]),
+            version: row.get(2)?,
+            sha256: row.get(3)?,
+            enabled: row.get(4)?,
+            load_order: row.get(5)?,
+            pinned_sha256: row.get(6)?,
+        })
+    }
+}
+
+impl Main {
+    ///
+    /// Creates the table that tracks known plugins and scrapers
+    ///
+    pub(in crate::database) fn plugin_registry_create_v1(&self, tn: &Transaction) {
+        tn.execute(
+            "
+CREATE TABLE IF NOT EXISTS PluginRegistry (
+  file TEXT PRIMARY KEY NOT NULL,
+  name TEXT NOT NULL,
+  version INTEGER NOT NULL,
+  sha256 TEXT NOT NULL,
+  enabled INTEGER NOT NULL DEFAULT 1,
+  load_order INTEGER NOT NULL DEFAULT 0,
+  pinned_sha256 TEXT
+);
+",
+            [],
+        )
+        .unwrap();
+    }
+
+    ///
+    /// Gets the registry entry for a library's file name
+    ///
+    pub fn plugin_registry_get(&self, file: &str) -> Option<PluginRegistryEntry> {
+        let conn = self.get_database_connection();
+        conn.query_row(
+            "SELECT file, name, version, sha256, enabled, load_order, pinned_sha256 FROM PluginRegistry WHERE file = ?",
+            params![file],
+            PluginRegistryEntry::from_row,
+        )
+        .optional()
+        .unwrap_or_default()
+    }
+
+    ///
+    /// Lists every known library in load order
+    ///
+    pub fn plugin_registry_list(&self) -> Vec<PluginRegistryEntry> {
+        let conn = self.get_database_connection();
+        let mut prep = conn
+            .prepare(
+                "SELECT file, name, version, sha256, enabled, load_order, pinned_sha256 FROM PluginRegistry ORDER BY load_order, file",
+            )
+            .unwrap();
+        prep.query_map([], PluginRegistryEntry::from_row)
+            .unwrap()
+            .flatten()
+            .collect()
+    }
+
+    ///
+    /// Adds or updates a library in the registry
+    ///
+    pub fn plugin_registry_put(&self, entry: &PluginRegistryEntry) {
+        let mut write_conn = self.write_conn.lock();
+        let tn = write_conn.transaction().unwrap();
+        tn.execute(
+            "INSERT OR REPLACE INTO PluginRegistry (file, name, version, sha256, enabled, load_order, pinned_sha256) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![
+                entry.file,
+                serde_json::to_string(&entry.names).unwrap(),
+                entry.version,
+                entry.sha256,
+                entry.enabled,
+                entry.load_order,
+                entry.pinned_sha256
+            ],
+        )
+        .unwrap();
+        tn.commit().unwrap();
+    }
+
+    ///
+    /// Runs an update against a single registry row. Returns false if the library isn't known
+    ///
+    fn plugin_registry_update(&self, sql: &str, params: &[&dyn rusqlite::ToSql]) -> bool {
+        let mut write_conn = self.write_conn.lock();
+        let tn = write_conn.transaction().unwrap();
+        let changed = tn.execute(sql, params).unwrap();
+        tn.commit().unwrap();
+        changed > 0
+    }
+
+    ///
+    /// Enables or disables loading a library
+    ///
+    pub fn plugin_registry_enabled_set(&self, file: &str, enabled: bool) -> bool {
+        self.plugin_registry_update(
+            "UPDATE PluginRegistry SET enabled = ? WHERE file = ?",
+            params![enabled, file],
+        )
+    }
+
+    ///
+    /// Pins a library to the hash it was last loaded with or clears the pin
+    ///
+    pub fn plugin_registry_pin_set(&self, file: &str, pinned: bool) -> bool {
+        let sql = if pinned {
+            "UPDATE PluginRegistry SET pinned_sha256 = sha256 WHERE file = ?"
+        } else {
+            "UPDATE PluginRegistry SET pinned_sha256 = NULL WHERE file = ?"
+        };
+        self.plugin_registry_update(sql, params![file])
+    }
+
+    ///
+    /// Sets where a library loads relative to the others in its folder
+    ///
+    pub fn plugin_registry_order_set(&self, file: &str, load_order: i64) -> bool {
+        self.plugin_registry_update(
+            "UPDATE PluginRegistry SET load_order = ? WHERE file = ?",
+            params![load_order, file],
+        )
+    }
+
+    ///
+    /// Forgets a library. Its next load is treated as new so an older version can load
+    ///
+    pub fn plugin_registry_remove(&self, file: &str) -> bool {
+        self.plugin_registry_update("DELETE FROM PluginRegistry WHERE file = ?", params![file])
+    }
+
+    ///
+    /// Removes a library from the plugin and scraper folders along with its registry
+    /// entry and everything it kept in plugin storage
+    ///
+    pub fn plugin_uninstall(&self, file: &str) -> bool {
+        let Some(entry) = self.plugin_registry_get(file) else {
+            return false;
+        };
+        for name in entry.names.iter() {
+            let removed = self.plugin_storage_purge_sql(name);
+            logging::info_log(format!("Purged {} storage keys for {}", removed, name));
+        }
+        self.plugin_registry_remove(file);
+
+        for folder in [self.loaded_scraper_folder(), self.loaded_plugin_folder()] {
+            let path = folder.join(file);
+            if !path.exists() {
+                continue;
+            }
+            let sig = crate::pluginverify::signature_path(&path);
+            let mut caps = path.as_os_str().to_owned();
+            caps.push(".caps.json");
+            for remove in [path, sig, caps.into()] {
+                if remove.exists()
+                    && let Err(err) = std::fs::remove_file(&remove)
+                {
+                    logging::error_log(format!("Cannot remove {}: {}", remove.display(), err));
+                }
+            }
+        }
+        true
+    }
+
+    ///
+    /// Public keys that can sign plugins. Stored as hex
+    ///
+    pub fn plugin_trusted_keys_get(&self) -> Vec<String> {
+        self.settings_get_name(&TRUSTED_KEYS_SETTING.to_string())
+            .and_then(|setting| setting.param)
+            .map(|param| {
+                param
+                    .split(',')
+                    .map(|key| key.trim().to_string())
+                    .filter(|key| !key.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    ///
+    /// Overwrites the trusted plugin keys
+    ///
+    pub fn plugin_trusted_keys_set(&self, keys: &[String]) {
+        self.setting_add(
+            TRUSTED_KEYS_SETTING.to_string(),
+            Some("Hex ed25519 public keys that can sign plugins and scrapers.".to_string()),
+            None,
+            Some(keys.join(",")),
+        );
+    }
+
+    ///
+    /// Checks if unsigned plugins should be refused
+    ///
+    pub fn plugin_require_signature_get(&self) -> bool {
+        match self.settings_get_name(&REQUIRE_SIGNATURE_SETTING.to_string()) {
+            Some(setting) => setting.num == Some(1),
+            None => false,
+        }
+    }
+
+    pub fn plugin_require_signature_set(&self, required: bool) {
+        self.setting_add(
+            REQUIRE_SIGNATURE_SETTING.to_string(),
+            Some(
+                "Refuses plugins and scrapers without a valid signature when set to 1.".to_string(),
+            ),
+            Some(required as u64),
+            None,
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::database::database::Main;
+use crate::database::database::Transaction;
+use crate::logging;
+use rusqlite::OptionalExtension;
+use rusqlite::params;
+
+impl Main {
+    ///
+    /// Creates the table plugins keep their own data in
+    ///
+    pub(in crate::database) fn plugin_storage_create_v1(&self, tn: &Transaction) {
+        tn.execute(
+            "
+CREATE TABLE IF NOT EXISTS PluginStorage (
+  plugin TEXT NOT NULL,
+  key TEXT NOT NULL,
+  value BLOB NOT NULL,
+  PRIMARY KEY (plugin, key)
+) WITHOUT ROWID;
+",
+            [],
+        )
+        .unwrap();
+    }
+
+    fn plugin_storage_value_decode(
+        plugin: &str,
+        key: &str,
+        value: Vec<u8>,
+    ) -> Option<sharedtypes::PluginStorageValue> {
+        match bitcode::decode(&value) {
+            Ok(value) => Some(value),
+            Err(err) => {
+                logging::error_log(format!(
+                    "Plugin storage for {} has a bad value at {}: {}",
+                    plugin, key, err
+                ));
+                None
+            }
+        }
+    }
+
+    pub(in crate::database) fn plugin_storage_get_sql(
+        &self,
+        plugin: &str,
+        key: &str,
+    ) -> Option<sharedtypes::PluginStorageValue> {
+        let conn = self.get_database_connection();
+        let value: Vec<u8> = conn
+            .query_row(
+                "SELECT value FROM PluginStorage WHERE plugin = ? AND key = ?",
+                params![plugin, key],
+                |row| row.get(0),
+            )
+            .optional()
+            .unwrap_or_default()?;
+        Self::plugin_storage_value_decode(plugin, key, value)
+    }
+
+    pub(in crate::database) fn plugin_storage_list_sql(
+        &self,
+        plugin: &str,
+        prefix: &str,
+    ) -> Vec<(String, sharedtypes::PluginStorageValue)> {
+        let conn = self.get_database_connection();
+        let mut prep = conn
+            .prepare(
+                "SELECT key, value FROM PluginStorage WHERE plugin = ?1 AND substr(key, 1, length(?2)) = ?2 ORDER BY key",
+            )
+            .unwrap();
+        prep.query_map(params![plugin, prefix], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?))
+        })
+        .unwrap()
+        .flatten()
+        .filter_map(|(key, value)| {
+            Self::plugin_storage_value_decode(plugin, &key, value).map(|value| (key, value))
+        })
+        .collect()
+    }
+
+    ///
+    /// Applies every op for a plugin in one transaction. Either all land or none do
+    ///
+    pub(in crate::database) fn plugin_storage_batch_sql(
+        &self,
+        plugin: &str,
+        ops: &[sharedtypes::PluginStorageOp],
+    ) -> bool {
+        let mut write_conn = self.write_conn.lock();
+        let tn = write_conn.transaction().unwrap();
+        for op in ops {
+            let out = match op {
+                sharedtypes::PluginStorageOp::Put(key, value) => tn.execute(
+                    "INSERT OR REPLACE INTO PluginStorage (plugin, key, value) VALUES (?, ?, ?)",
+                    params![plugin, key, bitcode::encode(value)],
+                ),
+                sharedtypes::PluginStorageOp::Delete(key) => tn.execute(
+                    "DELETE FROM PluginStorage WHERE plugin = ? AND key = ?",
+                    params![plugin, key],
+                ),
+            };
+            if let Err(err) = out {
+                logging::error_log(format!(
+                    "Plugin storage batch for {} failed, rolling back: {}",
+                    plugin, err
+                ));
+                // Dropping the transaction rolls it back
+                return false;
+            }
+        }
+        tn.commit().is_ok()
+    }
+
+    pub(in crate::database) fn plugin_storage_purge_sql(&self, plugin: &str) -> usize {
+        let mut write_conn = self.write_conn.lock();
+        let tn = write_conn.transaction().unwrap();
+        let removed = tn
+            .execute(
+                "DELETE FROM PluginStorage WHERE plugin = ?",
+                params![plugin],
+            )
+            .unwrap();
+        tn.commit().unwrap();
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VERS;
+    use sharedtypes::{PluginStorageOp, PluginStorageValue};
+
+    #[test]
+    fn plugin_storage_is_namespaced() {
+        let db = Main::new(None, VERS);
+        let thumb = "thumbnailer".to_string();
+        let hash = "hasher".to_string();
+
+        assert!(db.plugin_storage_batch(
+            &thumb,
+            &vec![
+                PluginStorageOp::Put("location".into(), PluginStorageValue::Text("/tmp".into())),
+                PluginStorageOp::Put("loc_count".into(), PluginStorageValue::UInt(3)),
+                PluginStorageOp::Put("size".into(), PluginStorageValue::Int(-1)),
+            ]
+        ));
+        db.plugin_storage_put(&hash, &"location".into(), &PluginStorageValue::Bool(true));
+
+        assert_eq!(
+            db.plugin_storage_get(&thumb, &"location".into()),
+            Some(PluginStorageValue::Text("/tmp".into()))
+        );
+        let listed: Vec<String> = db
+            .plugin_storage_list(&thumb, &"loc".into())
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(
+            listed,
+            vec!["loc_count".to_string(), "location".to_string()]
+        );
+
+        db.plugin_storage_delete(&thumb, &"size".into());
+        assert_eq!(db.plugin_storage_get(&thumb, &"size".into()), None);
+
+        assert_eq!(db.plugin_storage_purge(&thumb), 2);
+        assert!(db.plugin_storage_list(&thumb, &String::new()).is_empty());
+        assert_eq!(
+            db.plugin_storage_get(&hash, &"location".into()),
+            Some(PluginStorageValue::Bool(true))
+        );
+    }
+}
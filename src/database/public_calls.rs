@@ -1,9 +1,11 @@
 use crate::database::database::CacheType;
 use crate::database::database::Main;
+use crate::database::enclave::ConditionInput;
+use crate::database::storagebackend::backend_owned;
+use crate::download::hash_bytes;
 use crate::download::hash_file;
 use crate::file;
-use crate::helpers;
-use crate::helpers::getfinpath;
+use crate::helpers::getfinpath_depth;
 use crate::logging;
 use crate::roaring_bitmap::SearchQuery;
 use remove_empty_subdirs::remove_empty_subdirs;
@@ -496,8 +498,10 @@ impl Main {
         let file_dump_path = Path::new(&file_dump);
 
         for db_path in db_paths.iter() {
+            let shard_depth = self.storage_shard_depths(db_path)[0];
             for entry in walkdir::WalkDir::new(db_path).into_iter().flatten() {
                 if entry.path().is_file()
+                    && !backend_owned(db_path, entry.path())
                     && let Some(filename) = entry.path().file_name()
                 {
                     if self
@@ -514,7 +518,7 @@ impl Main {
 
                             let cleaned_filename = cleaned_filepath.as_path().file_name().unwrap();
 
-                            let test_path = Path::new(&getfinpath(
+                            let test_path = Path::new(&getfinpath_depth(
                                 db_path,
                                 &entry
                                     .path()
@@ -522,6 +526,7 @@ impl Main {
                                     .unwrap()
                                     .to_string_lossy()
                                     .to_string(),
+                                shard_depth,
                                 true,
                             ))
                             .join(Path::new(&cleaned_filename));
@@ -560,7 +565,7 @@ impl Main {
 
                         let cleaned_filename = cleaned_filepath.as_path().file_name().unwrap();
 
-                        let test_path = Path::new(&getfinpath(
+                        let test_path = Path::new(&getfinpath_depth(
                             db_path,
                             &entry
                                 .path()
@@ -568,6 +573,7 @@ impl Main {
                                 .unwrap()
                                 .to_string_lossy()
                                 .to_string(),
+                            shard_depth,
                             true,
                         ))
                         .join(Path::new(&cleaned_filename));
@@ -698,46 +704,41 @@ impl Main {
     /// Returns a files bytes if the file exists. Note if called from intcom then this
     /// locks the DB while getting the file. One workaround it to use get_file and read
     /// bytes in manually in seperate thread. that way minimal locking happens.
+    /// Compressed and packed files come back as they were before they were stored.
     pub fn get_file_bytes(&self, file_id: &u64) -> Option<Vec<u8>> {
-        let loc = self.get_file(file_id);
-        if let Some(loc) = loc {
-            return Some(std::fs::read(loc).unwrap());
-        }
-        None
+        let file = match self.file_get_id(file_id)? {
+            sharedtypes::DbFileStorage::Exist(file) => file,
+            _ => return None,
+        };
+        let (store, ext) = self.file_store_find(&file)?;
+        store.read(&file.hash, &ext)
     }
 
     /// Gets the location of a file in the file system
+    /// Files that aren't stored as is get unpacked into a temp folder and that copy's
+    /// location is returned. Files in a bucket come back as a cached copy's location or
+    /// a presigned url, depending on how the bucket's root is set up
     pub fn get_file(&self, file_id: &u64) -> Option<String> {
         // Early match
         let file = match self.file_get_id(file_id)? {
             sharedtypes::DbFileStorage::Exist(file) => file,
             _ => return None,
         };
-
-        if let Some(ref ext) = self.extension_get_string(&file.ext_id) {
-            /*   for base in [
-                self.storage_get_string(&file.storage_id)?,
-                self.location_get(),
-            ].iter() {*/
-            for base in self.storage_get_likely(file_id).iter() {
-                let path = Path::new(&helpers::getfinpath(base, &file.hash, false))
-                    .join(&file.hash)
-                    .with_extension(ext);
-
-                if std::fs::metadata(&path).is_ok() {
-                    return Some(path.to_string_lossy().to_string());
-                }
-
-                let path_noext = path.with_extension("");
-
-                if std::fs::metadata(&path_noext).is_ok() {
-                    let _ = std::fs::rename(&path_noext, &path);
-                    return Some(path.to_string_lossy().to_string());
-                }
-            }
+        let (store, ext) = self.file_store_find(&file)?;
+        if let Some(shared) = store.share(&file.hash, &ext) {
+            return Some(shared);
         }
 
-        None
+        let path = self.file_unpacked_path(&file.hash, &ext);
+        if !path.is_file() {
+            let bytes = store.read(&file.hash, &ext)?;
+            std::fs::create_dir_all(path.parent()?).ok()?;
+            let temp = path.with_extension("part");
+            std::fs::write(&temp, bytes)
+                .and_then(|_| std::fs::rename(&temp, &path))
+                .ok()?;
+        }
+        Some(path.to_string_lossy().to_string())
     }
 
     /*pub fn get_file(&self, file_id: &u64) -> Option<String> {
@@ -935,7 +936,7 @@ impl Main {
                     .and_search(&and_tags)
                     .build();
 
-                println!("Roaring Search took: {:?}", start_time.elapsed());
+                crate::metrics::search_record("roaring", start_time.elapsed());
                 return if results.is_empty() {
                     None
                 } else {
@@ -1019,7 +1020,6 @@ impl Main {
             sql.push_str(" LIMIT ?");
             params.push(l);
         }
-        dbg!(&sql, &params);
 
         let mut stmt = conn.prepare(&sql).ok()?;
         let results: Vec<u64> = stmt
@@ -1028,13 +1028,127 @@ impl Main {
             .filter_map(|r| r.ok())
             .collect();
 
-        println!("SQL Search took: {:?}", start_time.elapsed());
+        crate::metrics::search_record("sql", start_time.elapsed());
         if results.is_empty() {
             None
         } else {
             Some(results)
         }
     }
+    ///
+    /// Sets the log level for a module path like rusthydrus::download. None sets the
+    /// default level. Returns false if the level isn't valid
+    ///
+    pub fn log_level_set(&self, module: &Option<String>, level: &String) -> bool {
+        match logging::level_parse(level) {
+            Some(level) => {
+                logging::level_set(module.as_deref(), level);
+                true
+            }
+            None => false,
+        }
+    }
+
+    ///
+    /// Removes a modules log level so it uses the default again
+    ///
+    pub fn log_level_clear(&self, module: &String) -> bool {
+        logging::level_clear(module)
+    }
+
+    ///
+    /// Gets the default log level and any per module levels
+    ///
+    pub fn log_levels_get(&self) -> BTreeMap<String, String> {
+        logging::levels_get()
+    }
+
+    ///
+    /// Switches the log output between json lines and text
+    ///
+    pub fn log_json_set(&self, json: &bool) {
+        logging::json_set(*json);
+    }
+
+    ///
+    /// Checks if all downloads are paused
+    ///
+    pub fn downloads_pause_get(&self) -> bool {
+        match self.settings_get_name(&"SYSTEM_PAUSE_DOWNLOADS".to_string()) {
+            Some(setting) => setting.num == Some(1),
+            None => false,
+        }
+    }
+
+    ///
+    /// Pauses or resumes all downloads. Workers wait at the next url or file while paused
+    ///
+    pub fn downloads_pause_set(&self, paused: &bool) {
+        logging::info_log(format!("Setting pause all downloads to: {}", paused));
+        self.setting_add(
+            "SYSTEM_PAUSE_DOWNLOADS".to_string(),
+            Some("Pauses all downloads when set to 1.".to_string()),
+            Some(*paused as u64),
+            None,
+        );
+    }
+
+    ///
+    /// Pauses a job so it won't get ran
+    ///
+    pub fn jobs_pause(&self, job_id: &u64) {
+        match self.globalload {
+            Some(ref globalload) => globalload.jobmanager.jobs_pause(job_id),
+            None => self.jobs_paused_set_db(job_id, true),
+        }
+    }
+
+    ///
+    /// Resumes a paused job
+    ///
+    pub fn jobs_resume(&self, job_id: &u64) {
+        match self.globalload {
+            Some(ref globalload) => globalload.jobmanager.jobs_resume(job_id),
+            None => self.jobs_paused_set_db(job_id, false),
+        }
+    }
+
+    ///
+    /// Cancels a running job at the next url or file
+    ///
+    pub fn jobs_cancel(&self, job_id: &u64) {
+        if let Some(ref globalload) = self.globalload {
+            globalload.jobmanager.jobs_cancel(job_id);
+        }
+    }
+
+    ///
+    /// Pauses a scraper's worker
+    ///
+    pub fn scraper_pause(&self, scraper_name: &String) {
+        if let Some(ref globalload) = self.globalload {
+            globalload.jobmanager.scraper_pause(scraper_name);
+        }
+    }
+
+    ///
+    /// Resumes a paused or cancelled scraper's worker
+    ///
+    pub fn scraper_resume(&self, scraper_name: &String) {
+        if let Some(ref globalload) = self.globalload {
+            globalload.jobmanager.scraper_resume(scraper_name);
+        }
+    }
+
+    ///
+    /// Drains a scraper's worker. Stops running jobs and doesn't start new ones
+    ///
+    pub fn scraper_cancel(&self, scraper_name: &String) {
+        if let Some(ref globalload) = self.globalload {
+            globalload.jobmanager.scraper_cancel(scraper_name);
+        }
+    }
+
     /// Gets all jobs loaded in the db
     pub fn jobs_get_all(&self) -> HashMap<u64, sharedtypes::DbJobsObj> {
         match &self._cache {
@@ -1294,4 +1408,300 @@ impl Main {
             Some(id) => id,
         }
     }
+
+    ///
+    /// Gets a value out of a plugin's storage. Plugins are namespaced by the name the
+    /// caller sends, nothing checks it's really that plugin asking
+    ///
+    pub fn plugin_storage_get(
+        &self,
+        plugin: &String,
+        key: &String,
+    ) -> Option<sharedtypes::PluginStorageValue> {
+        self.plugin_storage_get_sql(plugin, key)
+    }
+
+    ///
+    /// Puts a value into a plugin's storage. Overwrites whatever was at key
+    ///
+    pub fn plugin_storage_put(
+        &self,
+        plugin: &String,
+        key: &String,
+        value: &sharedtypes::PluginStorageValue,
+    ) -> bool {
+        self.plugin_storage_batch_sql(
+            plugin,
+            &[sharedtypes::PluginStorageOp::Put(
+                key.clone(),
+                value.clone(),
+            )],
+        )
+    }
+
+    ///
+    /// Removes a key from a plugin's storage
+    ///
+    pub fn plugin_storage_delete(&self, plugin: &String, key: &String) -> bool {
+        self.plugin_storage_batch_sql(plugin, &[sharedtypes::PluginStorageOp::Delete(key.clone())])
+    }
+
+    ///
+    /// Lists every key and value in a plugin's storage that starts with prefix.
+    /// An empty prefix lists everything.
+    ///
+    pub fn plugin_storage_list(
+        &self,
+        plugin: &String,
+        prefix: &String,
+    ) -> Vec<(String, sharedtypes::PluginStorageValue)> {
+        self.plugin_storage_list_sql(plugin, prefix)
+    }
+
+    ///
+    /// Applies a batch of puts and deletes to a plugin's storage in one transaction
+    ///
+    pub fn plugin_storage_batch(
+        &self,
+        plugin: &String,
+        ops: &Vec<sharedtypes::PluginStorageOp>,
+    ) -> bool {
+        self.plugin_storage_batch_sql(plugin, ops)
+    }
+
+    ///
+    /// Removes everything a plugin stored. Returns how many keys were removed
+    ///
+    pub fn plugin_storage_purge(&self, plugin: &String) -> u64 {
+        self.plugin_storage_purge_sql(plugin) as u64
+    }
+
+    ///
+    /// Every enclave and its steps in the order files get run through them
+    ///
+    pub fn enclave_list(&self) -> Vec<sharedtypes::EnclaveInfo> {
+        self.enclave_list_sql()
+    }
+
+    ///
+    /// Makes an empty enclave. Higher priorities get tried first
+    ///
+    pub fn enclave_create(&self, name: &String, priority: &u64) -> Result<u64, String> {
+        self.enclave_create_sql(name, priority)
+    }
+
+    pub fn enclave_rename(&self, name: &String, new_name: &String) -> Result<(), String> {
+        self.enclave_rename_sql(name, new_name)
+    }
+
+    ///
+    /// Removes an enclave and its steps. Files it already handled stay where they are
+    ///
+    pub fn enclave_delete(&self, name: &String) -> Result<(), String> {
+        self.enclave_delete_sql(name)
+    }
+
+    pub fn enclave_priority_set(&self, name: &String, priority: &u64) -> Result<(), String> {
+        self.enclave_priority_set_sql(name, priority)
+    }
+
+    ///
+    /// Adds a step to an enclave. No position puts it at the end
+    ///
+    pub fn enclave_step_add(
+        &self,
+        name: &String,
+        step: &sharedtypes::EnclaveStep,
+        position: &Option<u64>,
+    ) -> Result<(), String> {
+        self.enclave_step_add_sql(name, step, *position)
+    }
+
+    pub fn enclave_step_remove(&self, name: &String, position: &u64) -> Result<(), String> {
+        self.enclave_step_remove_sql(name, position)
+    }
+
+    pub fn enclave_step_move(&self, name: &String, from: &u64, to: &u64) -> Result<(), String> {
+        self.enclave_step_move_sql(name, from, to)
+    }
+
+    ///
+    /// Shows which enclave steps a file would hit without storing anything.
+    /// source_url and scraper are where the file would have come from
+    ///
+    pub fn enclave_dry_run(
+        &self,
+        bytes: &Vec<u8>,
+        tags: &Vec<sharedtypes::Tag>,
+        source_url: &Option<String>,
+        scraper: &Option<String>,
+    ) -> Vec<sharedtypes::EnclaveDryRunStep> {
+        let file = sharedtypes::FileObjectMain {
+            tag_list: vec![sharedtypes::FileTagAction {
+                operation: sharedtypes::TagOperation::Add,
+                tags: tags
+                    .iter()
+                    .map(|tag| sharedtypes::TagObject {
+                        namespace: tag.namespace.clone(),
+                        tag: tag.tag.clone(),
+                        tag_type: sharedtypes::TagType::Normal,
+                        relates_to: None,
+                    })
+                    .collect(),
+            }],
+            ..Default::default()
+        };
+        let (hash, _) = hash_bytes(
+            &bytes::Bytes::copy_from_slice(bytes),
+            &sharedtypes::HashesSupported::Sha512(String::new()),
+        );
+        let input = ConditionInput::new(bytes, &hash, source_url.as_ref(), scraper.as_ref());
+        self.enclave_dry_run_sql(&file, &input)
+    }
+
+    ///
+    /// Storage roots with their limits and how much they hold
+    ///
+    pub fn storage_roots_list(&self) -> Vec<sharedtypes::StorageRoot> {
+        self.storage_roots_list_sql()
+    }
+
+    ///
+    /// Adds a storage root or changes its limits. Returns its storage id for use in
+    /// DownloadToLocation
+    ///
+    pub fn storage_root_set(
+        &self,
+        location: &String,
+        capacity: &Option<u64>,
+        reserve: &u64,
+    ) -> u64 {
+        self.storage_root_set_sql(location, *capacity, *reserve)
+    }
+
+    ///
+    /// Stops a location from being a storage root. Its files are left alone
+    ///
+    pub fn storage_root_remove(&self, location: &String) -> bool {
+        self.storage_root_remove_sql(location)
+    }
+
+    ///
+    /// Changes how new files get stored in a root, adding the root if it's new. Files
+    /// already in it can be moved over with storage_migrate_queue
+    ///
+    pub fn storage_backend_set(
+        &self,
+        location: &String,
+        backend: &sharedtypes::StorageBackend,
+    ) -> u64 {
+        let storage_id = self.storage_root_ensure_sql(location);
+        self.storage_backend_set_sql(&storage_id, *backend);
+        storage_id
+    }
+
+    ///
+    /// Adds an S3 bucket as a storage root. Its location is s3://bucket/prefix, which is
+    /// what DownloadToLocation and storage_migrate_queue take. Returns its storage id
+    ///
+    pub fn storage_s3_set(&self, root: &sharedtypes::S3Root) -> u64 {
+        self.storage_s3_set_sql(root)
+    }
+
+    ///
+    /// Queues files to move into location. A shard_depth re-shards location as well and
+    /// a backend changes how it stores them. Returns how many files got queued
+    ///
+    pub fn storage_migrate_queue(
+        &self,
+        selection: &sharedtypes::StorageSelection,
+        location: &String,
+        shard_depth: &Option<u64>,
+        backend: &Option<sharedtypes::StorageBackend>,
+    ) -> Result<u64, String> {
+        self.storage_migration_queue_sql(selection, location, *shard_depth, *backend)
+    }
+
+    ///
+    /// Moves every queued file. Safe to call again after being interrupted
+    ///
+    pub fn storage_migrate_run(&self) -> sharedtypes::StorageMigrationReport {
+        self.storage_migration_run_sql()
+    }
+
+    ///
+    /// Files still waiting to be moved
+    ///
+    pub fn storage_migrate_pending(&self) -> u64 {
+        self.storage_migration_pending_sql()
+    }
+
+    ///
+    /// Re-hashes the files that are due and reports bitrot, missing and orphaned files
+    ///
+    pub fn file_scrub(&self, options: &sharedtypes::ScrubOptions) -> sharedtypes::ScrubReport {
+        self.file_scrub_sql(options)
+    }
+
+    ///
+    /// Files that had a problem the last time they were scrubbed
+    ///
+    pub fn file_scrub_problems(&self) -> Vec<(u64, sharedtypes::ScrubStatus)> {
+        self.file_scrub_problems_sql()
+    }
+
+    ///
+    /// Copies, links or archives the files a search finds into options.destination
+    /// named by options.template. Per file failures end up in the report
+    ///
+    pub fn export_files(
+        &self,
+        options: &sharedtypes::ExportOptions,
+    ) -> Result<sharedtypes::ExportReport, String> {
+        self.export_sql(options)
+    }
+
+    ///
+    /// Where a file's bytes sit on disk as they are. None when they're packed,
+    /// compressed or in a bucket
+    ///
+    pub fn file_path_local(&self, file_id: &u64) -> Option<String> {
+        let sharedtypes::DbFileStorage::Exist(file) = self.file_get_id(file_id)? else {
+            return None;
+        };
+        let (store, ext) = self.file_store_find(&file)?;
+        if store.backend() != sharedtypes::StorageBackend::Plain {
+            return None;
+        }
+        store
+            .locate(&file.hash, &ext)
+            .map(|path| path.to_string_lossy().to_string())
+    }
+
+    pub fn watch_folders_list(&self) -> Vec<sharedtypes::WatchFolder> {
+        self.watch_folders_list_sql()
+    }
+
+    ///
+    /// Starts watching folder.path or changes how it's watched. The id in folder is
+    /// ignored, the one for its path gets returned
+    ///
+    pub fn watch_folder_set(&self, folder: &sharedtypes::WatchFolder) -> u64 {
+        self.watch_folder_set_sql(folder)
+    }
+
+    pub fn watch_folder_remove(&self, path: &String) -> bool {
+        self.watch_folder_remove_sql(path)
+    }
+
+    ///
+    /// Files already imported from a watch folder with their length and modified time
+    ///
+    pub fn watch_folder_seen(&self, watch_id: &u64) -> HashMap<String, (u64, u64)> {
+        self.watch_folder_seen_sql(watch_id)
+    }
+
+    pub fn watch_folder_seen_set(&self, watch_id: &u64, path: &String, stamp: &Option<(u64, u64)>) {
+        self.watch_folder_seen_set_sql(watch_id, path, *stamp)
+    }
 }
@@ -247,19 +247,17 @@ impl RelationshipStorage {
     /// Checks if a tagid exists in the cache
     ///
     pub fn relationship_cache_tagid_exists(&self, tag_id: &u64) -> bool {
-        match self.internal_cache {
-            InternalCacheType::Popular(_) => {
-                if self.tag_id.contains_key(tag_id) {
-                    return true;
-                }
-                self.relationship_cache_tagid_get(&self.db.read().get_database_connection(), tag_id)
-                    .is_some()
-            }
-            InternalCacheType::Full => self.tag_id.contains_key(tag_id),
-            InternalCacheType::Table => self
-                .relationship_cache_tagid_get(&self.db.read().get_database_connection(), tag_id)
-                .is_some(),
-        }
+        // A hit is an answer out of memory, a miss is anything that had to ask the db
+        let in_memory = match self.internal_cache {
+            InternalCacheType::Popular(_) => self.tag_id.contains_key(tag_id).then_some(true),
+            InternalCacheType::Full => Some(self.tag_id.contains_key(tag_id)),
+            InternalCacheType::Table => None,
+        };
+        crate::metrics::roaring_cache_record(in_memory.is_some());
+        in_memory.unwrap_or_else(|| {
+            self.relationship_cache_tagid_get(&self.db.read().get_database_connection(), tag_id)
+                .is_some()
+        })
     }
 
     ///
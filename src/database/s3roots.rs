@@ -0,0 +1,289 @@
+use crate::database::database::Main;
+use crate::database::database::Transaction;
+use crate::database::storagebackend::FileStore;
+use crate::helpers::getfinpath_depth;
+use crate::logging;
+use crate::s3::S3Client;
+use rusqlite::OptionalExtension;
+use rusqlite::params;
+use std::path::PathBuf;
+use walkdir::WalkDir;
+
+///
+/// Files kept in a bucket. Reads go through a local cache folder so a file that's
+/// wanted again doesn't get downloaded again
+///
+pub(in crate::database) struct S3Store {
+    client: S3Client,
+    root: sharedtypes::S3Root,
+    depth: u64,
+}
+
+impl S3Store {
+    pub(in crate::database) fn new(root: sharedtypes::S3Root, depth: u64) -> Self {
+        S3Store {
+            client: S3Client::new(&root),
+            root,
+            depth,
+        }
+    }
+
+    fn key(&self, hash: &String, ext: &str) -> String {
+        self.client.key(
+            &getfinpath_depth("", hash, self.depth, false),
+            &format!("{}.{}", hash, ext),
+        )
+    }
+
+    fn cached(&self, hash: &String, ext: &str) -> PathBuf {
+        PathBuf::from(&self.root.cache).join(format!("{}.{}", hash, ext))
+    }
+
+    ///
+    /// Drops the copies that were used longest ago until the cache fits in cache_max
+    ///
+    fn cache_trim(&self) {
+        let Some(cache_max) = self.root.cache_max else {
+            return;
+        };
+        let mut entries: Vec<_> = WalkDir::new(&self.root.cache)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let meta = entry.metadata().ok()?;
+                meta.is_file()
+                    .then(|| (meta.modified().ok(), meta.len(), entry.into_path()))
+            })
+            .collect();
+        let mut size: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort();
+        for (_, len, path) in entries {
+            if size <= cache_max {
+                break;
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                size -= len;
+            }
+        }
+    }
+}
+
+impl FileStore for S3Store {
+    fn backend(&self) -> sharedtypes::StorageBackend {
+        sharedtypes::StorageBackend::S3
+    }
+
+    fn write(&self, hash: &String, ext: &str, bytes: &[u8]) -> Result<u64, String> {
+        self.client.put(&self.key(hash, ext), bytes)?;
+        Ok(bytes.len() as u64)
+    }
+
+    fn read(&self, hash: &String, ext: &str) -> Option<Vec<u8>> {
+        let cached = self.cached(hash, ext);
+        if let Ok(bytes) = std::fs::read(&cached) {
+            // Keeps it at the back of the line for cache_trim
+            if let Ok(file) = std::fs::File::options().append(true).open(&cached) {
+                let _ = file.set_modified(std::time::SystemTime::now());
+            }
+            return Some(bytes);
+        }
+
+        let bytes = match self.client.get(&self.key(hash, ext)) {
+            Ok(bytes) => bytes?,
+            Err(err) => {
+                logging::error_log(format!("S3: {}", err));
+                return None;
+            }
+        };
+        let temp = cached.with_extension("part");
+        let stored = std::fs::create_dir_all(&self.root.cache)
+            .and_then(|_| std::fs::write(&temp, &bytes))
+            .and_then(|_| std::fs::rename(&temp, &cached));
+        match stored {
+            Ok(_) => self.cache_trim(),
+            Err(err) => logging::error_log(format!(
+                "S3: cannot cache {} in {}: {}",
+                hash, self.root.cache, err
+            )),
+        }
+        Some(bytes)
+    }
+
+    fn locate(&self, _hash: &String, _ext: &str) -> Option<PathBuf> {
+        None
+    }
+
+    fn contains(&self, hash: &String, ext: &str) -> bool {
+        self.cached(hash, ext).is_file() || self.placed(hash, ext)
+    }
+
+    fn placed(&self, hash: &String, ext: &str) -> bool {
+        self.client
+            .exists(&self.key(hash, ext))
+            .unwrap_or_else(|err| {
+                logging::error_log(format!("S3: {}", err));
+                false
+            })
+    }
+
+    ///
+    /// The bucket doesn't say how big the object was so nothing is counted as freed
+    ///
+    fn remove(&self, hash: &String, ext: &str) -> Result<u64, String> {
+        self.client.delete(&self.key(hash, ext))?;
+        let _ = std::fs::remove_file(self.cached(hash, ext));
+        Ok(0)
+    }
+
+    fn share(&self, hash: &String, ext: &str) -> Option<String> {
+        if let Some(presign_secs) = self.root.presign_secs {
+            return Some(self.client.presign_get(&self.key(hash, ext), presign_secs));
+        }
+        self.read(hash, ext)?;
+        Some(self.cached(hash, ext).to_string_lossy().to_string())
+    }
+}
+
+impl Main {
+    ///
+    /// Connection details of roots that live in a bucket
+    ///
+    pub(in crate::database) fn storage_s3_create_v1(&self, tn: &Transaction) {
+        tn.execute(
+            "
+CREATE TABLE IF NOT EXISTS S3Roots (
+  storage_id INTEGER PRIMARY KEY NOT NULL,
+  endpoint TEXT NOT NULL,
+  region TEXT NOT NULL,
+  bucket TEXT NOT NULL,
+  prefix TEXT NOT NULL,
+  access_key TEXT NOT NULL,
+  secret_key TEXT NOT NULL,
+  cache TEXT NOT NULL,
+  cache_max INTEGER,
+  presign_secs INTEGER
+);
+",
+            [],
+        )
+        .unwrap();
+    }
+
+    pub(in crate::database) fn storage_s3_get(
+        &self,
+        storage_id: &u64,
+    ) -> Option<sharedtypes::S3Root> {
+        let tn = self.get_database_connection();
+        tn.query_row(
+            "SELECT endpoint, region, bucket, prefix, access_key, secret_key, cache, cache_max, presign_secs FROM S3Roots WHERE storage_id = ?",
+            params![storage_id],
+            |row| {
+                Ok(sharedtypes::S3Root {
+                    endpoint: row.get(0)?,
+                    region: row.get(1)?,
+                    bucket: row.get(2)?,
+                    prefix: row.get(3)?,
+                    access_key: row.get(4)?,
+                    secret_key: row.get::<_, String>(5)?.into(),
+                    cache: row.get(6)?,
+                    cache_max: row.get(7)?,
+                    presign_secs: row.get(8)?,
+                })
+            },
+        )
+        .optional()
+        .unwrap()
+    }
+
+    ///
+    /// Adds a bucket as a storage root or changes how it's reached. An empty cache
+    /// puts the cache in the temp folder
+    ///
+    pub(in crate::database) fn storage_s3_set_sql(&self, root: &sharedtypes::S3Root) -> u64 {
+        let mut root = root.clone();
+        if root.cache.is_empty() {
+            root.cache = std::env::temp_dir()
+                .join("rusthydrus-s3")
+                .join(&root.bucket)
+                .to_string_lossy()
+                .to_string();
+        }
+        let storage_id = self.storage_put(&root.location());
+
+        let mut write_conn = self.write_conn.lock();
+        let tn = write_conn.transaction().unwrap();
+        tn.execute(
+            "INSERT OR REPLACE INTO S3Roots (storage_id, endpoint, region, bucket, prefix, access_key, secret_key, cache, cache_max, presign_secs) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                storage_id,
+                root.endpoint,
+                root.region,
+                root.bucket,
+                root.prefix,
+                root.access_key,
+                root.secret_key.expose_secret(),
+                root.cache,
+                root.cache_max,
+                root.presign_secs
+            ],
+        )
+        .unwrap();
+        tn.execute(
+            "INSERT INTO StorageRoots (storage_id, capacity, reserve, used, backend) VALUES (?, NULL, 0, 0, ?)
+            ON CONFLICT(storage_id) DO UPDATE SET backend = excluded.backend",
+            params![
+                storage_id,
+                serde_json::to_string(&sharedtypes::StorageBackend::S3).unwrap()
+            ],
+        )
+        .unwrap();
+        tn.commit().unwrap();
+        storage_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::test_db;
+
+    ///
+    /// Runs against a real bucket. Point RUSTHYDRUS_S3_TEST_ENDPOINT at a MinIO or
+    /// other stand-in with RUSTHYDRUS_S3_TEST_BUCKET already made in it
+    ///
+    #[test]
+    fn bucket_round_trip() {
+        let Ok(endpoint) = std::env::var("RUSTHYDRUS_S3_TEST_ENDPOINT") else {
+            return;
+        };
+        let env = |name: &str, default: &str| std::env::var(name).unwrap_or(default.into());
+        let (db, cache) = test_db("s3roots");
+        let root = sharedtypes::S3Root {
+            endpoint,
+            region: env("RUSTHYDRUS_S3_TEST_REGION", "us-east-1"),
+            bucket: env("RUSTHYDRUS_S3_TEST_BUCKET", "rusthydrus"),
+            prefix: "test".into(),
+            access_key: env("RUSTHYDRUS_S3_TEST_ACCESS_KEY", "minioadmin"),
+            secret_key: env("RUSTHYDRUS_S3_TEST_SECRET_KEY", "minioadmin").into(),
+            cache: cache.to_string_lossy().to_string(),
+            cache_max: None,
+            presign_secs: None,
+        };
+        db.storage_s3_set_sql(&root);
+        let store = db.file_store(&root.location());
+        assert_eq!(store.backend(), sharedtypes::StorageBackend::S3);
+
+        let hash = "ABCDEF0123".to_string();
+        store.write(&hash, "txt", b"cold file").unwrap();
+        assert!(store.placed(&hash, "txt"));
+        assert_eq!(store.read(&hash, "txt").unwrap(), b"cold file");
+        let cached = cache.join("ABCDEF0123.txt");
+        assert!(cached.is_file());
+        assert_eq!(
+            store.share(&hash, "txt"),
+            Some(cached.to_string_lossy().to_string())
+        );
+        store.remove(&hash, "txt").unwrap();
+        assert!(!store.placed(&hash, "txt"));
+    }
+}
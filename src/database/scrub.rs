@@ -0,0 +1,361 @@
+use crate::database::database::Main;
+use crate::database::database::Transaction;
+use crate::database::storagebackend::FileStore;
+use crate::database::storagebackend::PACK_FOLDER;
+use crate::download;
+use crate::download::hash_bytes;
+use crate::helpers::SHARD_DEPTH_MAX;
+use crate::helpers::block_on;
+use crate::helpers::getfinpath_depth;
+use crate::logging;
+use bytes::Bytes;
+use chrono::Utc;
+use rusqlite::params;
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
+use walkdir::WalkDir;
+
+const MILLIS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+impl Main {
+    ///
+    /// When each file was last hashed by a scrub and what it found. Files that were
+    /// never scrubbed have no row
+    ///
+    pub(in crate::database) fn file_verify_create_v1(&self, tn: &Transaction) {
+        tn.execute(
+            "
+CREATE TABLE IF NOT EXISTS FileVerify (
+  file_id INTEGER PRIMARY KEY NOT NULL,
+  verified INTEGER NOT NULL,
+  status TEXT NOT NULL
+);
+",
+            [],
+        )
+        .unwrap();
+    }
+
+    ///
+    /// Files due for a scrub. Never verified first, then longest since verified
+    ///
+    fn file_scrub_due(&self, options: &sharedtypes::ScrubOptions) -> Vec<u64> {
+        let cutoff = options.older_than_days.map_or(i64::MAX, |days| {
+            Utc::now().timestamp_millis() - days as i64 * MILLIS_PER_DAY
+        });
+        let limit = options.limit.map_or(-1, |limit| limit as i64);
+        let tn = self.get_database_connection();
+        let mut stmt = tn
+            .prepare(
+                "SELECT File.id FROM File LEFT JOIN FileVerify ON FileVerify.file_id = File.id
+                WHERE File.hash IS NOT NULL AND (FileVerify.verified IS NULL OR FileVerify.verified < ?)
+                ORDER BY FileVerify.verified IS NOT NULL, FileVerify.verified, File.id LIMIT ?",
+            )
+            .unwrap();
+        stmt.query_map(params![cutoff, limit], |row| row.get(0))
+            .unwrap()
+            .flatten()
+            .collect()
+    }
+
+    fn file_verify_set(&self, file_id: &u64, status: sharedtypes::ScrubStatus) {
+        let mut write_conn = self.write_conn.lock();
+        let tn = write_conn.transaction().unwrap();
+        tn.execute(
+            "INSERT OR REPLACE INTO FileVerify (file_id, verified, status) VALUES (?, ?, ?)",
+            params![
+                file_id,
+                Utc::now().timestamp_millis(),
+                serde_json::to_string(&status).unwrap()
+            ],
+        )
+        .unwrap();
+        tn.commit().unwrap();
+    }
+
+    ///
+    /// Files whose last scrub didn't come back Ok
+    ///
+    pub(in crate::database) fn file_scrub_problems_sql(
+        &self,
+    ) -> Vec<(u64, sharedtypes::ScrubStatus)> {
+        let tn = self.get_database_connection();
+        let mut stmt = tn
+            .prepare("SELECT file_id, status FROM FileVerify ORDER BY file_id")
+            .unwrap();
+        stmt.query_map([], |row| {
+            Ok((row.get::<_, u64>(0)?, row.get::<_, String>(1)?))
+        })
+        .unwrap()
+        .flatten()
+        .filter_map(|(file_id, status)| Some((file_id, serde_json::from_str(&status).ok()?)))
+        .filter(|(_, status)| *status != sharedtypes::ScrubStatus::Ok)
+        .collect()
+    }
+
+    ///
+    /// Re-hashes files that are due and compares them against File.hash
+    ///
+    pub(in crate::database) fn file_scrub_sql(
+        &self,
+        options: &sharedtypes::ScrubOptions,
+    ) -> sharedtypes::ScrubReport {
+        let mirror = options.mirror.clone().or_else(|| {
+            self.settings_get_name(&"scrubmirrorloc".to_string())
+                .and_then(|setting| setting.param)
+        });
+        let mut report = sharedtypes::ScrubReport::default();
+
+        for file_id in self.file_scrub_due(options) {
+            let Some(sharedtypes::DbFileStorage::Exist(file)) = self.file_get_id(&file_id) else {
+                continue;
+            };
+            report.checked += 1;
+            let found = self.file_store_find(&file);
+            let mut status = match &found {
+                None => sharedtypes::ScrubStatus::Missing,
+                Some((store, ext)) => match store.read(&file.hash, ext) {
+                    Some(bytes) if bytes_match(&Bytes::from(bytes), &file.hash) => {
+                        sharedtypes::ScrubStatus::Ok
+                    }
+                    Some(_) => sharedtypes::ScrubStatus::Bitrot,
+                    None => sharedtypes::ScrubStatus::Missing,
+                },
+            };
+
+            if status != sharedtypes::ScrubStatus::Ok && options.repair {
+                let bad = found.as_ref().map(|(store, _)| store.as_ref());
+                match self.file_repair(&file, bad, mirror.as_ref()) {
+                    Ok(from) => {
+                        logging::info_log(format!(
+                            "Scrub: repaired file_id {} from {}",
+                            file_id, from
+                        ));
+                        report.repaired.push(file_id);
+                        status = sharedtypes::ScrubStatus::Ok;
+                    }
+                    Err(err) => logging::error_log(format!("Scrub: file_id {} {}", file_id, err)),
+                }
+            }
+
+            match status {
+                sharedtypes::ScrubStatus::Bitrot => report.bitrot.push(file_id),
+                sharedtypes::ScrubStatus::Missing => report.missing.push(file_id),
+                sharedtypes::ScrubStatus::Ok => {}
+            }
+            self.file_verify_set(&file_id, status);
+        }
+
+        if options.orphans {
+            report.orphaned = self.file_orphans();
+        }
+        for location in self.storage_get_all() {
+            match self.file_store(&location).compact() {
+                Ok(freed) => report.compacted += freed,
+                Err(err) => logging::error_log(format!("Scrub: compacting {} {}", location, err)),
+            }
+        }
+        report
+    }
+
+    ///
+    /// Stores good bytes from the mirror or the file's source urls in place of the bad
+    /// copy bad holds, if any. Returns where they came from
+    ///
+    fn file_repair(
+        &self,
+        file: &sharedtypes::DbFileObj,
+        bad: Option<&dyn FileStore>,
+        mirror: Option<&String>,
+    ) -> Result<String, String> {
+        let ext = self.extension_get_string(&file.ext_id).unwrap_or_default();
+        let from_mirror = mirror.and_then(|mirror| {
+            let found = mirror_find(mirror, &file.hash, &ext)?;
+            let bytes = Bytes::from(std::fs::read(&found).ok()?);
+            bytes_match(&bytes, &file.hash).then(|| (found.to_string_lossy().to_string(), bytes))
+        });
+        let (from, bytes) = match from_mirror {
+            Some(found) => found,
+            None => self
+                .file_source_urls(&file.id)
+                .into_iter()
+                .find_map(|url| match source_fetch(&url) {
+                    Ok(bytes) if bytes_match(&bytes, &file.hash) => Some((url, bytes)),
+                    Ok(_) => {
+                        logging::error_log(format!(
+                            "Scrub: {} doesn't match the hash anymore",
+                            url
+                        ));
+                        None
+                    }
+                    Err(err) => {
+                        logging::error_log(format!("Scrub: {}", err));
+                        None
+                    }
+                })
+                .ok_or("has no good copy in the mirror or at its source urls")?,
+        };
+
+        let location = self
+            .storage_get_string(&file.storage_id)
+            .unwrap_or_else(|| self.location_get());
+        let target = self.file_store(&location);
+        let stale = bad.and_then(|bad| bad.locate(&file.hash, &ext));
+        // A pack would keep serving its bad copy over the new one so it goes first
+        if let Some(bad) = bad
+            && stale.is_none()
+        {
+            bad.remove(&file.hash, &ext)?;
+        }
+        target.write(&file.hash, &ext, &bytes)?;
+        if let Some(stale) = stale
+            && target.locate(&file.hash, &ext).as_ref() != Some(&stale)
+        {
+            let _ = std::fs::remove_file(&stale);
+        }
+        Ok(from)
+    }
+
+    fn file_source_urls(&self, file_id: &u64) -> Vec<String> {
+        let source_url_ns_id = self.create_default_source_url_ns_id();
+        self.relationship_get_tagid(file_id)
+            .iter()
+            .filter_map(|tag_id| self.tag_id_get(tag_id))
+            .filter(|tag| tag.namespace == source_url_ns_id)
+            .map(|tag| tag.name)
+            .collect()
+    }
+
+    ///
+    /// Files sitting in the storage locations that the db has no record of
+    ///
+    fn file_orphans(&self) -> Vec<String> {
+        let known: HashSet<String> = {
+            let tn = self.get_database_connection();
+            let mut stmt = tn
+                .prepare("SELECT hash FROM File WHERE hash IS NOT NULL")
+                .unwrap();
+            stmt.query_map([], |row| row.get::<_, String>(0))
+                .unwrap()
+                .flatten()
+                .map(|hash| hash.to_uppercase())
+                .collect()
+        };
+        self.storage_get_all()
+            .iter()
+            .flat_map(|location| {
+                WalkDir::new(location)
+                    .into_iter()
+                    .flatten()
+                    .filter(move |entry| {
+                        !entry
+                            .path()
+                            .starts_with(Path::new(location).join(PACK_FOLDER))
+                    })
+            })
+            .filter(|entry| entry.file_type().is_file())
+            .filter(|entry| {
+                // Up to the first dot so compressed files get matched by their hash too
+                let name = entry.file_name().to_string_lossy();
+                let hash = name.split('.').next().unwrap_or_default();
+                !known.contains(&hash.to_uppercase())
+            })
+            .map(|entry| entry.path().to_string_lossy().to_string())
+            .collect()
+    }
+}
+
+pub(in crate::database) fn bytes_match(bytes: &Bytes, hash: &str) -> bool {
+    let (sha512, _) = hash_bytes(bytes, &sharedtypes::HashesSupported::Sha512(String::new()));
+    sha512.eq_ignore_ascii_case(hash)
+}
+
+///
+/// Looks for a file in a mirror at any shard depth, with or without its extension
+///
+fn mirror_find(mirror: &str, hash: &String, ext: &str) -> Option<PathBuf> {
+    (0..=SHARD_DEPTH_MAX)
+        .map(|depth| Path::new(&getfinpath_depth(mirror, hash, depth, false)).join(hash))
+        .flat_map(|path| [path.with_extension(ext), path])
+        .find(|path| path.is_file())
+}
+
+fn source_fetch(url: &String) -> Result<Bytes, String> {
+    let client = download::client_create(Vec::new(), false);
+    block_on(async {
+        client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await
+    })
+    .map_err(|err| format!("Cannot download {}: {}", url, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::getfinpath;
+    use crate::testutil::test_db;
+
+    #[test]
+    fn scrub_finds_and_repairs_bitrot() {
+        let (db, dir) = test_db("scrub");
+        let root = dir.location("root");
+        let mirror = dir.location("mirror");
+
+        let bytes = Bytes::from_static(b"keep me safe");
+        let (hash, _) = hash_bytes(&bytes, &sharedtypes::HashesSupported::Sha512(String::new()));
+        std::fs::create_dir_all(&root).unwrap();
+        let storage_id = db.storage_put(&root);
+        let path = Path::new(&getfinpath(&root, &hash, true))
+            .join(&hash)
+            .with_extension("txt");
+        std::fs::write(&path, b"keep me sofe").unwrap();
+        std::fs::write(Path::new(&root).join("stray.txt"), b"who").unwrap();
+        let file_id = db.file_add(sharedtypes::DbFileStorage::NoIdExist(
+            sharedtypes::DbFileObjNoId {
+                hash: hash.clone(),
+                ext_id: db.extension_put_string(&"txt".to_string()),
+                storage_id,
+            },
+        ));
+
+        let options = sharedtypes::ScrubOptions {
+            orphans: true,
+            ..Default::default()
+        };
+        let report = db.file_scrub_sql(&options);
+        assert_eq!(report.bitrot, vec![file_id]);
+        assert!(
+            report
+                .orphaned
+                .iter()
+                .any(|path| path.ends_with("stray.txt"))
+        );
+        assert_eq!(
+            db.file_scrub_problems_sql(),
+            vec![(file_id, sharedtypes::ScrubStatus::Bitrot)]
+        );
+        // Verified just now so it isn't due again
+        let recent = sharedtypes::ScrubOptions {
+            older_than_days: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(db.file_scrub_sql(&recent).checked, 0);
+
+        let mirror_path = Path::new(&getfinpath_depth(&mirror, &hash, 2, true)).join(&hash);
+        std::fs::write(&mirror_path, &bytes).unwrap();
+        let repair = sharedtypes::ScrubOptions {
+            repair: true,
+            mirror: Some(mirror.clone()),
+            ..Default::default()
+        };
+        assert_eq!(db.file_scrub_sql(&repair).repaired, vec![file_id]);
+        assert_eq!(std::fs::read(&path).unwrap(), bytes);
+        assert!(db.file_scrub_problems_sql().is_empty());
+    }
+}
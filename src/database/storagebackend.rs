@@ -0,0 +1,703 @@
+use crate::database::database::Main;
+use crate::database::database::Transaction;
+use crate::database::s3roots::S3Store;
+use crate::helpers::getfinpath_depth;
+use crate::logging;
+use rusqlite::OptionalExtension;
+use rusqlite::params;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Folder inside a packed root that holds its pack files
+pub const PACK_FOLDER: &str = "packs";
+
+/// A pack file stops taking new files once it's this big
+const PACK_SIZE_MAX: u64 = 1 << 30;
+
+/// A pack gets rewritten once removed files take up this fraction of it or more
+const PACK_COMPACT_DEAD: (u64, u64) = (1, 4);
+
+///
+/// Where and how the files of one storage root are kept. Files are looked up by their
+/// hash and extension
+///
+pub trait FileStore {
+    fn backend(&self) -> sharedtypes::StorageBackend;
+
+    ///
+    /// Stores bytes. Returns how much space they took up on disk
+    ///
+    fn write(&self, hash: &String, ext: &str, bytes: &[u8]) -> Result<u64, String>;
+
+    ///
+    /// Gives back the bytes as they were before they were written
+    ///
+    fn read(&self, hash: &String, ext: &str) -> Option<Vec<u8>>;
+
+    ///
+    /// The file on disk that holds hash by itself. None for files inside a pack
+    ///
+    fn locate(&self, hash: &String, ext: &str) -> Option<PathBuf>;
+
+    ///
+    /// Every file on disk that holds hash by itself. A root that changed its shard
+    /// depth can have one at each depth it used
+    ///
+    fn locate_all(&self, hash: &String, ext: &str) -> Vec<PathBuf> {
+        self.locate(hash, ext).into_iter().collect()
+    }
+
+    fn contains(&self, hash: &String, ext: &str) -> bool {
+        self.locate(hash, ext).is_some()
+    }
+
+    ///
+    /// If hash is already where a write would put it now
+    ///
+    fn placed(&self, hash: &String, ext: &str) -> bool;
+
+    ///
+    /// Drops hash from the store. Returns how much space came back
+    ///
+    fn remove(&self, hash: &String, ext: &str) -> Result<u64, String>;
+
+    ///
+    /// A path or url get_file can hand out as is. None means the file has to be
+    /// unpacked first
+    ///
+    fn share(&self, _hash: &String, _ext: &str) -> Option<String> {
+        None
+    }
+
+    ///
+    /// Gives back the space removed files still take up. Returns how much came back
+    ///
+    fn compact(&self) -> Result<u64, String> {
+        Ok(0)
+    }
+}
+
+///
+/// Files as they came in, sharded by hash
+///
+struct PlainStore {
+    location: String,
+    depths: Vec<u64>,
+}
+
+impl PlainStore {
+    fn path(&self, hash: &String, depth: u64) -> PathBuf {
+        Path::new(&getfinpath_depth(&self.location, hash, depth, false)).join(hash)
+    }
+}
+
+impl FileStore for PlainStore {
+    fn backend(&self) -> sharedtypes::StorageBackend {
+        sharedtypes::StorageBackend::Plain
+    }
+
+    fn write(&self, hash: &String, ext: &str, bytes: &[u8]) -> Result<u64, String> {
+        let path = Path::new(&getfinpath_depth(
+            &self.location,
+            hash,
+            self.depths[0],
+            true,
+        ))
+        .join(hash);
+        let path = path.with_extension(ext);
+        // Written next to the real name first so a failed write never looks like a file
+        let mut temp = path.clone().into_os_string();
+        temp.push(".part");
+        std::fs::write(&temp, bytes)
+            .and_then(|_| std::fs::rename(&temp, &path))
+            .map_err(|err| format!("Cannot write {}: {}", path.display(), err))?;
+        Ok(bytes.len() as u64)
+    }
+
+    fn read(&self, hash: &String, ext: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.locate(hash, ext)?).ok()
+    }
+
+    fn locate(&self, hash: &String, ext: &str) -> Option<PathBuf> {
+        for depth in self.depths.iter() {
+            let path = self.path(hash, *depth).with_extension(ext);
+            if std::fs::metadata(&path).is_ok() {
+                return Some(path);
+            }
+
+            // Really old files got written without an extension
+            let path_noext = path.with_extension("");
+            if std::fs::metadata(&path_noext).is_ok() {
+                let _ = std::fs::rename(&path_noext, &path);
+                return Some(path);
+            }
+        }
+        None
+    }
+
+    fn locate_all(&self, hash: &String, ext: &str) -> Vec<PathBuf> {
+        let mut out: Vec<PathBuf> = self
+            .depths
+            .iter()
+            .map(|depth| self.path(hash, *depth))
+            // Really old files got written without an extension
+            .flat_map(|path| [path.with_extension(ext), path])
+            .filter(|path| path.is_file())
+            .collect();
+        out.dedup();
+        out
+    }
+
+    fn placed(&self, hash: &String, ext: &str) -> bool {
+        self.path(hash, self.depths[0])
+            .with_extension(ext)
+            .is_file()
+    }
+
+    fn remove(&self, hash: &String, ext: &str) -> Result<u64, String> {
+        remove_located(self.locate(hash, ext))
+    }
+
+    fn share(&self, hash: &String, ext: &str) -> Option<String> {
+        self.locate(hash, ext)
+            .map(|path| path.to_string_lossy().to_string())
+    }
+}
+
+///
+/// Same layout as PlainStore but every file is zstd compressed
+///
+struct ZstdStore {
+    location: String,
+    depths: Vec<u64>,
+}
+
+impl ZstdStore {
+    fn path(&self, hash: &String, ext: &str, depth: u64, create_dir: bool) -> PathBuf {
+        Path::new(&getfinpath_depth(&self.location, hash, depth, create_dir))
+            .join(format!("{}.{}.zst", hash, ext))
+    }
+}
+
+impl FileStore for ZstdStore {
+    fn backend(&self) -> sharedtypes::StorageBackend {
+        sharedtypes::StorageBackend::Zstd
+    }
+
+    fn write(&self, hash: &String, ext: &str, bytes: &[u8]) -> Result<u64, String> {
+        let path = self.path(hash, ext, self.depths[0], true);
+        let compressed = zstd::encode_all(bytes, 0)
+            .map_err(|err| format!("Cannot compress {}: {}", hash, err))?;
+        // Written next to the real name first so a failed write never looks like a file
+        let temp = path.with_extension("zst.part");
+        std::fs::write(&temp, &compressed)
+            .and_then(|_| std::fs::rename(&temp, &path))
+            .map_err(|err| format!("Cannot write {}: {}", path.display(), err))?;
+        Ok(compressed.len() as u64)
+    }
+
+    fn read(&self, hash: &String, ext: &str) -> Option<Vec<u8>> {
+        let file = std::fs::File::open(self.locate(hash, ext)?).ok()?;
+        zstd::decode_all(file).ok()
+    }
+
+    fn locate(&self, hash: &String, ext: &str) -> Option<PathBuf> {
+        self.depths
+            .iter()
+            .map(|depth| self.path(hash, ext, *depth, false))
+            .find(|path| path.is_file())
+    }
+
+    fn locate_all(&self, hash: &String, ext: &str) -> Vec<PathBuf> {
+        self.depths
+            .iter()
+            .map(|depth| self.path(hash, ext, *depth, false))
+            .filter(|path| path.is_file())
+            .collect()
+    }
+
+    fn placed(&self, hash: &String, ext: &str) -> bool {
+        self.path(hash, ext, self.depths[0], false).is_file()
+    }
+
+    fn remove(&self, hash: &String, ext: &str) -> Result<u64, String> {
+        remove_located(self.locate(hash, ext))
+    }
+}
+
+///
+/// Files appended one after another into pack files. The PackEntry table says which
+/// pack a hash is in and where. Removing a file only drops its entry, the pack keeps
+/// the bytes until compact rewrites it
+///
+struct PackedStore<'a> {
+    db: &'a Main,
+    storage_id: u64,
+    location: String,
+}
+
+impl PackedStore<'_> {
+    fn pack_path(&self, pack: u64) -> PathBuf {
+        Path::new(&self.location)
+            .join(PACK_FOLDER)
+            .join(format!("pack-{:06}.pack", pack))
+    }
+
+    fn entry(&self, hash: &String) -> Result<Option<(u64, u64, u64)>, String> {
+        let tn = self.db.get_database_connection();
+        tn.query_row(
+            "SELECT pack, offset, length FROM PackEntry WHERE storage_id = ? AND hash = ?",
+            params![self.storage_id, hash],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()
+        .map_err(pack_index_err)
+    }
+
+    ///
+    /// entry for callers that can't hand back an error. Logs it and acts like it's
+    /// not there
+    ///
+    fn entry_logged(&self, hash: &String) -> Option<(u64, u64, u64)> {
+        self.entry(hash)
+            .inspect_err(|err| logging::error_log(format!("{} in {}", err, self.location)))
+            .ok()
+            .flatten()
+    }
+
+    ///
+    /// Pack numbers of the pack files on disk
+    ///
+    fn packs(&self) -> Vec<u64> {
+        let Ok(entries) = std::fs::read_dir(Path::new(&self.location).join(PACK_FOLDER)) else {
+            return Vec::new();
+        };
+        let mut packs: Vec<u64> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                name.strip_prefix("pack-")?
+                    .strip_suffix(".pack")?
+                    .parse()
+                    .ok()
+            })
+            .collect();
+        packs.sort();
+        packs
+    }
+
+    ///
+    /// Moves the files still in pack into a new pack and deletes the old one, if
+    /// enough of it belongs to removed files. Returns how many bytes came back
+    ///
+    fn pack_compact(&self, pack: u64) -> Result<u64, String> {
+        // Same lock as write so nothing gets appended while entries move
+        let mut write_conn = self.db.write_conn.lock();
+        let tn = write_conn.transaction().map_err(pack_index_err)?;
+        let path = self.pack_path(pack);
+        let size = std::fs::metadata(&path).map_or(0, |meta| meta.len());
+        let live: Vec<(String, u64, u64)> = {
+            let mut stmt = tn
+                .prepare(
+                    "SELECT hash, offset, length FROM PackEntry WHERE storage_id = ? AND pack = ? ORDER BY offset",
+                )
+                .map_err(pack_index_err)?;
+            stmt.query_map(params![self.storage_id, pack], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .and_then(|rows| rows.collect())
+            .map_err(pack_index_err)?
+        };
+        let dead = size.saturating_sub(live.iter().map(|(_, _, length)| length).sum());
+        if live.is_empty() {
+            std::fs::remove_file(&path)
+                .map_err(|err| format!("Cannot remove {}: {}", path.display(), err))?;
+            return Ok(size);
+        }
+        if dead * PACK_COMPACT_DEAD.1 < size * PACK_COMPACT_DEAD.0 {
+            return Ok(0);
+        }
+
+        // Goes past anything on disk too so a pack left over from a crash isn't reused
+        let newest: u64 = tn
+            .query_row(
+                "SELECT COALESCE(MAX(pack), 0) FROM PackEntry WHERE storage_id = ?",
+                params![self.storage_id],
+                |row| row.get(0),
+            )
+            .map_err(pack_index_err)?;
+        let new_pack = self.packs().last().map_or(newest, |last| newest.max(*last)) + 1;
+        let new_path = self.pack_path(new_pack);
+        let mut old = std::fs::File::open(&path)
+            .map_err(|err| format!("Cannot open {}: {}", path.display(), err))?;
+        let mut new = std::fs::File::create(&new_path)
+            .map_err(|err| format!("Cannot make {}: {}", new_path.display(), err))?;
+        let mut new_offset = 0;
+        for (hash, offset, length) in live.iter() {
+            let mut bytes = vec![0; *length as usize];
+            old.seek(SeekFrom::Start(*offset))
+                .and_then(|_| old.read_exact(&mut bytes))
+                .and_then(|_| new.write_all(&bytes))
+                .map_err(|err| {
+                    let _ = std::fs::remove_file(&new_path);
+                    format!("Cannot repack {}: {}", path.display(), err)
+                })?;
+            tn.execute(
+                "UPDATE PackEntry SET pack = ?, offset = ? WHERE storage_id = ? AND hash = ?",
+                params![new_pack, new_offset, self.storage_id, hash],
+            )
+            .map_err(pack_index_err)?;
+            new_offset += length;
+        }
+        new.sync_data()
+            .map_err(|err| format!("Cannot write {}: {}", new_path.display(), err))?;
+        tn.commit().map_err(pack_index_err)?;
+        // Nothing points at the old pack anymore. If this fails the next compact gets it
+        let _ = std::fs::remove_file(&path);
+        Ok(dead)
+    }
+}
+
+impl FileStore for PackedStore<'_> {
+    fn backend(&self) -> sharedtypes::StorageBackend {
+        sharedtypes::StorageBackend::Packed
+    }
+
+    fn write(&self, hash: &String, _ext: &str, bytes: &[u8]) -> Result<u64, String> {
+        // Holding the write lock for the append keeps two writers off the same pack
+        let mut write_conn = self.db.write_conn.lock();
+        let tn = write_conn.transaction().map_err(pack_index_err)?;
+        let stored: Option<u64> = tn
+            .query_row(
+                "SELECT length FROM PackEntry WHERE storage_id = ? AND hash = ?",
+                params![self.storage_id, hash],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(pack_index_err)?;
+        if stored.is_some() {
+            return Ok(0);
+        }
+        let mut pack: u64 = tn
+            .query_row(
+                "SELECT COALESCE(MAX(pack), 0) FROM PackEntry WHERE storage_id = ?",
+                params![self.storage_id],
+                |row| row.get(0),
+            )
+            .map_err(pack_index_err)?;
+        let size = std::fs::metadata(self.pack_path(pack)).map_or(0, |meta| meta.len());
+        if size > 0 && size + bytes.len() as u64 > PACK_SIZE_MAX {
+            pack += 1;
+        }
+
+        let path = self.pack_path(pack);
+        std::fs::create_dir_all(path.parent().unwrap())
+            .map_err(|err| format!("Cannot make {}: {}", path.display(), err))?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|err| format!("Cannot open {}: {}", path.display(), err))?;
+        let offset = file.seek(SeekFrom::End(0)).unwrap_or_default();
+        file.write_all(bytes)
+            .and_then(|_| file.sync_data())
+            .map_err(|err| format!("Cannot append to {}: {}", path.display(), err))?;
+
+        tn.execute(
+            "INSERT INTO PackEntry (storage_id, hash, pack, offset, length) VALUES (?, ?, ?, ?, ?)",
+            params![self.storage_id, hash, pack, offset, bytes.len() as u64],
+        )
+        .and_then(|_| tn.commit())
+        .map_err(pack_index_err)?;
+        Ok(bytes.len() as u64)
+    }
+
+    fn read(&self, hash: &String, _ext: &str) -> Option<Vec<u8>> {
+        let (pack, offset, length) = self.entry_logged(hash)?;
+        let mut file = std::fs::File::open(self.pack_path(pack)).ok()?;
+        file.seek(SeekFrom::Start(offset)).ok()?;
+        let mut bytes = vec![0; length as usize];
+        file.read_exact(&mut bytes).ok()?;
+        Some(bytes)
+    }
+
+    fn locate(&self, _hash: &String, _ext: &str) -> Option<PathBuf> {
+        None
+    }
+
+    fn contains(&self, hash: &String, _ext: &str) -> bool {
+        self.entry_logged(hash).is_some()
+    }
+
+    fn placed(&self, hash: &String, ext: &str) -> bool {
+        self.contains(hash, ext)
+    }
+
+    fn remove(&self, hash: &String, _ext: &str) -> Result<u64, String> {
+        let mut write_conn = self.db.write_conn.lock();
+        let tn = write_conn.transaction().map_err(pack_index_err)?;
+        tn.execute(
+            "DELETE FROM PackEntry WHERE storage_id = ? AND hash = ?",
+            params![self.storage_id, hash],
+        )
+        .and_then(|_| tn.commit())
+        .map_err(pack_index_err)?;
+        // The bytes stay in the pack until compact
+        Ok(0)
+    }
+
+    fn compact(&self) -> Result<u64, String> {
+        let mut freed = 0;
+        for pack in self.packs() {
+            freed += self.pack_compact(pack)?;
+        }
+        Ok(freed)
+    }
+}
+
+fn pack_index_err(err: rusqlite::Error) -> String {
+    format!("Cannot update the pack index: {}", err)
+}
+
+///
+/// If location is a bucket instead of a folder on this machine
+///
+pub fn location_remote(location: &str) -> bool {
+    location.starts_with("s3://")
+}
+
+///
+/// If path is a compressed file or a pack. Those don't hash to their own name so
+/// anything walking a root looking at plain files has to leave them alone
+///
+pub fn backend_owned(location: &str, path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "zst")
+        || path.starts_with(Path::new(location).join(PACK_FOLDER))
+}
+
+fn remove_located(path: Option<PathBuf>) -> Result<u64, String> {
+    let Some(path) = path else {
+        return Ok(0);
+    };
+    let size = std::fs::metadata(&path).map_or(0, |meta| meta.len());
+    std::fs::remove_file(&path)
+        .map_err(|err| format!("Cannot remove {}: {}", path.display(), err))?;
+    Ok(size)
+}
+
+impl Main {
+    ///
+    /// Lets each root pick its backend and adds the index of what's in pack files
+    ///
+    pub(in crate::database) fn storage_backend_create_v1(&self, tn: &Transaction) {
+        tn.execute(
+            &format!(
+                "ALTER TABLE StorageRoots ADD COLUMN backend TEXT NOT NULL DEFAULT '{}'",
+                serde_json::to_string(&sharedtypes::StorageBackend::Plain).unwrap()
+            ),
+            [],
+        )
+        .unwrap();
+        tn.execute(
+            "
+CREATE TABLE IF NOT EXISTS PackEntry (
+  storage_id INTEGER NOT NULL,
+  hash TEXT NOT NULL,
+  pack INTEGER NOT NULL,
+  offset INTEGER NOT NULL,
+  length INTEGER NOT NULL,
+  PRIMARY KEY (storage_id, hash)
+);
+",
+            [],
+        )
+        .unwrap();
+    }
+
+    pub fn storage_backend(&self, storage_id: &u64) -> sharedtypes::StorageBackend {
+        let tn = self.get_database_connection();
+        tn.query_row(
+            "SELECT backend FROM StorageRoots WHERE storage_id = ?",
+            params![storage_id],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .unwrap()
+        .and_then(|backend| serde_json::from_str(&backend).ok())
+        .unwrap_or_default()
+    }
+
+    ///
+    /// Changes how new files get written into a root. Files already in it stay as
+    /// they are until they're migrated
+    ///
+    pub(in crate::database) fn storage_backend_set_sql(
+        &self,
+        storage_id: &u64,
+        backend: sharedtypes::StorageBackend,
+    ) {
+        let mut write_conn = self.write_conn.lock();
+        let tn = write_conn.transaction().unwrap();
+        tn.execute(
+            "UPDATE StorageRoots SET backend = ? WHERE storage_id = ?",
+            params![serde_json::to_string(&backend).unwrap(), storage_id],
+        )
+        .unwrap();
+        tn.commit().unwrap();
+    }
+
+    fn file_store_backend(
+        &self,
+        location: &String,
+        backend: sharedtypes::StorageBackend,
+    ) -> Option<Box<dyn FileStore + '_>> {
+        let depths = self.storage_shard_depths(location);
+        let store: Box<dyn FileStore + '_> = match backend {
+            sharedtypes::StorageBackend::Plain => Box::new(PlainStore {
+                location: location.clone(),
+                depths,
+            }),
+            sharedtypes::StorageBackend::Zstd => Box::new(ZstdStore {
+                location: location.clone(),
+                depths,
+            }),
+            sharedtypes::StorageBackend::Packed => Box::new(PackedStore {
+                db: self,
+                storage_id: self.storage_get_id(location)?,
+                location: location.clone(),
+            }),
+            sharedtypes::StorageBackend::S3 => Box::new(S3Store::new(
+                self.storage_s3_get(&self.storage_get_id(location)?)?,
+                depths[0],
+            )),
+        };
+        Some(store)
+    }
+
+    ///
+    /// The store new files in location get written to
+    ///
+    pub fn file_store(&self, location: &String) -> Box<dyn FileStore + '_> {
+        let backend = self
+            .storage_get_id(location)
+            .map(|storage_id| self.storage_backend(&storage_id))
+            .unwrap_or_default();
+        self.file_store_backend(location, backend)
+            .unwrap_or_else(|| {
+                Box::new(PlainStore {
+                    location: location.clone(),
+                    depths: self.storage_shard_depths(location),
+                })
+            })
+    }
+
+    ///
+    /// Every store a file in location could be in. The root's own backend comes first,
+    /// the others hold files written before it changed backend. A bucket only ever
+    /// has itself
+    ///
+    pub fn file_stores(&self, location: &String) -> Vec<Box<dyn FileStore + '_>> {
+        let own = self.file_store(location);
+        let own_backend = own.backend();
+        if location_remote(location) {
+            return vec![own];
+        }
+        let others = [
+            sharedtypes::StorageBackend::Plain,
+            sharedtypes::StorageBackend::Zstd,
+            sharedtypes::StorageBackend::Packed,
+        ]
+        .into_iter()
+        .filter(move |backend| *backend != own_backend)
+        .filter_map(|backend| self.file_store_backend(location, backend));
+        std::iter::once(own).chain(others).collect()
+    }
+
+    ///
+    /// Finds the store that has a file, looking through every location it's likely in
+    ///
+    pub fn file_store_find(
+        &self,
+        file: &sharedtypes::DbFileObj,
+    ) -> Option<(Box<dyn FileStore + '_>, String)> {
+        let ext = self.extension_get_string(&file.ext_id)?;
+        self.storage_get_likely(&file.id)
+            .iter()
+            .flat_map(|location| self.file_stores(location))
+            .find(|store| store.contains(&file.hash, &ext))
+            .map(|store| (store, ext))
+    }
+
+    ///
+    /// Where get_file leaves a readable copy of a file that isn't stored as is
+    ///
+    pub(in crate::database) fn file_unpacked_path(&self, hash: &String, ext: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join("rusthydrus")
+            .join(format!("{}.{}", hash, ext))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::test_db;
+
+    #[test]
+    fn backends_give_back_what_they_were_given() {
+        let (db, dir) = test_db("storagebackend");
+        let hash = "ABCDEF0123".to_string();
+        let bytes = b"{\"text\": \"squash me squash me squash me squash me\"}".to_vec();
+
+        for backend in [
+            sharedtypes::StorageBackend::Plain,
+            sharedtypes::StorageBackend::Zstd,
+            sharedtypes::StorageBackend::Packed,
+        ] {
+            let location = dir.location(&format!("{:?}", backend));
+            let storage_id = db.storage_root_ensure_sql(&location);
+            db.storage_backend_set_sql(&storage_id, backend);
+            let store = db.file_store(&location);
+            assert_eq!(store.backend(), backend);
+
+            store.write(&hash, "json", &bytes).unwrap();
+            assert!(store.placed(&hash, "json"));
+            assert_eq!(store.read(&hash, "json").unwrap(), bytes);
+            if backend == sharedtypes::StorageBackend::Packed {
+                assert_eq!(store.write(&hash, "json", &bytes).unwrap(), 0);
+            }
+            store.remove(&hash, "json").unwrap();
+            assert!(!store.contains(&hash, "json"));
+        }
+    }
+
+    #[test]
+    fn compact_drops_removed_files_from_packs() {
+        let (db, dir) = test_db("storagebackend-compact");
+        let location = dir.location("packed");
+        let storage_id = db.storage_root_ensure_sql(&location);
+        db.storage_backend_set_sql(&storage_id, sharedtypes::StorageBackend::Packed);
+        let store = db.file_store(&location);
+
+        let files: Vec<(String, Vec<u8>)> = (0..3)
+            .map(|num| (format!("HASH{}", num), vec![num as u8; 64]))
+            .collect();
+        for (hash, bytes) in files.iter() {
+            store.write(hash, "bin", bytes).unwrap();
+        }
+        store.remove(&files[0].0, "bin").unwrap();
+        store.remove(&files[1].0, "bin").unwrap();
+
+        assert_eq!(store.compact().unwrap(), 128);
+        assert_eq!(store.read(&files[2].0, "bin").unwrap(), files[2].1);
+        let packs = std::fs::read_dir(Path::new(&location).join(PACK_FOLDER))
+            .unwrap()
+            .count();
+        assert_eq!(packs, 1);
+        assert_eq!(store.compact().unwrap(), 0);
+    }
+}
@@ -0,0 +1,474 @@
+use crate::database::database::Main;
+use crate::database::database::Transaction;
+use crate::database::storagebackend::FileStore;
+use crate::download::hash_bytes;
+use crate::logging;
+use bytes::Bytes;
+use rusqlite::params;
+use std::path::PathBuf;
+
+impl Main {
+    ///
+    /// Queue of files waiting to move to another storage location. A file's row goes
+    /// away in the same transaction that changes its storage_id so an interrupted
+    /// migration carries on from where it stopped
+    ///
+    pub(in crate::database) fn storage_migration_create_v1(&self, tn: &Transaction) {
+        tn.execute(
+            "
+CREATE TABLE IF NOT EXISTS StorageMigration (
+  file_id INTEGER PRIMARY KEY NOT NULL,
+  storage_id INTEGER NOT NULL
+);
+",
+            [],
+        )
+        .unwrap();
+    }
+
+    fn storage_selection_files(&self, selection: &sharedtypes::StorageSelection) -> Vec<u64> {
+        let (query, param) = match selection {
+            sharedtypes::StorageSelection::Search(search) => {
+                return self
+                    .search_db_files(search.clone(), None)
+                    .unwrap_or_default();
+            }
+            sharedtypes::StorageSelection::Extension(extension) => {
+                match self.extension_get_id(extension.trim_start_matches('.')) {
+                    Some(ext_id) => (
+                        "SELECT id FROM File WHERE hash IS NOT NULL AND extension = ?",
+                        Some(ext_id),
+                    ),
+                    None => return Vec::new(),
+                }
+            }
+            sharedtypes::StorageSelection::StorageId(storage_id) => (
+                "SELECT id FROM File WHERE hash IS NOT NULL AND storage_id = ?",
+                Some(*storage_id),
+            ),
+            sharedtypes::StorageSelection::All => {
+                ("SELECT id FROM File WHERE hash IS NOT NULL", None)
+            }
+        };
+        let tn = self.get_database_connection();
+        let mut stmt = tn.prepare(query).unwrap();
+        match param {
+            Some(param) => stmt
+                .query_map(params![param], |row| row.get(0))
+                .unwrap()
+                .flatten()
+                .collect(),
+            None => stmt
+                .query_map([], |row| row.get(0))
+                .unwrap()
+                .flatten()
+                .collect(),
+        }
+    }
+
+    ///
+    /// Queues the selected files to move into location. shard_depth changes how deep
+    /// location shards its files and backend how it stores them. Changing either one
+    /// also queues the files already in location so they get moved over too
+    ///
+    pub(in crate::database) fn storage_migration_queue_sql(
+        &self,
+        selection: &sharedtypes::StorageSelection,
+        location: &String,
+        shard_depth: Option<u64>,
+        backend: Option<sharedtypes::StorageBackend>,
+    ) -> Result<u64, String> {
+        let storage_id = self.storage_root_ensure_sql(location);
+        let mut relayout = false;
+        if let Some(shard_depth) = shard_depth
+            && shard_depth != self.storage_shard_depth(&storage_id)
+        {
+            self.storage_shard_depth_set_sql(&storage_id, shard_depth)?;
+            relayout = true;
+        }
+        if let Some(backend) = backend
+            && backend != self.storage_backend(&storage_id)
+        {
+            self.storage_backend_set_sql(&storage_id, backend);
+            relayout = true;
+        }
+        let mut file_ids = self.storage_selection_files(selection);
+        if relayout {
+            file_ids.extend(
+                self.storage_selection_files(&sharedtypes::StorageSelection::StorageId(storage_id)),
+            );
+            file_ids.sort_unstable();
+            file_ids.dedup();
+        }
+
+        let mut write_conn = self.write_conn.lock();
+        let tn = write_conn.transaction().unwrap();
+        for file_id in file_ids.iter() {
+            tn.execute(
+                "INSERT OR REPLACE INTO StorageMigration (file_id, storage_id) VALUES (?, ?)",
+                params![file_id, storage_id],
+            )
+            .unwrap();
+        }
+        tn.commit().unwrap();
+        Ok(file_ids.len() as u64)
+    }
+
+    pub(in crate::database) fn storage_migration_pending_sql(&self) -> u64 {
+        let tn = self.get_database_connection();
+        tn.query_row("SELECT COUNT(*) FROM StorageMigration", [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    }
+
+    ///
+    /// Moves everything in the queue
+    ///
+    pub(in crate::database) fn storage_migration_run_sql(
+        &self,
+    ) -> sharedtypes::StorageMigrationReport {
+        let queue: Vec<(u64, u64)> = {
+            let tn = self.get_database_connection();
+            let mut stmt = tn
+                .prepare("SELECT file_id, storage_id FROM StorageMigration ORDER BY file_id")
+                .unwrap();
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+                .flatten()
+                .collect()
+        };
+
+        let mut report = sharedtypes::StorageMigrationReport::default();
+        for (file_id, storage_id) in queue {
+            match self.storage_migration_file(&file_id, &storage_id) {
+                Ok(true) => report.moved += 1,
+                Ok(false) => report.skipped += 1,
+                Err(err) => {
+                    logging::error_log(format!("Storage migration: {}", err));
+                    report.failed += 1;
+                }
+            }
+        }
+        report.pending = self.storage_migration_pending_sql();
+        report
+    }
+
+    ///
+    /// Copies one file, checks the copy against its hash, points the db at the copy and
+    /// then removes the original. A copy left behind by a run that got interrupted is
+    /// checked the same way and written again if it doesn't match. Returns false if
+    /// the file didn't need to move
+    ///
+    fn storage_migration_file(&self, file_id: &u64, storage_id: &u64) -> Result<bool, String> {
+        let Some(sharedtypes::DbFileStorage::Exist(file)) = self.file_get_id(file_id) else {
+            self.storage_migration_commit(file_id, None, &[]);
+            return Ok(false);
+        };
+        let ext = self
+            .extension_get_string(&file.ext_id)
+            .ok_or_else(|| format!("file_id {} has no extension", file_id))?;
+        let location = self
+            .storage_get_string(storage_id)
+            .ok_or_else(|| format!("storage_id {} doesn't exist", storage_id))?;
+        let target = self.file_store(&location);
+        let storage_new = (file.storage_id != *storage_id).then_some(*storage_id);
+
+        let mut placed = None;
+        if target.placed(&file.hash, &ext) {
+            let located = target.locate(&file.hash, &ext);
+            match storage_copy_check(&*target, &file.hash, &ext) {
+                Some(size) => placed = Some((located, size)),
+                None => {
+                    if self
+                        .storage_migration_original(&file, &ext, storage_id, &*target, &located)
+                        .is_none()
+                    {
+                        return Err(format!(
+                            "file_id {} in {} doesn't match its hash and there's no other copy of it",
+                            file_id, location
+                        ));
+                    }
+                    target.remove(&file.hash, &ext)?;
+                }
+            }
+        }
+
+        let skip = placed.as_ref().and_then(|(located, _)| located.clone());
+        let original = self.storage_migration_original(&file, &ext, storage_id, &*target, &skip);
+        let (source, stale) = match (original, &placed) {
+            (Some(original), _) => original,
+            // Already where it belongs and nothing else to clean up
+            (None, Some(_)) => {
+                self.storage_migration_commit(file_id, storage_new, &[]);
+                return Ok(false);
+            }
+            (None, None) => return Err(format!("file_id {} is missing from disk", file_id)),
+        };
+        let stale_size = stale
+            .as_ref()
+            .and_then(|stale| std::fs::metadata(stale).ok())
+            .map_or(0, |meta| meta.len());
+
+        let written = match placed {
+            Some((_, size)) => size,
+            None => {
+                let bytes = source
+                    .read(&file.hash, &ext)
+                    .ok_or_else(|| format!("Cannot read file_id {}", file_id))?;
+                target.write(&file.hash, &ext, &bytes)?;
+                let Some(size) = storage_copy_check(&*target, &file.hash, &ext) else {
+                    let _ = target.remove(&file.hash, &ext);
+                    return Err(format!(
+                        "Copy of file_id {} in {} doesn't match its hash",
+                        file_id, location
+                    ));
+                };
+                size
+            }
+        };
+
+        self.storage_migration_commit(
+            file_id,
+            storage_new,
+            &[
+                (file.storage_id, -(stale_size as i64)),
+                (*storage_id, written as i64),
+            ],
+        );
+
+        let removed = match stale {
+            Some(stale) => std::fs::remove_file(&stale)
+                .map_err(|err| format!("couldn't remove {}: {}", stale.display(), err)),
+            None => source.remove(&file.hash, &ext).map(|_| ()),
+        };
+        if let Err(err) = removed {
+            logging::error_log(format!(
+                "Storage migration: moved file_id {} but {}",
+                file_id, err
+            ));
+        }
+        Ok(true)
+    }
+
+    ///
+    /// Finds the copy of a file that has to go once it's in target. skip is the copy
+    /// already in target so a root that's being re-sharded or changing backend
+    /// doesn't find itself. Gives back the store and the file on disk if it has one
+    ///
+    fn storage_migration_original(
+        &self,
+        file: &sharedtypes::DbFileObj,
+        ext: &str,
+        storage_id: &u64,
+        target: &dyn FileStore,
+        skip: &Option<PathBuf>,
+    ) -> Option<(Box<dyn FileStore + '_>, Option<PathBuf>)> {
+        for location in self.storage_get_likely(&file.id) {
+            let same_root = self.storage_get_id(&location) == Some(*storage_id);
+            for store in self.file_stores(&location) {
+                if same_root && store.backend() == target.backend() {
+                    let other = store
+                        .locate_all(&file.hash, ext)
+                        .into_iter()
+                        .find(|path| Some(path) != skip.as_ref());
+                    if let Some(other) = other {
+                        return Some((store, Some(other)));
+                    }
+                    continue;
+                }
+                if store.contains(&file.hash, ext) {
+                    let stale = store.locate(&file.hash, ext);
+                    return Some((store, stale));
+                }
+            }
+        }
+        None
+    }
+
+    ///
+    /// Takes a file off the queue. storage_new is set when its storage_id changes and
+    /// used holds the bytes each root gained or lost
+    ///
+    fn storage_migration_commit(
+        &self,
+        file_id: &u64,
+        storage_new: Option<u64>,
+        used: &[(u64, i64)],
+    ) {
+        let mut write_conn = self.write_conn.lock();
+        let tn = write_conn.transaction().unwrap();
+        if let Some(storage_id) = storage_new {
+            self.file_update_storage_location(&tn, file_id, &storage_id);
+        }
+        for (storage_id, bytes) in used {
+            self.storage_root_used_add(&tn, storage_id, *bytes);
+        }
+        tn.execute(
+            "DELETE FROM StorageMigration WHERE file_id = ?",
+            params![file_id],
+        )
+        .unwrap();
+        tn.commit().unwrap();
+    }
+}
+
+///
+/// Reads a stored file back and checks it against its hash. Gives back how much space
+/// it takes up if it matches
+///
+fn storage_copy_check(store: &dyn FileStore, hash: &String, ext: &str) -> Option<u64> {
+    let copied = store.read(hash, ext)?;
+    let len = copied.len() as u64;
+    let (copied_hash, _) = hash_bytes(
+        &Bytes::from(copied),
+        &sharedtypes::HashesSupported::Sha512(String::new()),
+    );
+    if !copied_hash.eq_ignore_ascii_case(hash) {
+        return None;
+    }
+    let size = store
+        .locate(hash, ext)
+        .and_then(|path| std::fs::metadata(path).ok())
+        .map_or(len, |meta| meta.len());
+    Some(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::getfinpath;
+    use crate::helpers::getfinpath_depth;
+    use crate::testutil::test_db;
+    use std::path::Path;
+
+    #[test]
+    fn files_move_between_roots_and_reshard() {
+        let (db, dir) = test_db("storagemigrate");
+        let old_root = dir.location("old");
+        let new_root = dir.location("new");
+
+        let bytes = Bytes::from_static(b"migrate me");
+        let (hash, _) = hash_bytes(&bytes, &sharedtypes::HashesSupported::Sha512(String::new()));
+        let old_storage = db.storage_root_ensure_sql(&old_root);
+        let old_path = Path::new(&getfinpath(&old_root, &hash, true))
+            .join(&hash)
+            .with_extension("txt");
+        std::fs::write(&old_path, &bytes).unwrap();
+        let file_id = db.file_add(sharedtypes::DbFileStorage::NoIdExist(
+            sharedtypes::DbFileObjNoId {
+                hash: hash.clone(),
+                ext_id: db.extension_put_string(&"txt".to_string()),
+                storage_id: old_storage,
+            },
+        ));
+
+        let queued = db
+            .storage_migration_queue_sql(
+                &sharedtypes::StorageSelection::StorageId(old_storage),
+                &new_root,
+                Some(1),
+                None,
+            )
+            .unwrap();
+        assert_eq!(queued, 1);
+        assert!(
+            db.storage_migration_queue_sql(
+                &sharedtypes::StorageSelection::All,
+                &new_root,
+                Some(9),
+                None
+            )
+            .is_err()
+        );
+
+        let report = db.storage_migration_run_sql();
+        assert_eq!((report.moved, report.failed, report.pending), (1, 0, 0));
+        assert!(!old_path.exists());
+        let new_path = Path::new(&getfinpath_depth(&new_root, &hash, 1, false))
+            .join(&hash)
+            .with_extension("txt");
+        let found = db.get_file(&file_id).unwrap();
+        assert_eq!(
+            Path::new(&found).canonicalize().unwrap(),
+            new_path.canonicalize().unwrap()
+        );
+
+        // Switching backend moves what's already there into the new one
+        let new_storage = db.storage_root_ensure_sql(&new_root);
+        db.storage_migration_queue_sql(
+            &sharedtypes::StorageSelection::StorageId(new_storage),
+            &new_root,
+            None,
+            Some(sharedtypes::StorageBackend::Zstd),
+        )
+        .unwrap();
+        assert_eq!(db.storage_migration_run_sql().moved, 1);
+        assert!(!new_path.exists());
+        assert!(new_path.with_extension("txt.zst").exists());
+        assert_eq!(db.get_file_bytes(&file_id).unwrap(), bytes);
+    }
+
+    #[test]
+    fn interrupted_copies_get_checked() {
+        let (db, dir) = test_db("storageresume");
+        let old_root = dir.location("old");
+        let new_root = dir.location("new");
+        let used = |storage_id| {
+            db.storage_roots_list_sql()
+                .into_iter()
+                .find(|root| root.storage_id == storage_id)
+                .unwrap()
+                .used
+        };
+
+        let old_storage = db.storage_root_ensure_sql(&old_root);
+        let new_storage = db.storage_root_ensure_sql(&new_root);
+        let mut files = Vec::new();
+        for (text, copy) in [("left behind", "left behind"), ("torn", "to")] {
+            let bytes = Bytes::from(text);
+            let (hash, _) =
+                hash_bytes(&bytes, &sharedtypes::HashesSupported::Sha512(String::new()));
+            let old_path = Path::new(&getfinpath(&old_root, &hash, true))
+                .join(&hash)
+                .with_extension("txt");
+            std::fs::write(&old_path, &bytes).unwrap();
+            // What a run that stopped before committing leaves in the new root
+            let new_path = Path::new(&getfinpath(&new_root, &hash, true))
+                .join(&hash)
+                .with_extension("txt");
+            std::fs::write(&new_path, copy).unwrap();
+            let file_id = db.file_add(sharedtypes::DbFileStorage::NoIdExist(
+                sharedtypes::DbFileObjNoId {
+                    hash: hash.clone(),
+                    ext_id: db.extension_put_string(&"txt".to_string()),
+                    storage_id: old_storage,
+                },
+            ));
+            files.push((file_id, bytes, old_path, new_path));
+        }
+        let (old_used, new_used) = (used(old_storage), used(new_storage));
+
+        db.storage_migration_queue_sql(
+            &sharedtypes::StorageSelection::StorageId(old_storage),
+            &new_root,
+            None,
+            None,
+        )
+        .unwrap();
+        let report = db.storage_migration_run_sql();
+        assert_eq!((report.moved, report.failed, report.pending), (2, 0, 0));
+
+        let total: u64 = files
+            .iter()
+            .map(|(_, bytes, _, _)| bytes.len() as u64)
+            .sum();
+        assert_eq!(used(new_storage), new_used + total);
+        assert_eq!(used(old_storage), old_used.saturating_sub(total));
+        for (file_id, bytes, old_path, new_path) in files {
+            assert!(!old_path.exists());
+            assert_eq!(std::fs::read(&new_path).unwrap(), bytes);
+            assert_eq!(db.get_file_bytes(&file_id).unwrap(), bytes);
+        }
+    }
+}
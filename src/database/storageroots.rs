@@ -0,0 +1,297 @@
+use crate::database::database::Main;
+use crate::database::database::Transaction;
+use crate::database::storagebackend::location_remote;
+use crate::file::folder_make;
+use crate::helpers::SHARD_DEPTH_DEFAULT;
+use crate::helpers::SHARD_DEPTH_MAX;
+use crate::os;
+use rusqlite::OptionalExtension;
+use rusqlite::params;
+use std::path::Path;
+use walkdir::WalkDir;
+
+impl Main {
+    ///
+    /// Creates the table that holds the limits of each storage root
+    ///
+    pub(in crate::database) fn storage_roots_create_v1(&self, tn: &Transaction) {
+        tn.execute(
+            "
+CREATE TABLE IF NOT EXISTS StorageRoots (
+  storage_id INTEGER PRIMARY KEY NOT NULL,
+  capacity INTEGER,
+  reserve INTEGER NOT NULL,
+  used INTEGER NOT NULL
+);
+",
+            [],
+        )
+        .unwrap();
+    }
+
+    ///
+    /// Lets a root change how many folders deep its files get sharded
+    ///
+    pub(in crate::database) fn storage_roots_shard_depth_add_v1(&self, tn: &Transaction) {
+        tn.execute(
+            &format!(
+                "ALTER TABLE StorageRoots ADD COLUMN shard_depth INTEGER NOT NULL DEFAULT {}",
+                SHARD_DEPTH_DEFAULT
+            ),
+            [],
+        )
+        .unwrap();
+    }
+
+    pub(in crate::database) fn storage_roots_list_sql(&self) -> Vec<sharedtypes::StorageRoot> {
+        let rows: Vec<_> = {
+            let tn = self.get_database_connection();
+            let mut stmt = tn
+                .prepare(
+                    "SELECT storage_id, capacity, reserve, used, shard_depth, backend FROM StorageRoots ORDER BY storage_id",
+                )
+                .unwrap();
+            stmt.query_map([], |row| {
+                Ok(sharedtypes::StorageRoot {
+                    storage_id: row.get(0)?,
+                    location: String::new(),
+                    capacity: row.get(1)?,
+                    reserve: row.get(2)?,
+                    used: row.get(3)?,
+                    shard_depth: row.get(4)?,
+                    backend: serde_json::from_str(&row.get::<_, String>(5)?).unwrap_or_default(),
+                })
+            })
+            .unwrap()
+            .flatten()
+            .collect()
+        };
+        rows.into_iter()
+            .filter_map(|mut root| {
+                root.location = self.storage_get_string(&root.storage_id)?;
+                Some(root)
+            })
+            .collect()
+    }
+
+    ///
+    /// How many folders deep files in a storage location are sharded
+    ///
+    pub fn storage_shard_depth(&self, storage_id: &u64) -> u64 {
+        let tn = self.get_database_connection();
+        tn.query_row(
+            "SELECT shard_depth FROM StorageRoots WHERE storage_id = ?",
+            params![storage_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .unwrap()
+        .unwrap_or(SHARD_DEPTH_DEFAULT)
+    }
+
+    ///
+    /// Depths to look for a file at in location. Its own depth comes first, the rest are
+    /// there for files that haven't been moved yet by a re-shard
+    ///
+    pub fn storage_shard_depths(&self, location: &String) -> Vec<u64> {
+        let depth = self
+            .storage_get_id(location)
+            .map_or(SHARD_DEPTH_DEFAULT, |storage_id| {
+                self.storage_shard_depth(&storage_id)
+            });
+        std::iter::once(depth)
+            .chain((0..=SHARD_DEPTH_MAX).filter(|other| *other != depth))
+            .collect()
+    }
+
+    ///
+    /// Makes sure a location has a root row so it can hold a shard depth. Limits stay
+    /// as they are if it already had one
+    ///
+    pub(in crate::database) fn storage_root_ensure_sql(&self, location: &String) -> u64 {
+        if !location_remote(location) {
+            folder_make(location);
+        }
+        let storage_id = self.storage_put(location);
+        if !self
+            .storage_roots_list_sql()
+            .iter()
+            .any(|root| root.storage_id == storage_id)
+        {
+            self.storage_root_set_sql(location, None, 0);
+        }
+        storage_id
+    }
+
+    pub(in crate::database) fn storage_shard_depth_set_sql(
+        &self,
+        storage_id: &u64,
+        shard_depth: u64,
+    ) -> Result<(), String> {
+        if shard_depth > SHARD_DEPTH_MAX {
+            return Err(format!(
+                "Shard depth {} is deeper than the max of {}",
+                shard_depth, SHARD_DEPTH_MAX
+            ));
+        }
+        let mut write_conn = self.write_conn.lock();
+        let tn = write_conn.transaction().unwrap();
+        tn.execute(
+            "UPDATE StorageRoots SET shard_depth = ? WHERE storage_id = ?",
+            params![shard_depth, storage_id],
+        )
+        .unwrap();
+        tn.commit().unwrap();
+        Ok(())
+    }
+
+    ///
+    /// Sets the limits of a root, adding the location if it's new.
+    /// A new root starts with used at whatever is already in the folder
+    ///
+    pub(in crate::database) fn storage_root_set_sql(
+        &self,
+        location: &String,
+        capacity: Option<u64>,
+        reserve: u64,
+    ) -> u64 {
+        if !location_remote(location) {
+            folder_make(location);
+        }
+        let storage_id = self.storage_put(location);
+        let exists = self
+            .storage_roots_list_sql()
+            .iter()
+            .any(|root| root.storage_id == storage_id);
+        let used = if exists { 0 } else { folder_size(location) };
+
+        let mut write_conn = self.write_conn.lock();
+        let tn = write_conn.transaction().unwrap();
+        tn.execute(
+            "INSERT INTO StorageRoots (storage_id, capacity, reserve, used) VALUES (?, ?, ?, ?)
+            ON CONFLICT(storage_id) DO UPDATE SET capacity = excluded.capacity, reserve = excluded.reserve",
+            params![storage_id, capacity, reserve, used],
+        )
+        .unwrap();
+        tn.commit().unwrap();
+        storage_id
+    }
+
+    ///
+    /// Stops treating a location as a root. Files in it stay where they are
+    ///
+    pub(in crate::database) fn storage_root_remove_sql(&self, location: &String) -> bool {
+        let Some(storage_id) = self.storage_get_id(location) else {
+            return false;
+        };
+        let mut write_conn = self.write_conn.lock();
+        let tn = write_conn.transaction().unwrap();
+        let removed = tn
+            .execute(
+                "DELETE FROM StorageRoots WHERE storage_id = ?",
+                params![storage_id],
+            )
+            .unwrap();
+        tn.commit().unwrap();
+        removed > 0
+    }
+
+    ///
+    /// Counts bytes written into a root against its capacity. Negative for bytes that
+    /// left it
+    ///
+    pub(in crate::database) fn storage_root_used_add(
+        &self,
+        tn: &Transaction,
+        storage_id: &u64,
+        bytes: i64,
+    ) {
+        tn.execute(
+            "UPDATE StorageRoots SET used = MAX(used + ?, 0) WHERE storage_id = ?",
+            params![bytes, storage_id],
+        )
+        .unwrap();
+    }
+
+    ///
+    /// Picks where a file of size bytes goes when it's routed to storage_id.
+    /// When that root is full the other roots get tried in id order after it
+    ///
+    pub(in crate::database) fn storage_root_pick(
+        &self,
+        storage_id: &u64,
+        size: u64,
+    ) -> Option<(u64, String)> {
+        let roots = self.storage_roots_list_sql();
+        let target = match roots.iter().find(|root| root.storage_id == *storage_id) {
+            Some(root) => root.clone(),
+            // Locations that were never set up as a root have no limits of their own
+            None => sharedtypes::StorageRoot {
+                storage_id: *storage_id,
+                location: self.storage_get_string(storage_id)?,
+                capacity: None,
+                reserve: 0,
+                used: 0,
+                shard_depth: SHARD_DEPTH_DEFAULT,
+                backend: sharedtypes::StorageBackend::Plain,
+            },
+        };
+        let (before, after): (Vec<_>, Vec<_>) = roots
+            .into_iter()
+            .filter(|root| root.storage_id != *storage_id)
+            .partition(|root| root.storage_id < *storage_id);
+
+        std::iter::once(target)
+            .chain(after)
+            .chain(before)
+            .find(|root| root_fits(root, size))
+            .map(|root| (root.storage_id, root.location))
+    }
+}
+
+///
+/// If a file of size bytes can go into root without going over its capacity or
+/// eating into the free space it keeps in reserve
+///
+fn root_fits(root: &sharedtypes::StorageRoot, size: u64) -> bool {
+    let under_capacity = root
+        .capacity
+        .is_none_or(|capacity| root.used.saturating_add(size) <= capacity);
+    let has_space = os::free_space(Path::new(&root.location))
+        .is_none_or(|free| free >= size.saturating_add(root.reserve));
+    under_capacity && has_space
+}
+
+fn folder_size(location: &str) -> u64 {
+    WalkDir::new(location)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_roots_spill_over() {
+        let root = |storage_id, capacity, used| sharedtypes::StorageRoot {
+            storage_id,
+            location: "/".into(),
+            capacity,
+            reserve: 0,
+            used,
+            shard_depth: SHARD_DEPTH_DEFAULT,
+            backend: sharedtypes::StorageBackend::Plain,
+        };
+        assert!(root_fits(&root(1, None, 0), 10));
+        assert!(root_fits(&root(1, Some(100), 90), 10));
+        assert!(!root_fits(&root(1, Some(100), 95), 10));
+        let mut reserved = root(1, None, 0);
+        reserved.reserve = u64::MAX;
+        assert!(os::free_space(Path::new("/")).is_none() || !root_fits(&reserved, 1));
+    }
+}
@@ -1171,4 +1171,117 @@ WHERE p.tag_id != p.relate_tag_id;",
         }
         self.db_version_set(12);
     }
+
+    /// Adds the plugin registry
+    pub fn db_update_twelve_to_thirteen(&mut self) {
+        {
+            let mut write_conn = self.write_conn.lock();
+            let tn = write_conn.transaction().unwrap();
+
+            logging::info_log("Creating plugin registry");
+            self.plugin_registry_create_v1(&tn);
+
+            tn.commit().unwrap();
+        }
+        self.db_version_set(13);
+    }
+
+    /// Adds storage for plugins to keep their own data in
+    pub fn db_update_thirteen_to_fourteen(&mut self) {
+        {
+            let mut write_conn = self.write_conn.lock();
+            let tn = write_conn.transaction().unwrap();
+
+            logging::info_log("Creating plugin storage");
+            self.plugin_storage_create_v1(&tn);
+
+            tn.commit().unwrap();
+        }
+        self.db_version_set(14);
+    }
+
+    /// Adds the capacity, reserve and usage of each storage root
+    pub fn db_update_fourteen_to_fifteen(&mut self) {
+        {
+            let mut write_conn = self.write_conn.lock();
+            let tn = write_conn.transaction().unwrap();
+
+            logging::info_log("Creating storage roots");
+            self.storage_roots_create_v1(&tn);
+
+            tn.commit().unwrap();
+        }
+        self.db_version_set(15);
+    }
+
+    /// Adds per root shard depth and the queue for moving files between roots
+    pub fn db_update_fifteen_to_sixteen(&mut self) {
+        {
+            let mut write_conn = self.write_conn.lock();
+            let tn = write_conn.transaction().unwrap();
+
+            logging::info_log("Adding shard depth to storage roots");
+            self.storage_roots_shard_depth_add_v1(&tn);
+            self.storage_migration_create_v1(&tn);
+
+            tn.commit().unwrap();
+        }
+        self.db_version_set(16);
+    }
+
+    /// Adds when each file was last checked by a scrub and what it found
+    pub fn db_update_sixteen_to_seventeen(&mut self) {
+        {
+            let mut write_conn = self.write_conn.lock();
+            let tn = write_conn.transaction().unwrap();
+
+            logging::info_log("Creating file verify table for scrubs");
+            self.file_verify_create_v1(&tn);
+
+            tn.commit().unwrap();
+        }
+        self.db_version_set(17);
+    }
+
+    /// Adds the backend of each storage root and the index of files in pack files
+    pub fn db_update_seventeen_to_eighteen(&mut self) {
+        {
+            let mut write_conn = self.write_conn.lock();
+            let tn = write_conn.transaction().unwrap();
+
+            logging::info_log("Adding storage backends and the pack index");
+            self.storage_backend_create_v1(&tn);
+
+            tn.commit().unwrap();
+        }
+        self.db_version_set(18);
+    }
+
+    /// Adds connection details for storage roots kept in S3 buckets
+    pub fn db_update_eighteen_to_nineteen(&mut self) {
+        {
+            let mut write_conn = self.write_conn.lock();
+            let tn = write_conn.transaction().unwrap();
+
+            logging::info_log("Creating table for S3 storage roots");
+            self.storage_s3_create_v1(&tn);
+
+            tn.commit().unwrap();
+        }
+        self.db_version_set(19);
+    }
+
+    /// Adds watch folders and the files already imported from them
+    pub fn db_update_nineteen_to_twenty(&mut self) {
+        {
+            let mut write_conn = self.write_conn.lock();
+            let tn = write_conn.transaction().unwrap();
+
+            logging::info_log("Creating tables for watch folders");
+            self.watch_folder_create_v1(&tn);
+
+            tn.commit().unwrap();
+        }
+        self.db_version_set(20);
+    }
 }
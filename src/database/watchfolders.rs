@@ -0,0 +1,230 @@
+use crate::database::database::Main;
+use crate::database::database::Transaction;
+use rusqlite::OptionalExtension;
+use rusqlite::params;
+use std::collections::HashMap;
+use std::path::Path;
+
+///
+/// Watch folders are kept by their absolute path so it doesn't matter where the
+/// daemon gets started from
+///
+fn watch_path(path: &String) -> String {
+    Path::new(path)
+        .canonicalize()
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or(path.clone())
+}
+
+impl Main {
+    ///
+    /// Folders that get imported from automatically and the files already taken from
+    /// them. WatchFolderSeen is what lets a restart tell new files from old ones
+    ///
+    pub(in crate::database) fn watch_folder_create_v1(&self, tn: &Transaction) {
+        tn.execute(
+            "
+CREATE TABLE IF NOT EXISTS WatchFolders (
+  id INTEGER PRIMARY KEY NOT NULL,
+  path TEXT NOT NULL UNIQUE,
+  file_action TEXT NOT NULL,
+  recursive INTEGER NOT NULL,
+  settle_secs INTEGER NOT NULL,
+  ignore TEXT NOT NULL,
+  tags TEXT NOT NULL
+);
+",
+            [],
+        )
+        .unwrap();
+        tn.execute(
+            "
+CREATE TABLE IF NOT EXISTS WatchFolderSeen (
+  watch_id INTEGER NOT NULL,
+  path TEXT NOT NULL,
+  len INTEGER NOT NULL,
+  modified INTEGER NOT NULL,
+  PRIMARY KEY (watch_id, path)
+);
+",
+            [],
+        )
+        .unwrap();
+    }
+
+    pub(in crate::database) fn watch_folders_list_sql(&self) -> Vec<sharedtypes::WatchFolder> {
+        let tn = self.get_database_connection();
+        let mut stmt = tn
+            .prepare(
+                "SELECT id, path, file_action, recursive, settle_secs, ignore, tags FROM WatchFolders ORDER BY id",
+            )
+            .unwrap();
+        stmt.query_map([], |row| {
+            Ok(sharedtypes::WatchFolder {
+                id: row.get(0)?,
+                path: row.get(1)?,
+                file_action: serde_json::from_str(&row.get::<_, String>(2)?)
+                    .unwrap_or(sharedtypes::FileAction::Copy),
+                recursive: row.get(3)?,
+                settle_secs: row.get(4)?,
+                ignore: serde_json::from_str(&row.get::<_, String>(5)?).unwrap_or_default(),
+                tags: serde_json::from_str(&row.get::<_, String>(6)?).unwrap_or_default(),
+            })
+        })
+        .unwrap()
+        .flatten()
+        .collect()
+    }
+
+    ///
+    /// Adds a watch folder or changes the one at the same path. Returns its id
+    ///
+    pub(in crate::database) fn watch_folder_set_sql(
+        &self,
+        folder: &sharedtypes::WatchFolder,
+    ) -> u64 {
+        let path = watch_path(&folder.path);
+        let mut write_conn = self.write_conn.lock();
+        let tn = write_conn.transaction().unwrap();
+        tn.execute(
+            "INSERT INTO WatchFolders (path, file_action, recursive, settle_secs, ignore, tags) VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(path) DO UPDATE SET file_action = excluded.file_action, recursive = excluded.recursive,
+            settle_secs = excluded.settle_secs, ignore = excluded.ignore, tags = excluded.tags",
+            params![
+                path,
+                serde_json::to_string(&folder.file_action).unwrap(),
+                folder.recursive,
+                folder.settle_secs,
+                serde_json::to_string(&folder.ignore).unwrap(),
+                serde_json::to_string(&folder.tags).unwrap(),
+            ],
+        )
+        .unwrap();
+        let id = tn
+            .query_row(
+                "SELECT id FROM WatchFolders WHERE path = ?",
+                params![path],
+                |row| row.get(0),
+            )
+            .unwrap();
+        tn.commit().unwrap();
+        id
+    }
+
+    ///
+    /// Stops watching a folder. Nothing in it gets touched
+    ///
+    pub(in crate::database) fn watch_folder_remove_sql(&self, path: &String) -> bool {
+        let path = watch_path(path);
+        let mut write_conn = self.write_conn.lock();
+        let tn = write_conn.transaction().unwrap();
+        let Some(id) = tn
+            .query_row(
+                "SELECT id FROM WatchFolders WHERE path = ?",
+                params![path],
+                |row| row.get::<_, u64>(0),
+            )
+            .optional()
+            .unwrap()
+        else {
+            return false;
+        };
+        tn.execute("DELETE FROM WatchFolders WHERE id = ?", params![id])
+            .unwrap();
+        tn.execute(
+            "DELETE FROM WatchFolderSeen WHERE watch_id = ?",
+            params![id],
+        )
+        .unwrap();
+        tn.commit().unwrap();
+        true
+    }
+
+    ///
+    /// Length and modified time in ms of every file already imported from a folder,
+    /// keyed by the path inside it
+    ///
+    pub(in crate::database) fn watch_folder_seen_sql(
+        &self,
+        watch_id: &u64,
+    ) -> HashMap<String, (u64, u64)> {
+        let tn = self.get_database_connection();
+        let mut stmt = tn
+            .prepare("SELECT path, len, modified FROM WatchFolderSeen WHERE watch_id = ?")
+            .unwrap();
+        stmt.query_map(params![watch_id], |row| {
+            Ok((row.get(0)?, (row.get(1)?, row.get(2)?)))
+        })
+        .unwrap()
+        .flatten()
+        .collect()
+    }
+
+    ///
+    /// Remembers a file as imported. None forgets it so it gets imported again if it
+    /// shows back up
+    ///
+    pub(in crate::database) fn watch_folder_seen_set_sql(
+        &self,
+        watch_id: &u64,
+        path: &String,
+        stamp: Option<(u64, u64)>,
+    ) {
+        let mut write_conn = self.write_conn.lock();
+        let tn = write_conn.transaction().unwrap();
+        match stamp {
+            Some((len, modified)) => tn.execute(
+                "INSERT OR REPLACE INTO WatchFolderSeen (watch_id, path, len, modified) VALUES (?, ?, ?, ?)",
+                params![watch_id, path, len, modified],
+            ),
+            None => tn.execute(
+                "DELETE FROM WatchFolderSeen WHERE watch_id = ? AND path = ?",
+                params![watch_id, path],
+            ),
+        }
+        .unwrap();
+        tn.commit().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VERS;
+
+    #[test]
+    fn watch_folders_survive_being_set_again() {
+        let db = Main::new(None, VERS);
+        let mut folder = sharedtypes::WatchFolder {
+            id: 0,
+            path: "/nonexistent/inbox".to_string(),
+            file_action: sharedtypes::FileAction::Move,
+            recursive: true,
+            settle_secs: 10,
+            ignore: vec!["*.part".to_string()],
+            tags: vec![sharedtypes::Tag {
+                tag: "inbox".to_string(),
+                namespace: sharedtypes::GenericNamespaceObj {
+                    name: "source".to_string(),
+                    description: None,
+                },
+            }],
+        };
+        let id = db.watch_folder_set_sql(&folder);
+        db.watch_folder_seen_set_sql(&id, &"a.jpg".to_string(), Some((3, 1000)));
+
+        folder.recursive = false;
+        assert_eq!(db.watch_folder_set_sql(&folder), id);
+        folder.id = id;
+        assert_eq!(db.watch_folders_list_sql(), vec![folder.clone()]);
+        assert_eq!(
+            db.watch_folder_seen_sql(&id),
+            HashMap::from([("a.jpg".to_string(), (3, 1000))])
+        );
+
+        assert!(db.watch_folder_remove_sql(&folder.path));
+        assert!(!db.watch_folder_remove_sql(&folder.path));
+        assert!(db.watch_folders_list_sql().is_empty());
+        assert!(db.watch_folder_seen_sql(&id).is_empty());
+    }
+}
@@ -0,0 +1,802 @@
+use crate::htmlparse::{self, CompiledQuery};
+use scraper::{ElementRef, Html};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::time::Duration;
+
+const PAGE_KEY: &str = "declarative_page";
+const PAGES_KEY: &str = "declarative_pages";
+const TAGS_KEY: &str = "declarative_tags";
+
+///
+/// A scraper described by a config file instead of a library.
+/// Lives next to the compiled scrapers as <name>.scraper.toml or <name>.scraper.json
+///
+#[derive(Debug, Clone, Deserialize)]
+pub struct SiteDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub version: u64,
+    /// Site names jobs use to reach this scraper
+    pub sites: Vec<String>,
+    #[serde(default)]
+    pub ratelimit: RateLimit,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    pub search: Search,
+    #[serde(default)]
+    pub pagination: Pagination,
+    pub post: PostFields,
+    #[serde(default)]
+    pub tags: Vec<TagField>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateLimit {
+    pub count: u64,
+    pub seconds: u64,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit {
+            count: 1,
+            seconds: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Search {
+    /// Url with {tags} and {page} in it
+    pub url: String,
+    /// What goes between search terms in {tags}
+    #[serde(default = "separator_default")]
+    pub tag_separator: String,
+    /// Path to the list of posts. Empty when the body is the list.
+    /// For html it's a selector for each post and the page is one post when empty
+    #[serde(default)]
+    pub posts: String,
+    #[serde(default)]
+    pub format: Format,
+}
+
+///
+/// What the search pages come back as. Html paths are css selectors or xpaths,
+/// css can end in @attr to read an attribute instead of the text
+///
+#[derive(Debug, Clone, Copy, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Json,
+    Html,
+}
+
+fn separator_default() -> String {
+    "+".to_string()
+}
+
+///
+/// {page} starts at start and goes up by step. Offset style apis use the page size as the step
+///
+#[derive(Debug, Clone, Deserialize)]
+pub struct Pagination {
+    #[serde(default = "start_default")]
+    pub start: u64,
+    #[serde(default = "step_default")]
+    pub step: u64,
+    #[serde(default = "max_pages_default")]
+    pub max_pages: u64,
+}
+
+fn start_default() -> u64 {
+    1
+}
+
+fn step_default() -> u64 {
+    1
+}
+
+fn max_pages_default() -> u64 {
+    1000
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Pagination {
+            start: start_default(),
+            step: step_default(),
+            max_pages: max_pages_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HashType {
+    #[default]
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+///
+/// Paths into a single post
+///
+#[derive(Debug, Clone, Deserialize)]
+pub struct PostFields {
+    pub file_url: String,
+    #[serde(default)]
+    pub hash: Option<String>,
+    #[serde(default)]
+    pub hash_type: HashType,
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub id_namespace: Option<String>,
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub source_namespace: Option<String>,
+}
+
+///
+/// Where to find tags in a post and which namespace they land in.
+/// path can point at a string of tags, a list of tags, an object of category to
+/// tags or a list of objects with tag_key and category_key.
+/// For html each element path matches is one tag. tag_key is a selector for the
+/// text inside of it and category_key an attribute holding the category.
+///
+#[derive(Debug, Clone, Deserialize)]
+pub struct TagField {
+    pub path: String,
+    /// Used for plain tags and categories that aren't in categories
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// Category name to namespace
+    #[serde(default)]
+    pub categories: BTreeMap<String, String>,
+    /// Namespace for categories not in categories. {category} gets replaced
+    #[serde(default)]
+    pub category_namespace: Option<String>,
+    #[serde(default)]
+    pub tag_key: Option<String>,
+    #[serde(default)]
+    pub category_key: Option<String>,
+    /// Splits a string of tags
+    #[serde(default = "split_default")]
+    pub split: String,
+}
+
+fn split_default() -> String {
+    " ".to_string()
+}
+
+///
+/// Config files we load as scrapers
+///
+pub fn is_definition(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy())
+        .is_some_and(|name| name.ends_with(".scraper.toml") || name.ends_with(".scraper.json"))
+}
+
+///
+/// Walks a dotted path like data.posts.0. Missing keys give Null
+///
+pub fn json_path<'a>(value: &'a Value, path: &str) -> &'a Value {
+    path.split('.')
+        .filter(|key| !key.is_empty())
+        .fold(value, |value, key| match value {
+            Value::Array(list) => key
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| list.get(index))
+                .unwrap_or(&Value::Null),
+            _ => value.get(key).unwrap_or(&Value::Null),
+        })
+}
+
+///
+/// Strings and numbers as text. Everything else is None
+///
+fn value_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) if !text.is_empty() => Some(text.to_string()),
+        Value::Number(num) => Some(num.to_string()),
+        _ => None,
+    }
+}
+
+///
+/// A field path as a query. Links resolve against the page and default to href
+///
+fn html_query(path: &str, link: bool) -> sharedtypes::HtmlQuery {
+    let (selector, attr) = match path.rsplit_once('@') {
+        Some((selector, attr)) if !path.starts_with('/') => (selector, Some(attr)),
+        _ => (path, None),
+    };
+    let extract = match (attr, link) {
+        (Some(attr), true) => sharedtypes::HtmlExtract::Link(attr.to_string()),
+        (Some(attr), false) => sharedtypes::HtmlExtract::Attr(attr.to_string()),
+        (None, true) => sharedtypes::HtmlExtract::Link("href".to_string()),
+        (None, false) => sharedtypes::HtmlExtract::Text,
+    };
+    sharedtypes::HtmlQuery {
+        selector: selector.trim().to_string(),
+        extract,
+    }
+}
+
+///
+/// A post that fields can be read from, either a json object or an html element
+///
+trait PostSource {
+    fn text(&self, path: &str, link: bool) -> Option<String>;
+    fn texts(&self, path: &str, link: bool) -> Vec<String>;
+    fn tags(&self, field: &TagField) -> Vec<(Option<String>, String)>;
+}
+
+impl PostSource for Value {
+    fn text(&self, path: &str, _link: bool) -> Option<String> {
+        value_text(json_path(self, path))
+    }
+
+    fn texts(&self, path: &str, _link: bool) -> Vec<String> {
+        match json_path(self, path) {
+            Value::Array(list) => list.iter().filter_map(value_text).collect(),
+            value => value_text(value).into_iter().collect(),
+        }
+    }
+
+    fn tags(&self, field: &TagField) -> Vec<(Option<String>, String)> {
+        field.collect(json_path(self, &field.path))
+    }
+}
+
+struct HtmlPost<'a> {
+    element: ElementRef<'a>,
+    base: Option<&'a url::Url>,
+}
+
+impl HtmlPost<'_> {
+    fn query(path: &str, link: bool) -> Option<CompiledQuery> {
+        // Selectors were checked when the definition loaded
+        CompiledQuery::new(&html_query(path, link)).ok()
+    }
+}
+
+impl PostSource for HtmlPost<'_> {
+    fn text(&self, path: &str, link: bool) -> Option<String> {
+        Self::query(path, link)?.first(self.element, self.base)
+    }
+
+    fn texts(&self, path: &str, link: bool) -> Vec<String> {
+        Self::query(path, link)
+            .map(|query| query.all(self.element, self.base))
+            .unwrap_or_default()
+    }
+
+    fn tags(&self, field: &TagField) -> Vec<(Option<String>, String)> {
+        let Some(query) = Self::query(&field.path, false) else {
+            return Vec::new();
+        };
+        let inner = field
+            .tag_key
+            .as_ref()
+            .and_then(|key| Self::query(key, false));
+        query
+            .elements(self.element)
+            .into_iter()
+            .filter_map(|element| {
+                let text = match &inner {
+                    Some(inner) => inner.first(element, self.base),
+                    None => htmlparse::extract(element, &sharedtypes::HtmlExtract::Text, None),
+                }?;
+                let category = field
+                    .category_key
+                    .as_ref()
+                    .and_then(|key| element.value().attr(key))
+                    .map(|category| category.to_string());
+                Some((category, text))
+            })
+            .collect()
+    }
+}
+
+fn namespace(name: &str) -> sharedtypes::GenericNamespaceObj {
+    sharedtypes::GenericNamespaceObj {
+        name: name.to_string(),
+        description: None,
+    }
+}
+
+fn tag(namespace_name: &str, tag: String) -> sharedtypes::TagObject {
+    sharedtypes::TagObject {
+        namespace: namespace(namespace_name),
+        tag,
+        tag_type: sharedtypes::TagType::Normal,
+        relates_to: None,
+    }
+}
+
+impl SiteDefinition {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let definition: SiteDefinition = if path.extension().is_some_and(|ext| ext == "toml") {
+            toml::from_str(&text).map_err(|err| err.to_string())?
+        } else {
+            serde_json::from_str(&text).map_err(|err| err.to_string())?
+        };
+        if !definition.search.url.contains("{page}") {
+            return Err("search.url needs a {page}".to_string());
+        }
+        if definition.sites.is_empty() {
+            return Err("needs at least one site".to_string());
+        }
+        if definition.search.format == Format::Html {
+            for path in definition.html_paths() {
+                CompiledQuery::new(&html_query(path, false))?;
+            }
+        }
+        Ok(definition)
+    }
+
+    ///
+    /// What a native scraper would return from get_global_info
+    ///
+    pub fn global_info(&self) -> sharedtypes::GlobalPluginScraper {
+        let mut global = sharedtypes::return_default_globalpluginparser();
+        global.name = self.name.clone();
+        global.version = self.version;
+        let modifiers = self
+            .user_agent
+            .iter()
+            .flat_map(|agent| {
+                [
+                    sharedtypes::ModifierTarget::Text,
+                    sharedtypes::ModifierTarget::Media,
+                ]
+                .map(|target| sharedtypes::TargetModifiers {
+                    target,
+                    modifier: sharedtypes::ScraperModifiers::Useragent(agent.clone()),
+                })
+            })
+            .collect();
+        global.storage_type = Some(sharedtypes::ScraperOrPlugin::Scraper(
+            sharedtypes::ScraperInfo {
+                ratelimit: (
+                    self.ratelimit.count,
+                    Duration::from_secs(self.ratelimit.seconds),
+                ),
+                sites: self.sites.clone(),
+                priority: sharedtypes::DEFAULT_PRIORITY,
+                num_threads: None,
+                modifiers,
+            },
+        ));
+        global
+    }
+
+    fn html_paths(&self) -> Vec<&String> {
+        let post = &self.post;
+        [&self.search.posts, &post.file_url]
+            .into_iter()
+            .filter(|path| !path.is_empty())
+            .chain(post.hash.iter())
+            .chain(post.id.iter())
+            .chain(post.source.iter())
+            .chain(self.tags.iter().map(|field| &field.path))
+            .chain(self.tags.iter().filter_map(|field| field.tag_key.as_ref()))
+            .collect()
+    }
+
+    fn page_url(&self, tags: &str, page: u64) -> String {
+        self.search
+            .url
+            .replace("{tags}", tags)
+            .replace("{page}", &page.to_string())
+    }
+
+    fn page_job(
+        &self,
+        scraperdata: &sharedtypes::ScraperDataReturn,
+        tags: &str,
+        pages: u64,
+    ) -> sharedtypes::ScraperDataReturn {
+        let page = self.pagination.start + pages * self.pagination.step;
+        let mut user_data = scraperdata.job.user_data.clone();
+        user_data.insert(TAGS_KEY.to_string(), tags.to_string());
+        user_data.insert(PAGE_KEY.to_string(), page.to_string());
+        user_data.insert(PAGES_KEY.to_string(), pages.to_string());
+        sharedtypes::ScraperDataReturn {
+            job: sharedtypes::DbJobsObj {
+                site: scraperdata.job.site.clone(),
+                priority: sharedtypes::DEFAULT_PRIORITY - 2,
+                param: vec![sharedtypes::ScraperParam::Url(self.page_url(tags, page))],
+                jobmanager: sharedtypes::DbJobsManager {
+                    jobtype: sharedtypes::DbJobType::Scraper,
+                    ..Default::default()
+                },
+                user_data,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    ///
+    /// Turns search terms into the first page. Later pages get queued by the parser
+    /// while pages keep coming back with posts.
+    ///
+    pub fn url_dump(
+        &self,
+        params: &[sharedtypes::ScraperParam],
+        scraperdata: &sharedtypes::ScraperDataReturn,
+    ) -> Vec<sharedtypes::ScraperDataReturn> {
+        let terms: Vec<String> = params
+            .iter()
+            .filter_map(|param| match param {
+                sharedtypes::ScraperParam::Normal(term) => {
+                    Some(url::form_urlencoded::byte_serialize(term.as_bytes()).collect())
+                }
+                _ => None,
+            })
+            .collect();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+        let tags = terms.join(&self.search.tag_separator);
+        vec![self.page_job(scraperdata, &tags, 0)]
+    }
+
+    ///
+    /// Reads the posts off of a page. source_url is what html links resolve against
+    ///
+    pub fn parser(
+        &self,
+        body: &str,
+        source_url: &str,
+        scraperdata: &sharedtypes::ScraperDataReturn,
+    ) -> Vec<sharedtypes::ScraperReturn> {
+        // Some apis send back nothing at all instead of an empty list
+        if body.trim().is_empty() {
+            return vec![sharedtypes::ScraperReturn::Nothing];
+        }
+        let files = match self.search.format {
+            Format::Json => self.json_files(body),
+            Format::Html => Ok(self.html_files(body, source_url)),
+        };
+        let files = match files {
+            Ok(files) if files.is_empty() => return vec![sharedtypes::ScraperReturn::Nothing],
+            Ok(files) => files,
+            Err(err) => return vec![err],
+        };
+
+        let mut out = sharedtypes::ScraperObject::default();
+        for file in files.into_iter().flatten() {
+            out.files.insert(sharedtypes::FileObject::V1(file));
+        }
+
+        let user_data = &scraperdata.job.user_data;
+        let pages = user_data
+            .get(PAGES_KEY)
+            .and_then(|pages| pages.parse::<u64>().ok());
+        if let (Some(pages), Some(tags)) = (pages, user_data.get(TAGS_KEY))
+            && pages + 1 < self.pagination.max_pages
+        {
+            out.jobs.insert(self.page_job(scraperdata, tags, pages + 1));
+        }
+        vec![sharedtypes::ScraperReturn::Data(out)]
+    }
+
+    ///
+    /// One entry per post. Posts without a file are None so they still count as a page
+    /// that had something on it
+    ///
+    #[allow(clippy::result_large_err)]
+    fn json_files(
+        &self,
+        body: &str,
+    ) -> Result<Vec<Option<sharedtypes::FileObjectV1>>, sharedtypes::ScraperReturn> {
+        let js: Value = serde_json::from_str(body).map_err(|err| {
+            sharedtypes::ScraperReturn::Stop(format!(
+                "{} sent back something that isn't json: {}",
+                self.name, err
+            ))
+        })?;
+        match json_path(&js, &self.search.posts) {
+            Value::Array(posts) => Ok(posts.iter().map(|post| self.post_file(post)).collect()),
+            Value::Null => Ok(Vec::new()),
+            _ => Err(sharedtypes::ScraperReturn::Stop(format!(
+                "{} posts path {} isn't a list",
+                self.name, self.search.posts
+            ))),
+        }
+    }
+
+    fn html_files(&self, body: &str, source_url: &str) -> Vec<Option<sharedtypes::FileObjectV1>> {
+        let base = url::Url::parse(source_url).ok();
+        let document = Html::parse_document(body);
+        let root = document.root_element();
+        let posts = match HtmlPost::query(&self.search.posts, false) {
+            Some(query) if !self.search.posts.is_empty() => query.elements(root),
+            _ => vec![root],
+        };
+        posts
+            .into_iter()
+            .map(|element| {
+                self.post_file(&HtmlPost {
+                    element,
+                    base: base.as_ref(),
+                })
+            })
+            // Selectors can match things that aren't posts so only files count
+            .filter(Option::is_some)
+            .collect()
+    }
+
+    fn post_file<P: PostSource + ?Sized>(&self, post: &P) -> Option<sharedtypes::FileObjectV1> {
+        let file_url = post.text(&self.post.file_url, true)?;
+        let hash = match self
+            .post
+            .hash
+            .as_ref()
+            .and_then(|path| post.text(path, false))
+        {
+            None => sharedtypes::HashesSupported::None,
+            Some(hash) => match self.post.hash_type {
+                HashType::Md5 => sharedtypes::HashesSupported::Md5(hash),
+                HashType::Sha1 => sharedtypes::HashesSupported::Sha1(hash),
+                HashType::Sha256 => sharedtypes::HashesSupported::Sha256(hash),
+                HashType::Sha512 => sharedtypes::HashesSupported::Sha512(hash),
+            },
+        };
+
+        let mut tags = self.post_tags(post);
+        let mut skip_if = Vec::new();
+        if let (Some(path), Some(ns)) = (&self.post.id, &self.post.id_namespace)
+            && let Some(id) = post.text(path, false)
+        {
+            skip_if.push(sharedtypes::SkipIf::FileTagRelationship(sharedtypes::Tag {
+                tag: id.clone(),
+                namespace: namespace(ns),
+            }));
+            tags.push(tag(ns, id));
+        }
+        if let (Some(path), Some(ns)) = (&self.post.source, &self.post.source_namespace) {
+            tags.extend(post.texts(path, true).into_iter().map(|src| tag(ns, src)));
+        }
+
+        Some(sharedtypes::FileObjectV1 {
+            source: Some(sharedtypes::FileSource::Url(file_url)),
+            hash,
+            tag_list: vec![sharedtypes::FileTagAction {
+                operation: sharedtypes::TagOperation::Add,
+                tags,
+            }],
+            skip_if,
+            ..Default::default()
+        })
+    }
+
+    fn post_tags<P: PostSource + ?Sized>(&self, post: &P) -> Vec<sharedtypes::TagObject> {
+        let mut out = Vec::new();
+        let mut seen = HashSet::new();
+        for field in self.tags.iter() {
+            for (category, text) in post.tags(field) {
+                let Some(ns) = field.namespace_for(category.as_deref()) else {
+                    continue;
+                };
+                if seen.insert((ns.clone(), text.clone())) {
+                    out.push(tag(&ns, text));
+                }
+            }
+        }
+        out
+    }
+}
+
+impl TagField {
+    ///
+    /// Every tag under the field with its category if it has one
+    ///
+    fn collect(&self, value: &Value) -> Vec<(Option<String>, String)> {
+        let mut out = Vec::new();
+        match value {
+            Value::String(text) => out.extend(
+                text.split(self.split.as_str())
+                    .filter(|tag| !tag.is_empty())
+                    .map(|tag| (None, tag.to_string())),
+            ),
+            Value::Array(list) => {
+                for item in list {
+                    match (item, &self.tag_key) {
+                        (Value::Object(_), Some(tag_key)) => {
+                            if let Some(text) = value_text(json_path(item, tag_key)) {
+                                let category = self
+                                    .category_key
+                                    .as_ref()
+                                    .and_then(|key| value_text(json_path(item, key)));
+                                out.push((category, text));
+                            }
+                        }
+                        _ => out.extend(value_text(item).map(|text| (None, text))),
+                    }
+                }
+            }
+            Value::Object(categories) => {
+                for (category, tags) in categories {
+                    for (_, text) in self.collect(tags) {
+                        out.push((Some(category.clone()), text));
+                    }
+                }
+            }
+            _ => {}
+        }
+        out
+    }
+
+    fn namespace_for(&self, category: Option<&str>) -> Option<String> {
+        let Some(category) = category else {
+            return self.namespace.clone();
+        };
+        if let Some(ns) = self.categories.get(category) {
+            return Some(ns.clone());
+        }
+        if let Some(template) = &self.category_namespace {
+            return Some(template.replace("{category}", category));
+        }
+        self.namespace.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFINITION: &str = r#"
+name = "Example"
+sites = ["example"]
+
+[search]
+url = "https://example.net/posts.json?tags={tags}&page={page}"
+
+[pagination]
+max_pages = 2
+
+[post]
+file_url = "file.url"
+hash = "file.md5"
+id = "id"
+id_namespace = "Example_Id"
+
+[[tags]]
+path = "tags"
+category_namespace = "Example_{category}"
+[tags.categories]
+general = "Example_General"
+
+[[tags]]
+path = "tag_info"
+tag_key = "tag"
+category_key = "type"
+namespace = "Example_Other"
+"#;
+
+    #[test]
+    fn definitions_parse_posts() {
+        let definition: SiteDefinition = toml::from_str(DEFINITION).unwrap();
+        let first = definition.url_dump(
+            &[
+                sharedtypes::ScraperParam::Normal("fox".into()),
+                sharedtypes::ScraperParam::Normal("rating:s".into()),
+            ],
+            &sharedtypes::ScraperDataReturn::default(),
+        );
+        assert_eq!(
+            first[0].job.param,
+            vec![sharedtypes::ScraperParam::Url(
+                "https://example.net/posts.json?tags=fox+rating%3As&page=1".into()
+            )]
+        );
+
+        let body = r#"{"posts": [{"id": 7, "file": {"url": "https://cdn/a.png", "md5": "abc"},
+            "tags": {"general": ["fox"], "artist": ["someone"]},
+            "tag_info": [{"tag": "solo", "type": 4}]}]}"#;
+        let mut definition = definition;
+        definition.search.posts = "posts".into();
+        let out = definition.parser(body, "", &first[0]);
+        let [sharedtypes::ScraperReturn::Data(data)] = out.as_slice() else {
+            panic!("expected data");
+        };
+        let Some(sharedtypes::FileObject::V1(file)) = data.files.iter().next() else {
+            panic!("expected a file");
+        };
+        assert_eq!(file.hash, sharedtypes::HashesSupported::Md5("abc".into()));
+        let mut tags: Vec<_> = file.tag_list[0]
+            .tags
+            .iter()
+            .map(|tag| format!("{}:{}", tag.namespace.name, tag.tag))
+            .collect();
+        tags.sort();
+        assert_eq!(
+            tags,
+            vec![
+                "Example_General:fox",
+                "Example_Id:7",
+                "Example_Other:solo",
+                "Example_artist:someone"
+            ]
+        );
+        // Second page is queued but max_pages stops a third
+        assert_eq!(data.jobs.len(), 1);
+        let second = data.jobs.iter().next().unwrap();
+        assert!(
+            definition
+                .parser(body, "", second)
+                .iter()
+                .all(|ret| match ret {
+                    sharedtypes::ScraperReturn::Data(data) => data.jobs.is_empty(),
+                    _ => false,
+                })
+        );
+    }
+
+    #[test]
+    fn html_definitions_use_selectors() {
+        let definition: SiteDefinition = toml::from_str(
+            r#"
+name = "Gallery"
+sites = ["gallery"]
+
+[search]
+url = "https://gallery.net/search?q={tags}&p={page}"
+format = "html"
+posts = "div.thumb"
+
+[post]
+file_url = "a.full@href"
+id = "//span[@class='id']/text()"
+id_namespace = "Gallery_Id"
+
+[[tags]]
+path = "li.tag"
+category_key = "data-type"
+category_namespace = "Gallery_{category}"
+"#,
+        )
+        .unwrap();
+        let body = r#"<div class="thumb"><a class="full" href="/img/9.jpg">9</a>
+            <span class="id">9</span><ul><li class="tag" data-type="artist"> someone </li></ul></div>
+            <div class="thumb"><span class="id">10</span></div>"#;
+        let out = definition.parser(
+            body,
+            "https://gallery.net/search?q=fox&p=1",
+            &sharedtypes::ScraperDataReturn::default(),
+        );
+        let [sharedtypes::ScraperReturn::Data(data)] = out.as_slice() else {
+            panic!("expected data");
+        };
+        assert_eq!(data.files.len(), 1);
+        let Some(sharedtypes::FileObject::V1(file)) = data.files.iter().next() else {
+            panic!("expected a file");
+        };
+        assert_eq!(
+            file.source,
+            Some(sharedtypes::FileSource::Url(
+                "https://gallery.net/img/9.jpg".into()
+            ))
+        );
+        let tags: Vec<_> = file.tag_list[0]
+            .tags
+            .iter()
+            .map(|tag| format!("{}:{}", tag.namespace.name, tag.tag))
+            .collect();
+        assert_eq!(tags, vec!["Gallery_artist:someone", "Gallery_Id:9"]);
+    }
+}
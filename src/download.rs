@@ -1,14 +1,17 @@
 use crate::Main;
 use crate::downloadlogic::LocalStorage;
+use crate::helpers;
 use crate::logging::error_log;
 
 // extern crate urlparse;
 use crate::logging;
+use crate::logging::LogCtx;
 use crate::logging::info_log;
+use crate::metrics;
 use bytes::Bytes;
 use core::time;
 use file_format::FileFormat;
-use log::{error, info};
+use log::info;
 use reqwest::Client;
 use reqwest::ClientBuilder;
 use reqwest::header::HeaderMap;
@@ -35,9 +38,15 @@ use std::fs::File;
 
 use crate::RwLock;
 use crate::ui::ui::*;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::LazyLock;
 use std::thread;
 
+/// Ratelimiters by site with the ratelimit they were made with
+static RATELIMITERS: LazyLock<RwLock<HashMap<String, ((u64, Duration), Arc<Ratelimiter>)>>> =
+    LazyLock::new(Default::default);
+
 /// Makes ratelimiter and example
 pub fn ratelimiter_create(
     workerid: &u64,
@@ -45,9 +54,9 @@ pub fn ratelimiter_create(
     number: u64,
     duration: Duration,
 ) -> Ratelimiter {
-    logging::info_log(format!(
-        "Worker: {} JobId: {} -- Making ratelimiter with: {} Request Per: {:?}",
-        workerid, jobid, &number, &duration
+    LogCtx::worker(*workerid).job(*jobid).info(format!(
+        "Making ratelimiter with: {} Request Per: {:?}",
+        &number, &duration
     ));
     loop {
         // The wrapper that implements ratelimiting
@@ -99,6 +108,7 @@ pub async fn ratelimiter_wait(ratelimit_object: &Arc<Ratelimiter>) {
         match limit {
             Ok(_) => break,
             Err(sleep) => {
+                metrics::ratelimit_wait_record(sleep);
                 tokio::time::sleep(sleep).await;
             }
         }
@@ -204,10 +214,10 @@ pub async fn dltext_new(
 
         ratelimiter_wait(ratelimiter_obj).await;
 
-        logging::info_log(format!(
-            "Worker: {} JobId: {} -- Spawned web reach to: {}",
-            worker_id, job_id, &url_string
-        ));
+        LogCtx::worker(*worker_id)
+            .job(*job_id)
+            .url(url_string)
+            .info("Spawned web reach");
 
         let futureresult = match post_data {
             None => client.get(url).header("Accept", "text/css").send(),
@@ -230,15 +240,19 @@ pub async fn dltext_new(
         }
         match futureresult {
             Ok(res) => {
+                metrics::http_status_record(res.status().as_u16());
                 // Exit for error codes 400
                 if let Err(err) = res.error_for_status_ref() {
                     if err.is_timeout() {
                         let time_secs = 5;
                         tokio::time::sleep(std::time::Duration::from_secs(time_secs)).await;
-                        logging::error_log(format!(
-                            "Worker: {} JobId: {} -- While processing job {:?} was unable to download text. Had err {:?} sleeping for {} seconds.",
-                            &worker_id, &job_id, &url_string, err, time_secs
-                        ));
+                        LogCtx::worker(*worker_id)
+                            .job(*job_id)
+                            .url(url_string)
+                            .error(format!(
+                                "Unable to download text. Had err {:?} sleeping for {} seconds.",
+                                err, time_secs
+                            ));
 
                         continue;
                     }
@@ -259,10 +273,13 @@ pub async fn dltext_new(
             Err(err) => {
                 if err.is_timeout() {
                     let time_secs = 5;
-                    logging::error_log(format!(
-                        "Worker: {} JobId: {} -- While processing job {:?} was unable to download text. Had err {:?} sleeping for {} seconds.",
-                        &worker_id, &job_id, &url_string, err, time_secs
-                    ));
+                    LogCtx::worker(*worker_id)
+                        .job(*job_id)
+                        .url(url_string)
+                        .error(format!(
+                            "Unable to download text. Had err {:?} sleeping for {} seconds.",
+                            err, time_secs
+                        ));
                     tokio::time::sleep(std::time::Duration::from_secs(time_secs)).await;
 
                     cnt += 1;
@@ -465,10 +482,11 @@ pub async fn dlfile_new(
                     return FileReturnStatus::DeadUrl(source_url.to_string());
                 }
                 let url = url.unwrap();
+                let log_ctx = LogCtx::worker(*workerid).job(*jobid).url(&url);
 
                 ratelimiter_wait(ratelimiter_obj).await;
 
-                logging::info_log(format!("Downloading: {}", &source_url));
+                log_ctx.info("Downloading");
 
                 // Assuming post_data logic exists based on your compiler error snippet
                 let response_result = { client.get(url.as_ref()).send().await };
@@ -480,23 +498,21 @@ pub async fn dlfile_new(
 
                 match response_result {
                     Ok(res) => {
+                        metrics::http_status_record(res.status().as_u16());
                         if let Err(err) = res.error_for_status_ref() {
                             if let Some(status) = err.status() {
                                 if status.is_server_error() {
-                                    logging::error_log(&format!(
-                                        "Worker: {workerid} JobID: {jobid} -- Repeating job due to server err {:?} url: {}",
-                                        err,
-                                        &url.to_string()
+                                    log_ctx.error(format!(
+                                        "Repeating job due to server err {:?}",
+                                        err
                                     ));
                                     tokio::time::sleep(Duration::from_secs(10)).await;
                                     cnt += 1;
                                     continue;
                                 }
                                 if status.is_client_error() {
-                                    logging::error_log(&format!(
-                                        "Worker: {workerid} JobID: {jobid} -- Stopping file download due to: {:?}",
-                                        err
-                                    ));
+                                    log_ctx
+                                        .error(format!("Stopping file download due to: {:?}", err));
                                     return FileReturnStatus::DeadUrl(source_url.clone());
                                 }
                             }
@@ -504,7 +520,7 @@ pub async fn dlfile_new(
                         break res;
                     }
                     Err(_) => {
-                        error!("Worker: {workerid} JobID: {jobid} -- Repeating: {}", &url);
+                        log_ctx.error("Repeating");
                         tokio::time::sleep(Duration::from_secs(10)).await;
                         cnt += 1;
                     }
@@ -519,22 +535,24 @@ pub async fn dlfile_new(
                 let jid = jobid.clone();
                 // Checks if system memory is large enough plus some overage
                 let _ = tokio::task::spawn_blocking(async move || {
-                loop {
-                    let sys = System::new();
-                    if let Ok(meminfo) = sys.memory() {
-                        let freemem = (meminfo.free.as_u64() as f64) * 0.8;
-                        if freemem > total_size as f64 {
-                            break;
+                    loop {
+                        let sys = System::new();
+                        if let Ok(meminfo) = sys.memory() {
+                            let freemem = (meminfo.free.as_u64() as f64) * 0.8;
+                            if freemem > total_size as f64 {
+                                break;
+                            } else {
+                                LogCtx::worker(wid)
+                                    .job(jid)
+                                    .info("Waiting 1 sec for memory to free up.");
+                                tokio::time::sleep(Duration::from_secs(1)).await;
+                            }
                         } else {
-                            logging::info_log(format!("Worker: {wid} JobId: {jid} -- Waiting 1 sec for memory to free up."));
-                            tokio::time::sleep(Duration::from_secs(1)).await;
+                            break;
                         }
-                    } else {
-                        break;
                     }
-                }
-            })
-            .await;
+                })
+                .await;
             }
 
             let mut downloaded: u64 = 0;
@@ -601,10 +619,13 @@ pub async fn dlfile_new(
                     let status = hash_bytes(bytes.as_ref().unwrap(), &file.hash);
 
                     if !status.1 {
-                        error!(
-                            "Worker: {workerid} JobID: {jobid} -- Parser file: {:?} FAILED HASHCHECK: {} {}",
-                            &file.hash, status.0, status.1
-                        );
+                        LogCtx::worker(*workerid)
+                            .job(*jobid)
+                            .hash(&status.0)
+                            .error(format!(
+                                "Parser file: {:?} FAILED HASHCHECK: {}",
+                                &file.hash, status.1
+                            ));
                         cnt += 1;
                     }
                     if cnt >= 3 {
@@ -615,7 +636,11 @@ pub async fn dlfile_new(
             };
         }
     }
-    logging::info_log(format!("Downloaded hash: {}", &hash));
+    LogCtx::worker(*workerid)
+        .job(*jobid)
+        .url(source_url)
+        .hash(&hash)
+        .info("Downloaded");
 
     if let Some(ref mut file_storage) = file_storage.clone() {
         file_storage.status = FilesStatus::Processing(0.0);
@@ -623,6 +648,7 @@ pub async fn dlfile_new(
     }
 
     if let Some(bytes_data) = bytes {
+        metrics::download_record(scraper, bytes_data.len() as u64);
         let file_ext = FileFormat::from_bytes(&bytes_data).extension().to_string();
 
         // 1. Prepare owned data to move into the thread pool
@@ -631,6 +657,7 @@ pub async fn dlfile_new(
         let ext_clone = file_ext.clone();
         let mut file_clone = file.clone(); // Assuming FileObjectMain implements Clone
         let source_url_clone = Some(source_url.clone());
+        let scraper_name = scraper.map(|scraper| scraper.name.clone());
         let ctx_clone_spawn = ctx.clone();
         let ctx_clone = ctx.clone();
 
@@ -644,6 +671,7 @@ pub async fn dlfile_new(
                 &ext_clone,
                 &mut file_clone,
                 source_url_clone.as_ref(),
+                scraper_name.as_ref(),
                 ctx_clone,
             );
 
@@ -672,6 +700,7 @@ pub fn process_bytes(
     file_ext: &String,
     file: &mut sharedtypes::FileObjectMain,
     source_url: Option<&String>,
+    scraper: Option<&String>,
     ctx: Arc<LocalStorage>,
 ) -> Option<u64> {
     /* {
@@ -691,7 +720,7 @@ pub fn process_bytes(
     {
         if let Some(file_id) = ctx
             .db
-            .enclave_determine_processing(file, bytes, hash, source_url)
+            .enclave_determine_processing(file, bytes, hash, source_url, scraper)
         {
             out = Some(file_id);
         }
@@ -740,30 +769,24 @@ pub fn hash_file(
 pub fn write_to_disk(
     location: std::path::PathBuf,
     //file: &sharedtypes::FileObjectMain,
-    bytes: &Bytes,
+    bytes: &[u8],
     sha512hash: &String,
+    file_ext: &str,
+    shard_depth: u64,
 ) {
     let mut local_location = location.clone();
 
     // Adds directory name back into the full path
-    if local_location.is_dir() && sha512hash.len() > 6 {
-        let sha512hash_str = sha512hash.as_str();
-        local_location = local_location.join(&sha512hash_str[0..2]);
-        local_location = local_location.join(&sha512hash_str[2..4]);
-        local_location = local_location.join(&sha512hash_str[4..6]);
-        match std::fs::create_dir_all(&local_location) {
-            Ok(_) => {}
-            Err(err) => {
-                logging::error_log(format!("{} {}", sha512hash_str, err));
-            }
-        }
-
-        local_location = local_location.join("FILENAMEFILLER");
+    if local_location.is_dir() && sha512hash.len() as u64 > shard_depth * 2 {
+        local_location = std::path::PathBuf::from(helpers::getfinpath_depth(
+            &location.to_string_lossy(),
+            sha512hash,
+            shard_depth,
+            true,
+        ))
+        .join("FILENAMEFILLER");
     }
 
-    // Gives file extension
-    let file_ext = FileFormat::from_bytes(bytes).extension().to_string();
-
     local_location.set_file_name(sha512hash);
     local_location.set_extension(file_ext);
 
@@ -808,6 +831,27 @@ pub fn create_ratelimiter(
     Arc::new(ratelimiter_create(worker_id, job_id, input.0, input.1))
 }
 
+///
+/// Gets the ratelimiter for a site so everything talking to it waits on the same one.
+/// Gets remade if the site's ratelimit changed
+///
+pub fn ratelimiter_shared(site: &str, ratelimit: (u64, Duration)) -> Arc<Ratelimiter> {
+    if let Some((made_with, ratelimiter)) = RATELIMITERS.read().get(site)
+        && *made_with == ratelimit
+    {
+        return ratelimiter.clone();
+    }
+    let mut ratelimiters = RATELIMITERS.write();
+    match ratelimiters.get(site) {
+        Some((made_with, ratelimiter)) if *made_with == ratelimit => ratelimiter.clone(),
+        _ => {
+            let ratelimiter = create_ratelimiter(ratelimit, &0, &0);
+            ratelimiters.insert(site.to_string(), (ratelimit, ratelimiter.clone()));
+            ratelimiter
+        }
+    }
+}
+
 /// Parses weather we should skip downloading the file
 /// Returns a Some(u64) if the fileid exists
 pub fn parse_skipif(
@@ -860,10 +904,13 @@ pub fn parse_skipif(
             if let Some(nsid) = ctx.db.namespace_get(&tag.namespace.name)
                 && ctx.db.tag_get_name(tag.tag.to_string(), nsid).is_some()
             {
-                info_log(format!(
-                    "Worker: {worker_id} JobId: {job_id} -- Skipping file: {} Due to skip tag {} already existing in Tags Table.",
-                    file_url_source, tag.tag
-                ));
+                LogCtx::worker(*worker_id)
+                    .job(*job_id)
+                    .url(file_url_source)
+                    .info(format!(
+                        "Skipping file due to skip tag {} already existing in Tags Table.",
+                        tag.tag
+                    ));
                 if let Some(tid) = ctx.db.tag_get_name(tag.tag.to_string(), nsid) {
                     return ctx.db.relationship_get_one_fileid(&tid);
                 }
@@ -949,10 +996,10 @@ pub async fn main_file_loop(
                                     file_storage.status = FilesStatus::Done;
                                     ctx.update_file(worker_id, job_id, &file_storage);
                                 }
-                                info_log(format!(
-                                    "Worker: {worker_id} JobId: {job_id} -- Skipping file: {} Due to already existing in Tags Table.",
-                                    &source_url
-                                ));
+                                LogCtx::worker(*worker_id)
+                                    .job(*job_id)
+                                    .url(&source_url)
+                                    .info("Skipping file due to already existing in Tags Table.");
                                 Some(f_id)
                             }
                             None => {
@@ -983,7 +1030,15 @@ pub async fn main_file_loop(
                 let file_ext = FileFormat::from_bytes(bytes).extension().to_string();
                 let sha512 = hash_bytes(bytes, &sharedtypes::HashesSupported::Sha512("".into()));
 
-                process_bytes(bytes, &sha512.0, &file_ext, file, None, ctx.clone());
+                process_bytes(
+                    bytes,
+                    &sha512.0,
+                    &file_ext,
+                    file,
+                    None,
+                    Some(&scraper.name),
+                    ctx.clone(),
+                );
 
                 fileid = ctx.db.file_get_hash(&sha512.0);
                 ctx.db.add_tags_to_fileid(fileid, &file.tag_list);
@@ -1018,10 +1073,10 @@ async fn download_add_to_db(
     // Early exit for if the file is a dead url
     {
         if ctx.db.check_dead_url(source) {
-            logging::info_log(format!(
-                "Worker: {worker_id} JobID: {job_id} -- Skipping {} because it's a dead link.",
-                source
-            ));
+            LogCtx::worker(*worker_id)
+                .job(*job_id)
+                .url(source)
+                .info("Skipping because it's a dead link.");
             return None;
         }
     }
@@ -7,9 +7,10 @@ use thread_control::{Control, Flag, make_pair};
 use tokio::task::JoinSet;
 
 use crate::download::parse_skipif;
+use crate::jobs::CancelToken;
 use crate::{RwLock, logging};
 use crate::{
-    database::database::Main, download, globalload::GlobalLoad, jobs::Jobs, logging::info_log,
+    database::database::Main, download, globalload::GlobalLoad, jobs::Jobs, logging::LogCtx,
     ui::ui::*,
 };
 
@@ -91,12 +92,8 @@ impl DownloadManager {
                 id,
                 thread_control,
                 scraper: scraper.clone(),
-                ratelimiter: Arc::new(download::ratelimiter_create(
-                    &id,
-                    &id,
-                    ratelimit.0,
-                    ratelimit.1,
-                )),
+                // Shared with wasm plugins and html fetches for the same site
+                ratelimiter: download::ratelimiter_shared(&scraper.name, ratelimit),
                 ctx: self.ctx.clone(),
             };
 
@@ -146,6 +143,25 @@ impl DownloadManager {
 }
 
 impl LocalStorage {
+    ///
+    /// Checks if a job should keep going. Waits while downloads or the scraper are paused.
+    /// Returns false if the job or its worker got cancelled.
+    ///
+    pub async fn checkpoint(&self, scraper_name: &String, job_token: &CancelToken) -> bool {
+        loop {
+            if job_token.is_cancelled()
+                || self.jobs.is_shutting_down()
+                || self.jobs.scraper_token_get(scraper_name).is_cancelled()
+            {
+                return false;
+            }
+            if !self.db.downloads_pause_get() && !self.jobs.scraper_is_paused(scraper_name) {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
     pub fn has_active_downloads(&self, worker_id: &u64) -> bool {
         let files_guard = self.files.read();
         if let Some(job_map) = files_guard.get(&worker_id) {
@@ -224,7 +240,7 @@ impl LocalStorage {
 
 impl ScraperInternal {
     fn finish_scraper(&self) {
-        info_log(format!("Shutting Down Worker from Worker: {}", self.id));
+        LogCtx::worker(self.id).info("Shutting Down Worker");
 
         if self.ctx.files.read().is_empty() {
             let _ = self.ctx.uisender.send(UIEvent::ScraperStatusChanged {
@@ -270,15 +286,16 @@ impl ScraperInternal {
     async fn run_job(&self, job: sharedtypes::DbJobsObj) {
         let mut should_remove_job = true;
 
-        logging::info_log(format!(
-            "Worker: {} JobId: {} -- Starting Job {:?}",
-            self.id, & job.id.unwrap_or(0),
-            &job
-        ));
+        let log_ctx = LogCtx::worker(self.id)
+            .job(job.id.unwrap_or(0))
+            .scraper(&self.scraper.name);
+        log_ctx.info(format!("Starting Job {:?}", &job));
 
         {
             self.ctx.jobs.job_set_is_running(&self.scraper, &job);
         }
+        let cancel_token = self.ctx.jobs.job_token_get(&job.id.unwrap_or(0));
+        let mut was_cancelled = false;
 
         self.ctx
             .files
@@ -342,12 +359,7 @@ impl ScraperInternal {
                         }
                     }
                     Err(err) => {
-                        logging::error_log(format!(
-                            "Worker: {} JobId: {} -- Parameter parsing error: {:?}",
-                            self.id,
-                            job.id.unwrap_or(0),
-                            err
-                        ));
+                        log_ctx.error(format!("Parameter parsing error: {:?}", err));
                         self.ctx.jobs.jobs_remove_job(&scraper, &job);
                         should_remove_job = false;
                     }
@@ -382,6 +394,12 @@ impl ScraperInternal {
             let resp;
             let scraper_return;
 
+            if !self.ctx.checkpoint(&scraper.name, &cancel_token).await {
+                log_ctx.info("Job was cancelled.");
+                was_cancelled = true;
+                break 'urlloop;
+            }
+
             match &scraperparam {
                 sharedtypes::ScraperParam::Url(url_string) => {
                     if !scraper.should_handle_text_scraping {
@@ -402,10 +420,7 @@ impl ScraperInternal {
                                 &scraper,
                             ),
                             Err(err) => {
-                                logging::error_log(format!(
-                                    "Worker: {} -- Text download failed: {:?}",
-                                    self.id, err
-                                ));
+                                log_ctx.error(format!("Text download failed: {:?}", err));
                                 break 'urlloop;
                             }
                         };
@@ -436,10 +451,7 @@ impl ScraperInternal {
                             &scraper,
                         ),
                         Err(err) => {
-                            logging::error_log(format!(
-                                "Worker: {} -- POST download failed: {:?}",
-                                self.id, err
-                            ));
+                            log_ctx.error(format!("POST download failed: {:?}", err));
                             break 'urlloop;
                         }
                     };
@@ -519,7 +531,16 @@ impl ScraperInternal {
                             let ratelimiter = self.ratelimiter.clone();
                             let worker_id = self.id;
                             let job_id = job.id.unwrap_or(0);
+                            let cancel_token = cancel_token.clone();
                             set.spawn(async move {
+                                if !ctx.checkpoint(&scraper.name, &cancel_token).await {
+                                    if let Some(mut file_storage) = file_storage {
+                                        file_storage.status =
+                                            FilesStatus::Stopped("Cancelled".to_string());
+                                        ctx.update_file(&worker_id, &job_id, &file_storage);
+                                    }
+                                    return;
+                                }
                                 download::main_file_loop(
                                     &mut file.into(),
                                     client_file,
@@ -536,20 +557,11 @@ impl ScraperInternal {
                         set.join_all().await;
                     }
                     sharedtypes::ScraperReturn::Nothing => {
-                        logging::info_log(format!(
-                            "Worker: {} JobId: {} -- Exiting loop due to Nothing.",
-                            self.id,
-                            job.id.unwrap_or(0)
-                        ));
+                        log_ctx.info("Exiting loop due to Nothing.");
                         break 'urlloop;
                     }
                     sharedtypes::ScraperReturn::Stop(stop_string) => {
-                        logging::error_log(format!(
-                            "Worker: {} JobId: {} -- Stopping job: {:?}",
-                            self.id,
-                            job.id.unwrap_or(0),
-                            stop_string
-                        ));
+                        log_ctx.error(format!("Stopping job: {:?}", stop_string));
                         break 'urlloop;
                     }
                     sharedtypes::ScraperReturn::Fatal(fatal_string) => {
@@ -561,22 +573,12 @@ impl ScraperInternal {
                         );
                     }
                     sharedtypes::ScraperReturn::Timeout(timeout_time) => {
-                        logging::info_log(format!(
-                            "Worker: {} JobId: {} -- Timeout for: {:?} Seconds",
-                            self.id,
-                            job.id.unwrap_or(0),
-                            &timeout_time
-                        ));
+                        log_ctx.info(format!("Timeout for: {:?} Seconds", &timeout_time));
                         tokio::time::sleep(Duration::from_secs(timeout_time)).await;
                         continue;
                     }
                     sharedtypes::ScraperReturn::RetryLater(try_later_time) => {
-                        logging::info_log(format!(
-                            "Worker: {} JobId: {} -- Retrying later for: {:?}",
-                            self.id,
-                            job.id.unwrap_or(0),
-                            &try_later_time
-                        ));
+                        log_ctx.info(format!("Retrying later for: {:?}", &try_later_time));
                         let mut data = job.clone();
                         data.time = crate::time_func::time_secs();
                         data.reptime = try_later_time.as_secs();
@@ -589,6 +591,21 @@ impl ScraperInternal {
             }
         }
 
+        // Cancelled jobs get paused instead of removed so they can be resumed later.
+        // Jobs stopped by a shutdown stay queued for the next start.
+        if was_cancelled || cancel_token.is_cancelled() {
+            if !self.ctx.jobs.is_shutting_down()
+                && let Some(job_id) = job.id
+            {
+                self.ctx.jobs.jobs_pause(&job_id);
+            }
+            let mut data = job.clone();
+            data.isrunning = false;
+            self.ctx.jobs.jobs_update(data, &self.scraper);
+            should_remove_job = false;
+        }
+        self.ctx.jobs.job_token_remove(&job.id.unwrap_or(0));
+
         if should_remove_job {
             self.ctx
                 .jobs
@@ -617,6 +634,15 @@ impl ScraperInternal {
 
     pub async fn start_scraper(self: Arc<Self>) {
         'mainloop: loop {
+            if self
+                .ctx
+                .jobs
+                .scraper_token_get(&self.scraper.name)
+                .is_cancelled()
+                || self.ctx.jobs.is_shutting_down()
+            {
+                break 'mainloop;
+            }
             let jobstorage = self.ctx.jobs.jobs_get_priority_order(&self.scraper);
 
             if jobstorage.is_empty() {
@@ -2,11 +2,10 @@ use crate::Main;
 use crate::download::{hash_file, process_archive_files};
 use crate::enclave;
 use crate::globalload::GlobalLoad;
+use crate::sidecar;
 use crate::{Arc, RwLock, logging};
 use sharedtypes;
 use std::fs;
-use std::fs::File;
-use std::io::{self, BufRead};
 use std::io::{Cursor, Error};
 use std::path::{Path, PathBuf};
 
@@ -50,9 +49,25 @@ pub fn find_sidecar(location: &Path) -> Vec<PathBuf> {
             out.push(test_path.to_path_buf());
         }
     }
+    // yt-dlp swaps the extension out instead of adding onto it
+    let info_json = location.with_extension("info.json");
+    if info_json != location && info_json.exists() {
+        out.push(info_json);
+    }
     out
 }
 
+///
+/// Tags from a sidecar picked by its extension. None if it's not a sidecar
+///
+pub fn parse_sidecar_any(sidecar_location: &Path) -> Option<Vec<sharedtypes::FileTagAction>> {
+    match sidecar_location.extension()?.to_str()? {
+        "txt" => Some(parse_sidecar_txt(sidecar_location)),
+        "json" => Some(parse_sidecar_json(sidecar_location)),
+        _ => None,
+    }
+}
+
 ///
 /// Parses a file as it gets input into the system.
 /// If it's an archive file it will extract its internals into the system
@@ -82,12 +97,8 @@ pub fn parse_file(
             let mut tag_list = vec![];
 
             for sidecar in sidecars {
-                if let Ok(filetype) = file_format::FileFormat::from_file(sidecar) {
-                    if filetype.extension() == "txt" {
-                        tag_list.append(&mut parse_sidecar_txt(sidecar));
-                    } else if filetype.extension() == "json" {
-                        tag_list.append(&mut parse_sidecar_json(sidecar));
-                    }
+                if let Some(mut sidecar_tags) = parse_sidecar_any(sidecar) {
+                    tag_list.append(&mut sidecar_tags);
                 }
             }
 
@@ -262,15 +273,7 @@ pub fn parse_sidecar(file_location: &Path, sidecar_location: &Path, database: Ma
         }
 
         Ok((sha512hash, bytes)) => {
-            let tag_list;
-            if let Ok(filetype) = file_format::FileFormat::from_file(sidecar_location) {
-                if filetype.extension() == "txt" {
-                    tag_list = parse_sidecar_txt(sidecar_location);
-                } else if filetype.extension() == "json" {
-                    tag_list = parse_sidecar_json(sidecar_location);
-                } else {
-                    return;
-                }
+            if let Some(tag_list) = parse_sidecar_any(sidecar_location) {
                 database.enclave_run_process(
                     &mut sharedtypes::FileObjectMain {
                         hash: sharedtypes::HashesSupported::Sha512(sha512hash.clone()),
@@ -288,51 +291,39 @@ pub fn parse_sidecar(file_location: &Path, sidecar_location: &Path, database: Ma
     }
 }
 
+///
+/// Tags from a TXT sidecar. See sidecar::txt_tags
+///
 pub fn parse_sidecar_txt(sidecar_location: &Path) -> Vec<sharedtypes::FileTagAction> {
-    let mut tags = Vec::new();
-
-    if let Ok(file) = read_lines(sidecar_location) {
-        for line in file.map(Result::ok) {
-            match line {
-                None => {}
-                Some(line) => {
-                    tags.push(sharedtypes::TagObject {
-                        namespace: sharedtypes::GenericNamespaceObj {
-                            name: "SYSTEM_Sidecar_TXT".into(),
-                            description: Some("Information from a sidecar file. TXT Import".into()),
-                        },
-                        tag: line,
-                        tag_type: sharedtypes::TagType::Normal,
-                        relates_to: None,
-                    });
-                }
-            }
+    match fs::read_to_string(sidecar_location) {
+        Ok(contents) => sidecar::txt_tags(&contents),
+        Err(err) => {
+            logging::error_log(format!(
+                "Cannot read sidecar {}: {}",
+                sidecar_location.display(),
+                err
+            ));
+            Vec::new()
         }
     }
-
-    let action = sharedtypes::FileTagAction {
-        operation: sharedtypes::TagOperation::Add,
-        tags,
-    };
-
-    vec![action]
 }
 
-/// Holder function need to impliment
+///
+/// Tags from a JSON sidecar. See sidecar::json_tags for the formats it reads
+///
 pub fn parse_sidecar_json(sidecar_location: &Path) -> Vec<sharedtypes::FileTagAction> {
-    let out = Vec::new();
-    dbg!(&sidecar_location);
-
-    out
-}
-
-/// Stolen from: <https://doc.rust-lang.org/rust-by-example/std_misc/file/read_lines.html>
-/// The output is wrapped in a Result to allow matching on errors.
-/// Returns an Iterator to the Reader of the lines of the file.
-fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
-where
-    P: AsRef<Path>,
-{
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
+    let tags = fs::read_to_string(sidecar_location)
+        .map_err(|err| err.to_string())
+        .and_then(|contents| sidecar::json_tags(&contents));
+    match tags {
+        Ok(tags) => tags,
+        Err(err) => {
+            logging::error_log(format!(
+                "Cannot read sidecar {}: {}",
+                sidecar_location.display(),
+                err
+            ));
+            Vec::new()
+        }
+    }
 }
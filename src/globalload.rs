@@ -1,20 +1,93 @@
 use crate::Main;
+use crate::database::pluginregistry::PluginRegistryEntry;
+use crate::declarative::{self, SiteDefinition};
+use crate::hotreload::{self, FileStamp};
 use crate::logging::info_log;
+use crate::pluginabi::{self, AbiError};
+use crate::pluginhost::{self, PluginWorker};
+use crate::pluginverify::{self, SignatureStatus};
+use crate::wasmhost::WasmPlugin;
 use crate::{jobs::Jobs, logging, server};
 use libloading::Library;
 use sharedtypes::{self, GlobalPluginScraper};
+use std::collections::BTreeSet;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use std::{collections::HashMap, path::Path, thread};
 use std::{path::PathBuf, thread::JoinHandle};
 
 use parking_lot::RwLock;
 ///
-/// Runs the on_start callback
+/// Where a plugin's on_start gets ran
 ///
-fn c_run_onstart(path: &Path, global: &sharedtypes::GlobalPluginScraper) {
-    let liba;
-    unsafe {
-        liba = Library::new(path).unwrap();
+enum OnStartRunner {
+    InProcess(Arc<Library>),
+    Worker(Arc<PluginWorker>),
+    Wasm(Arc<WasmPlugin>),
+}
+
+///
+/// Private copy of a plugin file to verify and load from so the file can't be swapped
+/// in between the two
+///
+fn shadow_for_load(path: &Path) -> Option<PathBuf> {
+    match hotreload::shadow_copy(path) {
+        Ok(shadow) => Some(shadow),
+        Err(err) => {
+            logging::error_log(format!(
+                "Cannot copy {} for loading: {}",
+                path.display(),
+                err
+            ));
+            None
+        }
+    }
+}
+
+///
+/// Runs the on_start callback. Isolated plugins run it inside of their worker
+///
+fn c_run_onstart(path: &Path, global: &sharedtypes::GlobalPluginScraper, runner: OnStartRunner) {
+    let liba = match runner {
+        OnStartRunner::Worker(worker) => {
+            // on_start can run for the lifetime of the plugin so it never times out
+            if let Err(err) = worker.call::<_, ()>("on_start", global, None)
+                && !matches!(err, AbiError::NotExported)
+            {
+                logging::error_log(format!(
+                    "Cannot run on_start for path: {} {}",
+                    path.to_string_lossy(),
+                    err
+                ));
+            }
+            return;
+        }
+        OnStartRunner::Wasm(wasm) => {
+            // Wasm plugins don't have to export on_start and have no legacy symbol
+            match wasm.call::<_, ()>("on_start", global, None) {
+                Ok(()) | Err(AbiError::NotExported) => {}
+                Err(err) => logging::error_log(format!(
+                    "Cannot run on_start for path: {} {}",
+                    path.to_string_lossy(),
+                    err
+                )),
+            }
+            return;
+        }
+        OnStartRunner::InProcess(liba) => liba,
+    };
+    match pluginabi::abi_call::<_, ()>(&liba, "on_start", global) {
+        Err(AbiError::NotStable) => {}
+        Ok(()) | Err(AbiError::NotExported) => return,
+        Err(err) => {
+            logging::error_log(format!(
+                "Cannot run on_start for path: {} {}",
+                path.to_string_lossy(),
+                err
+            ));
+            return;
+        }
     }
     unsafe {
         let plugindatafunc: libloading::Symbol<
@@ -29,10 +102,6 @@ fn c_run_onstart(path: &Path, global: &sharedtypes::GlobalPluginScraper) {
                 return;
             }
         };
-        liba.get::<libloading::Symbol<unsafe extern "C" fn(&sharedtypes::GlobalPluginScraper)>>(
-            b"on_start",
-        )
-        .unwrap();
         plugindatafunc(global);
     };
 }
@@ -52,6 +121,17 @@ pub fn callback_on_import(
 /// Gets called onstartup of the software
 ///
 pub fn on_start(libloading: &libloading::Library, site_struct: &sharedtypes::GlobalPluginScraper) {
+    match pluginabi::abi_call::<_, ()>(libloading, "on_start", site_struct) {
+        Err(AbiError::NotStable) => {}
+        Ok(()) | Err(AbiError::NotExported) => return,
+        Err(err) => {
+            logging::error_log_silent(format!(
+                "Cannot run on_start for name: {} {}",
+                site_struct.name, err
+            ));
+            return;
+        }
+    }
     let temp: libloading::Symbol<unsafe extern "C" fn(&sharedtypes::GlobalPluginScraper)> =
         match unsafe { libloading.get(b"on_start\0") } {
             Err(_) => {
@@ -83,7 +163,7 @@ pub struct GlobalLoad {
     >,
     sites: Arc<RwLock<HashMap<sharedtypes::GlobalPluginScraper, Vec<String>>>>,
     library_path: Arc<RwLock<HashMap<sharedtypes::GlobalPluginScraper, PathBuf>>>,
-    library_lib: HashMap<sharedtypes::GlobalPluginScraper, Arc<libloading::Library>>,
+    library_lib: Arc<RwLock<HashMap<sharedtypes::GlobalPluginScraper, Arc<libloading::Library>>>>,
     default_load: Arc<RwLock<LoadableType>>,
     thread: Arc<RwLock<HashMap<sharedtypes::GlobalPluginScraper, JoinHandle<()>>>>,
     regex_storage: Arc<
@@ -99,8 +179,22 @@ pub struct GlobalLoad {
         >,
     >,
     pub jobmanager: Arc<Jobs>,
+    // Out of process workers by library path. Only filled when isolation is on
+    workers: Arc<RwLock<HashMap<PathBuf, Arc<PluginWorker>>>>,
+    // Shared so clones like the hot reloader see changes
+    isolate: Arc<AtomicBool>,
+    // Libraries without the stable ABI only load when this is set
+    legacy: Arc<AtomicBool>,
+    wasm: Arc<RwLock<HashMap<sharedtypes::GlobalPluginScraper, Arc<WasmPlugin>>>>,
+    // Scrapers loaded from a definition file instead of a library
+    definitions: Arc<RwLock<HashMap<sharedtypes::GlobalPluginScraper, Arc<SiteDefinition>>>>,
+    // What each library file looked like when it was loaded. Used to spot changes
+    stamps: Arc<RwLock<HashMap<PathBuf, FileStamp>>>,
 }
 
+/// Longest a reload waits for calls into the old library to finish
+const RELOAD_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
 ///
 /// Determines what we should return from our get_loadable_paths function
 ///
@@ -109,9 +203,19 @@ enum LoadableType {
     Debug,
 }
 
+///
+/// Libraries are tracked by file name so moving the folder keeps their settings
+///
+fn registry_file(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
 impl GlobalLoad {
     pub fn new(db: Main, jobs: Arc<Jobs>) -> Self {
         logging::log("Starting IPC Server.".to_string());
+        hotreload::shadow_sweep();
 
         GlobalLoad {
             db,
@@ -120,23 +224,126 @@ impl GlobalLoad {
             callback_storage: Arc::new(RwLock::new(HashMap::new())),
             sites: Arc::new(RwLock::new(HashMap::new())),
             library_path: Arc::new(RwLock::new(HashMap::new())),
-            library_lib: HashMap::new(),
+            library_lib: Arc::new(RwLock::new(HashMap::new())),
             default_load: Arc::new(RwLock::new(LoadableType::Release)),
             thread: Arc::new(RwLock::new(HashMap::new())),
             regex_storage: Arc::new(RwLock::new(HashMap::new())),
             jobmanager: jobs,
+            workers: Arc::new(RwLock::new(HashMap::new())),
+            isolate: Arc::new(AtomicBool::new(false)),
+            legacy: Arc::new(AtomicBool::new(false)),
+            wasm: Arc::new(RwLock::new(HashMap::new())),
+            definitions: Arc::new(RwLock::new(HashMap::new())),
+            stamps: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
     ///
-    /// This function gets called after a DB upgrade
+    /// Runs libraries in child processes. Libraries without the stable ABI can't be
+    /// isolated and get refused. Needs to be set before anything gets loaded.
     ///
-    fn db_upgrade_call(
+    pub fn isolation_set(&mut self, isolate: bool) {
+        self.isolate.store(isolate, Ordering::Relaxed);
+    }
+
+    fn isolated(&self) -> bool {
+        self.isolate.load(Ordering::Relaxed)
+    }
+
+    ///
+    /// Loads libraries that only have the old raw rust exports. Those have to be
+    /// built with the same toolchain and sharedtypes as this build or they can crash.
+    ///
+    pub fn legacy_set(&mut self, legacy: bool) {
+        self.legacy.store(legacy, Ordering::Relaxed);
+    }
+
+    fn legacy_allowed(&self) -> bool {
+        self.legacy.load(Ordering::Relaxed)
+    }
+
+    ///
+    /// Returns the worker process for a library if its running out of process
+    ///
+    fn worker_get(&self, path: &Path) -> Option<Arc<PluginWorker>> {
+        self.workers.read().get(path).cloned()
+    }
+
+    ///
+    /// Returns the wasm runtime for a plugin if it was loaded from a .wasm file
+    ///
+    fn wasm_get(&self, scraper: &sharedtypes::GlobalPluginScraper) -> Option<Arc<WasmPlugin>> {
+        self.wasm.read().get(scraper).cloned()
+    }
+
+    ///
+    /// Returns the definition for a scraper if it came from a config file
+    ///
+    fn definition_get(
         &self,
-        libloading: &Arc<libloading::Library>,
-        db_version: &u64,
-        site_struct: &sharedtypes::GlobalPluginScraper,
-    ) {
+        scraper: &sharedtypes::GlobalPluginScraper,
+    ) -> Option<Arc<SiteDefinition>> {
+        self.definitions.read().get(scraper).cloned()
+    }
+
+    ///
+    /// Calls into a plugin over the stable ABI. Wasm plugins go to their runtime and
+    /// isolated plugins to their worker process. NotStable means only the legacy
+    /// symbol can be used. That only happens for libraries without the stable ABI,
+    /// which only get loaded with legacy plugins allowed.
+    ///
+    fn plugin_call<I, O>(
+        &self,
+        scraper: &sharedtypes::GlobalPluginScraper,
+        func: &str,
+        input: &I,
+    ) -> Result<O, AbiError>
+    where
+        I: bitcode::Encode + ?Sized,
+        O: bitcode::DecodeOwned,
+    {
+        if let Some(wasm) = self.wasm_get(scraper) {
+            return wasm.call(func, input, Some(pluginhost::CALL_TIMEOUT));
+        }
+        let worker = self
+            .library_path
+            .read()
+            .get(scraper)
+            .and_then(|path| self.worker_get(path));
+        if let Some(worker) = worker {
+            return worker.call(func, input, Some(pluginhost::CALL_TIMEOUT));
+        }
+        match self.library_get(scraper) {
+            Some(lib) => match pluginabi::abi_call(&lib, func, input) {
+                Err(AbiError::NotStable) if !self.legacy_allowed() => Err(AbiError::NotExported),
+                out => out,
+            },
+            None => Err(AbiError::NotExported),
+        }
+    }
+
+    ///
+    /// This function gets called after a DB upgrade
+    ///
+    fn db_upgrade_call(&self, db_version: &u64, site_struct: &sharedtypes::GlobalPluginScraper) {
+        match self.plugin_call::<_, ()>(
+            site_struct,
+            "db_upgrade_call",
+            &(*db_version, site_struct.clone()),
+        ) {
+            Err(AbiError::NotStable) => {}
+            Ok(()) | Err(AbiError::NotExported) => return,
+            Err(err) => {
+                logging::error_log(format!(
+                    "Could not run scraper upgrade for db version {} because of {}.",
+                    db_version, err
+                ));
+                return;
+            }
+        }
+        let Some(libloading) = self.library_get(site_struct) else {
+            return;
+        };
         let temp: libloading::Symbol<
             unsafe extern "C" fn(&u64, &sharedtypes::GlobalPluginScraper),
         > = match unsafe { libloading.get(b"db_upgrade_call\0") } {
@@ -161,6 +368,34 @@ impl GlobalLoad {
             .get_scrapers_from_callback(&sharedtypes::GlobalCallbacks::Download)
             .iter()
         {
+            // If the api isnt set then just return and dont call anything
+            let Some(api_info) = db.api_info.read().clone() else {
+                return;
+            };
+            match self.plugin_call::<_, Vec<sharedtypes::DBPluginOutputEnum>>(
+                scraper,
+                "on_download",
+                &(
+                    cursorpass.to_vec(),
+                    hash.clone(),
+                    ext.clone(),
+                    api_info.clone(),
+                ),
+            ) {
+                Err(AbiError::NotStable) => {}
+                Err(AbiError::NotExported) => continue,
+                Ok(output) => {
+                    self.parse_plugin_output(output, db.clone(), scraper);
+                    continue;
+                }
+                Err(err) => {
+                    logging::error_log(format!(
+                        "on_download failed for plugin: {} {}",
+                        scraper.name, err
+                    ));
+                    continue;
+                }
+            }
             if let Some(lib) = self.library_get(scraper) {
                 let output;
                 unsafe {
@@ -183,16 +418,7 @@ impl GlobalLoad {
                             continue;
                         }
                     };
-                    // If the api isnt set then just return and dont call anything
-                    output = {
-                        // Limit the scope of the read guard
-                        let api_info_guard = db.api_info.read();
-                        if let Some(ref api_info) = *api_info_guard {
-                            plugindatafunc(cursorpass, hash, ext, api_info)
-                        } else {
-                            return;
-                        } // `api_info_guard` is dropped here automatically!
-                    };
+                    output = plugindatafunc(cursorpass, hash, ext, &api_info);
                 }
 
                 let jobmanager;
@@ -318,6 +544,22 @@ impl GlobalLoad {
         scraperdata: &sharedtypes::ScraperDataReturn,
         scraper: &GlobalPluginScraper,
     ) -> Vec<sharedtypes::ScraperReturn> {
+        match self.plugin_call(
+            scraper,
+            "text_scraping",
+            &(
+                url_output.to_string(),
+                actual_params.to_vec(),
+                scraperdata.clone(),
+            ),
+        ) {
+            Err(AbiError::NotStable) => {}
+            Ok(out) => return out,
+            Err(err) => {
+                logging::error_log(format!("text_scraping failed for {} {}", scraper.name, err));
+                return vec![sharedtypes::ScraperReturn::Nothing];
+            }
+        }
         if let Some(lib) = self.library_get(scraper) {
             let temp: libloading::Symbol<
                 unsafe extern "C" fn(
@@ -355,6 +597,17 @@ impl GlobalLoad {
         scraperdata: &sharedtypes::ScraperDataReturn,
         scraper: &sharedtypes::GlobalPluginScraper,
     ) -> Result<Vec<sharedtypes::ScraperDataReturn>, libloading::Error> {
+        if let Some(definition) = self.definition_get(scraper) {
+            return Ok(definition.url_dump(params, scraperdata));
+        }
+        match self.plugin_call(scraper, "url_dump", &(params.to_vec(), scraperdata.clone())) {
+            Err(AbiError::NotStable) => {}
+            Ok(out) => return Ok(out),
+            Err(err) => {
+                logging::error_log(format!("url_dump failed for {} {}", scraper.name, err));
+                return Err(libloading::Error::FreeLibraryUnknown);
+            }
+        }
         if let Some(lib) = self.library_get(scraper) {
             let temp: libloading::Symbol<
                 unsafe extern "C" fn(
@@ -376,11 +629,20 @@ impl GlobalLoad {
         file: sharedtypes::FileObjectMain,
         scraper: &sharedtypes::GlobalPluginScraper,
     ) -> Option<Vec<u8>> {
+        let file: sharedtypes::FileObject = file.into();
+        match self.plugin_call(scraper, "download_from", &file) {
+            Err(AbiError::NotStable) => {}
+            Ok(out) => return out,
+            Err(err) => {
+                logging::error_log(format!("download_from failed for {} {}", scraper.name, err));
+                return None;
+            }
+        }
         if let Some(lib) = self.library_get(scraper) {
             let temp: libloading::Symbol<
                 unsafe extern "C" fn(&sharedtypes::FileObject) -> Option<Vec<u8>>,
             > = unsafe { lib.get(b"download_from\0").unwrap() };
-            return unsafe { temp(&file.into()) };
+            return unsafe { temp(&file) };
         }
         None
     }
@@ -395,6 +657,27 @@ impl GlobalLoad {
         scraperdata: &sharedtypes::ScraperDataReturn,
         scraper: &GlobalPluginScraper,
     ) -> Vec<sharedtypes::ScraperReturn> {
+        if let Some(definition) = self.definition_get(scraper) {
+            return definition.parser(url_output, source_url, scraperdata);
+        }
+        match self.plugin_call(
+            scraper,
+            "parser",
+            &(
+                url_output.to_string(),
+                source_url.to_string(),
+                scraperdata.clone(),
+            ),
+        ) {
+            Err(AbiError::NotStable) => {}
+            Ok(out) => return out,
+            Err(err) => {
+                return vec![sharedtypes::ScraperReturn::Stop(format!(
+                    "Parser call failed: {}",
+                    err
+                ))];
+            }
+        }
         if let Some(scraper_library) = self.library_get(scraper) {
             let temp: libloading::Symbol<
                 unsafe extern "C" fn(
@@ -424,6 +707,25 @@ impl GlobalLoad {
         let scrapers = self.get_scrapers_from_callback(&sharedtypes::GlobalCallbacks::Import);
 
         for scraper in scrapers.iter() {
+            match self.plugin_call::<_, Vec<sharedtypes::DBPluginOutputEnum>>(
+                scraper,
+                "on_import",
+                &(bytes.to_vec(), hash.clone()),
+            ) {
+                Err(AbiError::NotStable) => {}
+                Err(AbiError::NotExported) => continue,
+                Ok(output) => {
+                    self.parse_plugin_output_local(output, scraper);
+                    continue;
+                }
+                Err(err) => {
+                    logging::error_log(format!(
+                        "on_import failed for plugin: {} {}",
+                        scraper.name, err
+                    ));
+                    continue;
+                }
+            }
             if let Some(lib) = self.library_get(scraper) {
                 let output;
                 unsafe {
@@ -527,24 +829,25 @@ impl GlobalLoad {
                 }
             }
 
-            let liba;
-            {
-                match self.library_get_path(&pluginscraper) {
-                    None => {
-                        liba = None;
-                    }
-                    Some(libpath) => {
-                        liba = Some(libpath.clone());
-                    }
-                }
+            // Wasm plugins have no library to load
+            if self.wasm_get(&pluginscraper).is_some() {
+                return self.c_regex_match(
+                    name,
+                    namespace,
+                    regex,
+                    searchtype,
+                    None,
+                    &pluginscraper,
+                );
             }
-            if let Some(liba) = liba {
+
+            if let Some(liba) = self.library_get(&pluginscraper) {
                 return self.c_regex_match(
                     name,
                     namespace,
                     regex,
                     searchtype,
-                    &unsafe { libloading::Library::new(liba).unwrap() },
+                    Some(&liba),
                     &pluginscraper,
                 );
             }
@@ -586,9 +889,32 @@ impl GlobalLoad {
         tag_namespace: &sharedtypes::GenericNamespaceObj,
         regex_match: &str,
         plugin_callback: &Option<sharedtypes::SearchType>,
-        liba: &libloading::Library,
+        liba: Option<&libloading::Library>,
         scraper: &GlobalPluginScraper,
     ) -> Vec<(Vec<sharedtypes::DBPluginOutputEnum>, GlobalPluginScraper)> {
+        match self.plugin_call(
+            scraper,
+            "on_regex_match",
+            &(
+                tag.to_string(),
+                tag_namespace.clone(),
+                regex_match.to_string(),
+                plugin_callback.clone(),
+            ),
+        ) {
+            Err(AbiError::NotStable) => {}
+            Ok(output) => return vec![(output, scraper.clone())],
+            Err(err) => {
+                logging::error_log_silent(format!(
+                    "Could not run on_regex_match for plugin: {} {}",
+                    scraper.name, err
+                ));
+                return vec![];
+            }
+        }
+        let Some(liba) = liba else {
+            return vec![];
+        };
         let output;
         unsafe {
             let plugindatafunc: libloading::Symbol<
@@ -813,8 +1139,8 @@ impl GlobalLoad {
         let mut out = Vec::new();
         if let Some(callbacklist) = self.callback.read().get(callback) {
             for callback_item in callbacklist {
-                if let Some(libp) = self.library_lib.get(callback_item) {
-                    out.push(libp.clone());
+                if let Some(libp) = self.library_get(callback_item) {
+                    out.push(libp);
                 }
             }
         }
@@ -877,7 +1203,18 @@ impl GlobalLoad {
         if let Some(callback_list) = self.callback_storage.read().get(func_name) {
             for (callback, global_plugin) in callback_list {
                 if *vers == callback.vers {
-                    if let Some(plugin_lib) = self.library_lib.get(global_plugin) {
+                    match self.plugin_call(global_plugin, func_name, input_data) {
+                        Err(AbiError::NotStable) => {}
+                        Ok(out) => return out,
+                        Err(err) => {
+                            logging::error_log(format!(
+                                "Callback {} failed for plugin: {} {}",
+                                func_name, global_plugin.name, err
+                            ));
+                            return HashMap::new();
+                        }
+                    }
+                    if let Some(plugin_lib) = self.library_get(global_plugin) {
                         let plugininfo;
                         unsafe {
                             let plugindatafunc: libloading::Symbol<
@@ -937,7 +1274,7 @@ impl GlobalLoad {
     /// Returns a Library if it exists
     ///
     pub fn library_get(&self, global: &sharedtypes::GlobalPluginScraper) -> Option<Arc<Library>> {
-        self.library_lib.get(global).cloned()
+        self.library_lib.read().get(global).cloned()
     }
 
     ///
@@ -955,10 +1292,10 @@ impl GlobalLoad {
     }*/
 
     pub fn run_upgrade_logic(&self, db_version: &u64) {
-        for internal_scraper in self.library_lib.keys() {
-            if let Some(scraper_library) = self.library_lib.get(internal_scraper) {
-                self.db_upgrade_call(scraper_library, &db_version, internal_scraper);
-            }
+        // Isolated plugins only have a path so go off of that instead of library_lib
+        let scrapers: Vec<_> = self.library_path.read().keys().cloned().collect();
+        for internal_scraper in scrapers.iter() {
+            self.db_upgrade_call(db_version, internal_scraper);
         }
     }
 
@@ -1014,35 +1351,60 @@ impl GlobalLoad {
     /// Triggers the on_start for the plugins
     ///
     pub fn pluginscraper_on_start(&mut self) {
+        let mut to_start = Vec::new();
         for (callback, list) in self.callback.read().iter() {
             if let sharedtypes::GlobalCallbacks::Start(thread_type) = callback {
                 for to_run in list {
-                    logging::log(format!("Starting Call Start for: {}", &to_run.name));
-                    let file = self.library_path.read().get(to_run).unwrap().clone();
-                    match thread_type {
-                        sharedtypes::StartupThreadType::Spawn => {
-                            let run = to_run.clone();
-                            let to_run = to_run.clone();
-                            let thread = thread::spawn(move || {
-                                c_run_onstart(&file, &to_run.clone());
-                            });
-                            self.thread.write().insert(run.clone(), thread);
-                        }
-                        sharedtypes::StartupThreadType::SpawnInline => {
-                            let run = to_run.clone();
-                            let to_run = to_run.clone();
-                            let thread = thread::spawn(move || {
-                                c_run_onstart(&file, &to_run);
-                            });
-                            self.thread.write().insert(run.clone(), thread);
-                        }
-                        sharedtypes::StartupThreadType::Inline => {
-                            c_run_onstart(&file, to_run);
-                        }
-                    }
+                    to_start.push((to_run.clone(), thread_type.clone()));
                 }
             }
         }
+        for (to_run, thread_type) in to_start.iter() {
+            self.scraper_on_start(to_run, thread_type);
+        }
+    }
+
+    ///
+    /// Runs the on_start for a single plugin or scraper
+    ///
+    fn scraper_on_start(
+        &self,
+        to_run: &sharedtypes::GlobalPluginScraper,
+        thread_type: &sharedtypes::StartupThreadType,
+    ) {
+        logging::log(format!("Starting Call Start for: {}", &to_run.name));
+        let Some(file) = self.library_get_path(to_run) else {
+            return;
+        };
+        let worker = match self.wasm_get(to_run) {
+            Some(wasm) => OnStartRunner::Wasm(wasm),
+            None => match (self.worker_get(&file), self.library_get(to_run)) {
+                (Some(worker), _) => OnStartRunner::Worker(worker),
+                (None, Some(lib)) => OnStartRunner::InProcess(lib),
+                (None, None) => return,
+            },
+        };
+        match thread_type {
+            sharedtypes::StartupThreadType::Spawn => {
+                let run = to_run.clone();
+                let to_run = to_run.clone();
+                let thread = thread::spawn(move || {
+                    c_run_onstart(&file, &to_run.clone(), worker);
+                });
+                self.thread.write().insert(run.clone(), thread);
+            }
+            sharedtypes::StartupThreadType::SpawnInline => {
+                let run = to_run.clone();
+                let to_run = to_run.clone();
+                let thread = thread::spawn(move || {
+                    c_run_onstart(&file, &to_run, worker);
+                });
+                self.thread.write().insert(run.clone(), thread);
+            }
+            sharedtypes::StartupThreadType::Inline => {
+                c_run_onstart(&file, to_run, worker);
+            }
+        }
     }
 
     ///
@@ -1096,9 +1458,66 @@ impl GlobalLoad {
     /// TODO needs to make easy pulls for scraper and plugin info
     ///
     fn parse_lib(&mut self, path: &Path) {
+        let Some(shadow) = shadow_for_load(path) else {
+            return;
+        };
+        self.parse_lib_at(path, &shadow);
+        // Stays mapped after the file is gone. A worker reopens it when it restarts so
+        // its copy goes away with the worker instead
+        if self.workers.read().get(path).is_none() {
+            let _ = std::fs::remove_file(shadow);
+        }
+    }
+
+    ///
+    /// Loads the library from load_path but registers it as path. load_path is a private
+    /// copy so what got verified is what gets loaded, and hot reloads can keep the old
+    /// library mapped until its calls finish.
+    ///
+    fn parse_lib_at(&mut self, path: &Path, load_path: &Path) {
+        if let Some(stamp) = hotreload::stamp(path) {
+            self.stamps.write().insert(path.to_path_buf(), stamp);
+        }
+        let Some(sha256) = self.registry_admit(path, load_path) else {
+            return;
+        };
+
+        // Isolated plugins never get loaded into the host
+        if self.isolated() {
+            self.parse_lib_isolated(path, load_path, sha256);
+            return;
+        }
+
+        // get_global_info runs in a throwaway worker first so a library whose version went
+        // backwards gets refused before any of it runs inside of the host
+        let probed = match self.info_probe(path, load_path) {
+            Ok((_, items)) => {
+                if !self.registry_record(path, sha256.clone(), &items) {
+                    return;
+                }
+                Some(items)
+            }
+            Err(err) if self.legacy_allowed() => {
+                logging::info_log(format!(
+                    "Cannot check the version of {} before loading it: {}",
+                    path.to_string_lossy(),
+                    err
+                ));
+                None
+            }
+            Err(err) => {
+                logging::error_log(format!(
+                    "Refusing to load plugin at {}: {}",
+                    path.to_string_lossy(),
+                    err
+                ));
+                return;
+            }
+        };
+
         // 1. Load the library first
         let lib = unsafe {
-            match libloading::Library::new(path) {
+            match libloading::Library::new(load_path) {
                 Ok(l) => l,
                 Err(e) => {
                     logging::error_log(format!(
@@ -1111,19 +1530,309 @@ impl GlobalLoad {
             }
         };
 
+        // Version handshake before we call anything inside of the library
+        match pluginabi::abi_check(&lib) {
+            Ok(true) => {}
+            Ok(false) => {
+                if !self.legacy_allowed() {
+                    logging::error_log(format!(
+                        "Refusing to load plugin at {}. It does not export the stable plugin ABI. Rebuild it with plugin_export! or start with --allow-legacy-plugins.",
+                        path.to_string_lossy()
+                    ));
+                    return;
+                }
+                logging::info_log(format!(
+                    "Plugin at {} does not export the stable plugin ABI. It has to be built with the same toolchain and sharedtypes as this build or it can crash.",
+                    path.to_string_lossy()
+                ));
+            }
+            Err(err) => {
+                logging::error_log(format!(
+                    "Refusing to load plugin at {}: {}",
+                    path.to_string_lossy(),
+                    err
+                ));
+                return;
+            }
+        }
+
         // 2. Wrap directly into an Arc (No RwLock)
         let shared_lib = Arc::new(lib);
 
-        // 3. Pass a reference to the library inside the Arc to get_info
-        if let Some(items) = self.get_info(&shared_lib, path) {
-            if items.is_empty() {
+        // 3. Legacy libraries can't run in a worker so they only get checked after
+        let items = match probed {
+            Some(items) => items,
+            None => {
+                let Some(items) = self.get_info(&shared_lib, path) else {
+                    return;
+                };
+                if !self.registry_record(path, sha256, &items) {
+                    return;
+                }
+                items
+            }
+        };
+        for global in self.register_info(path, items) {
+            // 4. Directly insert the cloned Arc reference into your map
+            self.library_lib
+                .write()
+                .insert(global, Arc::clone(&shared_lib));
+        }
+    }
+
+    ///
+    /// Starts a worker process for the library and registers what it reports. The
+    /// worker does the version handshake so libraries without the stable ABI fail
+    /// to start and don't get loaded at all.
+    ///
+    fn parse_lib_isolated(&mut self, path: &Path, load_path: &Path, sha256: String) {
+        let (worker, items) = match self.info_probe(path, load_path) {
+            Ok(probed) => probed,
+            Err(err) => {
                 logging::error_log(format!(
-                    "Was unable to pull any sites from: {}",
-                    path.to_string_lossy()
+                    "Refusing to load plugin at {}. It cannot run isolated: {}",
+                    path.to_string_lossy(),
+                    err
                 ));
                 return;
             }
+        };
+        if !self.registry_record(path, sha256, &items) {
+            return;
+        }
+        worker.owns_path();
+        self.workers.write().insert(path.to_path_buf(), worker);
+        self.register_info(path, items);
+    }
 
+    ///
+    /// Runs get_global_info inside of a worker process for the library at load_path
+    ///
+    fn info_probe(
+        &self,
+        path: &Path,
+        load_path: &Path,
+    ) -> Result<(Arc<PluginWorker>, Vec<sharedtypes::GlobalPluginScraper>), String> {
+        let worker = PluginWorker::spawn(load_path)?;
+        logging::log(format!(
+            "Trying to load library at path: {}",
+            path.to_string_lossy()
+        ));
+        let items = worker
+            .call("get_global_info", &(), Some(pluginhost::CALL_TIMEOUT))
+            .map_err(|err| format!("Could not run global info pull for lib. {}", err))?;
+        Ok((worker, items))
+    }
+
+    ///
+    /// Loads a wasm component. Gets registered the same way as a native library
+    ///
+    fn parse_wasm(&mut self, path: &Path) {
+        if let Some(stamp) = hotreload::stamp(path) {
+            self.stamps.write().insert(path.to_path_buf(), stamp);
+        }
+        let Some(shadow) = shadow_for_load(path) else {
+            return;
+        };
+        // The component is compiled into memory so the copy isn't needed after
+        let wasm = self
+            .registry_admit(path, &shadow)
+            .map(|sha256| (sha256, WasmPlugin::load(path, &shadow)));
+        let _ = std::fs::remove_file(shadow);
+        let Some((sha256, wasm)) = wasm else {
+            return;
+        };
+        let wasm = match wasm {
+            Ok(wasm) => wasm,
+            Err(err) => {
+                logging::error_log(format!(
+                    "Failed to load wasm plugin at {}: {}",
+                    path.to_string_lossy(),
+                    err
+                ));
+                return;
+            }
+        };
+        let items = match wasm.call("get_global_info", &(), Some(pluginhost::CALL_TIMEOUT)) {
+            Ok(items) => items,
+            Err(err) => {
+                logging::error_log(format!(
+                    "Could not run global info pull for wasm plugin. {} {}",
+                    path.to_string_lossy(),
+                    err
+                ));
+                return;
+            }
+        };
+        if !self.registry_record(path, sha256, &items) {
+            return;
+        }
+        for global in self.register_info(path, items) {
+            wasm.info_set(&global);
+            self.wasm.write().insert(global, wasm.clone());
+        }
+    }
+
+    ///
+    /// Loads a scraper from a definition file. Runs through the same registry checks as
+    /// a library so it can be disabled, pinned or need a signature.
+    ///
+    fn parse_definition(&mut self, path: &Path) {
+        if let Some(stamp) = hotreload::stamp(path) {
+            self.stamps.write().insert(path.to_path_buf(), stamp);
+        }
+        let Some(shadow) = shadow_for_load(path) else {
+            return;
+        };
+        let definition = self
+            .registry_admit(path, &shadow)
+            .map(|sha256| (sha256, SiteDefinition::load(&shadow)));
+        let _ = std::fs::remove_file(shadow);
+        let Some((sha256, definition)) = definition else {
+            return;
+        };
+        let definition = match definition {
+            Ok(definition) => Arc::new(definition),
+            Err(err) => {
+                logging::error_log(format!(
+                    "Failed to load scraper definition at {}: {}",
+                    path.to_string_lossy(),
+                    err
+                ));
+                return;
+            }
+        };
+        let items = vec![definition.global_info()];
+        if !self.registry_record(path, sha256, &items) {
+            return;
+        }
+        for global in self.register_info(path, items) {
+            self.definitions.write().insert(global, definition.clone());
+        }
+    }
+
+    ///
+    /// Checks the registry and signature before a library gets loaded.
+    /// Returns the hash of the bytes at load_path when it's allowed to load.
+    ///
+    fn registry_admit(&self, path: &Path, load_path: &Path) -> Option<String> {
+        let data = match std::fs::read(load_path) {
+            Ok(data) => data,
+            Err(err) => {
+                logging::error_log(format!(
+                    "Cannot read library at {}: {}",
+                    path.to_string_lossy(),
+                    err
+                ));
+                return None;
+            }
+        };
+        let sha256 = pluginverify::sha256_hex(&data);
+        if let Some(entry) = self.db.plugin_registry_get(&registry_file(path)) {
+            if !entry.enabled {
+                logging::info_log(format!(
+                    "Skipping disabled library at {}",
+                    path.to_string_lossy()
+                ));
+                return None;
+            }
+            if let Some(pinned) = entry.pinned_sha256
+                && pinned != sha256
+            {
+                logging::error_log(format!(
+                    "Refusing to load library at {}. It is pinned to {} but hashes to {}",
+                    path.to_string_lossy(),
+                    pinned,
+                    sha256
+                ));
+                return None;
+            }
+        }
+
+        match pluginverify::signature_check(path, &data, &self.db.plugin_trusted_keys_get()) {
+            SignatureStatus::Trusted => {}
+            SignatureStatus::Unsigned => {
+                if self.db.plugin_require_signature_get() {
+                    logging::error_log(format!(
+                        "Refusing to load unsigned library at {}",
+                        path.to_string_lossy()
+                    ));
+                    return None;
+                }
+            }
+            SignatureStatus::Invalid(reason) => {
+                logging::error_log(format!(
+                    "Refusing to load library at {}. Bad signature: {}",
+                    path.to_string_lossy(),
+                    reason
+                ));
+                return None;
+            }
+        }
+        Some(sha256)
+    }
+
+    ///
+    /// Records a library in the registry once we know what it is.
+    /// Returns false if its version went backwards.
+    ///
+    fn registry_record(
+        &self,
+        path: &Path,
+        sha256: String,
+        items: &[sharedtypes::GlobalPluginScraper],
+    ) -> bool {
+        let file = registry_file(path);
+        let version = items.iter().map(|item| item.version).max().unwrap_or(0);
+        let existing = self.db.plugin_registry_get(&file);
+        if let Some(ref entry) = existing
+            && version < entry.version
+        {
+            logging::error_log(format!(
+                "Refusing to load library at {}. Its version went backwards from {} to {}. Run plugin forget {} to allow it.",
+                path.to_string_lossy(),
+                entry.version,
+                version,
+                file
+            ));
+            return false;
+        }
+        let names = items.iter().map(|item| item.name.clone()).collect();
+        let (enabled, load_order, pinned_sha256) = match existing {
+            Some(entry) => (entry.enabled, entry.load_order, entry.pinned_sha256),
+            None => (true, 0, None),
+        };
+        self.db.plugin_registry_put(&PluginRegistryEntry {
+            file,
+            names,
+            version,
+            sha256,
+            enabled,
+            load_order,
+            pinned_sha256,
+        });
+        true
+    }
+
+    ///
+    /// Registers the sites, callbacks and regexes a plugin or scraper told us about.
+    /// Returns the entries that were valid.
+    ///
+    fn register_info(
+        &mut self,
+        path: &Path,
+        items: Vec<sharedtypes::GlobalPluginScraper>,
+    ) -> Vec<sharedtypes::GlobalPluginScraper> {
+        let mut out = Vec::new();
+        if items.is_empty() {
+            logging::error_log(format!(
+                "Was unable to pull any sites from: {}",
+                path.to_string_lossy()
+            ));
+            return out;
+        }
+
+        {
             for global in items {
                 match global.storage_type {
                     None => {
@@ -1212,11 +1921,10 @@ impl GlobalLoad {
                 self.library_path
                     .write()
                     .insert(global.clone(), path.to_path_buf());
-
-                // 4. Directly insert the cloned Arc reference into your map
-                self.library_lib.insert(global, Arc::clone(&shared_lib));
+                out.push(global);
             }
         }
+        out
     }
 
     fn get_info(
@@ -1228,6 +1936,18 @@ impl GlobalLoad {
             "Trying to load library at path: {}",
             path.to_string_lossy()
         ));
+        match pluginabi::abi_call(lib, "get_global_info", &()) {
+            Err(AbiError::NotStable) => {}
+            Ok(out) => return Some(out),
+            Err(err) => {
+                logging::error_log(format!(
+                    "Could not run global info pull for lib. {} {}",
+                    path.to_string_lossy(),
+                    err
+                ));
+                return None;
+            }
+        }
         let temp: libloading::Symbol<
             unsafe extern "C" fn() -> Vec<sharedtypes::GlobalPluginScraper>,
         > = match unsafe { lib.get(b"get_global_info\0") } {
@@ -1270,19 +1990,320 @@ impl GlobalLoad {
 
         info_log(format!("{}", folder.to_string_lossy().to_string()));
 
-        for entry in walkdir::WalkDir::new(folder)
+        let mut libraries: Vec<(i64, PathBuf)> = walkdir::WalkDir::new(folder)
             .max_depth(1)
             .into_iter()
             .flatten()
-        {
-            if entry.path().is_file() {
-                info_log(format!("{}", entry.path().to_string_lossy().to_string()));
-                // Going to try and load hopefully valid library
-                self.parse_lib(entry.path());
+            .filter(|entry| hotreload::is_loadable(entry.path()))
+            .map(|entry| {
+                let load_order = self
+                    .db
+                    .plugin_registry_get(&registry_file(entry.path()))
+                    .map_or(0, |entry| entry.load_order);
+                (load_order, entry.path().to_path_buf())
+            })
+            .collect();
+        libraries.sort();
+
+        for (_, path) in libraries {
+            info_log(format!("{}", path.to_string_lossy().to_string()));
+            // Going to try and load hopefully valid library
+            self.library_load(&path);
+        }
+    }
+
+    ///
+    /// Loads a single library or wasm component. Returns what it registered
+    ///
+    pub fn library_load(&mut self, path: &Path) -> Vec<sharedtypes::GlobalPluginScraper> {
+        if declarative::is_definition(path) {
+            self.parse_definition(path);
+        } else if path.extension().is_some_and(|ext| ext == "wasm") {
+            self.parse_wasm(path);
+        } else {
+            self.parse_lib(path);
+        }
+        self.globals_for_path(path)
+    }
+
+    ///
+    /// Starts watching the scraper and plugin folders for changed libraries
+    ///
+    pub fn hot_reload_spawn(&self) {
+        hotreload::spawn(self.clone());
+    }
+
+    ///
+    /// Returns every library that was added, changed or removed since it was
+    /// loaded. None means the file is gone.
+    ///
+    pub(crate) fn library_changes(&self) -> Vec<(PathBuf, Option<FileStamp>)> {
+        let mut out = Vec::new();
+        let mut found = BTreeSet::new();
+        for folder in [
+            self.db.loaded_scraper_folder(),
+            self.db.loaded_plugin_folder(),
+        ] {
+            for entry in walkdir::WalkDir::new(&folder)
+                .max_depth(1)
+                .into_iter()
+                .flatten()
+            {
+                if !hotreload::is_loadable(entry.path()) {
+                    continue;
+                }
+                let path = entry.path().to_path_buf();
+                let now = hotreload::stamp(&path);
+                if now.is_some() && self.stamps.read().get(&path) != now.as_ref() {
+                    out.push((path.clone(), now));
+                }
+                found.insert(path);
+            }
+        }
+        for path in self.stamps.read().keys() {
+            if !found.contains(path) {
+                out.push((path.clone(), None));
+            }
+        }
+        out
+    }
+
+    ///
+    /// Reloads every library that changed on disk. Returns how many were touched
+    ///
+    pub fn libraries_reload_changed(&mut self) -> usize {
+        let changes = self.library_changes();
+        for (path, now) in changes.iter() {
+            match now {
+                Some(_) => self.library_reload(path),
+                None => self.library_unload(path),
+            }
+        }
+        changes.len()
+    }
+
+    ///
+    /// Every plugin or scraper entry that came from a library file
+    ///
+    fn globals_for_path(&self, path: &Path) -> Vec<sharedtypes::GlobalPluginScraper> {
+        self.library_path
+            .read()
+            .iter()
+            .filter(|(_, lib_path)| lib_path.as_path() == path)
+            .map(|(global, _)| global.clone())
+            .collect()
+    }
+
+    ///
+    /// Sites, callbacks and regexes a set of entries registered. Used to log what a
+    /// reload changed
+    ///
+    fn registration_summary(
+        &self,
+        globals: &[sharedtypes::GlobalPluginScraper],
+    ) -> (BTreeSet<String>, BTreeSet<String>, usize) {
+        let mut sites = BTreeSet::new();
+        let mut callbacks = BTreeSet::new();
+        let mut regexes = 0;
+        for global in globals {
+            if let Some(global_sites) = self.sites.read().get(global) {
+                sites.extend(global_sites.iter().cloned());
+            }
+            for callback in global.callbacks.iter() {
+                if let sharedtypes::GlobalCallbacks::Tag(_) = callback {
+                    regexes += 1;
+                }
+                callbacks.insert(format!("{}: {:?}", global.name, callback));
+            }
+        }
+        (sites, callbacks, regexes)
+    }
+
+    ///
+    /// Removes everything a library registered
+    ///
+    fn unregister_path(&self, path: &Path) -> Vec<sharedtypes::GlobalPluginScraper> {
+        let globals = self.globals_for_path(path);
+        for global in globals.iter() {
+            self.library_path.write().remove(global);
+            self.library_lib.write().remove(global);
+            self.wasm.write().remove(global);
+            self.definitions.write().remove(global);
+            self.sites.write().remove(global);
+            self.callback_cross.write().remove(global);
+        }
+        self.callback.write().retain(|_, list| {
+            list.retain(|item| !globals.contains(item));
+            !list.is_empty()
+        });
+        self.callback_storage.write().retain(|_, list| {
+            list.retain(|(_, item)| !globals.contains(item));
+            !list.is_empty()
+        });
+        self.regex_storage.write().retain(|_, list| {
+            list.retain(|item| !globals.contains(item));
+            !list.is_empty()
+        });
+        // Dropping the worker kills the child process and removes its shadow copy
+        self.workers.write().remove(path);
+        self.stamps.write().remove(path);
+        globals
+    }
+
+    ///
+    /// Waits for calls into the old libraries to finish. Each library comes with how many
+    /// on_start threads still hold it. Anything else still holding one after the timeout
+    /// keeps the old copy mapped until it lets go.
+    ///
+    fn library_drain(&self, path: &Path, libs: &[(Arc<Library>, usize)]) {
+        let start = Instant::now();
+        loop {
+            let busy = {
+                let library_lib = self.library_lib.read();
+                libs.iter().any(|(lib, starting)| {
+                    let in_map = library_lib
+                        .values()
+                        .filter(|mapped| Arc::ptr_eq(mapped, lib))
+                        .count();
+                    // Our own clone plus the map entries and the running on_starts
+                    Arc::strong_count(lib) > in_map + 1 + starting
+                })
+            };
+            if !busy {
+                return;
             }
+            if start.elapsed() > RELOAD_DRAIN_TIMEOUT {
+                logging::info_log(format!(
+                    "Library {} is still in use after {:?}. Old copy stays loaded until its calls return.",
+                    path.display(),
+                    RELOAD_DRAIN_TIMEOUT
+                ));
+                return;
+            }
+            thread::sleep(Duration::from_millis(100));
         }
     }
 
+    ///
+    /// Swaps a changed library for the new one once its in-flight calls finish.
+    /// Reruns get_global_info and on_start and logs what changed.
+    ///
+    pub fn library_reload(&mut self, path: &Path) {
+        let old_globals = self.globals_for_path(path);
+        // A running on_start holds the old library so it has to stop before the drain
+        let old_starts = self.on_start_stop(&old_globals);
+        // Libraries and how many of their on_starts are still running. Those don't hold
+        // up the drain since the new on_start waits on them anyway
+        let mut old_libs: Vec<(Arc<Library>, usize)> = Vec::new();
+        for global in old_globals.iter() {
+            let Some(lib) = self.library_get(global) else {
+                continue;
+            };
+            let starting = old_starts
+                .iter()
+                .any(|(started, handle)| started == global && !handle.is_finished());
+            match old_libs.iter_mut().find(|(old, _)| Arc::ptr_eq(old, &lib)) {
+                Some((_, running)) => *running += starting as usize,
+                None => old_libs.push((lib, starting as usize)),
+            }
+        }
+        self.library_drain(path, &old_libs);
+
+        let (old_sites, old_callbacks, old_regexes) = self.registration_summary(&old_globals);
+        self.unregister_path(path);
+
+        if declarative::is_definition(path) {
+            self.parse_definition(path);
+        } else if path.extension().is_some_and(|ext| ext == "wasm") {
+            self.parse_wasm(path);
+        } else {
+            self.parse_lib(path);
+        }
+        drop(old_libs);
+        let old_starts_running = old_starts.iter().any(|(_, old)| !old.is_finished());
+
+        let new_globals = self.globals_for_path(path);
+        let (new_sites, new_callbacks, new_regexes) = self.registration_summary(&new_globals);
+        logging::info_log(format!(
+            "Reloaded {}. Sites added: {:?} removed: {:?}. Callbacks added: {:?} removed: {:?}. Regexes: {} -> {}",
+            path.display(),
+            new_sites.difference(&old_sites).collect::<Vec<_>>(),
+            old_sites.difference(&new_sites).collect::<Vec<_>>(),
+            new_callbacks.difference(&old_callbacks).collect::<Vec<_>>(),
+            old_callbacks.difference(&new_callbacks).collect::<Vec<_>>(),
+            old_regexes,
+            new_regexes
+        ));
+
+        // Two on_starts writing to the db at once would trip over each other so the
+        // new one waits until the old one has returned
+        let globalload = self.clone();
+        let start_new = move || {
+            for (_, old) in old_starts {
+                let _ = old.join();
+            }
+            for global in new_globals.iter() {
+                for callback in global.callbacks.iter() {
+                    if let sharedtypes::GlobalCallbacks::Start(thread_type) = callback {
+                        globalload.scraper_on_start(global, thread_type);
+                    }
+                }
+            }
+        };
+        if old_starts_running {
+            logging::info_log(format!(
+                "on_start for {} is still running. The new one starts once it returns.",
+                path.display()
+            ));
+            thread::spawn(start_new);
+        } else {
+            start_new();
+        }
+    }
+
+    ///
+    /// Asks the on_start threads of these entries to return and hands back their
+    /// handles. Plugins with an on_start that runs for a long time export on_stop for it.
+    /// Nothing can stop one that doesn't so the next on_start waits on it
+    ///
+    fn on_start_stop(
+        &self,
+        globals: &[sharedtypes::GlobalPluginScraper],
+    ) -> Vec<(sharedtypes::GlobalPluginScraper, JoinHandle<()>)> {
+        let mut handles = Vec::new();
+        for global in globals.iter() {
+            let Some(handle) = self.thread.write().remove(global) else {
+                continue;
+            };
+            if !handle.is_finished() {
+                // No legacy fallback. on_stop is newer than the raw rust exports
+                match self.plugin_call::<_, ()>(global, "on_stop", global) {
+                    Ok(()) | Err(AbiError::NotExported) | Err(AbiError::NotStable) => {}
+                    Err(err) => logging::error_log(format!(
+                        "Cannot run on_stop for: {} {}",
+                        global.name, err
+                    )),
+                }
+            }
+            handles.push((global.clone(), handle));
+        }
+        handles
+    }
+
+    ///
+    /// Removes a library that was deleted from its folder
+    ///
+    pub fn library_unload(&mut self, path: &Path) {
+        let old_globals = self.globals_for_path(path);
+        self.on_start_stop(&old_globals);
+        let globals = self.unregister_path(path);
+        logging::info_log(format!(
+            "Unloaded {} entries from {}",
+            globals.len(),
+            path.display()
+        ));
+    }
+
     pub fn filter_sites_return_lib(&self, site: &String) -> Option<Arc<Library>> {
         for scraper in self.scraper_get().iter() {
             if let Some(ref storage_type) = scraper.storage_type
@@ -1300,11 +2321,17 @@ impl GlobalLoad {
 /// Returns filehashes that have to be regenned.
 /// I don't think this gets used?
 ///
-pub fn scraper_file_regen(lib: &RwLock<libloading::Library>) -> sharedtypes::ScraperFileRegen {
+pub fn scraper_file_regen(
+    lib: &RwLock<libloading::Library>,
+) -> Result<sharedtypes::ScraperFileRegen, AbiError> {
     let libloading = lib.read();
+    match pluginabi::abi_call(&libloading, "scraper_file_regen", &()) {
+        Err(AbiError::NotStable) => {}
+        out => return out,
+    }
     let temp: libloading::Symbol<unsafe extern "C" fn() -> sharedtypes::ScraperFileRegen> =
-        unsafe { libloading.get(b"scraper_file_regen\0").unwrap() };
-    unsafe { temp() }
+        unsafe { libloading.get(b"scraper_file_regen\0") }.map_err(|_| AbiError::NotExported)?;
+    Ok(unsafe { temp() })
 }
 ///
 /// Used to generate a download link given the input data
@@ -1312,12 +2339,16 @@ pub fn scraper_file_regen(lib: &RwLock<libloading::Library>) -> sharedtypes::Scr
 pub fn scraper_file_return(
     lib: &RwLock<libloading::Library>,
     regen: &sharedtypes::ScraperFileInput,
-) -> sharedtypes::SubTag {
+) -> Result<sharedtypes::SubTag, AbiError> {
     let libloading = lib.read();
+    match pluginabi::abi_call(&libloading, "scraper_file_return", regen) {
+        Err(AbiError::NotStable) => {}
+        out => return out,
+    }
     let temp: libloading::Symbol<
         unsafe extern "C" fn(&sharedtypes::ScraperFileInput) -> sharedtypes::SubTag,
-    > = unsafe { libloading.get(b"scraper_file_return\0").unwrap() };
-    unsafe { temp(regen) }
+    > = unsafe { libloading.get(b"scraper_file_return\0") }.map_err(|_| AbiError::NotExported)?;
+    Ok(unsafe { temp(regen) })
 }
 
 #[cfg(test)]
@@ -1,19 +1,30 @@
 use crate::logging::error_log;
 use cfg_if::cfg_if;
+use std::future::Future;
+
+/// Folders deep that files get sharded into when a storage root doesn't say otherwise
+pub const SHARD_DEPTH_DEFAULT: u64 = 3;
+/// Deepest a storage root can be sharded
+pub const SHARD_DEPTH_MAX: u64 = 6;
 
 /// Returns the location as a string that will store the string
 pub fn getfinpath(location: &str, hash: &String, create_dir: bool) -> String {
+    getfinpath_depth(location, hash, SHARD_DEPTH_DEFAULT, create_dir)
+}
+
+///
+/// Same as getfinpath but with depth folders of two hash characters each instead of
+/// the default ab/cd/ef
+///
+pub fn getfinpath_depth(location: &str, hash: &String, depth: u64, create_dir: bool) -> String {
     // Gets and makes folderpath.
-    let final_loc = format!(
-        "{}/{}{}/{}{}/{}{}",
-        location,
-        hash.chars().next().unwrap(),
-        hash.chars().nth(1).unwrap(),
-        hash.chars().nth(2).unwrap(),
-        hash.chars().nth(3).unwrap(),
-        hash.chars().nth(4).unwrap(),
-        hash.chars().nth(5).unwrap()
-    );
+    let mut chars = hash.chars();
+    let mut final_loc = location.to_string();
+    for _ in 0..depth {
+        final_loc.push('/');
+        final_loc.push(chars.next().unwrap());
+        final_loc.push(chars.next().unwrap());
+    }
     if create_dir {
         match std::fs::create_dir_all(&final_loc) {
             Ok(_) => {}
@@ -25,6 +36,21 @@ pub fn getfinpath(location: &str, hash: &String, create_dir: bool) -> String {
     final_loc
 }
 
+///
+/// Runs a future from sync code. Works both inside and outside of the tokio
+/// runtime.
+///
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => tokio::task::block_in_place(|| handle.block_on(fut)),
+        Err(_) => tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(fut),
+    }
+}
+
 ///
 /// Checks if a url is valid
 ///
@@ -0,0 +1,217 @@
+use crate::daemon::process_alive;
+use crate::declarative;
+use crate::globalload::GlobalLoad;
+use crate::logging;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How often the plugin and scraper folders get checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+static SHADOW_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+///
+/// What a library file looked like when we loaded it
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+pub fn stamp(path: &Path) -> Option<FileStamp> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some(FileStamp {
+        modified: meta.modified().ok()?,
+        len: meta.len(),
+    })
+}
+
+///
+/// Only files that can be a plugin or scraper definition get loaded. Skips caps files and the like
+///
+pub fn is_loadable(path: &Path) -> bool {
+    path.is_file()
+        && (path
+            .extension()
+            .is_some_and(|ext| ext == "so" || ext == "dll" || ext == "dylib" || ext == "wasm")
+            || declarative::is_definition(path))
+}
+
+///
+/// Copies a library somewhere unique and private before loading it.
+/// dlopen hands back the already loaded copy for a path that's still open, and
+/// writing over a mapped library crashes whoever is running it. The copy is also
+/// what gets verified so nobody can change it between the check and the load.
+///
+pub fn shadow_copy(path: &Path) -> std::io::Result<PathBuf> {
+    let dir = shadow_dir()?;
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut name = format!(
+        "{}-{}-{}",
+        stem,
+        std::process::id(),
+        SHADOW_COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+    if let Some(ext) = path.extension() {
+        name += &format!(".{}", ext.to_string_lossy());
+    }
+    let shadow = dir.join(name);
+    std::fs::copy(path, &shadow)?;
+    Ok(shadow)
+}
+
+///
+/// Removes shadow copies left behind by a process that isn't running anymore.
+/// A crash never gets to drop its workers so their copies would stay forever
+///
+pub fn shadow_sweep() {
+    let Ok(entries) = shadow_dir().and_then(std::fs::read_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let pid = path.file_stem().and_then(|stem| {
+            let mut parts = stem.to_str()?.rsplitn(3, '-');
+            parts.next()?;
+            parts.next()?.parse::<u32>().ok()
+        });
+        if let Some(pid) = pid
+            && pid != std::process::id()
+            && !process_alive(pid)
+        {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+///
+/// Folder the shadow copies go in. Only we can write to it
+///
+fn shadow_dir() -> std::io::Result<PathBuf> {
+    let dir = std::env::temp_dir().join("rusthydrus-hotreload");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+        match std::fs::DirBuilder::new().mode(0o700).create(&dir) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(err),
+        }
+        // Someone else could have made it first in a shared temp dir
+        let meta = std::fs::symlink_metadata(&dir)?;
+        if !meta.is_dir() || meta.uid() != unsafe { libc::getuid() } {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("{} is not our folder", dir.display()),
+            ));
+        }
+        if meta.permissions().mode() & 0o077 != 0 {
+            std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+        }
+    }
+    #[cfg(not(unix))]
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+///
+/// Watches the loaded scraper and plugin folders and reloads anything that changes.
+/// A change has to look the same for two polls so we don't load a half written file.
+///
+pub fn spawn(mut globalload: GlobalLoad) {
+    thread::spawn(move || {
+        let mut pending: HashMap<PathBuf, Option<FileStamp>> = HashMap::new();
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let changes: HashMap<PathBuf, Option<FileStamp>> =
+                globalload.library_changes().into_iter().collect();
+
+            for (path, now) in changes.iter() {
+                if pending.get(path) != Some(now) {
+                    continue;
+                }
+                match now {
+                    Some(_) => {
+                        logging::info_log(format!("Hot reloading: {}", path.display()));
+                        globalload.library_reload(path);
+                    }
+                    None => {
+                        logging::info_log(format!("Unloading removed library: {}", path.display()));
+                        globalload.library_unload(path);
+                    }
+                }
+            }
+            pending = changes
+                .into_iter()
+                .filter(|(path, now)| pending.get(path) != Some(now))
+                .collect();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn only_libraries_are_loadable() {
+        let dir = TempDir::new("hotreload");
+        let lib = dir.join("scraper.so");
+        let caps = dir.join("scraper.wasm.caps.json");
+        let definition = dir.join("booru.scraper.toml");
+        std::fs::write(&lib, b"").unwrap();
+        std::fs::write(&caps, b"{}").unwrap();
+        std::fs::write(&definition, b"").unwrap();
+
+        assert!(is_loadable(&lib));
+        assert!(is_loadable(&definition));
+        assert!(!is_loadable(&caps));
+        assert!(!is_loadable(&dir));
+    }
+
+    #[test]
+    fn shadow_copies_are_unique() {
+        let dir = TempDir::new("hotreload-shadow");
+        let lib = dir.join("scraper.so");
+        std::fs::write(&lib, b"lib").unwrap();
+
+        let first = shadow_copy(&lib).unwrap();
+        let second = shadow_copy(&lib).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(first.extension().unwrap(), "so");
+        assert_eq!(std::fs::read(&second).unwrap(), b"lib");
+
+        let _ = std::fs::remove_file(first);
+        let _ = std::fs::remove_file(second);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dead_process_shadows_are_swept() {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let dead = child.id();
+        child.wait().unwrap();
+
+        let dir = shadow_dir().unwrap();
+        let stale = dir.join(format!("scraper-{}-0.so", dead));
+        std::fs::write(&stale, b"lib").unwrap();
+        let lib_dir = TempDir::new("hotreload-sweep");
+        let lib = lib_dir.join("scraper.so");
+        std::fs::write(&lib, b"lib").unwrap();
+        let ours = shadow_copy(&lib).unwrap();
+
+        shadow_sweep();
+        assert!(!stale.exists());
+        assert!(ours.exists());
+
+        let _ = std::fs::remove_file(ours);
+    }
+}
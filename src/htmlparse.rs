@@ -0,0 +1,334 @@
+use crate::download;
+use crate::helpers::block_on;
+use reqwest::Client;
+use scraper::{ElementRef, Html, Selector};
+use sharedtypes::{HtmlExtract, HtmlQuery};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use url::Url;
+
+// Ratelimit for hosts fetched through fetch_select
+const FETCH_RATELIMIT: (u64, Duration) = (1, Duration::from_secs(1));
+
+static FETCH_CLIENT: LazyLock<Arc<Client>> =
+    LazyLock::new(|| Arc::new(download::client_create(Vec::new(), true)));
+
+///
+/// A query that's ready to run against a parsed page
+///
+#[derive(Debug, Clone)]
+pub struct CompiledQuery {
+    selector: Selector,
+    extract: HtmlExtract,
+}
+
+impl CompiledQuery {
+    ///
+    /// Compiles a query. XPaths ending in /@attr or /text() override the extract,
+    /// /@attr stays a link if the query asked for one.
+    ///
+    pub fn new(query: &HtmlQuery) -> Result<Self, String> {
+        let (css, tail) = if query.selector.starts_with('/') {
+            xpath_to_css(&query.selector)?
+        } else {
+            (query.selector.clone(), None)
+        };
+        let selector = Selector::parse(&css).map_err(|err| format!("{}: {}", css, err))?;
+        let extract = match (tail, &query.extract) {
+            (Some(XPathTail::Attr(attr)), HtmlExtract::Link(_)) => HtmlExtract::Link(attr),
+            (Some(XPathTail::Attr(attr)), _) => HtmlExtract::Attr(attr),
+            (Some(XPathTail::Text), _) => HtmlExtract::Text,
+            (None, extract) => extract.clone(),
+        };
+        Ok(CompiledQuery { selector, extract })
+    }
+
+    ///
+    /// Everything the query matches under root
+    ///
+    pub fn all(&self, root: ElementRef, base: Option<&Url>) -> Vec<String> {
+        root.select(&self.selector)
+            .filter_map(|element| extract(element, &self.extract, base))
+            .collect()
+    }
+
+    pub fn first(&self, root: ElementRef, base: Option<&Url>) -> Option<String> {
+        root.select(&self.selector)
+            .find_map(|element| extract(element, &self.extract, base))
+    }
+
+    ///
+    /// The elements the query matches. Used to scope other queries to each one
+    ///
+    pub fn elements<'a>(&self, root: ElementRef<'a>) -> Vec<ElementRef<'a>> {
+        root.select(&self.selector).collect()
+    }
+}
+
+///
+/// Pulls a value out of a single element
+///
+pub fn extract(element: ElementRef, extract: &HtmlExtract, base: Option<&Url>) -> Option<String> {
+    let out = match extract {
+        HtmlExtract::Text => element
+            .text()
+            .flat_map(|text| text.split_whitespace())
+            .collect::<Vec<_>>()
+            .join(" "),
+        HtmlExtract::Html => element.html(),
+        HtmlExtract::Attr(attr) => element.value().attr(attr)?.trim().to_string(),
+        HtmlExtract::Link(attr) => {
+            let link = element.value().attr(attr)?.trim();
+            match base {
+                Some(base) => base.join(link).ok()?.to_string(),
+                None => link.to_string(),
+            }
+        }
+    };
+    (!out.is_empty()).then_some(out)
+}
+
+///
+/// Runs every query against a page. Each query gets its own result so one bad
+/// selector doesn't hide the rest.
+///
+pub fn select(
+    body: &str,
+    base_url: Option<&str>,
+    queries: &[HtmlQuery],
+) -> Vec<Result<Vec<String>, String>> {
+    let base = base_url.and_then(|base| Url::parse(base).ok());
+    let document = Html::parse_document(body);
+    queries
+        .iter()
+        .map(|query| {
+            CompiledQuery::new(query).map(|query| query.all(document.root_element(), base.as_ref()))
+        })
+        .collect()
+}
+
+///
+/// Downloads a page and runs the queries against it. Links resolve against
+/// wherever the page ended up after redirects. Every fetch to the same host waits
+/// on the same ratelimiter.
+///
+pub fn fetch_select(
+    url: &String,
+    queries: &[HtmlQuery],
+) -> Result<Vec<Result<Vec<String>, String>>, String> {
+    let host = Url::parse(url)
+        .ok()
+        .and_then(|parsed| parsed.host_str().map(str::to_lowercase))
+        .ok_or_else(|| format!("Cannot download {}: no host in url", url))?;
+    let ratelimiter = download::ratelimiter_shared(&host, FETCH_RATELIMIT);
+    let (body, resp_url) = block_on(download::dltext_new(
+        url,
+        None,
+        FETCH_CLIENT.clone(),
+        &ratelimiter,
+        &0,
+        &0,
+    ))
+    .map_err(|err| format!("Cannot download {}: {}", url, err))?;
+    Ok(select(&body, Some(&resp_url), queries))
+}
+
+#[derive(Debug, PartialEq)]
+enum XPathTail {
+    Attr(String),
+    Text,
+}
+
+///
+/// Turns the XPath most people write into CSS. Handles / and //, tag names or *,
+/// [@attr], [@attr='value'], [contains(@attr, 'value')], [n] and a trailing
+/// /@attr or /text(). Anything else is an error instead of a wrong match.
+///
+fn xpath_to_css(xpath: &str) -> Result<(String, Option<XPathTail>), String> {
+    let mut css = String::new();
+    let mut tail = None;
+    let mut rest = xpath;
+    let mut first = true;
+    while !rest.is_empty() {
+        let descendant = rest.starts_with("//");
+        rest = rest
+            .strip_prefix("//")
+            .or_else(|| rest.strip_prefix('/'))
+            .ok_or_else(|| format!("{} has a step that doesn't start with /", xpath))?;
+        let end = step_end(rest);
+        let step = &rest[..end];
+        rest = &rest[end..];
+
+        if let Some(attr) = step.strip_prefix('@') {
+            tail = Some(XPathTail::Attr(attr.to_string()));
+        } else if step == "text()" {
+            tail = Some(XPathTail::Text);
+        }
+        if tail.is_some() {
+            if !rest.is_empty() || first {
+                return Err(format!("{} can only end with /@attr or /text()", xpath));
+            }
+            break;
+        }
+
+        if !first {
+            css += if descendant { " " } else { " > " };
+        }
+        let (name, predicates) = step.split_at(step.find('[').unwrap_or(step.len()));
+        if name.is_empty() {
+            return Err(format!("{} has an empty step", xpath));
+        }
+        css += name;
+        if first && !descendant {
+            css += ":root";
+        }
+        css += &predicates_to_css(predicates).map_err(|err| format!("{}: {}", xpath, err))?;
+        first = false;
+    }
+    if css.is_empty() {
+        return Err(format!("{} doesn't select anything", xpath));
+    }
+    Ok((css, tail))
+}
+
+///
+/// Where a step ends. Slashes inside of predicates don't count
+///
+fn step_end(rest: &str) -> usize {
+    let mut depth = 0;
+    let mut quote = None;
+    for (pos, c) in rest.char_indices() {
+        match (c, quote) {
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            ('[', None) => depth += 1,
+            (']', None) => depth -= 1,
+            ('/', None) if depth == 0 => return pos,
+            _ => {}
+        }
+    }
+    rest.len()
+}
+
+fn predicates_to_css(mut predicates: &str) -> Result<String, String> {
+    let mut css = String::new();
+    while let Some(inner) = predicates.strip_prefix('[') {
+        let close = step_end_bracket(inner).ok_or("unclosed [")?;
+        let predicate = inner[..close].trim();
+        predicates = &inner[close + 1..];
+
+        if let Ok(index) = predicate.parse::<u64>() {
+            css += &format!(":nth-of-type({})", index);
+        } else if let Some(args) = predicate
+            .strip_prefix("contains(")
+            .and_then(|args| args.strip_suffix(')'))
+        {
+            let (attr, value) = args.split_once(',').ok_or("contains needs two args")?;
+            let attr = attr
+                .trim()
+                .strip_prefix('@')
+                .ok_or("contains needs an @attr")?;
+            css += &format!("[{}*={}]", attr, quoted(value.trim())?);
+        } else if let Some(attr) = predicate.strip_prefix('@') {
+            match attr.split_once('=') {
+                Some((attr, value)) => {
+                    css += &format!("[{}={}]", attr.trim(), quoted(value.trim())?)
+                }
+                None => css += &format!("[{}]", attr.trim()),
+            }
+        } else {
+            return Err(format!("can't turn [{}] into css", predicate));
+        }
+    }
+    if !predicates.is_empty() {
+        return Err(format!("unexpected {}", predicates));
+    }
+    Ok(css)
+}
+
+fn step_end_bracket(inner: &str) -> Option<usize> {
+    let mut quote = None;
+    for (pos, c) in inner.char_indices() {
+        match (c, quote) {
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            (']', None) => return Some(pos),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn quoted(value: &str) -> Result<String, String> {
+    let inner = value
+        .strip_prefix('\'')
+        .and_then(|value| value.strip_suffix('\''))
+        .or_else(|| {
+            value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+        })
+        .ok_or_else(|| format!("{} isn't quoted", value))?;
+    Ok(format!("\"{}\"", inner.replace('"', "\\\"")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(selector: &str, extract: HtmlExtract) -> HtmlQuery {
+        HtmlQuery {
+            selector: selector.to_string(),
+            extract,
+        }
+    }
+
+    #[test]
+    fn xpaths_become_css() {
+        assert_eq!(
+            xpath_to_css("//div[@class='post']/a[2]/@href").unwrap(),
+            (
+                "div[class=\"post\"] > a:nth-of-type(2)".to_string(),
+                Some(XPathTail::Attr("href".to_string()))
+            )
+        );
+        assert_eq!(
+            xpath_to_css("/html/body//span[contains(@class, 'tag')]/text()").unwrap(),
+            (
+                "html:root > body span[class*=\"tag\"]".to_string(),
+                Some(XPathTail::Text)
+            )
+        );
+        assert!(xpath_to_css("//a[last()]").is_err());
+        assert!(xpath_to_css("//a/@href/b").is_err());
+    }
+
+    #[test]
+    fn queries_resolve_links() {
+        let body = r#"<html><head><meta property="og:image" content="/img/1.png"></head>
+            <body><div class="post"><a href="../p/2">Post   two</a></div></body></html>"#;
+        let out = select(
+            body,
+            Some("https://example.net/list/index.html"),
+            &[
+                query(
+                    "meta[property='og:image']",
+                    HtmlExtract::Link("content".into()),
+                ),
+                query(
+                    "//div[@class='post']/a/@href",
+                    HtmlExtract::Link(String::new()),
+                ),
+                query("div.post a", HtmlExtract::Text),
+                query("div[", HtmlExtract::Text),
+            ],
+        );
+        assert_eq!(
+            out[0],
+            Ok(vec!["https://example.net/img/1.png".to_string()])
+        );
+        assert_eq!(out[1], Ok(vec!["https://example.net/p/2".to_string()]));
+        assert_eq!(out[2], Ok(vec!["Post two".to_string()]));
+        assert!(out[3].is_err());
+    }
+}
@@ -5,9 +5,31 @@ use rusqlite::Transaction;
 use sharedtypes;
 use std::collections::BTreeMap;
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 //use std::sync::Mutex;
 use crate::{Arc, RwLock};
 
+/// Key inside of a jobs system_data that marks it as paused
+pub const JOB_PAUSED_KEY: &str = "SYSTEM_PAUSED";
+
+///
+/// Cooperative cancellation token. Workers check this between urls and files
+///
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
 ///
 /// Holds the previously seen jobs
 ///
@@ -24,6 +46,16 @@ pub struct Jobs {
     db: Main,
     site_job: RwLock<HashMap<sharedtypes::GlobalPluginScraper, HashSet<sharedtypes::DbJobsObj>>>,
     previously_seen: RwLock<HashMap<sharedtypes::GlobalPluginScraper, HashSet<PreviouslySeenObj>>>,
+    // Job ids that should not get picked up by a worker
+    paused: RwLock<HashSet<u64>>,
+    // Scraper names that should stop at the next url or file
+    paused_scrapers: RwLock<HashSet<String>>,
+    // Tokens for jobs that are currently running
+    job_tokens: RwLock<HashMap<u64, CancelToken>>,
+    // Tokens for each worker, keyed by scraper name
+    scraper_tokens: RwLock<HashMap<String, CancelToken>>,
+    // Cancelled when the program is shutting down
+    shutdown: CancelToken,
 }
 
 impl Jobs {
@@ -32,6 +64,11 @@ impl Jobs {
             db,
             site_job: HashMap::new().into(),
             previously_seen: HashMap::new().into(),
+            paused: HashSet::new().into(),
+            paused_scrapers: HashSet::new().into(),
+            job_tokens: HashMap::new().into(),
+            scraper_tokens: HashMap::new().into(),
+            shutdown: CancelToken::default(),
         })
     }
 
@@ -176,6 +213,18 @@ impl Jobs {
         scraper: &sharedtypes::GlobalPluginScraper,
         job: &sharedtypes::DbJobsObj,
     ) -> bool {
+        if let Some(job_id) = job.id
+            && self.jobs_is_paused(&job_id)
+        {
+            return false;
+        }
+        if self.is_shutting_down()
+            || self.db.downloads_pause_get()
+            || self.scraper_is_paused(&scraper.name)
+            || self.scraper_token_get(&scraper.name).is_cancelled()
+        {
+            return false;
+        }
         if let Some(jobs_local) = self.site_job.read().get(scraper) {
             for job_local in jobs_local {
                 if job_local.id == job.id {
@@ -223,6 +272,165 @@ impl Jobs {
         out
     }
 
+    ///
+    /// Gets every loaded job with the scraper that it's queued against.
+    /// Sorted by priority highest first.
+    ///
+    pub fn jobs_get_all_loaded(
+        &self,
+    ) -> Vec<(sharedtypes::GlobalPluginScraper, sharedtypes::DbJobsObj)> {
+        let mut out = Vec::new();
+        for (scraper, jobs) in self.site_job.read().iter() {
+            for job in jobs {
+                out.push((scraper.clone(), job.clone()));
+            }
+        }
+        out.sort_by(|a, b| b.1.priority.cmp(&a.1.priority).then(a.1.id.cmp(&b.1.id)));
+        out
+    }
+
+    ///
+    /// Pauses a job. Paused jobs stay loaded but won't get ran by a worker.
+    /// Persisted into the jobs system_data so it survives a restart.
+    ///
+    pub fn jobs_pause(&self, job_id: &u64) {
+        logging::info_log(format!("Pausing Job: {}", job_id));
+        self.paused.write().insert(*job_id);
+        self.db.jobs_paused_set_db(job_id, true);
+    }
+
+    ///
+    /// Resumes a paused job
+    ///
+    pub fn jobs_resume(&self, job_id: &u64) {
+        logging::info_log(format!("Resuming Job: {}", job_id));
+        self.paused.write().remove(job_id);
+        self.db.jobs_paused_set_db(job_id, false);
+    }
+
+    ///
+    /// Checks if a job is paused
+    ///
+    pub fn jobs_is_paused(&self, job_id: &u64) -> bool {
+        self.paused.read().contains(job_id)
+    }
+
+    ///
+    /// Cancels a running job. The worker stops it at the next url or file and the job
+    /// gets paused so it isn't picked straight back up.
+    ///
+    pub fn jobs_cancel(&self, job_id: &u64) {
+        if let Some(token) = self.job_tokens.read().get(job_id) {
+            logging::info_log(format!("Cancelling Job: {}", job_id));
+            token.cancel();
+        }
+    }
+
+    ///
+    /// Gets the cancel token for a job. Creates one if the job doesn't have one yet
+    ///
+    pub fn job_token_get(&self, job_id: &u64) -> CancelToken {
+        self.job_tokens.write().entry(*job_id).or_default().clone()
+    }
+
+    ///
+    /// Removes a jobs cancel token once it's finished running
+    ///
+    pub fn job_token_remove(&self, job_id: &u64) {
+        self.job_tokens.write().remove(job_id);
+    }
+
+    ///
+    /// Gets the cancel token for a worker
+    ///
+    pub fn scraper_token_get(&self, scraper_name: &String) -> CancelToken {
+        self.scraper_tokens
+            .write()
+            .entry(scraper_name.clone())
+            .or_default()
+            .clone()
+    }
+
+    ///
+    /// Pauses a scraper. Its worker waits at the next url or file until resumed
+    ///
+    pub fn scraper_pause(&self, scraper_name: &String) {
+        logging::info_log(format!("Pausing Scraper: {}", scraper_name));
+        self.paused_scrapers.write().insert(scraper_name.clone());
+    }
+
+    ///
+    /// Resumes a paused or cancelled scraper
+    ///
+    pub fn scraper_resume(&self, scraper_name: &String) {
+        logging::info_log(format!("Resuming Scraper: {}", scraper_name));
+        self.paused_scrapers.write().remove(scraper_name);
+        let mut tokens = self.scraper_tokens.write();
+        if tokens
+            .get(scraper_name)
+            .is_some_and(|token| token.is_cancelled())
+        {
+            tokens.insert(scraper_name.clone(), CancelToken::default());
+        }
+    }
+
+    ///
+    /// Drains a scraper. Running jobs stop at the next url or file and no new jobs get
+    /// started until the scraper is resumed.
+    ///
+    pub fn scraper_cancel(&self, scraper_name: &String) {
+        logging::info_log(format!("Cancelling Scraper: {}", scraper_name));
+        self.scraper_token_get(scraper_name).cancel();
+    }
+
+    ///
+    /// Checks if a scraper is paused
+    ///
+    pub fn scraper_is_paused(&self, scraper_name: &String) -> bool {
+        self.paused_scrapers.read().contains(scraper_name)
+    }
+
+    ///
+    /// Stops any new jobs from starting and tells running jobs to stop at the next url or
+    /// file
+    ///
+    pub fn shutdown_begin(&self) {
+        self.shutdown.cancel();
+    }
+
+    ///
+    /// Checks if we're shutting down
+    ///
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.is_cancelled()
+    }
+
+    ///
+    /// Number of jobs that are currently running
+    ///
+    pub fn jobs_running_count(&self) -> usize {
+        self.job_tokens.read().len()
+    }
+
+    ///
+    /// Changes the priority of a loaded job and updates the DB
+    ///
+    pub fn jobs_set_priority(
+        &self,
+        scraper: &sharedtypes::GlobalPluginScraper,
+        job_id: &u64,
+        priority: u64,
+    ) {
+        let job = self
+            .jobs_get(scraper)
+            .into_iter()
+            .find(|job| job.id == Some(*job_id));
+        if let Some(mut job) = job {
+            job.priority = priority;
+            self.jobs_update(job, scraper);
+        }
+    }
+
     ///
     /// Gets all the GlobalPluginScraper objs that are loaded for jobs
     ///
@@ -246,6 +454,9 @@ impl Jobs {
                 if job.id == data.id && job_list.remove(&job) {
                     logging::info_log(format!("Worker: {worker_id} --Removing Job: {:?}", &job));
                     self.db.del_from_jobs_byid(job.id);
+                    if let Some(id) = job.id {
+                        self.paused.write().remove(&id);
+                    }
                 }
             }
         }
@@ -303,6 +514,15 @@ impl Jobs {
         data: sharedtypes::DbJobsObj,
         scraper: &sharedtypes::GlobalPluginScraper,
     ) {
+        // Workers hold a copy of the job from when it started so keep the paused flag
+        let mut data = data;
+        if let Some(job_id) = data.id
+            && self.jobs_is_paused(&job_id)
+        {
+            data.system_data
+                .insert(JOB_PAUSED_KEY.to_string(), "true".to_string());
+        }
+
         if let Some(job_list) = self.site_job.write().get_mut(scraper) {
             let mut database_updated = false;
 
@@ -385,6 +605,10 @@ impl Jobs {
                 None => continue,
             };
 
+            if job.system_data.contains_key(JOB_PAUSED_KEY) {
+                self.paused.write().insert(job_id);
+            }
+
             for (scraper, site) in &globalplugin_sites {
                 // Only allow actual scrapers
                 let scraper_info = match &scraper.storage_type {
@@ -1,36 +1,375 @@
 #![forbid(unsafe_code)]
 
+use crate::RwLock;
+use fast_log::appender::{Command, FastLogRecord, RecordFormat};
 use fast_log::consts::LogSize;
+use fast_log::filter::Filter;
 use fast_log::plugin::file_split::{KeepType, Rolling};
 use fast_log::plugin::packer::LZ4Packer;
-use log::{LevelFilter, error, info};
+use log::{Level, LevelFilter, error, info};
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::fmt::Display;
 use std::fs;
+use std::panic::Location;
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Logs from other crates are noisy. Only show warnings unless overridden
+const EXTERNAL_DEFAULT_LEVEL: LevelFilter = LevelFilter::Warn;
+
+// Starts a record that LogCtx already rendered as a json object. Plain messages
+// that happen to look like json still get logged as a msg
+const STRUCTURED_MARK: char = '\u{1e}';
+
+///
+/// How the logger should be setup on startup
+///
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    pub json: bool,
+    pub level: LevelFilter,
+    // Size in MB before the log gets rotated
+    pub rotate_mb: u64,
+    // Number of rotated logs to keep
+    pub keep: i64,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            json: false,
+            level: LevelFilter::Info,
+            rotate_mb: 2048,
+            keep: 2,
+        }
+    }
+}
+
+///
+/// Runtime changeable log state
+///
+struct LogState {
+    json: AtomicBool,
+    default_level: RwLock<LevelFilter>,
+    // module path prefix -> level
+    modules: RwLock<BTreeMap<String, LevelFilter>>,
+}
+
+static LOG_STATE: LazyLock<LogState> = LazyLock::new(|| LogState {
+    json: AtomicBool::new(false),
+    default_level: RwLock::new(LevelFilter::Info),
+    modules: RwLock::new(BTreeMap::new()),
+});
+
+///
+/// Returns the level that applies to a module. Longest matching prefix wins.
+///
+fn level_for(target: &str) -> LevelFilter {
+    let modules = LOG_STATE.modules.read();
+    let mut best: Option<(&String, &LevelFilter)> = None;
+    for (module, level) in modules.iter() {
+        let matches = target == module
+            || (target.starts_with(module.as_str()) && target[module.len()..].starts_with("::"));
+        if matches && best.is_none_or(|(best_module, _)| module.len() > best_module.len()) {
+            best = Some((module, level));
+        }
+    }
+    if let Some((_, level)) = best {
+        return *level;
+    }
+    if target.starts_with(env!("CARGO_PKG_NAME")) {
+        *LOG_STATE.default_level.read()
+    } else {
+        EXTERNAL_DEFAULT_LEVEL
+    }
+}
+
+///
+/// Drops records below the level set for their module
+///
+struct ModuleLevelFilter;
+
+impl Filter for ModuleLevelFilter {
+    fn do_log(&self, record: &log::Record) -> bool {
+        record.level() <= level_for(record.target())
+    }
+}
+
+///
+/// Writes records as plain text or one json object per line
+///
+struct HydrusFormat;
+
+impl RecordFormat for HydrusFormat {
+    fn do_format(&self, arg: &mut FastLogRecord) {
+        if let Command::CommandRecord = arg.command {
+            arg.formated = record_format(
+                arg.now.into(),
+                arg.level,
+                &arg.target,
+                &arg.args,
+                LOG_STATE.json.load(Ordering::Relaxed),
+            );
+        }
+    }
+}
+
+///
+/// Formats one log line. Only records marked by LogCtx get their fields merged in
+///
+fn record_format(
+    now: chrono::DateTime<chrono::Local>,
+    level: Level,
+    target: &str,
+    args: &str,
+    json: bool,
+) -> String {
+    let structured = args.strip_prefix(STRUCTURED_MARK);
+    if !json {
+        return format!(
+            "{} [{}] {}\n",
+            now.format("%Y-%m-%d %H:%M:%S%.3f"),
+            level,
+            structured.unwrap_or(args)
+        );
+    }
+    let mut line = serde_json::Map::new();
+    line.insert("ts".to_string(), now.to_rfc3339().into());
+    line.insert("level".to_string(), level.to_string().into());
+    line.insert("target".to_string(), target.into());
+    match structured.and_then(|fields| {
+        serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(fields).ok()
+    }) {
+        Some(fields) => line.extend(fields),
+        None => {
+            line.insert("msg".to_string(), args.into());
+        }
+    }
+    format!("{}\n", serde_json::Value::Object(line))
+}
 
 /// TODO Needs to make check if I have access to folder before I write db.
-pub fn main(log_location: &String) {
+pub fn main(log_location: &String, config: &LogConfig) {
     let log_bool = Path::new(log_location).exists();
     if log_bool {
         fs::remove_file(log_location).unwrap();
     }
+    LOG_STATE.json.store(config.json, Ordering::Relaxed);
+    *LOG_STATE.default_level.write() = config.level;
+
     let fastlog = fast_log::Config::new()
         .chan_len(Some(100000))
         .file(log_location)
         .file_split(
             "logs/",
             Rolling::new(fast_log::plugin::file_split::RollingType::BySize(
-                LogSize::GB(2),
+                LogSize::MB(config.rotate_mb as usize),
             )),
-            KeepType::KeepNum(2),
+            KeepType::KeepNum(config.keep),
             LZ4Packer {},
         )
-        .level(LevelFilter::Info);
+        .format(HydrusFormat)
+        .add_filter(ModuleLevelFilter)
+        // Filtering per module is done by ModuleLevelFilter
+        .level(LevelFilter::Trace);
     fast_log::init(fastlog).unwrap();
     info!("Initing Logger.");
     log::logger().flush();
 }
 
+///
+/// Parses a level name. off, error, warn, info, debug or trace
+///
+pub fn level_parse(level: &str) -> Option<LevelFilter> {
+    LevelFilter::from_str(level).ok()
+}
+
+///
+/// Sets the level for a module path prefix. None sets the default level for our
+/// own modules.
+///
+pub fn level_set(module: Option<&str>, level: LevelFilter) {
+    match module {
+        None => *LOG_STATE.default_level.write() = level,
+        Some(module) => {
+            LOG_STATE.modules.write().insert(module.to_string(), level);
+        }
+    }
+}
+
+///
+/// Removes a module override so it falls back to the default level
+///
+pub fn level_clear(module: &str) -> bool {
+    LOG_STATE.modules.write().remove(module).is_some()
+}
+
+///
+/// Returns the default level and every module override. The default is under the
+/// key "default"
+///
+pub fn levels_get() -> BTreeMap<String, String> {
+    let mut out: BTreeMap<String, String> = LOG_STATE
+        .modules
+        .read()
+        .iter()
+        .map(|(module, level)| (module.clone(), level.to_string()))
+        .collect();
+    out.insert(
+        "default".to_string(),
+        LOG_STATE.default_level.read().to_string(),
+    );
+    out
+}
+
+///
+/// Switches between json and text output
+///
+pub fn json_set(json: bool) {
+    LOG_STATE.json.store(json, Ordering::Relaxed);
+}
+
+///
+/// Turns a source file into the module path it was compiled as
+///
+fn target_from_file(file: &str) -> String {
+    let file = file.trim_start_matches("./");
+    let file = file.strip_prefix("src/").unwrap_or(file);
+    let file = file.strip_suffix(".rs").unwrap_or(file);
+    let file = file.strip_suffix("/mod").unwrap_or(file);
+    if file == "main" {
+        return env!("CARGO_PKG_NAME").to_string();
+    }
+    format!("{}::{}", env!("CARGO_PKG_NAME"), file.replace('/', "::"))
+}
+
+///
+/// Sends a record to the logger with the callers module as the target
+///
+fn log_at(level: Level, location: &Location, msg: &dyn Display) {
+    let target = target_from_file(location.file());
+    if level > level_for(&target) {
+        return;
+    }
+    log::log!(target: target.as_str(), level, "{}", msg);
+}
+
+///
+/// Fields attached to a log line so one job or file can be followed through the logs
+///
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LogCtx {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worker_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scraper: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+impl LogCtx {
+    pub fn new() -> Self {
+        LogCtx::default()
+    }
+
+    ///
+    /// Starts a context for a download worker
+    ///
+    pub fn worker(worker_id: u64) -> Self {
+        LogCtx {
+            worker_id: Some(worker_id),
+            ..Default::default()
+        }
+    }
+
+    pub fn job(mut self, job_id: u64) -> Self {
+        self.job_id = Some(job_id);
+        self
+    }
+
+    pub fn scraper<T: Display>(mut self, scraper: T) -> Self {
+        self.scraper = Some(scraper.to_string());
+        self
+    }
+
+    pub fn url<T: Display>(mut self, url: T) -> Self {
+        self.url = Some(url.to_string());
+        self
+    }
+
+    pub fn hash<T: Display>(mut self, hash: T) -> Self {
+        self.hash = Some(hash.to_string());
+        self
+    }
+
+    ///
+    /// Renders the context and message in the current output format
+    ///
+    fn render<T: Display>(&self, msg: T) -> String {
+        self.render_as(msg, LOG_STATE.json.load(Ordering::Relaxed))
+    }
+
+    fn render_as<T: Display>(&self, msg: T, json: bool) -> String {
+        if json {
+            let mut value = serde_json::to_value(self).unwrap_or_default();
+            if let serde_json::Value::Object(ref mut map) = value {
+                map.insert(
+                    "msg".to_string(),
+                    serde_json::Value::String(msg.to_string()),
+                );
+            }
+            return format!("{}{}", STRUCTURED_MARK, value);
+        }
+
+        let mut out = String::new();
+        if let Some(worker_id) = self.worker_id {
+            out += &format!("Worker: {} ", worker_id);
+        }
+        if let Some(job_id) = self.job_id {
+            out += &format!("JobId: {} ", job_id);
+        }
+        if let Some(scraper) = &self.scraper {
+            out += &format!("Scraper: {} ", scraper);
+        }
+        if let Some(url) = &self.url {
+            out += &format!("Url: {} ", url);
+        }
+        if let Some(hash) = &self.hash {
+            out += &format!("FileHash: {} ", hash);
+        }
+        if out.is_empty() {
+            msg.to_string()
+        } else {
+            format!("{}-- {}", out, msg)
+        }
+    }
+
+    /// Dumps info to log with this context.
+    #[track_caller]
+    pub fn info<T: Display>(&self, info: T) {
+        log_at(Level::Info, Location::caller(), &self.render(info));
+    }
+
+    /// Dumps error to log with this context.
+    #[track_caller]
+    pub fn error<T: Display>(&self, error: T) {
+        log_at(Level::Error, Location::caller(), &self.render(error));
+    }
+
+    /// Dumps debug info to log with this context.
+    #[track_caller]
+    pub fn debug<T: Display>(&self, debug: T) {
+        log_at(Level::Debug, Location::caller(), &self.render(debug));
+    }
+}
+
 /// Dumps error to log and panics.
 pub fn panic_log<T: Display>(error: T) {
     error!("{}", error);
@@ -38,23 +377,105 @@ pub fn panic_log<T: Display>(error: T) {
 }
 
 /// Dumps error to log and doesn't panic.
+#[track_caller]
 pub fn error_log<T: Display>(error: T) {
-    error!("{}", error);
+    log_at(Level::Error, Location::caller(), &error);
 }
 
 /// Dumps error to log and doesn't panic.
 /// Does NOT print anything to the screen
+#[track_caller]
 pub fn error_log_silent<T: Display>(error: T) {
-    error!("{}", error);
+    log_at(Level::Error, Location::caller(), &error);
 }
 
 /// Dumps info to log and prints it.
+#[track_caller]
 pub fn info_log<T: Display>(info: T) {
-    info!("{}", info);
+    log_at(Level::Info, Location::caller(), &info);
     //println!("{}", info);
 }
 
 /// Dumps info to log and DOES NOT prints it.
+#[track_caller]
 pub fn log<T: Display>(info: T) {
-    info!("{}", info);
+    log_at(Level::Info, Location::caller(), &info);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(line: &str) -> serde_json::Value {
+        serde_json::from_str(line.trim_end()).unwrap()
+    }
+
+    #[test]
+    fn log_ctx_renders_in_both_formats() {
+        let ctx = LogCtx::worker(2).job(7).hash("abc");
+        assert_eq!(
+            ctx.render_as("done", false),
+            "Worker: 2 JobId: 7 FileHash: abc -- done"
+        );
+        assert_eq!(LogCtx::new().render_as("done", false), "done");
+
+        let now = chrono::Local::now();
+        let line = record_format(
+            now,
+            Level::Info,
+            "rusthydrus::download",
+            &ctx.render_as("done", true),
+            true,
+        );
+        let line = fields(&line);
+        assert_eq!(line["worker_id"], 2);
+        assert_eq!(line["job_id"], 7);
+        assert_eq!(line["msg"], "done");
+        assert_eq!(line["target"], "rusthydrus::download");
+        assert!(line.get("scraper").is_none());
+
+        // Messages that look like json are still just messages
+        let line = fields(&record_format(
+            now,
+            Level::Info,
+            "rusthydrus",
+            "{\"level\":\"fake\"}",
+            true,
+        ));
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["msg"], "{\"level\":\"fake\"}");
+
+        let text = record_format(
+            now,
+            Level::Info,
+            "rusthydrus",
+            &ctx.render_as("done", true),
+            false,
+        );
+        assert!(!text.contains(STRUCTURED_MARK));
+    }
+
+    #[test]
+    fn longest_module_prefix_wins() {
+        level_set(Some("rusthydrus::leveltest"), LevelFilter::Debug);
+        level_set(Some("rusthydrus::leveltest::inner"), LevelFilter::Error);
+        assert_eq!(level_for("rusthydrus::leveltest"), LevelFilter::Debug);
+        assert_eq!(
+            level_for("rusthydrus::leveltest::other"),
+            LevelFilter::Debug
+        );
+        assert_eq!(
+            level_for("rusthydrus::leveltest::inner::deep"),
+            LevelFilter::Error
+        );
+        // Prefixes only match whole path segments
+        assert_ne!(level_for("rusthydrus::leveltester"), LevelFilter::Debug);
+        assert_eq!(level_for("hyper::client"), EXTERNAL_DEFAULT_LEVEL);
+        assert!(level_clear("rusthydrus::leveltest::inner"));
+        assert_eq!(
+            level_for("rusthydrus::leveltest::inner::deep"),
+            LevelFilter::Debug
+        );
+        level_clear("rusthydrus::leveltest");
+    }
 }
@@ -13,7 +13,7 @@ use std::{
 };
 use tokio::time::Interval;
 
-pub const VERS: u64 = 12;
+pub const VERS: u64 = 20;
 pub const DEFAULT_LOC_NAME: &str = "main.db";
 pub const DEFAULT_LOC_LOGNAME: &str = "log.txt";
 pub const DEFAULT_LOC_PLUGIN: &str = "./target/release";
@@ -21,16 +21,23 @@ pub const DEFAULT_LOC_SCRAPER: &str = "packages";
 extern crate ratelimit;
 
 pub mod cli;
+pub mod daemon;
 pub mod database;
+pub mod declarative;
 pub use database::*; //
 pub mod download;
 pub mod file;
 pub mod globalload;
 pub mod jobs;
 pub mod logging;
+pub mod metrics;
+pub mod pluginabi;
+pub mod pluginhost;
+pub mod pluginverify;
 //#[path = "./scr/plugins.rs"]
 //pub mod plugins;
 pub mod reimport;
+pub mod scrapertest;
 //#[path = "./scr/scraper.rs"]
 //pub mod scraper;
 pub mod downloadlogic;
@@ -43,10 +50,19 @@ pub mod time_func;
 //pub mod bypasses;
 pub mod client;
 pub mod helpers;
+pub mod hotreload;
+pub mod htmlparse;
+pub mod mediainfo;
 pub mod os;
+pub mod s3;
 pub mod server;
+pub mod sidecar;
+#[cfg(test)]
+mod testutil;
 pub mod types;
 pub mod ui;
+pub mod wasmhost;
+pub mod watchfolder;
 
 use database::database::Main;
 
@@ -100,6 +116,13 @@ fn db_file_sanity(dbloc: &str) {
 #[tokio::main]
 async fn main() -> io::Result<()> {
     //console_subscriber::init();
+
+    // Plugin workers are children of a running instance. They must not touch the
+    // log, lock or db of the parent.
+    if let Some((path, socket)) = cli::plugin_worker() {
+        std::process::exit(pluginhost::worker_main(&path, &socket));
+    }
+
     memory_manage();
 
     #[cfg(debug_assertions)]
@@ -126,10 +149,35 @@ async fn main() -> io::Result<()> {
 
     {
         // Makes Logging work
-        logging::main(&DEFAULT_LOC_LOGNAME.to_string());
+        logging::main(&DEFAULT_LOC_LOGNAME.to_string(), &cli::log_config());
     }
     os::check_os_compatibility();
 
+    let daemon_mode = cli::daemon_mode();
+
+    // Control commands go to an instance that's already running instead of
+    // fighting it over the db
+    if let Some(holder) = daemon::InstanceLock::holder(DEFAULT_LOC_NAME)
+        && let Some(api) = holder.api
+        && let Some(out) = cli::remote_main(api).await
+    {
+        if let Err(err) = out {
+            logging::error_log(&err);
+            eprintln!("{}", err);
+        }
+        return Ok(());
+    }
+
+    // Held until we exit so a second instance can't open the same db.
+    let instance_lock = match daemon::InstanceLock::acquire(DEFAULT_LOC_NAME) {
+        Ok(lock) => lock,
+        Err(err) => {
+            logging::error_log(&err);
+            eprintln!("{}", err);
+            return Ok(());
+        }
+    };
+
     // Inits Database.
     let mut database = makedb(DEFAULT_LOC_NAME);
 
@@ -139,10 +187,18 @@ async fn main() -> io::Result<()> {
     let heavy_processing_pool = Arc::new(rayon::ThreadPoolBuilder::new().build().unwrap());
 
     let mut globalload = globalload::GlobalLoad::new(database.clone(), jobmanager.clone());
+    globalload.isolation_set(cli::plugin_isolation());
+    globalload.legacy_set(cli::allow_legacy_plugins());
     {
         database.load_table(&sharedtypes::LoadDBTable::Settings);
         database.load_table(&sharedtypes::LoadDBTable::Jobs);
 
+        // Jobs marked as running can only be left over from a crash.
+        let recovered = database.jobs_recover_stale();
+        if recovered > 0 {
+            logging::info_log(format!("Recovered {} stale jobs", recovered));
+        }
+
         //let mut globalload_data =
         //    plugins::globalload_data::new(plugin_loc.to_string(), database.clone(), jobmanager.clone());
 
@@ -157,14 +213,13 @@ async fn main() -> io::Result<()> {
             heavy_processing_pool.clone(),
         );
 
-        // Spawn the entire asynchronous router/listener pool in the background
-        // without locking up execution flow or using OS thread hacks
+        // Spawns the router/listener pool in the background. Only waits for the API
+        // to bind so commands started after us know where to send their work
         let database_for_ipc = database.clone();
-        tokio::spawn(async move {
-            if let Err(e) = ipc_interact.spawn_listener(database_for_ipc).await {
-                eprintln!("Critical breakdown in IPC listener framework loop: {}", e);
-            }
-        });
+        match ipc_interact.spawn_listener(database_for_ipc).await {
+            Ok(api) => instance_lock.api_set(api),
+            Err(e) => eprintln!("Critical breakdown in IPC listener framework loop: {}", e),
+        }
 
         // Putting this down here after plugin manager because that's when the IPC server
         // starts and we can then inside of the scraper start calling IPC functions
@@ -195,16 +250,16 @@ async fn main() -> io::Result<()> {
         cli::main(database.clone());
     }
 
-    let mut terminal = ratatui::init();
-
-    let (uisender, uireciever) = tokio::sync::mpsc::unbounded_channel();
-
-    let mut app = App::new(uireciever);
+    let (uisender, mut uireciever) = tokio::sync::mpsc::unbounded_channel();
 
     {
         globalload.reload_regex();
         // Calls the on_start func for the plugins
         globalload.pluginscraper_on_start();
+        // Picks up rebuilt scrapers and plugins without a restart
+        globalload.hot_reload_spawn();
+        // Imports whatever lands in the watch folders
+        watchfolder::spawn(database.clone(), globalload.clone());
     }
 
     // A way to get around a mutex lock but it works lol
@@ -233,6 +288,8 @@ async fn main() -> io::Result<()> {
     // Creates a threadhandler that manages callable threads.
     //let mut threadhandler = threading::Threads::new(Arc::new(uisender.clone()));
     let tokio_handle = tokio::runtime::Handle::current();
+    let shutdown_db = database.clone();
+    let database_ui = database.clone();
     let mut threadhandler = Arc::new(Mutex::new(DownloadManager::new(
         uisender.into(),
         database,
@@ -290,10 +347,24 @@ async fn main() -> io::Result<()> {
         }
     });
 
-    let app_result = app.run(&mut terminal).await;
-
-    //thread_handle.join().unwrap();
-    ratatui::restore();
+    let app_result = if daemon_mode {
+        logging::info_log("Running in daemon mode".to_string());
+        // Nothing reads the ui messages so just drain them.
+        tokio::spawn(async move { while uireciever.recv().await.is_some() {} });
+        daemon::wait_for_shutdown().await;
+        Ok(())
+    } else {
+        let mut terminal = ratatui::init();
+        let mut app = App::new(uireciever, database_ui, jobmanager.clone());
+        let app_result = tokio::select! {
+            app_result = app.run(&mut terminal) => app_result,
+            _ = daemon::wait_for_shutdown() => Ok(()),
+        };
+        ratatui::restore();
+        app_result
+    };
+
+    daemon::graceful_shutdown(&jobmanager, &shutdown_db).await;
     app_result
     /*
 
@@ -0,0 +1,113 @@
+use std::io::Read;
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How long ffprobe gets before it's killed. Broken files can make it hang
+const FFPROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+///
+/// Size and length of an image, video or audio file. Anything that couldn't be read is None
+///
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MediaInfo {
+    pub width: Option<u64>,
+    pub height: Option<u64>,
+    /// Seconds
+    pub duration: Option<f64>,
+}
+
+///
+/// Reads what it can out of bytes. Images only need their header read, everything else
+/// goes through ffprobe when it's installed
+///
+pub fn probe(bytes: &[u8]) -> MediaInfo {
+    if let Ok(size) = imagesize::blob_size(bytes) {
+        return MediaInfo {
+            width: Some(size.width as u64),
+            height: Some(size.height as u64),
+            duration: None,
+        };
+    }
+    ffprobe(bytes).unwrap_or_default()
+}
+
+///
+/// Pipes bytes into ffprobe and reads the first video stream's size and the container length.
+/// Gives up and kills ffprobe after FFPROBE_TIMEOUT
+///
+fn ffprobe(bytes: &[u8]) -> Option<MediaInfo> {
+    let mut child = Command::new("ffprobe")
+        .args([
+            "-v",
+            "quiet",
+            "-print_format",
+            "json",
+            "-show_entries",
+            "stream=width,height:format=duration",
+            "-i",
+            "pipe:0",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let mut stdin = child.stdin.take()?;
+    let mut stdout = child.stdout.take()?;
+    let deadline = Instant::now() + FFPROBE_TIMEOUT;
+    // ffprobe can stop reading early so the pipes get handled off thread to not block on them
+    let output = std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let _ = stdin.write_all(bytes);
+        });
+        let reader = scope.spawn(move || {
+            let mut output = Vec::new();
+            let _ = stdout.read_to_end(&mut output);
+            output
+        });
+        loop {
+            match child.try_wait() {
+                Ok(Some(_)) => break,
+                Ok(None) if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(10))
+                }
+                _ => {
+                    // Killing it closes the pipes so the threads above can finish
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return None;
+                }
+            }
+        }
+        reader.join().ok()
+    })?;
+
+    let json: serde_json::Value = serde_json::from_slice(&output).ok()?;
+    let stream = json["streams"]
+        .as_array()?
+        .iter()
+        .find(|stream| stream["width"].is_u64());
+    Some(MediaInfo {
+        width: stream.and_then(|stream| stream["width"].as_u64()),
+        height: stream.and_then(|stream| stream["height"].as_u64()),
+        duration: json["format"]["duration"]
+            .as_str()
+            .and_then(|duration| duration.parse().ok()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_size_from_header() {
+        let mut gif = b"GIF89a\x02\x00\x03\x00".to_vec();
+        gif.resize(64, 0);
+        let info = probe(&gif);
+        assert_eq!((info.width, info.height), (Some(2), Some(3)));
+    }
+}
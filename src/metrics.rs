@@ -0,0 +1,309 @@
+use crate::RwLock;
+use crate::jobs::Jobs;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// Seconds. Covers fast single row writes up to long batch imports
+const DURATION_BUCKETS: [f64; 12] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0,
+];
+
+///
+/// Counter split by a single label
+///
+#[derive(Default)]
+struct LabeledCounter {
+    values: RwLock<BTreeMap<String, u64>>,
+}
+
+impl LabeledCounter {
+    fn inc_by(&self, label: &str, amount: u64) {
+        let mut values = self.values.write();
+        if let Some(value) = values.get_mut(label) {
+            *value += amount;
+            return;
+        }
+        values.insert(label.to_string(), amount);
+    }
+
+    fn render(&self, out: &mut String, name: &str, label_name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        for (label, value) in self.values.read().iter() {
+            let _ = writeln!(
+                out,
+                "{}{{{}=\"{}\"}} {}",
+                name,
+                label_name,
+                escape_label(label),
+                value
+            );
+        }
+    }
+}
+
+///
+/// Histogram split by a single label
+///
+#[derive(Default)]
+struct LabeledHistogram {
+    // label -> (bucket counts, sum, count)
+    values: RwLock<BTreeMap<String, ([u64; DURATION_BUCKETS.len()], f64, u64)>>,
+}
+
+impl LabeledHistogram {
+    fn observe(&self, label: &str, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        let mut values = self.values.write();
+        let entry =
+            values
+                .entry(label.to_string())
+                .or_insert(([0; DURATION_BUCKETS.len()], 0.0, 0));
+        for (cnt, bucket) in DURATION_BUCKETS.iter().enumerate() {
+            if secs <= *bucket {
+                entry.0[cnt] += 1;
+            }
+        }
+        entry.1 += secs;
+        entry.2 += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, label_name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (label, (buckets, sum, count)) in self.values.read().iter() {
+            let label = escape_label(label);
+            for (cnt, bucket) in DURATION_BUCKETS.iter().enumerate() {
+                let _ = writeln!(
+                    out,
+                    "{}_bucket{{{}=\"{}\",le=\"{}\"}} {}",
+                    name, label_name, label, bucket, buckets[cnt]
+                );
+            }
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}=\"{}\",le=\"+Inf\"}} {}",
+                name, label_name, label, count
+            );
+            let _ = writeln!(out, "{}_sum{{{}=\"{}\"}} {}", name, label_name, label, sum);
+            let _ = writeln!(
+                out,
+                "{}_count{{{}=\"{}\"}} {}",
+                name, label_name, label, count
+            );
+        }
+    }
+}
+
+///
+/// All of the telemetry we collect. Lives for the whole program
+///
+#[derive(Default)]
+struct Metrics {
+    downloaded_bytes: LabeledCounter,
+    downloaded_files: LabeledCounter,
+    http_status: LabeledCounter,
+    ratelimit_waits: AtomicU64,
+    ratelimit_wait_micros: AtomicU64,
+    enclave_actions: LabeledCounter,
+    db_write_lock: LabeledHistogram,
+    roaring_cache: LabeledCounter,
+    search: LabeledHistogram,
+}
+
+static METRICS: std::sync::LazyLock<Metrics> = std::sync::LazyLock::new(Metrics::default);
+
+/// Escapes a label value for the prometheus text format
+fn escape_label(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+///
+/// Records a file that finished downloading for a scraper
+///
+pub fn download_record(scraper: Option<&sharedtypes::GlobalPluginScraper>, bytes: u64) {
+    let name = scraper
+        .map(|scraper| scraper.name.as_str())
+        .unwrap_or("none");
+    METRICS.downloaded_bytes.inc_by(name, bytes);
+    METRICS.downloaded_files.inc_by(name, 1);
+}
+
+///
+/// Records the status code of a http response
+///
+pub fn http_status_record(status: u16) {
+    METRICS.http_status.inc_by(&status.to_string(), 1);
+}
+
+///
+/// Records a time the ratelimiter made us wait
+///
+pub fn ratelimit_wait_record(waited: Duration) {
+    METRICS.ratelimit_waits.fetch_add(1, Ordering::Relaxed);
+    METRICS
+        .ratelimit_wait_micros
+        .fetch_add(waited.as_micros() as u64, Ordering::Relaxed);
+}
+
+///
+/// Records an enclave action being ran
+///
+pub fn enclave_action_record(action: &sharedtypes::EnclaveAction) {
+    let name = match action {
+        sharedtypes::EnclaveAction::DownloadToLocation(_) => "DownloadToLocation",
+        sharedtypes::EnclaveAction::AddTagAndNamespace(_) => "AddTagAndNamespace",
+        sharedtypes::EnclaveAction::DownloadToDefault => "DownloadToDefault",
+        sharedtypes::EnclaveAction::PutAtDefault => "PutAtDefault",
+        sharedtypes::EnclaveAction::Skip => "Skip",
+    };
+    METRICS.enclave_actions.inc_by(name, 1);
+}
+
+///
+/// Records how long the write connection was held
+///
+pub fn db_write_lock_record(duration: Duration) {
+    METRICS.db_write_lock.observe("write", duration);
+}
+
+///
+/// Records if the roaring cache answered out of memory or had to go to the db
+///
+pub fn roaring_cache_record(hit: bool) {
+    METRICS
+        .roaring_cache
+        .inc_by(if hit { "hit" } else { "miss" }, 1);
+}
+
+///
+/// Records how long a search took. backend is either sql or roaring
+///
+pub fn search_record(backend: &str, duration: Duration) {
+    METRICS.search.observe(backend, duration);
+}
+
+///
+/// Renders all metrics in the prometheus text format
+///
+pub fn render(jobs: Option<&Jobs>) -> String {
+    let mut out = String::new();
+
+    METRICS.downloaded_bytes.render(
+        &mut out,
+        "hydrus_downloaded_bytes_total",
+        "scraper",
+        "Bytes downloaded per scraper",
+    );
+    METRICS.downloaded_files.render(
+        &mut out,
+        "hydrus_downloaded_files_total",
+        "scraper",
+        "Files downloaded per scraper",
+    );
+    METRICS.http_status.render(
+        &mut out,
+        "hydrus_http_responses_total",
+        "code",
+        "HTTP responses by status code",
+    );
+
+    let _ = writeln!(
+        out,
+        "# HELP hydrus_ratelimit_waits_total Times the ratelimiter made a request wait"
+    );
+    let _ = writeln!(out, "# TYPE hydrus_ratelimit_waits_total counter");
+    let _ = writeln!(
+        out,
+        "hydrus_ratelimit_waits_total {}",
+        METRICS.ratelimit_waits.load(Ordering::Relaxed)
+    );
+    let _ = writeln!(
+        out,
+        "# HELP hydrus_ratelimit_wait_seconds_total Time spent waiting on the ratelimiter"
+    );
+    let _ = writeln!(out, "# TYPE hydrus_ratelimit_wait_seconds_total counter");
+    let _ = writeln!(
+        out,
+        "hydrus_ratelimit_wait_seconds_total {}",
+        METRICS.ratelimit_wait_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0
+    );
+
+    if let Some(jobs) = jobs {
+        let mut depth: BTreeMap<String, u64> = BTreeMap::new();
+        for (scraper, _) in jobs.jobs_get_all_loaded() {
+            *depth.entry(scraper.name).or_insert(0) += 1;
+        }
+        let _ = writeln!(
+            out,
+            "# HELP hydrus_job_queue_depth Jobs currently queued per scraper"
+        );
+        let _ = writeln!(out, "# TYPE hydrus_job_queue_depth gauge");
+        for (scraper, count) in depth.iter() {
+            let _ = writeln!(
+                out,
+                "hydrus_job_queue_depth{{scraper=\"{}\"}} {}",
+                escape_label(scraper),
+                count
+            );
+        }
+    }
+
+    METRICS.enclave_actions.render(
+        &mut out,
+        "hydrus_enclave_actions_total",
+        "action",
+        "Enclave actions ran",
+    );
+    METRICS.db_write_lock.render(
+        &mut out,
+        "hydrus_db_write_lock_held_seconds",
+        "conn",
+        "Time the db write connection was held",
+    );
+    METRICS.roaring_cache.render(
+        &mut out,
+        "hydrus_roaring_cache_total",
+        "result",
+        "Roaring relationship cache lookups",
+    );
+    METRICS.search.render(
+        &mut out,
+        "hydrus_search_seconds",
+        "backend",
+        "File search latency by backend",
+    );
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let hist = LabeledHistogram::default();
+        hist.observe("sql", Duration::from_millis(3));
+        hist.observe("sql", Duration::from_secs(10));
+
+        let mut out = String::new();
+        hist.render(&mut out, "test_seconds", "backend", "test");
+
+        assert!(out.contains("test_seconds_bucket{backend=\"sql\",le=\"0.001\"} 0"));
+        assert!(out.contains("test_seconds_bucket{backend=\"sql\",le=\"0.005\"} 1"));
+        assert!(out.contains("test_seconds_bucket{backend=\"sql\",le=\"5\"} 1"));
+        assert!(out.contains("test_seconds_bucket{backend=\"sql\",le=\"+Inf\"} 2"));
+        assert!(out.contains("test_seconds_count{backend=\"sql\"} 2"));
+    }
+
+    #[test]
+    fn labels_get_escaped() {
+        assert_eq!(escape_label("a\"b\\c"), "a\\\"b\\\\c");
+    }
+}
@@ -11,3 +11,26 @@ pub fn check_os_compatibility() {
         logging::panic_log("UNKNOWN OS Detected. PANICING".to_string());
     }
 }
+
+///
+/// Bytes free for us on the disk that holds path. None if it can't be read
+///
+#[cfg(unix)]
+pub fn free_space(path: &std::path::Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+///
+/// No statvfs here so free space is never known
+///
+#[cfg(not(unix))]
+pub fn free_space(_path: &std::path::Path) -> Option<u64> {
+    None
+}
@@ -0,0 +1,124 @@
+use libloading::Library;
+use sharedtypes::abi::{
+    ABI_CALL_SYMBOL, ABI_FREE_SYMBOL, ABI_VERSION_SYMBOL, AbiCallFn, AbiFreeFn, AbiStatus,
+    AbiVersionFn, abi_decode, abi_encode, abi_version_check,
+};
+use std::fmt;
+
+///
+/// Errors from calling a plugin over the stable ABI
+///
+#[derive(Debug)]
+pub enum AbiError {
+    // Plugin only has the old raw rust exports
+    NotStable,
+    // Plugin speaks the stable ABI but doesn't export this function
+    NotExported,
+    // Plugin returned a non ok status
+    Status(u32),
+    // Couldn't decode what the plugin sent back
+    Decode(String),
+    // Out of process worker crashed, timed out or is unhealthy
+    Worker(String),
+    // Wasm plugin trapped or couldn't be instantiated
+    Wasm(String),
+}
+
+impl fmt::Display for AbiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbiError::NotStable => write!(f, "plugin does not export the stable plugin ABI"),
+            AbiError::NotExported => write!(f, "function is not exported by the plugin"),
+            AbiError::Status(status) => {
+                let reason = if *status == AbiStatus::UnknownFunction as u32 {
+                    "function is not exported by the plugin"
+                } else if *status == AbiStatus::DecodeError as u32 {
+                    "plugin could not decode the arguments"
+                } else if *status == AbiStatus::Panic as u32 {
+                    "plugin panicked"
+                } else {
+                    "unknown status"
+                };
+                write!(f, "plugin call failed with status {}: {}", status, reason)
+            }
+            AbiError::Decode(err) => write!(f, "could not decode plugin output: {}", err),
+            AbiError::Worker(err) => write!(f, "plugin worker failed: {}", err),
+            AbiError::Wasm(err) => write!(f, "wasm plugin failed: {}", err),
+        }
+    }
+}
+
+///
+/// Checks the version handshake of a freshly loaded library.
+/// Ok(true) if it speaks our ABI, Ok(false) if it only has legacy exports and Err if
+/// it was built for a different ABI version.
+///
+pub fn abi_check(lib: &Library) -> Result<bool, String> {
+    let version: libloading::Symbol<AbiVersionFn> = match unsafe { lib.get(ABI_VERSION_SYMBOL) } {
+        Ok(version) => version,
+        Err(_) => return Ok(false),
+    };
+    abi_version_check(unsafe { version() })?;
+    unsafe {
+        if lib.get::<AbiCallFn>(ABI_CALL_SYMBOL).is_err()
+            || lib.get::<AbiFreeFn>(ABI_FREE_SYMBOL).is_err()
+        {
+            return Err(
+                "plugin reports a plugin ABI version but is missing hydrus_plugin_call or hydrus_plugin_free"
+                    .to_string(),
+            );
+        }
+    }
+    Ok(true)
+}
+
+///
+/// Calls a function inside of a plugin over the stable ABI.
+/// input is the single argument or a tuple of the arguments in order. Use () for none.
+///
+pub fn abi_call<I, O>(lib: &Library, func: &str, input: &I) -> Result<O, AbiError>
+where
+    I: bitcode::Encode + ?Sized,
+    O: bitcode::DecodeOwned,
+{
+    let (status, data) = abi_call_raw(lib, func, &abi_encode(input))?;
+    abi_status_decode(status, &data)
+}
+
+///
+/// Calls a plugin with already encoded arguments. Returns the status and the raw
+/// output. Used by the plugin worker process which never decodes anything itself.
+///
+pub fn abi_call_raw(lib: &Library, func: &str, input: &[u8]) -> Result<(u32, Vec<u8>), AbiError> {
+    let (call, free) = unsafe {
+        match (
+            lib.get::<AbiCallFn>(ABI_CALL_SYMBOL),
+            lib.get::<AbiFreeFn>(ABI_FREE_SYMBOL),
+        ) {
+            (Ok(call), Ok(free)) => (call, free),
+            _ => return Err(AbiError::NotStable),
+        }
+    };
+
+    let result = unsafe { call(func.as_ptr(), func.len(), input.as_ptr(), input.len()) };
+    let data = unsafe { result.data.as_slice() }.to_vec();
+
+    // Buffer was allocated by the plugin so it has to free it
+    unsafe { free(result.data) };
+    Ok((result.status, data))
+}
+
+///
+/// Turns a status and raw output from a plugin into the decoded output
+///
+pub fn abi_status_decode<O: bitcode::DecodeOwned>(status: u32, data: &[u8]) -> Result<O, AbiError> {
+    // Never falls back to a legacy symbol. Calling one with a type the plugin wasn't
+    // built with is undefined behaviour
+    if status == AbiStatus::UnknownFunction as u32 {
+        return Err(AbiError::NotExported);
+    }
+    if status != AbiStatus::Ok as u32 {
+        return Err(AbiError::Status(status));
+    }
+    abi_decode::<O>(data).map_err(|err| AbiError::Decode(err.to_string()))
+}
@@ -0,0 +1,545 @@
+use crate::logging;
+use crate::pluginabi::{self, AbiError};
+use crate::{Mutex, RwLock};
+use crossbeam_channel::{RecvTimeoutError, Sender};
+use interprocess::local_socket::{
+    GenericNamespaced, ListenerOptions, RecvHalf, SendHalf, Stream, ToNsName, prelude::*,
+};
+use libloading::Library;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Longest a single plugin call can take before the worker is killed
+pub const CALL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How long a freshly spawned worker gets to open its socket
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Crashes inside of CRASH_WINDOW before a plugin gets marked unhealthy
+const MAX_CRASHES: usize = 3;
+const CRASH_WINDOW: Duration = Duration::from_secs(600);
+
+// Anything bigger than this is a corrupt stream
+const MAX_FRAME: u32 = 1 << 30;
+
+// Statuses only a worker sends back. Kept away from the ones plugins use
+const STATUS_NOT_STABLE: u32 = u32::MAX;
+const STATUS_TOO_LARGE: u32 = u32::MAX - 1;
+
+static WORKER_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+///
+/// Request sent from the host to a worker
+///
+#[derive(Debug, bitcode::Encode, bitcode::Decode)]
+struct WorkerRequest {
+    id: u64,
+    func: String,
+    input: Vec<u8>,
+}
+
+///
+/// Response from a worker. status is a sharedtypes::abi::AbiStatus
+///
+#[derive(Debug, bitcode::Encode, bitcode::Decode)]
+struct WorkerResponse {
+    id: u64,
+    status: u32,
+    data: Vec<u8>,
+}
+
+fn frame_write<W: Write, T: bitcode::Encode>(writer: &mut W, item: &T) -> io::Result<()> {
+    let data = bitcode::encode(item);
+    // Refused before anything gets written so the stream stays usable
+    if data.len() > MAX_FRAME as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Frame of {} bytes is too large", data.len()),
+        ));
+    }
+    writer.write_all(&(data.len() as u32).to_le_bytes())?;
+    writer.write_all(&data)?;
+    writer.flush()
+}
+
+fn frame_read<R: Read, T: bitcode::DecodeOwned>(reader: &mut R) -> io::Result<T> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len);
+    if len > MAX_FRAME {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Frame of {} bytes is too large", len),
+        ));
+    }
+    let mut data = vec![0u8; len as usize];
+    reader.read_exact(&mut data)?;
+    bitcode::decode(&data).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+///
+/// Crash bookkeeping shared with the reader thread
+///
+#[derive(Default)]
+struct WorkerHealth {
+    crashes: Mutex<Vec<Instant>>,
+    unhealthy: AtomicBool,
+    // Set when we're killing the worker on purpose
+    stopping: AtomicBool,
+}
+
+///
+/// One running child process
+///
+struct WorkerConn {
+    child: Child,
+    send: SendHalf,
+    pending: Arc<Mutex<HashMap<u64, Sender<WorkerResponse>>>>,
+    alive: Arc<AtomicBool>,
+}
+
+impl WorkerConn {
+    fn kill(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+///
+/// Runs a plugin or scraper library inside of a child process so a panic or
+/// segfault only takes down the child. Restarted on the next call after a crash.
+///
+pub struct PluginWorker {
+    path: PathBuf,
+    conn: Mutex<Option<WorkerConn>>,
+    health: Arc<WorkerHealth>,
+    next_id: AtomicU64,
+    // on_start input so long running startup threads come back after a restart
+    on_start: RwLock<Option<Vec<u8>>>,
+    // path is a private copy that goes away with the worker
+    owns_path: AtomicBool,
+}
+
+impl PluginWorker {
+    ///
+    /// Spawns the worker for a library. Errors if it can't be started at all.
+    ///
+    pub fn spawn(path: &Path) -> Result<Arc<Self>, String> {
+        let worker = PluginWorker {
+            path: path.to_path_buf(),
+            conn: Mutex::new(None),
+            health: Arc::new(WorkerHealth::default()),
+            next_id: AtomicU64::new(0),
+            on_start: RwLock::new(None),
+            owns_path: AtomicBool::new(false),
+        };
+        *worker.conn.lock() = Some(worker.start()?);
+        Ok(Arc::new(worker))
+    }
+
+    ///
+    /// Removes the library file once the worker is dropped. For shadow copies that
+    /// only the worker still needs
+    ///
+    pub fn owns_path(&self) {
+        self.owns_path.store(true, Ordering::Relaxed);
+    }
+
+    ///
+    /// If this plugin crashed too often to keep restarting it
+    ///
+    pub fn is_unhealthy(&self) -> bool {
+        self.health.unhealthy.load(Ordering::Relaxed)
+    }
+
+    ///
+    /// Starts the child process and connects to it
+    ///
+    fn start(&self) -> Result<WorkerConn, String> {
+        let name = format!(
+            "rusthydrus-plugin-{}-{}.sock",
+            std::process::id(),
+            WORKER_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let exe = std::env::current_exe().map_err(|err| err.to_string())?;
+        let mut child = Command::new(exe)
+            .arg("--plugin-worker")
+            .arg(&self.path)
+            .arg("--plugin-socket")
+            .arg(&name)
+            .stdin(Stdio::null())
+            .spawn()
+            .map_err(|err| format!("Cannot spawn plugin worker: {}", err))?;
+
+        let start = Instant::now();
+        let stream = loop {
+            let ns_name = name
+                .as_str()
+                .to_ns_name::<GenericNamespaced>()
+                .map_err(|err| err.to_string())?;
+            match Stream::connect(ns_name) {
+                Ok(stream) => break stream,
+                Err(err) => {
+                    if let Ok(Some(status)) = child.try_wait() {
+                        return Err(format!("Plugin worker exited on startup with {}", status));
+                    }
+                    if start.elapsed() > CONNECT_TIMEOUT {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        return Err(format!("Could not connect to plugin worker: {}", err));
+                    }
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+            }
+        };
+
+        let (recv, send) = stream.split();
+        let pending: Arc<Mutex<HashMap<u64, Sender<WorkerResponse>>>> =
+            Arc::new(Mutex::new(HashMap::new()));
+        let alive = Arc::new(AtomicBool::new(true));
+        self.health.stopping.store(false, Ordering::Relaxed);
+
+        {
+            let pending = pending.clone();
+            let alive = alive.clone();
+            let health = self.health.clone();
+            let path = self.path.clone();
+            std::thread::spawn(move || reader_loop(recv, pending, alive, health, path));
+        }
+
+        let mut conn = WorkerConn {
+            child,
+            send,
+            pending,
+            alive,
+        };
+
+        // Brings back whatever the plugin was doing on startup
+        if let Some(input) = self.on_start.read().clone() {
+            let request = WorkerRequest {
+                id: self.next_id.fetch_add(1, Ordering::Relaxed),
+                func: "on_start".to_string(),
+                input,
+            };
+            if let Err(err) = frame_write(&mut conn.send, &request) {
+                logging::error_log(format!(
+                    "Could not rerun on_start for plugin worker: {} {}",
+                    self.path.display(),
+                    err
+                ));
+            }
+        }
+
+        Ok(conn)
+    }
+
+    ///
+    /// Calls a function in the worker. timeout of None waits forever which is
+    /// only meant for on_start.
+    ///
+    pub fn call<I, O>(
+        &self,
+        func: &str,
+        input: &I,
+        timeout: Option<Duration>,
+    ) -> Result<O, AbiError>
+    where
+        I: bitcode::Encode + ?Sized,
+        O: bitcode::DecodeOwned,
+    {
+        if self.is_unhealthy() {
+            return Err(AbiError::Worker(format!(
+                "{} is marked unhealthy after repeated crashes",
+                self.path.display()
+            )));
+        }
+
+        let input = sharedtypes::abi::abi_encode(input);
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, reciever) = crossbeam_channel::bounded(1);
+        {
+            let mut conn = self.conn.lock();
+            let dead = conn
+                .as_ref()
+                .is_none_or(|conn| !conn.alive.load(Ordering::Relaxed));
+            if dead {
+                if let Some(mut old) = conn.take() {
+                    old.kill();
+                }
+                logging::info_log(format!("Restarting plugin worker: {}", self.path.display()));
+                *conn = Some(self.start().map_err(AbiError::Worker)?);
+            }
+            let Some(conn) = conn.as_mut() else {
+                return Err(AbiError::Worker("Worker is not running".to_string()));
+            };
+            conn.pending.lock().insert(id, sender);
+            let request = WorkerRequest {
+                id,
+                func: func.to_string(),
+                input,
+            };
+            if let Err(err) = frame_write(&mut conn.send, &request) {
+                conn.pending.lock().remove(&id);
+                if err.kind() == io::ErrorKind::InvalidInput {
+                    return Err(AbiError::Worker(format!(
+                        "input for {} is too large to send to the worker. {}",
+                        func, err
+                    )));
+                }
+                return Err(AbiError::Worker(err.to_string()));
+            }
+            // Stored after sending so a restart in this call doesn't run it twice
+            if func == "on_start" {
+                *self.on_start.write() = Some(request.input);
+            }
+        }
+
+        let response = match timeout {
+            None => reciever.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            Some(timeout) => reciever.recv_timeout(timeout),
+        };
+        match response {
+            Ok(response) => response_decode(func, response.status, &response.data),
+            Err(RecvTimeoutError::Timeout) => {
+                logging::error_log(format!(
+                    "Plugin worker: {} took longer than {:?} on {}. Killing it.",
+                    self.path.display(),
+                    timeout.unwrap_or_default(),
+                    func
+                ));
+                // Reader thread sees the socket close and records the crash
+                if let Some(conn) = self.conn.lock().as_mut() {
+                    conn.kill();
+                }
+                Err(AbiError::Worker(format!("{} timed out", func)))
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(AbiError::Worker(format!(
+                "worker crashed while running {}",
+                func
+            ))),
+        }
+    }
+}
+
+impl Drop for PluginWorker {
+    fn drop(&mut self) {
+        self.health.stopping.store(true, Ordering::Relaxed);
+        if let Some(conn) = self.conn.lock().as_mut() {
+            conn.kill();
+        }
+        if self.owns_path.load(Ordering::Relaxed) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+///
+/// Decodes a worker response
+///
+fn response_decode<O: bitcode::DecodeOwned>(
+    func: &str,
+    status: u32,
+    data: &[u8],
+) -> Result<O, AbiError> {
+    match status {
+        STATUS_NOT_STABLE => Err(AbiError::Worker(format!(
+            "worker could not call {} over the stable plugin ABI",
+            func
+        ))),
+        STATUS_TOO_LARGE => Err(AbiError::Worker(format!(
+            "output of {} is too large to send back from the worker",
+            func
+        ))),
+        _ => pluginabi::abi_status_decode(status, data),
+    }
+}
+
+///
+/// Hands responses to whoever is waiting on them. Records a crash when the worker
+/// goes away without us asking it to.
+///
+fn reader_loop(
+    mut recv: RecvHalf,
+    pending: Arc<Mutex<HashMap<u64, Sender<WorkerResponse>>>>,
+    alive: Arc<AtomicBool>,
+    health: Arc<WorkerHealth>,
+    path: PathBuf,
+) {
+    loop {
+        match frame_read::<_, WorkerResponse>(&mut recv) {
+            Ok(response) => {
+                if let Some(sender) = pending.lock().remove(&response.id) {
+                    let _ = sender.send(response);
+                }
+            }
+            Err(err) => {
+                alive.store(false, Ordering::Relaxed);
+                // Dropping the senders wakes every caller with a disconnect
+                pending.lock().clear();
+                if health.stopping.load(Ordering::Relaxed) {
+                    return;
+                }
+                logging::error_log(format!(
+                    "Plugin worker for {} died: {}",
+                    path.display(),
+                    err
+                ));
+                let mut crashes = health.crashes.lock();
+                crashes.retain(|crash| crash.elapsed() < CRASH_WINDOW);
+                crashes.push(Instant::now());
+                if crashes.len() >= MAX_CRASHES {
+                    health.unhealthy.store(true, Ordering::Relaxed);
+                    logging::error_log(format!(
+                        "Plugin {} crashed {} times in {:?}. Marking it unhealthy and not restarting it.",
+                        path.display(),
+                        crashes.len(),
+                        CRASH_WINDOW
+                    ));
+                }
+                return;
+            }
+        }
+    }
+}
+
+///
+/// Entry point of the child process. Loads one library and serves calls to it
+/// until the host hangs up.
+///
+pub fn worker_main(path: &Path, socket: &str) -> i32 {
+    let lib = match unsafe { Library::new(path) } {
+        Ok(lib) => lib,
+        Err(err) => {
+            eprintln!("Plugin worker cannot load {}: {}", path.display(), err);
+            return 2;
+        }
+    };
+    match pluginabi::abi_check(&lib) {
+        Ok(true) => {}
+        Ok(false) => {
+            eprintln!(
+                "Plugin worker cannot run {} it doesn't export the stable plugin ABI",
+                path.display()
+            );
+            return 2;
+        }
+        Err(err) => {
+            eprintln!("Plugin worker cannot run {}: {}", path.display(), err);
+            return 2;
+        }
+    }
+
+    let listener = match socket
+        .to_ns_name::<GenericNamespaced>()
+        .map_err(|err| err.to_string())
+        .and_then(|name| {
+            ListenerOptions::new()
+                .name(name)
+                .create_sync()
+                .map_err(|err| err.to_string())
+        }) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("Plugin worker cannot listen on {}: {}", socket, err);
+            return 2;
+        }
+    };
+    // Only the host that spawned us ever connects
+    let stream = match listener.accept() {
+        Ok(stream) => stream,
+        Err(err) => {
+            eprintln!("Plugin worker accept failed: {}", err);
+            return 2;
+        }
+    };
+    drop(listener);
+
+    let lib = Arc::new(lib);
+    let (mut recv, send) = stream.split();
+    let send = Arc::new(Mutex::new(send));
+
+    // Each call gets its own thread so a long on_start doesn't block everything else
+    while let Ok(request) = frame_read::<_, WorkerRequest>(&mut recv) {
+        let lib = lib.clone();
+        let send = send.clone();
+        std::thread::spawn(move || {
+            let (status, data) = match pluginabi::abi_call_raw(&lib, &request.func, &request.input)
+            {
+                Ok(out) => out,
+                Err(_) => (STATUS_NOT_STABLE, Vec::new()),
+            };
+            let response = WorkerResponse {
+                id: request.id,
+                status,
+                data,
+            };
+            let mut send = send.lock();
+            // Too much output fails the one call instead of looking like a crash
+            let written = match frame_write(&mut *send, &response) {
+                Err(err) if err.kind() == io::ErrorKind::InvalidInput => frame_write(
+                    &mut *send,
+                    &WorkerResponse {
+                        id: request.id,
+                        status: STATUS_TOO_LARGE,
+                        data: Vec::new(),
+                    },
+                ),
+                written => written,
+            };
+            if written.is_err() {
+                std::process::exit(0);
+            }
+        });
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_roundtrip() {
+        let mut buf = Vec::new();
+        let request = WorkerRequest {
+            id: 7,
+            func: "parser".to_string(),
+            input: vec![1, 2, 3],
+        };
+        frame_write(&mut buf, &request).unwrap();
+        let out: WorkerRequest = frame_read(&mut buf.as_slice()).unwrap();
+        assert_eq!(out.id, 7);
+        assert_eq!(out.func, "parser");
+        assert_eq!(out.input, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn oversized_frame_is_rejected() {
+        let buf = (MAX_FRAME + 1).to_le_bytes();
+        let out: io::Result<WorkerRequest> = frame_read(&mut buf.as_slice());
+        assert!(out.is_err());
+    }
+
+    #[test]
+    fn worker_statuses_are_errors() {
+        let unknown = sharedtypes::abi::AbiStatus::UnknownFunction as u32;
+        assert!(matches!(
+            response_decode::<()>("on_start", unknown, &[]),
+            Err(AbiError::NotExported)
+        ));
+        assert!(matches!(
+            response_decode::<()>("parser", STATUS_TOO_LARGE, &[]),
+            Err(AbiError::Worker(_))
+        ));
+        assert!(matches!(
+            response_decode::<()>("parser", STATUS_NOT_STABLE, &[]),
+            Err(AbiError::Worker(_))
+        ));
+    }
+}
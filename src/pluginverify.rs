@@ -0,0 +1,119 @@
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+///
+/// What we found when checking a library's detached signature
+///
+#[derive(Debug, PartialEq, Eq)]
+pub enum SignatureStatus {
+    /// No <library>.sig next to the library
+    Unsigned,
+    /// Signed by one of the trusted keys
+    Trusted,
+    /// Has a signature but it doesn't verify against any trusted key
+    Invalid(String),
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+///
+/// Where the detached signature for a library lives
+///
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut sig_path = path.as_os_str().to_owned();
+    sig_path.push(".sig");
+    PathBuf::from(sig_path)
+}
+
+fn hex_decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+///
+/// Parses a hex ed25519 public key
+///
+pub fn key_parse(text: &str) -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = hex_decode(text)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| format!("{} is not a 32 byte hex key", text))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|err| err.to_string())
+}
+
+///
+/// Checks the library bytes against the hex signature in <library>.sig.
+/// Keys that don't parse are skipped.
+///
+pub fn signature_check(path: &Path, data: &[u8], trusted: &[String]) -> SignatureStatus {
+    let sig_path = signature_path(path);
+    let Ok(sig_text) = std::fs::read_to_string(&sig_path) else {
+        return SignatureStatus::Unsigned;
+    };
+    signature_verify(data, &sig_text, trusted)
+}
+
+fn signature_verify(data: &[u8], sig_text: &str, trusted: &[String]) -> SignatureStatus {
+    let Some(signature) = hex_decode(sig_text)
+        .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+        .map(|bytes| Signature::from_bytes(&bytes))
+    else {
+        return SignatureStatus::Invalid("signature is not 64 hex encoded bytes".to_string());
+    };
+    if trusted.is_empty() {
+        return SignatureStatus::Invalid("no trusted keys are set".to_string());
+    }
+    for key in trusted.iter().filter_map(|key| key_parse(key).ok()) {
+        if key.verify(data, &signature).is_ok() {
+            return SignatureStatus::Trusted;
+        }
+    }
+    SignatureStatus::Invalid("not signed by a trusted key".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn hex_encode(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn signatures_verify_against_trusted_keys() {
+        let signing = SigningKey::from_bytes(&[7; 32]);
+        let other = SigningKey::from_bytes(&[9; 32]);
+        let data = b"plugin bytes";
+        let sig = hex_encode(&signing.sign(data).to_bytes());
+        let trusted = vec![hex_encode(signing.verifying_key().as_bytes())];
+        let untrusted = vec![hex_encode(other.verifying_key().as_bytes())];
+
+        assert_eq!(
+            signature_verify(data, &sig, &trusted),
+            SignatureStatus::Trusted
+        );
+        assert!(matches!(
+            signature_verify(data, &sig, &untrusted),
+            SignatureStatus::Invalid(_)
+        ));
+        assert!(matches!(
+            signature_verify(b"tampered", &sig, &trusted),
+            SignatureStatus::Invalid(_)
+        ));
+    }
+
+    #[test]
+    fn bad_keys_are_rejected() {
+        assert!(key_parse("abcd").is_err());
+        assert!(key_parse(&"zz".repeat(32)).is_err());
+    }
+}
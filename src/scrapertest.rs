@@ -0,0 +1,493 @@
+use crate::database::database::Main;
+use crate::download;
+use crate::globalload::GlobalLoad;
+use crate::helpers::block_on;
+use crate::jobs::Jobs;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const CASE_FILE: &str = "case.json";
+const HTTP_FILE: &str = "http.json";
+const SNAPSHOT_FILE: &str = "snapshot.json";
+
+///
+/// What a fixture runs. Lives in case.json inside of the fixture folder
+///
+#[derive(Debug, Deserialize)]
+pub struct FixtureCase {
+    /// Name of the scraper from its GlobalPluginScraper
+    pub scraper: String,
+    /// Params goes through url_dump first. Scraper sends the urls in params straight to the parser
+    #[serde(default = "jobtype_default")]
+    pub jobtype: sharedtypes::DbJobType,
+    pub params: Vec<sharedtypes::ScraperParam>,
+    #[serde(default)]
+    pub user_data: BTreeMap<String, String>,
+    /// Only keeps this many of the jobs url_dump hands back. Paged scrapers dump hundreds
+    #[serde(default)]
+    pub max_pages: Option<usize>,
+}
+
+fn jobtype_default() -> sharedtypes::DbJobType {
+    sharedtypes::DbJobType::Params
+}
+
+///
+/// A recorded response. http.json holds a list of these
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpFixture {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post: Option<String>,
+    /// File next to http.json holding the response body
+    pub body: String,
+    /// Where we ended up after redirects. Defaults to url
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub final_url: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FixtureMode {
+    /// Overwrites snapshot.json with whatever the scraper returned
+    pub update: bool,
+    /// Downloads and saves responses that aren't recorded yet
+    pub record: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FixtureOutcome {
+    Passed,
+    Updated,
+    Failed(String),
+}
+
+///
+/// Loads scrapers into a throwaway in memory db so fixtures never touch the real one
+///
+pub struct FixtureRunner {
+    globalload: GlobalLoad,
+    scrapers: Vec<sharedtypes::GlobalPluginScraper>,
+}
+
+impl FixtureRunner {
+    pub fn new(library: &Path) -> Result<Self, String> {
+        let db = Main::new(None, crate::VERS);
+        let jobs = Jobs::new(db.clone());
+        let mut globalload = GlobalLoad::new(db, jobs);
+        let scrapers = globalload.library_load(library);
+        if scrapers.is_empty() {
+            return Err(format!("Nothing got loaded from {}", library.display()));
+        }
+        Ok(FixtureRunner {
+            globalload,
+            scrapers,
+        })
+    }
+
+    ///
+    /// Runs every fixture folder under path. path can also be a single fixture
+    ///
+    pub fn run_all(&self, path: &Path, mode: FixtureMode) -> Vec<(PathBuf, FixtureOutcome)> {
+        let mut cases: Vec<PathBuf> = walkdir::WalkDir::new(path)
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.file_name() == CASE_FILE)
+            .filter_map(|entry| entry.path().parent().map(Path::to_path_buf))
+            .collect();
+        cases.sort();
+        cases
+            .into_iter()
+            .map(|case| {
+                let outcome = self.run(&case, mode);
+                (case, outcome)
+            })
+            .collect()
+    }
+
+    ///
+    /// Runs a single fixture and checks it against its snapshot
+    ///
+    pub fn run(&self, dir: &Path, mode: FixtureMode) -> FixtureOutcome {
+        let case: FixtureCase = match read_json(&dir.join(CASE_FILE)) {
+            Ok(Some(case)) => case,
+            Ok(None) => return FixtureOutcome::Failed(format!("Missing {}", CASE_FILE)),
+            Err(err) => return FixtureOutcome::Failed(err),
+        };
+        let Some(scraper) = self.scrapers.iter().find(|item| item.name == case.scraper) else {
+            return FixtureOutcome::Failed(format!(
+                "No scraper named {} in the library",
+                case.scraper
+            ));
+        };
+        if scraper.should_handle_text_scraping {
+            return FixtureOutcome::Failed(format!(
+                "{} downloads its own pages so it can't be replayed",
+                scraper.name
+            ));
+        }
+        let mut http: Vec<HttpFixture> = match read_json(&dir.join(HTTP_FILE)) {
+            Ok(http) => http.unwrap_or_default(),
+            Err(err) => return FixtureOutcome::Failed(err),
+        };
+        let http_len = http.len();
+
+        let actual = match self.snapshot(dir, &case, scraper, &mut http, mode.record) {
+            Ok(actual) => actual,
+            Err(err) => return FixtureOutcome::Failed(err),
+        };
+        if http.len() != http_len
+            && let Err(err) = write_json(&dir.join(HTTP_FILE), &http)
+        {
+            return FixtureOutcome::Failed(err);
+        }
+
+        // Compared as values so key order in the file doesn't matter
+        let expected = std::fs::read_to_string(dir.join(SNAPSHOT_FILE)).ok();
+        let matches = expected
+            .as_deref()
+            .and_then(|expected| serde_json::from_str::<Value>(expected).ok())
+            .is_some_and(|expected| expected == actual);
+        let actual = serde_json::to_string_pretty(&actual).unwrap();
+        match expected {
+            Some(_) if matches => FixtureOutcome::Passed,
+            _ if mode.update => match std::fs::write(dir.join(SNAPSHOT_FILE), actual + "\n") {
+                Ok(_) => FixtureOutcome::Updated,
+                Err(err) => FixtureOutcome::Failed(err.to_string()),
+            },
+            Some(expected) => FixtureOutcome::Failed(line_diff(expected.trim_end(), &actual)),
+            None => FixtureOutcome::Failed(format!(
+                "No {} yet. Run with --update-snapshots to create it",
+                SNAPSHOT_FILE
+            )),
+        }
+    }
+
+    ///
+    /// Runs the scraper the same way a download worker would, but with recorded responses
+    ///
+    fn snapshot(
+        &self,
+        dir: &Path,
+        case: &FixtureCase,
+        scraper: &sharedtypes::GlobalPluginScraper,
+        http: &mut Vec<HttpFixture>,
+        record: bool,
+    ) -> Result<Value, String> {
+        let mut user_data = BTreeMap::new();
+        if let Some(sharedtypes::StoredInfo::Storage(storage)) = &scraper.stored_info {
+            for (key, val) in storage.iter() {
+                user_data.insert(key.to_string(), val.to_string());
+            }
+        }
+        user_data.extend(case.user_data.clone());
+        let mut job = sharedtypes::DbJobsObj {
+            site: scraper_site(scraper),
+            param: case.params.clone(),
+            user_data,
+            ..Default::default()
+        };
+        job.jobmanager.jobtype = case.jobtype;
+        let scraperdata = sharedtypes::ScraperDataReturn {
+            job,
+            ..Default::default()
+        };
+
+        let mut url_dump = Vec::new();
+        let urlload = match case.jobtype {
+            sharedtypes::DbJobType::Params => {
+                let dumped = self
+                    .globalload
+                    .url_dump(&case.params, &scraperdata, scraper)
+                    .map_err(|err| format!("url_dump failed: {}", err))?;
+                let mut out = Vec::new();
+                for dumped in dumped
+                    .into_iter()
+                    .take(case.max_pages.unwrap_or(usize::MAX))
+                {
+                    url_dump.push(serde_json::to_value(&dumped.job.param).unwrap());
+                    for param in dumped.job.param.iter() {
+                        if let sharedtypes::ScraperParam::Url(_)
+                        | sharedtypes::ScraperParam::UrlPost(_) = param
+                        {
+                            out.push((param.clone(), dumped.clone()));
+                        }
+                    }
+                }
+                out
+            }
+            sharedtypes::DbJobType::Scraper => case
+                .params
+                .iter()
+                .filter(|param| {
+                    matches!(
+                        param,
+                        sharedtypes::ScraperParam::Url(_) | sharedtypes::ScraperParam::UrlPost(_)
+                    )
+                })
+                .map(|param| (param.clone(), scraperdata.clone()))
+                .collect(),
+            other => return Err(format!("Fixtures can't run {:?} jobs", other)),
+        };
+
+        let mut pages = Vec::new();
+        for (param, data) in urlload {
+            let (url, post) = match param {
+                sharedtypes::ScraperParam::Url(url) => (url, None),
+                sharedtypes::ScraperParam::UrlPost(post) => (post.url, Some(post.post_data)),
+                _ => continue,
+            };
+            let (body, final_url) = match response_get(dir, http, &url, &post)? {
+                Some(found) => found,
+                None if record => response_record(dir, http, scraper, &url, &post)?,
+                None => {
+                    return Err(format!(
+                        "No recorded response for {}. Run with --record to save one",
+                        url
+                    ));
+                }
+            };
+            let returns: Vec<Value> = self
+                .globalload
+                .parser_call(&body, &final_url, &data, scraper)
+                .into_iter()
+                .map(return_snapshot)
+                .collect();
+            pages.push(json!({"url": url, "post": post, "returns": returns}));
+        }
+
+        Ok(json!({"url_dump": url_dump, "pages": pages}))
+    }
+}
+
+fn scraper_site(scraper: &sharedtypes::GlobalPluginScraper) -> String {
+    match &scraper.storage_type {
+        Some(sharedtypes::ScraperOrPlugin::Scraper(info)) => info
+            .sites
+            .first()
+            .cloned()
+            .unwrap_or_else(|| scraper.name.clone()),
+        _ => scraper.name.clone(),
+    }
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<Option<T>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let data = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    serde_json::from_str(&data)
+        .map(Some)
+        .map_err(|err| format!("Bad {}: {}", path.display(), err))
+}
+
+fn write_json<T: Serialize>(path: &Path, data: &T) -> Result<(), String> {
+    let data = serde_json::to_string_pretty(data).unwrap();
+    std::fs::write(path, data + "\n").map_err(|err| err.to_string())
+}
+
+fn response_get(
+    dir: &Path,
+    http: &[HttpFixture],
+    url: &String,
+    post: &Option<String>,
+) -> Result<Option<(String, String)>, String> {
+    let Some(fixture) = http
+        .iter()
+        .find(|fixture| &fixture.url == url && &fixture.post == post)
+    else {
+        return Ok(None);
+    };
+    let body = std::fs::read_to_string(dir.join(&fixture.body))
+        .map_err(|err| format!("Cannot read {}: {}", fixture.body, err))?;
+    Ok(Some((
+        body,
+        fixture.final_url.clone().unwrap_or_else(|| url.clone()),
+    )))
+}
+
+///
+/// Downloads a response and saves it next to the fixture
+///
+fn response_record(
+    dir: &Path,
+    http: &mut Vec<HttpFixture>,
+    scraper: &sharedtypes::GlobalPluginScraper,
+    url: &String,
+    post: &Option<String>,
+) -> Result<(String, String), String> {
+    let ratelimit = match &scraper.storage_type {
+        Some(sharedtypes::ScraperOrPlugin::Scraper(info)) => info.ratelimit,
+        _ => (1, std::time::Duration::from_secs(1)),
+    };
+    let client = Arc::new(download::client_create(
+        download::get_modifiers(scraper),
+        true,
+    ));
+    let ratelimiter = download::create_ratelimiter(ratelimit, &0, &0);
+    let (body, final_url) = block_on(download::dltext_new(
+        url,
+        post.clone(),
+        client,
+        &ratelimiter,
+        &0,
+        &0,
+    ))
+    .map_err(|err| format!("Recording {} failed: {}", url, err))?;
+
+    let name = format!("response{:03}.txt", http.len());
+    std::fs::write(dir.join(&name), &body).map_err(|err| err.to_string())?;
+    http.push(HttpFixture {
+        url: url.clone(),
+        post: post.clone(),
+        body: name,
+        final_url: (&final_url != url).then(|| final_url.clone()),
+    });
+    Ok((body, final_url))
+}
+
+///
+/// HashSets come back in any order so they get sorted before they're compared
+///
+fn sorted<'a, T: Serialize + 'a>(items: impl IntoIterator<Item = &'a T>) -> Vec<Value> {
+    let mut out: Vec<Value> = items
+        .into_iter()
+        .map(|item| serde_json::to_value(item).unwrap())
+        .collect();
+    out.sort_by_cached_key(|item| item.to_string());
+    out
+}
+
+fn return_snapshot(scraper_return: sharedtypes::ScraperReturn) -> Value {
+    match scraper_return {
+        sharedtypes::ScraperReturn::Data(data) => json!({"Data": {
+            "files": sorted(&data.files),
+            "tags": sorted(&data.tags),
+            "jobs": sorted(&data.jobs),
+            "flags": data.flags.iter().map(|flag| format!("{:?}", flag)).collect::<Vec<_>>(),
+        }}),
+        sharedtypes::ScraperReturn::Fatal(err) => json!({ "Fatal": err }),
+        sharedtypes::ScraperReturn::Nothing => json!("Nothing"),
+        sharedtypes::ScraperReturn::Stop(err) => json!({ "Stop": err }),
+        sharedtypes::ScraperReturn::Timeout(secs) => json!({ "Timeout": secs }),
+        sharedtypes::ScraperReturn::RetryLater(wait) => {
+            json!({ "RetryLater": wait.as_secs_f64() })
+        }
+    }
+}
+
+///
+/// Line diff between the snapshot and what we got. - is the snapshot, + is new
+///
+fn line_diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+
+    // Longest common subsequence table
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            out += &format!("+ {}\n", new[j]);
+            j += 1;
+        } else {
+            out += &format!("- {}\n", old[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_shows_changed_lines() {
+        let diff = line_diff("a\nb\nc", "a\nx\nc\nd");
+        assert_eq!(diff, "+ x\n- b\n+ d\n");
+        assert!(line_diff("same", "same").is_empty());
+    }
+
+    #[test]
+    fn sets_snapshot_in_a_stable_order() {
+        let first: std::collections::HashSet<String> =
+            ["b", "a", "c"].into_iter().map(String::from).collect();
+        let second: std::collections::HashSet<String> =
+            ["c", "b", "a"].into_iter().map(String::from).collect();
+        assert_eq!(sorted(&first), sorted(&second));
+    }
+
+    ///
+    /// Where cargo put the built library for a scraper crate. Test binaries live in
+    /// target/<profile>/deps so the libraries are one folder up
+    ///
+    fn built_library(crate_dir: &Path) -> PathBuf {
+        let manifest: toml::Value =
+            toml::from_str(&std::fs::read_to_string(crate_dir.join("Cargo.toml")).unwrap())
+                .unwrap();
+        let name = manifest
+            .get("lib")
+            .and_then(|lib| lib.get("name"))
+            .or_else(|| {
+                manifest
+                    .get("package")
+                    .and_then(|package| package.get("name"))
+            })
+            .and_then(toml::Value::as_str)
+            .unwrap()
+            .replace('-', "_");
+        let exe = std::env::current_exe().unwrap();
+        exe.parent().and_then(Path::parent).unwrap().join(format!(
+            "{}{}{}",
+            std::env::consts::DLL_PREFIX,
+            name,
+            std::env::consts::DLL_SUFFIX
+        ))
+    }
+
+    #[test]
+    fn committed_fixtures_pass() {
+        let scrapers = Path::new(env!("CARGO_MANIFEST_DIR")).join("scrapers");
+        let mut failed = Vec::new();
+        let mut ran = 0;
+        for entry in std::fs::read_dir(&scrapers).unwrap().flatten() {
+            let fixtures = entry.path().join("fixtures");
+            if !fixtures.is_dir() {
+                continue;
+            }
+            let library = built_library(&entry.path());
+            assert!(
+                library.exists(),
+                "{} isn't built. Run cargo build --workspace first",
+                library.display()
+            );
+            let runner = FixtureRunner::new(&library).unwrap();
+            for (case, outcome) in runner.run_all(&fixtures, FixtureMode::default()) {
+                ran += 1;
+                if let FixtureOutcome::Failed(err) = outcome {
+                    failed.push(format!("{}\n{}", case.display(), err));
+                }
+            }
+        }
+        assert!(ran > 0, "No fixtures found under {}", scrapers.display());
+        assert!(failed.is_empty(), "{}", failed.join("\n"));
+    }
+}
@@ -2,6 +2,7 @@
 #![allow(unused_variables)]
 use crate::Main;
 use crate::globalload::GlobalLoad;
+use crate::htmlparse;
 use crate::jobs::Jobs;
 use crate::logging;
 use anyhow::Context;
@@ -44,7 +45,8 @@ impl PluginIpcInteract {
     }
 
     /// Spawns a clean listener context directly natively inside your existing Tokio runtime.
-    pub async fn spawn_listener(&mut self, main_db: Main) -> anyhow::Result<()> {
+    /// Returns the address the API server ended up on.
+    pub async fn spawn_listener(&mut self, main_db: Main) -> anyhow::Result<std::net::SocketAddr> {
         use warp::Filter;
 
         let db = main_db.clone();
@@ -60,24 +62,35 @@ impl PluginIpcInteract {
 
         logging::info_log(format!("IPC Server running at {}", types::SOCKET_NAME));
 
+        // Prometheus scrape endpoint
+        let metrics_jobs = self.db_interface.jobmanager.clone();
+        let metrics_route = warp::path("metrics")
+            .and(warp::path::end())
+            .and(warp::get())
+            .map(move || {
+                warp::reply::with_header(
+                    crate::metrics::render(Some(&metrics_jobs)),
+                    "content-type",
+                    "text/plain; version=0.0.4",
+                )
+            });
+
         // Setup warp routes
-        let routes_with_fallback =
-            main_db
-                .clone()
-                .get_filters()
-                .recover(|err: warp::Rejection| async move {
-                    if err.is_not_found() {
-                        Ok::<_, warp::Rejection>(warp::reply::with_status(
-                            String::from("404 Not Found"), // Use an owned String here
-                            warp::http::StatusCode::NOT_FOUND,
-                        ))
-                    } else {
-                        Ok::<_, warp::Rejection>(warp::reply::with_status(
-                            String::from("500 Internal Server Error"), // Use an owned String here
-                            warp::http::StatusCode::INTERNAL_SERVER_ERROR,
-                        ))
-                    }
-                });
+        let routes_with_fallback = metrics_route.or(main_db.clone().get_filters()).recover(
+            |err: warp::Rejection| async move {
+                if err.is_not_found() {
+                    Ok::<_, warp::Rejection>(warp::reply::with_status(
+                        String::from("404 Not Found"), // Use an owned String here
+                        warp::http::StatusCode::NOT_FOUND,
+                    ))
+                } else {
+                    Ok::<_, warp::Rejection>(warp::reply::with_status(
+                        String::from("500 Internal Server Error"), // Use an owned String here
+                        warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                    ))
+                }
+            },
+        );
 
         // Spawn API Server directly on your existing background runtime context
         let mut api_url = db.get_api_url().url;
@@ -112,7 +125,7 @@ impl PluginIpcInteract {
                 handle_client(stream, db_clone, global_clone, job_clone);
             }
         });
-        Ok(())
+        Ok(api_url)
     }
 }
 
@@ -175,6 +188,27 @@ pub fn dbactions_to_function(
     jobmanager: Arc<Jobs>,
 ) -> Vec<u8> {
     match dbaction {
+        types::SupportedDBRequests::PluginStorageGet(plugin, key) => {
+            data_size_to_b(&database.plugin_storage_get(&plugin, &key))
+        }
+        types::SupportedDBRequests::PluginStoragePut(plugin, key, value) => {
+            data_size_to_b(&database.plugin_storage_put(&plugin, &key, &value))
+        }
+        types::SupportedDBRequests::PluginStorageDelete(plugin, key) => {
+            data_size_to_b(&database.plugin_storage_delete(&plugin, &key))
+        }
+        types::SupportedDBRequests::PluginStorageList(plugin, prefix) => {
+            data_size_to_b(&database.plugin_storage_list(&plugin, &prefix))
+        }
+        types::SupportedDBRequests::PluginStorageBatch(plugin, ops) => {
+            data_size_to_b(&database.plugin_storage_batch(&plugin, &ops))
+        }
+        types::SupportedDBRequests::HtmlSelect(body, base_url, queries) => {
+            data_size_to_b(&htmlparse::select(&body, base_url.as_deref(), &queries))
+        }
+        types::SupportedDBRequests::HtmlFetchSelect(url, queries) => {
+            data_size_to_b(&htmlparse::fetch_select(&url, &queries))
+        }
         types::SupportedDBRequests::GetFileIdsWhereExtensionIs(file_extension_type) => {
             let file_ids = match file_extension_type {
                 sharedtypes::FileExtensionType::Image => database.extensions_images_get_fileid(),
@@ -342,8 +376,7 @@ pub fn dbactions_to_function(
             data_size_to_b(&out)
         }
         types::SupportedDBRequests::ReloadLoadedPlugins() => {
-            //let mut plugin = globalload.write();
-            //plugin.reload_loaded_plugins();
+            globalload.libraries_reload_changed();
             data_size_to_b(&true)
         }
         types::SupportedDBRequests::NamespaceContainsId(namespaceid, tagid) => {
@@ -0,0 +1,383 @@
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Where TXT sidecar lines without a namespace go
+const TXT_NAMESPACE: &str = "SYSTEM_Sidecar_TXT";
+
+const SOURCE_URL_NAMESPACE: &str = "source_url";
+
+/// Timestamps are stored as tags in timestamp_<kind>, ie timestamp_posted
+const TIMESTAMP_PREFIX: &str = "timestamp_";
+
+///
+/// Prefixes gallery-dl leaves on tags from sites that namespace them, ie
+/// artist:bob from e-hentai. Anything else with a colon is part of the tag
+///
+const GALLERY_DL_NAMESPACES: [&str; 14] = [
+    "artist",
+    "character",
+    "copyright",
+    "cosplayer",
+    "female",
+    "general",
+    "group",
+    "language",
+    "male",
+    "meta",
+    "mixed",
+    "other",
+    "parody",
+    "reclass",
+];
+
+///
+/// Tags from a TXT sidecar, one per line. A line of namespace:tag keeps its
+/// namespace, anything else lands in SYSTEM_Sidecar_TXT
+///
+pub fn txt_tags(contents: &str) -> Vec<sharedtypes::FileTagAction> {
+    let mut sidecar = sharedtypes::SidecarJson::default();
+    for line in contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        let (namespace, tag) = match line.split_once(':') {
+            Some((namespace, tag))
+                if !namespace.is_empty()
+                    && !namespace.contains(char::is_whitespace)
+                    && !tag.is_empty()
+                    // A bare url isn't a namespace
+                    && !tag.starts_with("//") =>
+            {
+                (namespace, tag)
+            }
+            _ => (TXT_NAMESPACE, line),
+        };
+        sidecar.tags.push(sidecar_tag(namespace, tag));
+    }
+    json_actions(&sidecar)
+}
+
+///
+/// Tags from a JSON sidecar. Reads the layout in sharedtypes::SidecarJson along
+/// with the metadata files gallery-dl and yt-dlp write next to their downloads
+///
+pub fn json_tags(contents: &str) -> Result<Vec<sharedtypes::FileTagAction>, String> {
+    let value: Value = serde_json::from_str(contents).map_err(|err| err.to_string())?;
+    let sidecar = if value.get("category").is_some() && value.get("subcategory").is_some() {
+        gallery_dl(&value)
+    } else if value.get("extractor").is_some() && value.get("webpage_url").is_some() {
+        yt_dlp(&value)
+    } else {
+        serde_json::from_value(value).map_err(|err| err.to_string())?
+    };
+    Ok(json_actions(&sidecar))
+}
+
+///
+/// Builds the JSON sidecar for a file's tags by namespace. Source urls and
+/// timestamps get pulled back out of their namespaces
+///
+pub fn json_from_tags(tags: &BTreeMap<String, Vec<String>>) -> sharedtypes::SidecarJson {
+    let mut sidecar = sharedtypes::SidecarJson::default();
+    for (namespace, tags) in tags.iter() {
+        if namespace == SOURCE_URL_NAMESPACE {
+            sidecar.source_urls.extend(tags.iter().cloned());
+            continue;
+        }
+        if let Some(kind) = namespace.strip_prefix(TIMESTAMP_PREFIX)
+            && let [time] = tags.as_slice()
+            && let Ok(time) = time.parse()
+        {
+            sidecar.timestamps.insert(kind.to_string(), time);
+            continue;
+        }
+        for tag in tags.iter() {
+            sidecar.tags.push(sidecar_tag(namespace, tag));
+        }
+    }
+    sidecar
+}
+
+///
+/// Turns a sidecar into the tags to add to its file
+///
+fn json_actions(sidecar: &sharedtypes::SidecarJson) -> Vec<sharedtypes::FileTagAction> {
+    let mut tags = Vec::new();
+    for tag in sidecar.tags.iter() {
+        if tag.parents.is_empty() {
+            tags.push(tag_object(&tag.namespace, &tag.tag, None));
+        }
+        for parent in tag.parents.iter() {
+            let parent = sharedtypes::SubTag {
+                namespace: namespace(&parent.namespace),
+                tag: parent.tag.clone(),
+                limit_to: None,
+                tag_type: sharedtypes::TagType::Normal,
+            };
+            tags.push(tag_object(&tag.namespace, &tag.tag, Some(parent)));
+        }
+    }
+    for url in sidecar.source_urls.iter() {
+        tags.push(tag_object(SOURCE_URL_NAMESPACE, url, None));
+    }
+    for (kind, time) in sidecar.timestamps.iter() {
+        tags.push(tag_object(
+            &format!("{}{}", TIMESTAMP_PREFIX, kind),
+            &time.to_string(),
+            None,
+        ));
+    }
+    vec![sharedtypes::FileTagAction {
+        operation: sharedtypes::TagOperation::Add,
+        tags,
+    }]
+}
+
+///
+/// gallery-dl's --write-metadata output. Tags without a namespace go under the
+/// site they came from
+///
+fn gallery_dl(value: &Value) -> sharedtypes::SidecarJson {
+    let site = value["category"].as_str().unwrap_or("gallery-dl");
+    let mut sidecar = sharedtypes::SidecarJson::default();
+    let Some(fields) = value.as_object() else {
+        return sidecar;
+    };
+    for (key, field) in fields.iter() {
+        if key == "tags" {
+            match field {
+                // Some extractors group them, ie {"artist": [...], "general": [...]}
+                Value::Object(groups) => {
+                    for (namespace, group) in groups.iter() {
+                        sidecar.tags.extend(tag_list(group, namespace));
+                    }
+                }
+                _ => {
+                    for tag in tag_list(field, site) {
+                        let tag = match tag.tag.split_once(':') {
+                            Some((namespace, name))
+                                if GALLERY_DL_NAMESPACES.contains(&namespace) =>
+                            {
+                                sidecar_tag(namespace, name)
+                            }
+                            _ => tag,
+                        };
+                        sidecar.tags.push(tag);
+                    }
+                }
+            }
+        } else if let Some(namespace) = key
+            .strip_prefix("tags_")
+            .or_else(|| key.strip_prefix("tag_string_"))
+        {
+            // Booru tags split by type when gallery-dl's tags option is on
+            sidecar.tags.extend(tag_list(field, namespace));
+        }
+    }
+    if let Some(title) = value["title"].as_str() {
+        sidecar.tags.push(sidecar_tag("title", title));
+    }
+    for key in ["post_url", "source", "url", "file_url"] {
+        if let Some(url) = value[key].as_str()
+            && url.starts_with("http")
+            && !sidecar.source_urls.iter().any(|known| known == url)
+        {
+            sidecar.source_urls.push(url.to_string());
+        }
+    }
+    if let Some(date) = value["date"].as_str()
+        && let Ok(date) = NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S")
+    {
+        sidecar
+            .timestamps
+            .insert("posted".to_string(), date.and_utc().timestamp());
+    }
+    sidecar
+}
+
+///
+/// yt-dlp's --write-info-json output
+///
+fn yt_dlp(value: &Value) -> sharedtypes::SidecarJson {
+    let site = value["extractor"]
+        .as_str()
+        .unwrap_or("yt-dlp")
+        .to_lowercase();
+    let mut sidecar = sharedtypes::SidecarJson::default();
+    sidecar.tags.extend(tag_list(&value["tags"], &site));
+    sidecar
+        .tags
+        .extend(tag_list(&value["categories"], "category"));
+    if let Some(title) = value["title"].as_str() {
+        sidecar.tags.push(sidecar_tag("title", title));
+    }
+    if let Some(uploader) = value["uploader"].as_str() {
+        sidecar.tags.push(sidecar_tag("creator", uploader));
+    }
+    if let Some(url) = value["webpage_url"].as_str() {
+        sidecar.source_urls.push(url.to_string());
+    }
+    let posted = value["timestamp"].as_i64().or_else(|| {
+        let date = NaiveDate::parse_from_str(value["upload_date"].as_str()?, "%Y%m%d").ok()?;
+        Some(date.and_hms_opt(0, 0, 0)?.and_utc().timestamp())
+    });
+    if let Some(posted) = posted {
+        sidecar.timestamps.insert("posted".to_string(), posted);
+    }
+    sidecar
+}
+
+///
+/// Tags out of a list of strings or one space separated string
+///
+fn tag_list(value: &Value, namespace: &str) -> Vec<sharedtypes::SidecarJsonTag> {
+    match value {
+        Value::Array(tags) => tags
+            .iter()
+            .filter_map(Value::as_str)
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| sidecar_tag(namespace, tag))
+            .collect(),
+        Value::String(tags) => tags
+            .split_whitespace()
+            .map(|tag| sidecar_tag(namespace, tag))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn sidecar_tag(namespace: &str, tag: &str) -> sharedtypes::SidecarJsonTag {
+    sharedtypes::SidecarJsonTag {
+        namespace: namespace.to_string(),
+        tag: tag.to_string(),
+        parents: Vec::new(),
+    }
+}
+
+fn tag_object(
+    name: &str,
+    tag: &str,
+    relates_to: Option<sharedtypes::SubTag>,
+) -> sharedtypes::TagObject {
+    sharedtypes::TagObject {
+        namespace: namespace(name),
+        tag: tag.to_string(),
+        tag_type: sharedtypes::TagType::Normal,
+        relates_to,
+    }
+}
+
+fn namespace(name: &str) -> sharedtypes::GenericNamespaceObj {
+    let description = match name {
+        TXT_NAMESPACE => Some("Information from a sidecar file. TXT Import".to_string()),
+        SOURCE_URL_NAMESPACE => Some("Source URL for a file.".to_string()),
+        name if name.starts_with(TIMESTAMP_PREFIX) => {
+            Some("Unix time from a sidecar file".to_string())
+        }
+        _ => None,
+    };
+    sharedtypes::GenericNamespaceObj {
+        name: name.to_string(),
+        description,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flat(actions: Vec<sharedtypes::FileTagAction>) -> Vec<(String, String, Option<String>)> {
+        actions
+            .into_iter()
+            .flat_map(|action| action.tags)
+            .map(|tag| {
+                (
+                    tag.namespace.name,
+                    tag.tag,
+                    tag.relates_to
+                        .map(|parent| format!("{}:{}", parent.namespace.name, parent.tag)),
+                )
+            })
+            .collect()
+    }
+
+    fn tag(namespace: &str, tag: &str) -> (String, String, Option<String>) {
+        (namespace.to_string(), tag.to_string(), None)
+    }
+
+    #[test]
+    fn sidecars_keep_namespaces() {
+        assert_eq!(
+            flat(txt_tags(
+                "creator:bob\n\nplain tag\nhttps://example.com\r\n"
+            )),
+            vec![
+                tag("creator", "bob"),
+                tag(TXT_NAMESPACE, "plain tag"),
+                tag(TXT_NAMESPACE, "https://example.com"),
+            ]
+        );
+
+        let mut tags = BTreeMap::new();
+        tags.insert("creator".to_string(), vec!["bob".to_string()]);
+        tags.insert(
+            "source_url".to_string(),
+            vec!["https://example.com/1".to_string()],
+        );
+        tags.insert(
+            "timestamp_posted".to_string(),
+            vec!["1700000000".to_string()],
+        );
+        let written = serde_json::to_string(&json_from_tags(&tags)).unwrap();
+        assert_eq!(
+            flat(json_tags(&written).unwrap()),
+            vec![
+                tag("creator", "bob"),
+                tag("source_url", "https://example.com/1"),
+                tag("timestamp_posted", "1700000000"),
+            ]
+        );
+
+        let parents = r#"{"tags": [{"namespace": "character", "tag": "alice",
+            "parents": [{"namespace": "series", "tag": "wonderland"}]}]}"#;
+        assert_eq!(
+            flat(json_tags(parents).unwrap()),
+            vec![(
+                "character".to_string(),
+                "alice".to_string(),
+                Some("series:wonderland".to_string())
+            )]
+        );
+
+        let gallery_dl = r#"{"category": "exhentai", "subcategory": "gallery",
+            "tags": ["artist:bob", "re:zero"], "tags_general": "long_hair smile",
+            "title": "Book", "date": "2023-11-14 22:13:20"}"#;
+        assert_eq!(
+            flat(json_tags(gallery_dl).unwrap()),
+            vec![
+                tag("artist", "bob"),
+                tag("exhentai", "re:zero"),
+                tag("general", "long_hair"),
+                tag("general", "smile"),
+                tag("title", "Book"),
+                tag("timestamp_posted", "1700000000"),
+            ]
+        );
+
+        let yt_dlp = r#"{"extractor": "Youtube", "webpage_url": "https://youtu.be/x",
+            "tags": ["music"], "uploader": "bob", "upload_date": "20231114"}"#;
+        assert_eq!(
+            flat(json_tags(yt_dlp).unwrap()),
+            vec![
+                tag("youtube", "music"),
+                tag("creator", "bob"),
+                tag("source_url", "https://youtu.be/x"),
+                tag("timestamp_posted", "1699920000"),
+            ]
+        );
+        assert!(json_tags("not json").is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use crate::VERS;
+use crate::database::database::Main;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+///
+/// A folder under the temp dir that's made fresh on creation and deleted again when
+/// dropped, even if the test panicked
+///
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    ///
+    /// name keeps tests apart, the pid keeps test runs apart
+    ///
+    pub fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("rusthydrus-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+
+    ///
+    /// Path under the folder as a string, which is what storage roots are kept as
+    ///
+    pub fn location(&self, name: &str) -> String {
+        self.path.join(name).to_string_lossy().to_string()
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+///
+/// An in memory db at the current version plus a temp dir to keep its files in
+///
+pub fn test_db(name: &str) -> (Main, TempDir) {
+    (Main::new(None, VERS), TempDir::new(name))
+}
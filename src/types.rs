@@ -107,6 +107,13 @@ pub enum SupportedDBRequests {
     GetRelationshipFileidWhereNamespace((u64, u64, sharedtypes::GreqLeqOrEq)),
     GetRelationshipTagidWhereNamespace((u64, u64, sharedtypes::GreqLeqOrEq)),
     GetFileIdsWhereExtensionIs(sharedtypes::FileExtensionType),
+    PluginStorageGet(String, String),
+    PluginStoragePut(String, String, sharedtypes::PluginStorageValue),
+    PluginStorageDelete(String, String),
+    PluginStorageList(String, String),
+    PluginStorageBatch(String, Vec<sharedtypes::PluginStorageOp>),
+    HtmlSelect(String, Option<String>, Vec<sharedtypes::HtmlQuery>),
+    HtmlFetchSelect(String, Vec<sharedtypes::HtmlQuery>),
 }
 
 /// Actions for Database
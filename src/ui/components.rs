@@ -10,6 +10,7 @@ use crate::ui::ui::*;
 
 pub struct MonitorRender<'a> {
     pub scrapers: &'a HashMap<u64, UIScraper>,
+    pub downloads_paused: bool,
 }
 
 pub struct ScraperRender<'a> {
@@ -68,7 +69,11 @@ impl<'a> Widget for MonitorRender<'a> {
             Block::default()
                 .borders(Borders::ALL)
                 .border_type(BorderType::Rounded)
-                .title(" Active Thread Monitor "),
+                .title(if self.downloads_paused {
+                    " Active Thread Monitor (Downloads Paused) "
+                } else {
+                    " Active Thread Monitor "
+                }),
         );
 
         ratatui::prelude::Widget::render(table, area, buf);
@@ -143,3 +148,244 @@ impl<'a> Widget for ScraperRender<'a> {
         ratatui::prelude::Widget::render(list, chunks[1], buf);
     }
 }
+
+pub struct TagSearchRender<'a> {
+    pub search: &'a SearchState,
+}
+
+pub struct SearchResultsRender<'a> {
+    pub search: &'a SearchState,
+}
+
+pub struct FileTagsRender<'a> {
+    pub file_tags: &'a UIFileTags,
+}
+
+pub struct JobQueueRender<'a> {
+    pub job_queue: &'a JobQueueState,
+}
+
+///
+/// Tag search input with the FTS autocomplete list underneath
+///
+impl<'a> Widget for TagSearchRender<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Min(0),
+            ])
+            .split(area);
+
+        let selected = self
+            .search
+            .selected_tags
+            .iter()
+            .map(|(tag, _)| format!("{}:{}", tag.namespace.name, tag.tag))
+            .collect::<Vec<_>>()
+            .join("  AND  ");
+        Paragraph::new(selected)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .title(" Selected Tags "),
+            )
+            .fg(Color::Cyan)
+            .render(chunks[0], buf);
+
+        Paragraph::new(format!("> {}", self.search.input))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .title(" Search "),
+            )
+            .render(chunks[1], buf);
+
+        let list_items: Vec<ListItem> = self
+            .search
+            .suggestions
+            .iter()
+            .enumerate()
+            .map(|(cnt, (tag, tag_id, count))| {
+                let item = ListItem::new(format!(
+                    "   {}:{}  ({} files) [{}]",
+                    tag.namespace.name, tag.tag, count, tag_id
+                ));
+                if cnt == self.search.suggestion_index {
+                    item.fg(Color::Yellow).bold()
+                } else {
+                    item
+                }
+            })
+            .collect();
+
+        let list = List::new(list_items).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title(" Suggestions "),
+        );
+        ratatui::prelude::Widget::render(list, chunks[2], buf);
+    }
+}
+
+///
+/// Files that matched a search
+///
+impl<'a> Widget for SearchResultsRender<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let rows: Vec<Row> = self
+            .search
+            .results
+            .iter()
+            .enumerate()
+            .map(|(cnt, file)| {
+                let row = Row::new(vec![
+                    ratatui::widgets::Cell::new(file.file_id.to_string()),
+                    ratatui::widgets::Cell::new(file.hash.clone()),
+                    ratatui::widgets::Cell::new(file.ext.clone()),
+                    ratatui::widgets::Cell::new(file.tag_count.to_string()),
+                ]);
+                if cnt == self.search.result_index {
+                    row.style(Style::default().fg(Color::Yellow).bold())
+                } else {
+                    row
+                }
+            })
+            .collect();
+
+        let header = Row::new(vec!["File ID", "Hash", "Ext", "Tags"])
+            .style(Style::default().fg(Color::Yellow).bold());
+
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Percentage(10),
+                Constraint::Percentage(70),
+                Constraint::Percentage(10),
+                Constraint::Percentage(10),
+            ],
+        )
+        .header(header)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title(format!(" {} Files Found ", self.search.results.len())),
+        );
+
+        let mut state = TableState::default().with_selected(Some(self.search.result_index));
+        StatefulWidget::render(table, area, buf, &mut state);
+    }
+}
+
+///
+/// All tags on a file grouped by their namespace
+///
+impl<'a> Widget for FileTagsRender<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(0)])
+            .split(area);
+
+        let file = &self.file_tags.file;
+        Paragraph::new(format!(
+            "File ID: {}  |  Hash: {}  |  Ext: {}  |  Tags: {}",
+            file.file_id, file.hash, file.ext, file.tag_count
+        ))
+        .block(Block::default().borders(Borders::ALL).fg(Color::Cyan))
+        .render(chunks[0], buf);
+
+        let mut list_items = Vec::new();
+        for (namespace, tags) in self.file_tags.namespaces.iter() {
+            list_items.push(
+                ListItem::new(format!("{} ({})", namespace, tags.len()))
+                    .fg(Color::Yellow)
+                    .bold(),
+            );
+            for tag in tags {
+                list_items.push(ListItem::new(format!("   {}", tag)));
+            }
+        }
+
+        let list = List::new(list_items).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title(" Tag Inspector "),
+        );
+        ratatui::prelude::Widget::render(list, chunks[1], buf);
+    }
+}
+
+///
+/// Loaded jobs ordered by priority
+///
+impl<'a> Widget for JobQueueRender<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let rows: Vec<Row> = self
+            .job_queue
+            .jobs
+            .iter()
+            .enumerate()
+            .map(|(cnt, (scraper, job, paused))| {
+                let status = if *paused {
+                    ratatui::widgets::Cell::new("Paused")
+                        .style(Style::default().fg(Color::DarkGray))
+                } else if job.isrunning {
+                    ratatui::widgets::Cell::new("Running")
+                        .style(Style::default().fg(Color::Green).bold())
+                } else {
+                    ratatui::widgets::Cell::new("Queued").style(Style::default().fg(Color::Blue))
+                };
+                let row = Row::new(vec![
+                    ratatui::widgets::Cell::new(
+                        job.id.map(|id| id.to_string()).unwrap_or_default(),
+                    ),
+                    ratatui::widgets::Cell::new(scraper.name.clone()),
+                    ratatui::widgets::Cell::new(job.site.clone()),
+                    ratatui::widgets::Cell::new(job.priority.to_string()),
+                    status,
+                    ratatui::widgets::Cell::new(format!("{:?}", job.param)),
+                ]);
+                if cnt == self.job_queue.index {
+                    row.style(Style::default().fg(Color::Yellow).bold())
+                } else {
+                    row
+                }
+            })
+            .collect();
+
+        let header = Row::new(vec![
+            "Job ID", "Scraper", "Site", "Priority", "Status", "Params",
+        ])
+        .style(Style::default().fg(Color::Yellow).bold());
+
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Percentage(8),
+                Constraint::Percentage(15),
+                Constraint::Percentage(15),
+                Constraint::Percentage(8),
+                Constraint::Percentage(10),
+                Constraint::Percentage(44),
+            ],
+        )
+        .header(header)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title(format!(" Job Queue ({}) ", self.job_queue.jobs.len())),
+        );
+
+        let mut state = TableState::default().with_selected(Some(self.job_queue.index));
+        StatefulWidget::render(table, area, buf, &mut state);
+    }
+}
@@ -9,16 +9,30 @@ use ratatui::{
 };
 use sharedtypes::HashesSupported;
 
+use crate::Main;
+use crate::jobs::Jobs;
 use crate::logging;
 use crate::ui::components::*;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io;
+use std::sync::Arc;
+
+/// Max number of autocomplete suggestions to pull from the FTS tables
+const SEARCH_SUGGESTION_LIMIT: u64 = 15;
+/// Max number of files to show on the results screen
+const SEARCH_RESULT_LIMIT: u64 = 500;
+
 pub struct App {
     exit: bool,
     receiver: tokio::sync::mpsc::UnboundedReceiver<UIEvent>,
     pub screen: Vec<AppScreen>,
     pub scrapers: HashMap<u64, UIScraper>,
+    db: Main,
+    jobs: Arc<Jobs>,
+    pub search: SearchState,
+    pub inspected_file: Option<UIFileTags>,
+    pub job_queue: JobQueueState,
 }
 
 #[derive(Default, Debug, Clone, PartialEq)]
@@ -26,6 +40,57 @@ pub enum AppScreen {
     #[default]
     Monitor, // Main view flag
     ViewScraper(u64), // Carries the target worker ID we are actively viewing
+    TagSearch,        // Tag search input with autocomplete
+    SearchResults,    // Files returned from a tag search
+    FileTags(u64),    // Tag inspector for a file id
+    JobQueue,         // Loaded jobs and their controls
+}
+
+///
+/// State for the tag search input and its results
+///
+#[derive(Debug, Clone, Default)]
+pub struct SearchState {
+    pub input: String,
+    // Tag, tag id, count
+    pub suggestions: Vec<(sharedtypes::Tag, u64, u64)>,
+    pub suggestion_index: usize,
+    pub selected_tags: Vec<(sharedtypes::Tag, u64)>,
+    pub results: Vec<UIFileResult>,
+    pub result_index: usize,
+}
+
+///
+/// A single file that was returned from a search
+///
+#[derive(Debug, Clone)]
+pub struct UIFileResult {
+    pub file_id: u64,
+    pub hash: String,
+    pub ext: String,
+    pub tag_count: usize,
+}
+
+///
+/// Tags for a file grouped by namespace name
+///
+#[derive(Debug, Clone)]
+pub struct UIFileTags {
+    pub file: UIFileResult,
+    pub namespaces: BTreeMap<String, Vec<String>>,
+}
+
+///
+/// State for the job queue screen
+///
+#[derive(Debug, Clone, Default)]
+pub struct JobQueueState {
+    pub jobs: Vec<(
+        sharedtypes::GlobalPluginScraper,
+        sharedtypes::DbJobsObj,
+        bool,
+    )>,
+    pub index: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -82,12 +147,21 @@ pub struct UIScraper {
 }
 
 impl App {
-    pub fn new(receiver: tokio::sync::mpsc::UnboundedReceiver<UIEvent>) -> Self {
+    pub fn new(
+        receiver: tokio::sync::mpsc::UnboundedReceiver<UIEvent>,
+        db: Main,
+        jobs: Arc<Jobs>,
+    ) -> Self {
         App {
             receiver,
             exit: false,
             screen: vec![AppScreen::Monitor],
             scrapers: HashMap::new(),
+            db,
+            jobs,
+            search: SearchState::default(),
+            inspected_file: None,
+            job_queue: JobQueueState::default(),
         }
     }
 
@@ -101,6 +175,11 @@ impl App {
                 self.handle_ui_event_internal(event);
             }
 
+            // Jobs get added and removed by the workers so keep the queue fresh
+            if self.screen.last() == Some(&AppScreen::JobQueue) {
+                self.refresh_job_queue();
+            }
+
             // 2. PAINT THE CURRENT STATE ONCE PER LOOP ROUND
             terminal.draw(|frame| self.draw(frame))?;
 
@@ -196,9 +275,21 @@ impl App {
         let ribbon_text;
         if let Some(screen) = self.screen.last() {
             ribbon_text = match screen {
-                AppScreen::Monitor => "⚡ [1-9] View Scraper Detail | [q] Quit",
+                AppScreen::Monitor => {
+                    "⚡ [1-9] View Scraper Detail | [/] Tag Search | [j] Job Queue | [p] Pause/Resume All Downloads | [q] Quit"
+                }
                 AppScreen::ViewScraper(_) => {
-                    "⚡ [Esc/Backspace] Return to Monitor Panel | [q] Quit"
+                    "⚡ [Esc/Backspace] Return to Monitor Panel | [p] Pause | [r] Resume | [c] Cancel Worker | [q] Quit"
+                }
+                AppScreen::TagSearch => {
+                    "⚡ [Type] Search Tags | [↑/↓] Select | [Tab] Add Tag | [Enter] Search | [Esc] Back"
+                }
+                AppScreen::SearchResults => {
+                    "⚡ [↑/↓] Select File | [Enter] Inspect Tags | [Esc] Back"
+                }
+                AppScreen::FileTags(_) => "⚡ [Esc] Back",
+                AppScreen::JobQueue => {
+                    "⚡ [↑/↓] Select | [p] Pause | [r] Resume | [c] Cancel | [d] Delete | [+/-] Priority | [Esc] Back"
                 }
             }
         } else {
@@ -216,14 +307,34 @@ impl App {
             return Ok(());
         }
 
-        match key_event.code {
-            KeyCode::Esc => {
-                let screen = self.screen.pop();
-                if screen.is_none() || screen == Some(AppScreen::Monitor) {
-                    self.exit = true;
+        if key_event.code == KeyCode::Esc {
+            let screen = self.screen.pop();
+            if screen.is_none() || screen == Some(AppScreen::Monitor) {
+                self.exit = true;
+            }
+            return Ok(());
+        }
+
+        match self.screen.last().cloned() {
+            Some(AppScreen::Monitor) => self.handle_key_monitor(key_event),
+            Some(AppScreen::TagSearch) => self.handle_key_tag_search(key_event),
+            Some(AppScreen::SearchResults) => self.handle_key_search_results(key_event),
+            Some(AppScreen::JobQueue) => self.handle_key_job_queue(key_event),
+            Some(AppScreen::ViewScraper(worker_id)) => {
+                self.handle_key_view_scraper(key_event, &worker_id)
+            }
+            Some(AppScreen::FileTags(_)) => {
+                if key_event.code == KeyCode::Backspace {
+                    self.screen.pop();
                 }
             }
+            None => {}
+        }
+        Ok(())
+    }
 
+    fn handle_key_monitor(&mut self, key_event: KeyEvent) {
+        match key_event.code {
             KeyCode::Char(c) if c.is_ascii_digit() => {
                 if let Some(digit) = c.to_digit(10) {
                     let target_worker_id = (digit as u64);
@@ -234,9 +345,273 @@ impl App {
                     }
                 }
             }
+            KeyCode::Char('/') => {
+                self.screen.push(AppScreen::TagSearch);
+            }
+            KeyCode::Char('j') => {
+                self.refresh_job_queue();
+                self.screen.push(AppScreen::JobQueue);
+            }
+            KeyCode::Char('p') => {
+                let paused = self.db.downloads_pause_get();
+                self.db.downloads_pause_set(&!paused);
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_key_view_scraper(&mut self, key_event: KeyEvent, worker_id: &u64) {
+        if key_event.code == KeyCode::Backspace {
+            self.screen.pop();
+            return;
+        }
+        let Some(scraper_name) = self.scrapers.get(worker_id).map(|s| s.name.clone()) else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Char('p') => self.jobs.scraper_pause(&scraper_name),
+            KeyCode::Char('r') => self.jobs.scraper_resume(&scraper_name),
+            KeyCode::Char('c') => self.jobs.scraper_cancel(&scraper_name),
+            _ => {}
+        }
+    }
+
+    fn handle_key_tag_search(&mut self, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Char(c) => {
+                self.search.input.push(c);
+                self.refresh_suggestions();
+            }
+            KeyCode::Backspace => {
+                if self.search.input.pop().is_none() {
+                    self.search.selected_tags.pop();
+                }
+                self.refresh_suggestions();
+            }
+            KeyCode::Up => {
+                self.search.suggestion_index = self.search.suggestion_index.saturating_sub(1);
+            }
+            KeyCode::Down => {
+                if self.search.suggestion_index + 1 < self.search.suggestions.len() {
+                    self.search.suggestion_index += 1;
+                }
+            }
+            KeyCode::Tab => {
+                self.select_suggestion();
+            }
+            KeyCode::Enter => {
+                // Lets a single tag get searched without having to tab it in first
+                if !self.search.input.is_empty() {
+                    self.select_suggestion();
+                }
+                self.run_search();
+                self.screen.push(AppScreen::SearchResults);
+            }
             _ => {}
         }
-        Ok(())
+    }
+
+    fn handle_key_search_results(&mut self, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Backspace => {
+                self.screen.pop();
+            }
+            KeyCode::Up => {
+                self.search.result_index = self.search.result_index.saturating_sub(1);
+            }
+            KeyCode::Down => {
+                if self.search.result_index + 1 < self.search.results.len() {
+                    self.search.result_index += 1;
+                }
+            }
+            KeyCode::Enter => {
+                if let Some(file) = self.search.results.get(self.search.result_index).cloned() {
+                    self.inspected_file = Some(self.file_tags_get(file.clone()));
+                    self.screen.push(AppScreen::FileTags(file.file_id));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_key_job_queue(&mut self, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Backspace => {
+                self.screen.pop();
+                return;
+            }
+            KeyCode::Up => {
+                self.job_queue.index = self.job_queue.index.saturating_sub(1);
+                return;
+            }
+            KeyCode::Down => {
+                if self.job_queue.index + 1 < self.job_queue.jobs.len() {
+                    self.job_queue.index += 1;
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        let Some((scraper, job, _)) = self.job_queue.jobs.get(self.job_queue.index).cloned() else {
+            return;
+        };
+        let Some(job_id) = job.id else {
+            return;
+        };
+
+        match key_event.code {
+            KeyCode::Char('p') => self.jobs.jobs_pause(&job_id),
+            KeyCode::Char('r') => self.jobs.jobs_resume(&job_id),
+            KeyCode::Char('c') => self.jobs.jobs_cancel(&job_id),
+            KeyCode::Char('d') => {
+                self.jobs.jobs_remove_dbjob(&scraper, &job, &0);
+                self.db.transaction_flush();
+            }
+            KeyCode::Char('+') => {
+                self.jobs
+                    .jobs_set_priority(&scraper, &job_id, job.priority.saturating_add(1));
+            }
+            KeyCode::Char('-') => {
+                self.jobs
+                    .jobs_set_priority(&scraper, &job_id, job.priority.saturating_sub(1));
+            }
+            _ => {}
+        }
+        self.refresh_job_queue();
+    }
+
+    ///
+    /// Pulls autocomplete suggestions for the current search input
+    ///
+    fn refresh_suggestions(&mut self) {
+        self.search.suggestion_index = 0;
+        if self.search.input.is_empty() {
+            self.search.suggestions.clear();
+            return;
+        }
+        self.search.suggestions = self.db.search_tags(
+            &self.search.input,
+            &SEARCH_SUGGESTION_LIMIT,
+            sharedtypes::TagPartialSearchType::PopularCount,
+        );
+    }
+
+    ///
+    /// Moves the highlighted suggestion into the list of tags to search for
+    ///
+    fn select_suggestion(&mut self) {
+        if let Some((tag, tag_id, _)) = self
+            .search
+            .suggestions
+            .get(self.search.suggestion_index)
+            .cloned()
+            && !self
+                .search
+                .selected_tags
+                .iter()
+                .any(|(_, id)| *id == tag_id)
+        {
+            self.search.selected_tags.push((tag, tag_id));
+        }
+        self.search.input.clear();
+        self.search.suggestions.clear();
+        self.search.suggestion_index = 0;
+    }
+
+    ///
+    /// Runs an AND search across all selected tags
+    ///
+    fn run_search(&mut self) {
+        self.search.results.clear();
+        self.search.result_index = 0;
+        if self.search.selected_tags.is_empty() {
+            return;
+        }
+
+        let search = sharedtypes::SearchObj {
+            search_relate: None,
+            searches: vec![sharedtypes::SearchHolder::And(
+                self.search
+                    .selected_tags
+                    .iter()
+                    .map(|(_, id)| *id)
+                    .collect(),
+            )],
+        };
+
+        if let Some(file_ids) = self.db.search_db_files(search, Some(SEARCH_RESULT_LIMIT)) {
+            for file_id in file_ids {
+                if let Some(file) = self.file_result_get(&file_id) {
+                    self.search.results.push(file);
+                }
+            }
+        }
+        logging::info_log(format!(
+            "UI search for {:?} returned {} files",
+            self.search.selected_tags,
+            self.search.results.len()
+        ));
+    }
+
+    ///
+    /// Gets the display info for a file id
+    ///
+    fn file_result_get(&self, file_id: &u64) -> Option<UIFileResult> {
+        let (hash, ext) = match self.db.file_get_id(file_id)? {
+            sharedtypes::DbFileStorage::Exist(file) => (
+                file.hash,
+                self.db
+                    .extension_get_string(&file.ext_id)
+                    .unwrap_or_default(),
+            ),
+            _ => (String::new(), String::new()),
+        };
+        Some(UIFileResult {
+            file_id: *file_id,
+            hash,
+            ext,
+            tag_count: self.db.relationship_get_tagid(file_id).len(),
+        })
+    }
+
+    ///
+    /// Gets all tags for a file grouped by namespace
+    ///
+    fn file_tags_get(&self, file: UIFileResult) -> UIFileTags {
+        let mut namespaces: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for tag_id in self.db.relationship_get_tagid(&file.file_id) {
+            if let Some(tag) = self.db.tag_id_get(&tag_id) {
+                let namespace = self
+                    .db
+                    .namespace_get_string(&tag.namespace)
+                    .map(|ns| ns.name)
+                    .unwrap_or_else(|| tag.namespace.to_string());
+                namespaces.entry(namespace).or_default().push(tag.name);
+            }
+        }
+        for tags in namespaces.values_mut() {
+            tags.sort();
+        }
+        UIFileTags { file, namespaces }
+    }
+
+    ///
+    /// Reloads the job list from the job manager
+    ///
+    fn refresh_job_queue(&mut self) {
+        self.job_queue.jobs = self
+            .jobs
+            .jobs_get_all_loaded()
+            .into_iter()
+            .map(|(scraper, job)| {
+                let paused = job.id.is_some_and(|id| self.jobs.jobs_is_paused(&id));
+                (scraper, job, paused)
+            })
+            .collect();
+        if self.job_queue.index >= self.job_queue.jobs.len() {
+            self.job_queue.index = self.job_queue.jobs.len().saturating_sub(1);
+        }
     }
 }
 // Handles drawing the main interaction body area
@@ -249,6 +624,7 @@ impl Widget for &App {
                     // Pass a reference of the master hashmap to the renderer component
                     MonitorRender {
                         scrapers: &self.scrapers,
+                        downloads_paused: self.db.downloads_pause_get(),
                     }
                     .render(area, buf);
                 }
@@ -263,6 +639,32 @@ impl Widget for &App {
                         .render(area, buf);
                     }
                 }
+                AppScreen::TagSearch => {
+                    TagSearchRender {
+                        search: &self.search,
+                    }
+                    .render(area, buf);
+                }
+                AppScreen::SearchResults => {
+                    SearchResultsRender {
+                        search: &self.search,
+                    }
+                    .render(area, buf);
+                }
+                AppScreen::FileTags(file_id) => {
+                    if let Some(file_tags) = &self.inspected_file {
+                        FileTagsRender { file_tags }.render(area, buf);
+                    } else {
+                        Paragraph::new(format!("No tags loaded for file id: {}", file_id))
+                            .render(area, buf);
+                    }
+                }
+                AppScreen::JobQueue => {
+                    JobQueueRender {
+                        job_queue: &self.job_queue,
+                    }
+                    .render(area, buf);
+                }
             }
         }
     }
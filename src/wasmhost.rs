@@ -0,0 +1,279 @@
+use crate::download;
+use crate::helpers::block_on;
+use crate::logging;
+use crate::pluginabi::{self, AbiError};
+use crate::pluginhost;
+use crate::{Mutex, RwLock};
+use ratelimit::Ratelimiter;
+use reqwest::Client;
+use serde::Deserialize;
+use sharedtypes::abi::{PLUGIN_ABI_VERSION, abi_encode};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use wasmtime::component::{Component, Linker, ResourceTable};
+use wasmtime::{Config, Engine, Store, UpdateDeadline};
+use wasmtime_wasi::{DirPerms, FilePerms, WasiCtx, WasiCtxBuilder, WasiView};
+
+wasmtime::component::bindgen!({
+    path: "wit/plugin.wit",
+    world: "plugin",
+});
+
+// Used until the plugin tells us its own ratelimit
+const DEFAULT_RATELIMIT: (u64, Duration) = (1, Duration::from_secs(1));
+
+// How often the engine's epoch ticks. Call deadlines are counted in these
+const EPOCH_TICK: Duration = Duration::from_millis(100);
+
+///
+/// Engine shared by every wasm plugin. Its epoch ticks in the background so a call
+/// that runs past its deadline traps instead of hanging whoever called it
+///
+static ENGINE: LazyLock<Result<Engine, String>> = LazyLock::new(|| {
+    let mut config = Config::new();
+    config.epoch_interruption(true);
+    let engine = Engine::new(&config).map_err(|err| err.to_string())?;
+    let ticker = engine.clone();
+    std::thread::spawn(move || {
+        loop {
+            std::thread::sleep(EPOCH_TICK);
+            ticker.increment_epoch();
+        }
+    });
+    Ok(engine)
+});
+
+///
+/// A directory the host lets a wasm plugin see
+///
+#[derive(Debug, Clone, Deserialize)]
+pub struct WasmDir {
+    pub host: PathBuf,
+    pub guest: String,
+    #[serde(default)]
+    pub write: bool,
+}
+
+///
+/// Capabilities granted to a wasm plugin. Read from <plugin>.wasm.caps.json next
+/// to the plugin. Without that file the plugin gets no filesystem at all.
+///
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WasmCaps {
+    #[serde(default)]
+    pub dirs: Vec<WasmDir>,
+}
+
+impl WasmCaps {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let mut caps_path = path.as_os_str().to_owned();
+        caps_path.push(".caps.json");
+        let caps_path = PathBuf::from(caps_path);
+        if !caps_path.exists() {
+            return Ok(WasmCaps::default());
+        }
+        let data = std::fs::read_to_string(&caps_path).map_err(|err| err.to_string())?;
+        serde_json::from_str(&data)
+            .map_err(|err| format!("Bad caps file {}: {}", caps_path.display(), err))
+    }
+}
+
+///
+/// Per call state inside of the wasm store
+///
+struct WasmState {
+    wasi: WasiCtx,
+    table: ResourceTable,
+    client: Arc<Client>,
+    ratelimiter: Arc<Ratelimiter>,
+    name: String,
+}
+
+impl WasiView for WasmState {
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+
+    fn ctx(&mut self) -> &mut WasiCtx {
+        &mut self.wasi
+    }
+}
+
+impl rusthydrus::plugin::host::Host for WasmState {
+    fn http_get(&mut self, url: String) -> Result<String, String> {
+        block_on(download::dltext_new(
+            &url,
+            None,
+            self.client.clone(),
+            &self.ratelimiter,
+            &0,
+            &0,
+        ))
+        .map(|(text, _)| text)
+        .map_err(|err| err.to_string())
+    }
+
+    fn log(&mut self, msg: String) {
+        logging::log(format!("Wasm plugin {}: {}", self.name, msg));
+    }
+}
+
+///
+/// A scraper or plugin compiled to a wasm component. Every call gets a fresh
+/// instance so a trap can't leave state behind and long calls don't block others.
+///
+pub struct WasmPlugin {
+    path: PathBuf,
+    engine: Engine,
+    component: Component,
+    linker: Linker<WasmState>,
+    caps: WasmCaps,
+    client: Arc<Client>,
+    ratelimiter: RwLock<Arc<Ratelimiter>>,
+    name: Mutex<String>,
+}
+
+impl WasmPlugin {
+    ///
+    /// Compiles the component at load_path and checks it was built for our plugin ABI.
+    /// Caps are read from next to path
+    ///
+    pub fn load(path: &Path, load_path: &Path) -> Result<Arc<Self>, String> {
+        let caps = WasmCaps::load(path)?;
+        let engine = ENGINE.clone()?;
+        let component = Component::from_file(&engine, load_path).map_err(|err| err.to_string())?;
+
+        let mut linker = Linker::new(&engine);
+        wasmtime_wasi::add_to_linker_sync(&mut linker).map_err(|err| err.to_string())?;
+        Plugin::add_to_linker(&mut linker, |state: &mut WasmState| state)
+            .map_err(|err| err.to_string())?;
+
+        let plugin = Arc::new(WasmPlugin {
+            path: path.to_path_buf(),
+            engine,
+            component,
+            linker,
+            caps,
+            client: Arc::new(download::client_create(Vec::new(), true)),
+            ratelimiter: RwLock::new(download::create_ratelimiter(DEFAULT_RATELIMIT, &0, &0)),
+            name: Mutex::new(path.to_string_lossy().to_string()),
+        });
+
+        let (mut store, instance) = plugin.instantiate(Some(pluginhost::CALL_TIMEOUT))?;
+        let version = instance
+            .call_abi_version(&mut store)
+            .map_err(|err| err.to_string())?;
+        if version != PLUGIN_ABI_VERSION {
+            return Err(format!(
+                "wasm plugin was built for plugin ABI version {} but this build uses version {}",
+                version, PLUGIN_ABI_VERSION
+            ));
+        }
+        Ok(plugin)
+    }
+
+    ///
+    /// Sets the name and ratelimit once we know what the plugin is
+    ///
+    pub fn info_set(&self, global: &sharedtypes::GlobalPluginScraper) {
+        *self.name.lock() = global.name.clone();
+        if let Some(sharedtypes::ScraperOrPlugin::Scraper(info)) = &global.storage_type {
+            // Same limiter the download workers use so http_get can't go around it
+            *self.ratelimiter.write() = download::ratelimiter_shared(&global.name, info.ratelimit);
+        }
+    }
+
+    ///
+    /// Makes a fresh instance. Calls on it trap once timeout runs out, None never times out
+    ///
+    fn instantiate(&self, timeout: Option<Duration>) -> Result<(Store<WasmState>, Plugin), String> {
+        let mut wasi = WasiCtxBuilder::new();
+        for dir in self.caps.dirs.iter() {
+            let (dir_perms, file_perms) = if dir.write {
+                (DirPerms::all(), FilePerms::all())
+            } else {
+                (DirPerms::READ, FilePerms::READ)
+            };
+            wasi.preopened_dir(&dir.host, &dir.guest, dir_perms, file_perms)
+                .map_err(|err| {
+                    format!(
+                        "Cannot grant {} to wasm plugin: {}",
+                        dir.host.display(),
+                        err
+                    )
+                })?;
+        }
+
+        let state = WasmState {
+            wasi: wasi.build(),
+            table: ResourceTable::new(),
+            client: self.client.clone(),
+            ratelimiter: self.ratelimiter.read().clone(),
+            name: self.name.lock().clone(),
+        };
+        let mut store = Store::new(&self.engine, state);
+        match timeout {
+            Some(timeout) => store
+                .set_epoch_deadline((timeout.as_millis() / EPOCH_TICK.as_millis()).max(1) as u64),
+            None => {
+                store.set_epoch_deadline(1);
+                store.epoch_deadline_callback(|_| Ok(UpdateDeadline::Continue(1)));
+            }
+        }
+        let instance = Plugin::instantiate(&mut store, &self.component, &self.linker)
+            .map_err(|err| err.to_string())?;
+        Ok((store, instance))
+    }
+
+    ///
+    /// Calls a function in the plugin. Same encoding as the native plugin ABI.
+    /// The call traps if it runs longer than timeout
+    ///
+    pub fn call<I, O>(
+        &self,
+        func: &str,
+        input: &I,
+        timeout: Option<Duration>,
+    ) -> Result<O, AbiError>
+    where
+        I: bitcode::Encode + ?Sized,
+        O: bitcode::DecodeOwned,
+    {
+        let (mut store, instance) = self.instantiate(timeout).map_err(AbiError::Wasm)?;
+        let out = instance
+            .call_call(&mut store, func, &abi_encode(input))
+            .map_err(|err| {
+                AbiError::Wasm(format!(
+                    "{} trapped in {}: {}",
+                    self.path.display(),
+                    func,
+                    err
+                ))
+            })?;
+        match out {
+            Ok(data) => pluginabi::abi_status_decode(sharedtypes::abi::AbiStatus::Ok as u32, &data),
+            Err(status) => pluginabi::abi_status_decode(status, &[]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caps_default_to_nothing() {
+        let caps = WasmCaps::load(Path::new("/nonexistent/plugin.wasm")).unwrap();
+        assert!(caps.dirs.is_empty());
+    }
+
+    #[test]
+    fn caps_parse_dirs() {
+        let caps: WasmCaps =
+            serde_json::from_str(r#"{"dirs":[{"host":"/tmp/a","guest":"/data"}]}"#).unwrap();
+        assert_eq!(caps.dirs.len(), 1);
+        assert_eq!(caps.dirs[0].guest, "/data");
+        assert!(!caps.dirs[0].write);
+    }
+}
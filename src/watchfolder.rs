@@ -0,0 +1,339 @@
+use crate::Main;
+use crate::file;
+use crate::globalload::GlobalLoad;
+use crate::logging;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+/// How often watch folders get scanned for new files
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Length and modified time in ms, the same as WatchFolderSeen keeps
+type Stamp = (u64, u64);
+
+///
+/// What we know about one watch folder between scans
+///
+struct Watched {
+    /// Files already imported by their path inside the folder
+    seen: HashMap<String, Stamp>,
+    /// Files that showed up last scan and haven't been imported yet
+    pending: HashMap<String, Stamp>,
+}
+
+///
+/// Imports new files out of the watch folders in the db. The first scan catches up
+/// on whatever arrived while we weren't running. A file gets imported once it looks
+/// the same for two scans in a row and is older than its folder's settle time.
+///
+pub fn spawn(database: Main, globalload: GlobalLoad) {
+    thread::spawn(move || {
+        let mut watched: HashMap<u64, Watched> = HashMap::new();
+        let mut tables_loaded = false;
+        loop {
+            let folders = database.watch_folders_list();
+            watched.retain(|id, _| folders.iter().any(|folder| folder.id == *id));
+            if !folders.is_empty() && !tables_loaded {
+                for table in [
+                    sharedtypes::LoadDBTable::Files,
+                    sharedtypes::LoadDBTable::Relationship,
+                    sharedtypes::LoadDBTable::Tags,
+                    sharedtypes::LoadDBTable::Namespace,
+                    sharedtypes::LoadDBTable::Parents,
+                ] {
+                    database.load_table(&table);
+                }
+                database.enclave_create_default_file_import();
+                tables_loaded = true;
+            }
+            for folder in folders.iter() {
+                let state = watched.entry(folder.id).or_insert_with(|| Watched {
+                    seen: database.watch_folder_seen(&folder.id),
+                    pending: HashMap::new(),
+                });
+                scan(&database, &globalload, folder, state);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
+
+fn scan(
+    database: &Main,
+    globalload: &GlobalLoad,
+    folder: &sharedtypes::WatchFolder,
+    state: &mut Watched,
+) {
+    let root = Path::new(&folder.path);
+    // Could be an unplugged drive. Forgetting everything in it would import it all again
+    if !root.is_dir() {
+        return;
+    }
+    let settle = Duration::from_secs(folder.settle_secs);
+    let mut present = HashSet::new();
+    let mut pending = HashMap::new();
+    let walk = WalkDir::new(root).max_depth(if folder.recursive { usize::MAX } else { 1 });
+    for entry in walk.into_iter().flatten() {
+        let path = entry.path();
+        if !entry.file_type().is_file() || is_sidecar(path) {
+            continue;
+        }
+        let Some(relative) = relative_name(root, path) else {
+            continue;
+        };
+        if folder.ignore.iter().any(|glob| glob_match(glob, &relative)) {
+            continue;
+        }
+        let Some((stamp, age)) = file_stamp(path) else {
+            continue;
+        };
+        present.insert(relative.clone());
+        if state.seen.get(&relative) == Some(&stamp) {
+            continue;
+        }
+        if state.pending.get(&relative) != Some(&stamp) || age < settle {
+            pending.insert(relative, stamp);
+            continue;
+        }
+        import(database, globalload, folder, state, path, relative, stamp);
+    }
+
+    // A file that was deleted gets imported again if it comes back
+    let gone: Vec<_> = state
+        .seen
+        .keys()
+        .filter(|relative| !present.contains(*relative))
+        .cloned()
+        .collect();
+    for relative in gone {
+        state.seen.remove(&relative);
+        database.watch_folder_seen_set(&folder.id, &relative, &None);
+    }
+    state.pending = pending;
+}
+
+fn import(
+    database: &Main,
+    globalload: &GlobalLoad,
+    folder: &sharedtypes::WatchFolder,
+    state: &mut Watched,
+    path: &Path,
+    relative: String,
+    stamp: Stamp,
+) {
+    logging::info_log(format!("Watch folder importing: {}", path.display()));
+    let sidecars = file::find_sidecar(path);
+    let Some(file_id) = file::parse_file(path, &sidecars, database.clone(), globalload.clone())
+    else {
+        // Still remembered so it isn't retried every scan. Changing the file retries it
+        logging::error_log(format!(
+            "Watch folder {} cannot import {}",
+            folder.path,
+            path.display()
+        ));
+        remember(database, folder, state, relative, Some(stamp));
+        return;
+    };
+
+    if !folder.tags.is_empty() {
+        let tags = folder
+            .tags
+            .iter()
+            .map(|tag| sharedtypes::TagObject {
+                namespace: tag.namespace.clone(),
+                tag: tag.tag.clone(),
+                tag_type: sharedtypes::TagType::Normal,
+                relates_to: None,
+            })
+            .collect();
+        database.add_tags_to_fileid(
+            Some(file_id),
+            &vec![sharedtypes::FileTagAction {
+                operation: sharedtypes::TagOperation::Add,
+                tags,
+            }],
+        );
+    }
+
+    match folder.file_action {
+        sharedtypes::FileAction::Copy => {
+            remember(database, folder, state, relative, Some(stamp));
+        }
+        sharedtypes::FileAction::Move => {
+            for remove in std::iter::once(path).chain(sidecars.iter().map(PathBuf::as_path)) {
+                if let Err(err) = std::fs::remove_file(remove) {
+                    logging::error_log(format!(
+                        "Watch folder cannot remove {}: {}",
+                        remove.display(),
+                        err
+                    ));
+                }
+            }
+            remember(database, folder, state, relative, None);
+        }
+        sharedtypes::FileAction::HardLink => {
+            match database.file_path_local(&file_id) {
+                Some(stored) => {
+                    if let Err(err) = link_over(path, Path::new(&stored)) {
+                        logging::error_log(format!(
+                            "Watch folder cannot link {} to {}: {}",
+                            path.display(),
+                            stored,
+                            err
+                        ));
+                    }
+                }
+                None => logging::info_log(format!(
+                    "Watch folder left {} as a copy, the db doesn't keep it as a plain file",
+                    path.display()
+                )),
+            }
+            // Linking changes its modified time to the stored file's
+            let stamp = file_stamp(path).map_or(stamp, |(stamp, _)| stamp);
+            remember(database, folder, state, relative, Some(stamp));
+        }
+    }
+}
+
+fn remember(
+    database: &Main,
+    folder: &sharedtypes::WatchFolder,
+    state: &mut Watched,
+    relative: String,
+    stamp: Option<Stamp>,
+) {
+    database.watch_folder_seen_set(&folder.id, &relative, &stamp);
+    match stamp {
+        Some(stamp) => state.seen.insert(relative, stamp),
+        None => state.seen.remove(&relative),
+    };
+}
+
+///
+/// Swaps path out for a hardlink to stored. Goes through a temp name so path is
+/// never missing
+///
+fn link_over(path: &Path, stored: &Path) -> std::io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".link");
+    let temp = PathBuf::from(temp);
+    let _ = std::fs::remove_file(&temp);
+    std::fs::hard_link(stored, &temp)?;
+    std::fs::rename(&temp, path)
+}
+
+fn file_stamp(path: &Path) -> Option<(Stamp, Duration)> {
+    let meta = std::fs::metadata(path).ok()?;
+    let modified = meta.modified().ok()?;
+    let millis = modified.duration_since(UNIX_EPOCH).ok()?.as_millis() as u64;
+    let age = SystemTime::now()
+        .duration_since(modified)
+        .unwrap_or_default();
+    Some(((meta.len(), millis), age))
+}
+
+///
+/// Sidecars get imported along with their file instead of on their own
+///
+fn is_sidecar(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    if let Some(stem) = name.strip_suffix(".info.json")
+        && let Some(Ok(entries)) = path.parent().map(std::fs::read_dir)
+    {
+        let prefix = format!("{}.", stem);
+        let has_file = entries.flatten().any(|entry| {
+            let other = entry.file_name().to_string_lossy().to_string();
+            other != name && other.starts_with(&prefix)
+        });
+        if has_file {
+            return true;
+        }
+    }
+    path.extension()
+        .is_some_and(|ext| ext == "txt" || ext == "json")
+        && path.with_extension("").is_file()
+}
+
+fn relative_name(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    Some(
+        relative
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+    )
+}
+
+///
+/// Matches a path inside a watch folder against an ignore glob. * and ? stay
+/// inside one folder and ** crosses them. A glob without a / only looks at the
+/// file's name
+///
+fn glob_match(glob: &str, relative: &str) -> bool {
+    let name = match glob.contains('/') {
+        true => relative,
+        false => relative.rsplit('/').next().unwrap_or(relative),
+    };
+    let glob: Vec<char> = glob.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    glob_chars(&glob, &name)
+}
+
+fn glob_chars(glob: &[char], name: &[char]) -> bool {
+    match glob {
+        [] => name.is_empty(),
+        ['*', '*', rest @ ..] => (0..=name.len()).any(|skip| glob_chars(rest, &name[skip..])),
+        ['*', rest @ ..] => (0..=name.len())
+            .take_while(|skip| *skip == 0 || name[skip - 1] != '/')
+            .any(|skip| glob_chars(rest, &name[skip..])),
+        ['?', rest @ ..] => {
+            name.first().is_some_and(|chr| *chr != '/') && glob_chars(rest, &name[1..])
+        }
+        [chr, rest @ ..] => name.first() == Some(chr) && glob_chars(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn ignores_and_sidecars() {
+        assert!(glob_match("*.part", "sub/video.mp4.part"));
+        assert!(!glob_match("*.part", "sub/video.mp4"));
+        assert!(glob_match("sub/*", "sub/a.jpg"));
+        assert!(!glob_match("sub/*", "sub/deeper/a.jpg"));
+        assert!(glob_match("sub/**", "sub/deeper/a.jpg"));
+        assert!(glob_match("?.jpg", "a.jpg"));
+        assert!(!glob_match("?.jpg", "ab.jpg"));
+
+        let dir = TempDir::new("watchfolder");
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        for name in [
+            "a.jpg",
+            "a.jpg.txt",
+            "notes.txt",
+            "clip.mp4",
+            "clip.info.json",
+            "sub/b.png",
+        ] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+        assert!(is_sidecar(&dir.join("a.jpg.txt")));
+        assert!(is_sidecar(&dir.join("clip.info.json")));
+        assert!(!is_sidecar(&dir.join("notes.txt")));
+        assert!(!is_sidecar(&dir.join("a.jpg")));
+        assert_eq!(
+            relative_name(&dir, &dir.join("sub").join("b.png")).unwrap(),
+            "sub/b.png"
+        );
+    }
+}